@@ -0,0 +1,562 @@
+//! This module provides metrics that characterize the dynamics of an automaton, computed on its current grid.
+//!
+//! The cells in an implicit state are considered to be in the state the implicit state belongs to.
+
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::fmt;
+use rand::Rng;
+use crate::automaton::Automaton;
+use crate::compiler::semantic::Rules;
+use crate::rng::CounterRng;
+
+pub struct Metrics {
+    /// Shannon entropy, in bits, of the 2x2 blocks of cells. It is 0 for a uniform grid.
+    pub spatial_entropy: f64,
+    /// Number of clusters of cells in the target state. Two cells are in the same cluster if they are neighbors.
+    pub cluster_count: usize,
+    pub mean_cluster_size: f64
+}
+
+/// Computes the metrics of the automaton's current grid. The clusters are the ones of the target state.
+pub fn compute_metrics(automaton: &Automaton, target_state: usize) -> Metrics {
+    let grid = parent_states(automaton);
+    let (width, height) = automaton.get_rules().grid_size();
+    let cluster_sizes = cluster_sizes(&grid, (width, height), target_state);
+    let cell_count: usize = cluster_sizes.iter().sum();
+    Metrics {
+        spatial_entropy: spatial_entropy(&grid, (width, height)),
+        cluster_count: cluster_sizes.len(),
+        mean_cluster_size: if cluster_sizes.is_empty() { 0.0 } else { cell_count as f64 / cluster_sizes.len() as f64 }
+    }
+}
+
+fn parent_states(automaton: &Automaton) -> Vec<usize> {
+    let rules = automaton.get_rules();
+    let (width, height) = rules.grid_size();
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| rules.parent_state(automaton.get_state(x as isize, y as isize)))
+        .collect()
+}
+
+/// The blocks overlap, there is one block per cell, whose top-left cell is this cell. The grid is wrapped around.
+fn spatial_entropy(grid: &[usize], (width, height): (usize, usize)) -> f64 {
+    let mut block_counts: HashMap<[usize; 4], usize> = HashMap::new();
+    for y in 0..height {
+        for x in 0..width {
+            let (x1, y1) = ((x + 1) % width, (y + 1) % height);
+            let block = [grid[y * width + x], grid[y * width + x1], grid[y1 * width + x], grid[y1 * width + x1]];
+            *block_counts.entry(block).or_insert(0) += 1;
+        }
+    }
+    let total = grid.len() as f64;
+    block_counts.values()
+        .map(|count| {
+            let p = *count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Returns the size of each cluster of the state, found with a flood fill. The grid is wrapped around.
+fn cluster_sizes(grid: &[usize], (width, height): (usize, usize), state: usize) -> Vec<usize> {
+    let mut visited = vec![false; grid.len()];
+    let mut sizes = Vec::new();
+    for start in 0..grid.len() {
+        if visited[start] || grid[start] != state {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![start];
+        let mut size = 0;
+        while let Some(index) = stack.pop() {
+            size += 1;
+            for neighbor in neighbors(index, (width, height)) {
+                if !visited[neighbor] && grid[neighbor] == state {
+                    visited[neighbor] = true;
+                    stack.push(neighbor);
+                }
+            }
+        }
+        sizes.push(size);
+    }
+    sizes
+}
+
+/// Returns the indices of the 8 neighbors of the cell. The grid is wrapped around.
+fn neighbors(index: usize, (width, height): (usize, usize)) -> impl Iterator<Item = usize> {
+    let (x, y) = (index % width, index / width);
+    let offsets = [(width - 1, height - 1), (0, height - 1), (1, height - 1), (width - 1, 0), (1, 0), (width - 1, 1), (0, 1), (1, 1)];
+    IntoIterator::into_iter(offsets)
+        .map(move |(u, v)| ((y + v) % height) * width + (x + u) % width)
+}
+
+/// Static measures of a rule table, estimated by evaluating the transitions on random neighborhoods.
+pub struct RuleTableReport {
+    /// Langton's lambda : the fraction of neighborhoods that lead to another state than the quiescent (default) state.
+    pub lambda: f64,
+    /// The fraction of neighborhoods whose center cell changes state.
+    pub activity: f64,
+    pub samples: usize
+}
+
+/// Estimates the static measures of the rule table with the given number of random neighborhoods.
+/// The cells of the neighborhoods are drawn uniformly among the states declared by the user.
+pub fn analyze_rules(rules: &Rules, samples: usize, seed: u64) -> RuleTableReport {
+    let quiescent_state = rules.default_state();
+    let state_count = rules.user_states().len();
+    let mut non_quiescent = 0;
+    let mut changed = 0;
+    for sample in 0..samples {
+        let mut rng = CounterRng::new(seed, 0, sample as u64);
+        let mut cells = vec![0; rules.neighborhood_cell_count()];
+        for cell in cells.iter_mut() {
+            *cell = rng.gen_range(0, state_count);
+        }
+        let state = rules.parent_state(rules.evaluate_neighborhood(&cells, &mut rng));
+        if state != quiescent_state {
+            non_quiescent += 1;
+        }
+        if state != cells[cells.len() / 2] {
+            changed += 1;
+        }
+    }
+    RuleTableReport {
+        lambda: non_quiescent as f64 / samples as f64,
+        activity: changed as f64 / samples as f64,
+        samples
+    }
+}
+
+/// The outcome of a run, according to the trend of the population of the states other than the quiescent (default) state.
+#[derive(Debug, PartialEq)]
+pub enum RunVerdict {
+    /// Only the quiescent state is left.
+    DiedOut,
+    /// The population at least doubled and covers at least half of the grid.
+    Exploded,
+    /// The population of each state did not change during the whole window. Oscillators whose population is constant are included.
+    Stabilized,
+    StillActive
+}
+
+impl fmt::Display for RunVerdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verdict = match self {
+            RunVerdict::DiedOut => "died out",
+            RunVerdict::Exploded => "exploded",
+            RunVerdict::Stabilized => "stabilized",
+            RunVerdict::StillActive => "still active"
+        };
+        write!(f, "{}", verdict)
+    }
+}
+
+/// Records the population of each state at each tick, to give a verdict on the run.
+pub struct RunMonitor {
+    window: usize,
+    quiescent_state: usize,
+    cell_count: usize,
+    initial_population: Option<usize>,
+    history: VecDeque<Vec<usize>> // The population of each state during the last ticks of the window
+}
+
+impl RunMonitor {
+    /// The verdict is based on the last `window` ticks.
+    pub fn new(rules: &Rules, window: usize) -> RunMonitor {
+        RunMonitor {
+            window,
+            quiescent_state: rules.default_state(),
+            cell_count: rules.grid_size().0 * rules.grid_size().1,
+            initial_population: None,
+            history: VecDeque::new()
+        }
+    }
+
+    /// Records the population of each state declared by the user, as given by `Automaton::state_counts`.
+    pub fn record(&mut self, state_counts: Vec<usize>) {
+        if self.initial_population.is_none() {
+            self.initial_population = Some(self.population(&state_counts));
+        }
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(state_counts);
+    }
+
+    pub fn verdict(&self) -> RunVerdict {
+        let last_population = match self.history.back() {
+            Some(state_counts) => self.population(state_counts),
+            None => return RunVerdict::StillActive
+        };
+        if last_population == 0 {
+            RunVerdict::DiedOut
+        } else if last_population >= 2 * self.initial_population.unwrap_or(0) && 2 * last_population >= self.cell_count {
+            RunVerdict::Exploded
+        } else if self.history.len() == self.window && self.history.iter().all(|counts| *counts == self.history[0]) {
+            RunVerdict::Stabilized
+        } else {
+            RunVerdict::StillActive
+        }
+    }
+
+    /// The number of cells that are not in the quiescent state.
+    fn population(&self, state_counts: &[usize]) -> usize {
+        state_counts.iter().enumerate()
+            .filter(|(state, _)| *state != self.quiescent_state)
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+/// The periodic behavior of the pattern formed by the cells that are not in the quiescent (default) state.
+#[derive(Debug, PartialEq)]
+pub enum Periodicity {
+    StillLife,
+    Oscillator { period: usize },
+    /// The pattern comes back in the same shape after the period, moved by the displacement.
+    Spaceship { period: usize, displacement: (isize, isize) }
+}
+
+impl fmt::Display for Periodicity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Periodicity::StillLife => write!(f, "still life"),
+            Periodicity::Oscillator { period } => write!(f, "oscillator of period {}", period),
+            Periodicity::Spaceship { period, displacement } => write!(f, "spaceship of period {}, moving by ({}, {}) every {} ticks",
+                                                                       period, displacement.0, displacement.1, period)
+        }
+    }
+}
+
+/// A pattern cropped to its bounding box, and the position of the bounding box in the grid.
+struct Pattern {
+    iteration: usize,
+    origin: (usize, usize),
+    size: (usize, usize),
+    cells: Vec<usize>
+}
+
+/// Detects when the pattern is periodic, whatever its position, by comparing it with the patterns of the last ticks.
+/// The patterns are indexed by a hash of their shape, so that a tick only costs a hash map lookup and a comparison on collision.
+///
+/// The bounding box of the pattern is computed without wrapping around the grid, so a pattern that crosses the border
+/// is only detected once it is entirely on one side.
+pub struct PeriodDetector {
+    quiescent_state: usize,
+    max_period: usize,
+    patterns: HashMap<u64, Vec<Pattern>>,
+    hashes: VecDeque<u64> // The hashes of the patterns of the last ticks, the oldest first
+}
+
+impl PeriodDetector {
+    /// Periods up to `max_period` ticks are detected.
+    pub fn new(rules: &Rules, max_period: usize) -> PeriodDetector {
+        PeriodDetector {
+            quiescent_state: rules.default_state(),
+            max_period,
+            patterns: HashMap::new(),
+            hashes: VecDeque::new()
+        }
+    }
+
+    /// Records the pattern of the automaton's current grid, and returns its periodicity if it's detected.
+    pub fn record(&mut self, automaton: &Automaton) -> Option<Periodicity> {
+        self.record_grid(&parent_states(automaton), automaton.get_rules().grid_size(), automaton.get_iteration())
+    }
+
+    fn record_grid(&mut self, grid: &[usize], (width, height): (usize, usize), iteration: usize) -> Option<Periodicity> {
+        let pattern = crop(grid, (width, height), self.quiescent_state, iteration)?;
+        let mut hasher = DefaultHasher::new();
+        (pattern.size, &pattern.cells).hash(&mut hasher);
+        let hash = hasher.finish();
+
+        // The most recent identical pattern gives the smallest period.
+        let periodicity = self.patterns.get(&hash)
+            .and_then(|patterns| patterns.iter().rev().find(|p| p.size == pattern.size && p.cells == pattern.cells))
+            .map(|previous| {
+                let period = iteration - previous.iteration;
+                let displacement = (
+                    wrapped_displacement(previous.origin.0, pattern.origin.0, width),
+                    wrapped_displacement(previous.origin.1, pattern.origin.1, height));
+                match (displacement, period) {
+                    ((0, 0), 1) => Periodicity::StillLife,
+                    ((0, 0), _) => Periodicity::Oscillator { period },
+                    _ => Periodicity::Spaceship { period, displacement }
+                }
+            });
+
+        self.patterns.entry(hash).or_default().push(pattern);
+        self.hashes.push_back(hash);
+        if self.hashes.len() > self.max_period {
+            let oldest = self.hashes.pop_front().unwrap();
+            let patterns = self.patterns.get_mut(&oldest).unwrap();
+            patterns.remove(0);
+            if patterns.is_empty() {
+                self.patterns.remove(&oldest);
+            }
+        }
+        periodicity
+    }
+}
+
+/// The iteration from which the whole grid repeats itself, and the period of the repetition, 1 if the grid is frozen.
+#[derive(Debug, PartialEq)]
+pub struct Stabilization {
+    pub iteration: usize,
+    pub period: usize
+}
+
+impl fmt::Display for Stabilization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.period {
+            1 => write!(f, "the grid is frozen since the iteration {}", self.iteration),
+            period => write!(f, "the grid repeats a cycle of period {} since the iteration {}", period, self.iteration)
+        }
+    }
+}
+
+/// Detects when the whole automaton comes back to the state it had at most `max_period` ticks before. Unlike the periodic
+/// patterns, the cells must be at the same place, and the implicit states, the data and the agents are compared too.
+pub struct SteadyStateDetector {
+    max_period: usize,
+    hashes: VecDeque<u64> // The hashes of the states of the last ticks, the oldest first
+}
+
+impl SteadyStateDetector {
+    pub fn new(max_period: usize) -> SteadyStateDetector {
+        SteadyStateDetector { max_period: max_period.max(1), hashes: VecDeque::new() }
+    }
+
+    /// Records the state of the automaton, and returns the stabilization if its state was met during the last ticks.
+    pub fn record(&mut self, automaton: &Automaton) -> Option<Stabilization> {
+        let hash = automaton.state_hash();
+        let stabilization = self.hashes.iter().rev().position(|previous| *previous == hash)
+            .map(|position| Stabilization { iteration: automaton.get_iteration() - position - 1, period: position + 1 });
+        self.hashes.push_back(hash);
+        if self.hashes.len() > self.max_period {
+            self.hashes.pop_front();
+        }
+        stabilization
+    }
+}
+
+/// Returns the pattern of the cells that are not in the quiescent state, or none if there are no such cells.
+fn crop(grid: &[usize], (width, height): (usize, usize), quiescent_state: usize, iteration: usize) -> Option<Pattern> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (width, height, 0, 0);
+    for (index, state) in grid.iter().enumerate() {
+        if *state != quiescent_state {
+            let (x, y) = (index % width, index / width);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    if min_x > max_x {
+        return None;
+    }
+    let size = (max_x - min_x + 1, max_y - min_y + 1);
+    let cells = (min_y..=max_y)
+        .flat_map(|y| grid[y * width + min_x..=y * width + max_x].iter().copied())
+        .collect();
+    Some(Pattern { iteration, origin: (min_x, min_y), size, cells })
+}
+
+/// The displacement between two coordinates on a wrapped axis, the shortest way.
+fn wrapped_displacement(from: usize, to: usize, size: usize) -> isize {
+    let displacement = (to + size - from) % size;
+    if displacement > size / 2 { displacement as isize - size as isize } else { displacement as isize }
+}
+
+/// The measures of a tagged pattern at some tick.
+pub struct TrackedPattern {
+    pub population: usize,
+    /// The bounding box (x, y, width, height), computed without wrapping around the grid. None if the pattern died out.
+    pub bounding_box: Option<(usize, usize, usize, usize)>,
+    /// The relative change of the population since the previous tick. It is 0 if the pattern had already died out.
+    pub growth_rate: f64
+}
+
+/// Tracks the cells descended from the cells of a region, with a marker per cell.
+/// At each tick, a cell that is not in the quiescent (default) state is marked if it or one of its neighbors was marked.
+pub struct PatternTracker {
+    quiescent_state: usize,
+    markers: Vec<bool>,
+    population: usize
+}
+
+impl PatternTracker {
+    /// Tags the cells of the region that are not in the quiescent state. Regions that go past the grid's borders are wrapped around.
+    pub fn new(automaton: &Automaton, (x, y, width, height): (usize, usize, usize, usize)) -> PatternTracker {
+        let rules = automaton.get_rules();
+        let quiescent_state = rules.default_state();
+        let grid = parent_states(automaton);
+        let (grid_width, grid_height) = rules.grid_size();
+        let mut markers = vec![false; grid.len()];
+        for v in y..(y + height) {
+            for u in x..(x + width) {
+                let index = (v % grid_height) * grid_width + u % grid_width;
+                markers[index] = grid[index] != quiescent_state;
+            }
+        }
+        let population = markers.iter().filter(|marked| **marked).count();
+        PatternTracker { quiescent_state, markers, population }
+    }
+
+    pub fn population(&self) -> usize {
+        self.population
+    }
+
+    /// Updates the markers with the automaton's current grid, which must be one tick after the previous update.
+    pub fn record(&mut self, automaton: &Automaton) -> TrackedPattern {
+        self.record_grid(&parent_states(automaton), automaton.get_rules().grid_size())
+    }
+
+    fn record_grid(&mut self, grid: &[usize], size: (usize, usize)) -> TrackedPattern {
+        let markers: Vec<bool> = (0..grid.len())
+            .map(|index| grid[index] != self.quiescent_state
+                && (self.markers[index] || neighbors(index, size).any(|neighbor| self.markers[neighbor])))
+            .collect();
+        let marked_grid: Vec<usize> = markers.iter()
+            .map(|marked| if *marked { 1 } else { 0 })
+            .collect();
+        let bounding_box = crop(&marked_grid, size, 0, 0)
+            .map(|pattern| (pattern.origin.0, pattern.origin.1, pattern.size.0, pattern.size.1));
+        let population = markers.iter().filter(|marked| **marked).count();
+        let growth_rate = if self.population == 0 { 0.0 } else { (population as f64 - self.population as f64) / self.population as f64 };
+        self.markers = markers;
+        self.population = population;
+        TrackedPattern { population, bounding_box, growth_rate }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analysis::{compute_metrics, analyze_rules, RunMonitor, RunVerdict, PeriodDetector, Periodicity, PatternTracker, SteadyStateDetector, Stabilization};
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+
+    static CLUSTER_FILE: &str = "resources/tests/analysis_cluster.txt";
+    static GAME_OF_LIFE_FILE: &str = "resources/game_of_life.txt";
+    static BLINKER_FILE: &str = "resources/tests/analysis_blinker.txt";
+
+    #[test]
+    fn compute_metrics_of_a_box() {
+        let automaton = Automaton::new(parse(CLUSTER_FILE).unwrap());
+        let metrics = compute_metrics(&automaton, 1);
+        assert_eq!(metrics.cluster_count, 1);
+        assert_eq!(metrics.mean_cluster_size, 9.0);
+        assert!(metrics.spatial_entropy > 0.0);
+
+        let metrics = compute_metrics(&automaton, 2);
+        assert_eq!(metrics.cluster_count, 0);
+        assert_eq!(metrics.mean_cluster_size, 0.0);
+    }
+
+    #[test]
+    fn analyze_rules_of_game_of_life() {
+        // With uniformly random neighborhoods, a cell is alive at the next iteration with probability 140/512 (about 0.27).
+        let report = analyze_rules(&parse(GAME_OF_LIFE_FILE).unwrap(), 20000, 42);
+        assert!((report.lambda - 0.27).abs() < 0.02);
+        assert!(report.activity > report.lambda);
+
+        let report = analyze_rules(&parse(CLUSTER_FILE).unwrap(), 1000, 42);
+        assert_eq!(report.activity, 0.0);
+
+        // A dead cell is born with at least one of its 26 neighbors alive, which is almost certain.
+        let report = analyze_rules(&parse("resources/tests/automaton_3d.txt").unwrap(), 1000, 42);
+        assert_eq!(report.lambda, 1.0);
+    }
+
+    #[test]
+    fn run_monitor_verdicts() {
+        let rules = parse(CLUSTER_FILE).unwrap();
+        let mut monitor = RunMonitor::new(&rules, 3);
+        monitor.record(vec![391, 9, 0]);
+        monitor.record(vec![390, 10, 0]);
+        assert_eq!(monitor.verdict(), RunVerdict::StillActive);
+        monitor.record(vec![390, 10, 0]);
+        assert_eq!(monitor.verdict(), RunVerdict::StillActive);
+        monitor.record(vec![390, 10, 0]);
+        assert_eq!(monitor.verdict(), RunVerdict::Stabilized);
+        monitor.record(vec![100, 200, 100]);
+        assert_eq!(monitor.verdict(), RunVerdict::Exploded);
+        monitor.record(vec![400, 0, 0]);
+        assert_eq!(monitor.verdict(), RunVerdict::DiedOut);
+    }
+
+    #[test]
+    fn detect_oscillator() {
+        let mut automaton = Automaton::new(parse(BLINKER_FILE).unwrap());
+        let mut detector = PeriodDetector::new(automaton.get_rules(), 10);
+        assert_eq!(detector.record(&automaton), None);
+        automaton.tick();
+        assert_eq!(detector.record(&automaton), None);
+        automaton.tick();
+        assert_eq!(detector.record(&automaton), Some(Periodicity::Oscillator { period: 2 }));
+    }
+
+    #[test]
+    fn detect_steady_state() {
+        // The blinker repeats a cycle of period 2, which is only a steady state if such cycles are detected.
+        let mut automaton = Automaton::new(parse(BLINKER_FILE).unwrap());
+        let mut frozen_detector = SteadyStateDetector::new(1);
+        let mut cycle_detector = SteadyStateDetector::new(2);
+        assert_eq!(cycle_detector.record(&automaton), None);
+        assert_eq!(frozen_detector.record(&automaton), None);
+        automaton.tick();
+        assert_eq!(cycle_detector.record(&automaton), None);
+        assert_eq!(frozen_detector.record(&automaton), None);
+        automaton.tick();
+        assert_eq!(cycle_detector.record(&automaton), Some(Stabilization { iteration: 0, period: 2 }));
+        assert_eq!(frozen_detector.record(&automaton), None);
+
+        // The burning cell counts down its delay in implicit states before the grid freezes.
+        let mut automaton = Automaton::new(parse("resources/tests/semantic_delay.txt").unwrap());
+        let mut detector = SteadyStateDetector::new(1);
+        let stabilization = (0..10).find_map(|_| {
+            automaton.tick();
+            detector.record(&automaton)
+        });
+        assert_eq!(stabilization, Some(Stabilization { iteration: 4, period: 1 }));
+        assert_eq!(stabilization.unwrap().to_string(), "the grid is frozen since the iteration 4");
+    }
+
+    #[test]
+    fn detect_spaceship_across_the_border() {
+        let rules = parse(CLUSTER_FILE).unwrap();
+        let mut detector = PeriodDetector::new(&rules, 10);
+        let mut grid = vec![0; 400];
+        grid[20 + 19] = 1;
+        grid[40 + 19] = 1;
+        assert_eq!(detector.record_grid(&grid, (20, 20), 0), None);
+        let mut grid = vec![0; 400];
+        grid[20] = 1;
+        grid[40] = 1;
+        assert_eq!(detector.record_grid(&grid, (20, 20), 1), Some(Periodicity::Spaceship { period: 1, displacement: (1, 0) }));
+    }
+
+    #[test]
+    fn track_tagged_pattern() {
+        let mut automaton = Automaton::new(parse(BLINKER_FILE).unwrap());
+        let mut tracker = PatternTracker::new(&automaton, (4, 4, 3, 5));
+        let mut untagged = PatternTracker::new(&automaton, (10, 10, 5, 5));
+        assert_eq!(tracker.population(), 3);
+        assert_eq!(untagged.population(), 0);
+
+        automaton.tick();
+        let pattern = tracker.record(&automaton);
+        assert_eq!(pattern.population, 3);
+        assert_eq!(pattern.bounding_box, Some((4, 6, 3, 1)));
+        assert_eq!(pattern.growth_rate, 0.0);
+        let pattern = untagged.record(&automaton);
+        assert_eq!(pattern.population, 0);
+        assert_eq!(pattern.bounding_box, None);
+
+        automaton.tick();
+        assert_eq!(tracker.record(&automaton).bounding_box, Some((5, 5, 1, 3)));
+    }
+}
@@ -1,62 +1,195 @@
-use crate::compiler::semantic::{State, Rules, Condition, StateDistribution};
-use crate::compiler::parser::{NeighborCell, ComparisonOperator};
-use rand::{Rng, rngs::ThreadRng};
+use crate::compiler::semantic::{State, Rules, Condition, StateDistribution, Region, RegionKind, Transition, Agent, Effects};
+use crate::compiler::parser::{CountedCells, NeighborCell, Heading, RandomDraw, TieBreak};
+use crate::rng::CounterRng;
+use crate::checkpoint::Checkpoint;
+use crate::interop::{load_image, RlePattern};
+use crate::simulation::{Snapshot, SharedGrid};
+use crate::topology::{Topology, get_index};
+use rand::Rng;
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
 use rayon::prelude::*;
 
-#[derive(Clone)]
-pub struct Cell {
-    state: usize,
-    index_in_grid: usize,
-    position: (usize, usize)
+/// Salt of the seed from which the noise draws are derived, so they are independent of the random conditions draws.
+const NOISE_SEED_SALT: u64 = 0x6E6F_6973_6500_0000;
+
+/// The number of outcomes the transition cache holds. When it is full, it starts again from the outcomes of the last tick.
+const TRANSITION_CACHE_CAPACITY: usize = 1 << 16;
+
+/// The largest number of entries of a transition table. The rules that would need more are interpreted.
+const TRANSITION_TABLE_CAPACITY: usize = 1 << 18;
+
+/// How the cells at the borders of the world see their neighbors outside of the world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BoundaryMode {
+    Wrap, // The world is a tore
+    Wall(usize), // The cells outside of the world are in this state
+    Mirror // The cells outside of the world reflect the cells inside, the cell -1 reflects the cell 0
+}
+
+impl BoundaryMode {
+    /// Returns the state of the cell at the position, which can be outside of the world.
+    fn state(self, states: &[usize], topology: &Topology, (x, y): (isize, isize)) -> usize {
+        let (width, height) = topology.size();
+        match self {
+            BoundaryMode::Wall(state) if !is_inside((x, y), (width, height)) => state,
+            BoundaryMode::Mirror => states[topology.index((mirror(x, width), mirror(y, height)))],
+            _ => states[topology.index((x, y))]
+        }
+    }
+
+    /// Returns the index of the cell at the position, or None if it is outside of a world that doesn't wrap around.
+    fn index(self, topology: &Topology, position: (isize, isize)) -> Option<usize> {
+        match self {
+            BoundaryMode::Wrap => Some(topology.index(position)),
+            _ if is_inside(position, topology.size()) => Some(topology.index(position)),
+            _ => None
+        }
+    }
+}
+
+fn is_inside((x, y): (isize, isize), (width, height): (usize, usize)) -> bool {
+    x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height
+}
+
+/// Reflects the coordinate into [0; size(.
+fn mirror(value: isize, size: usize) -> isize {
+    let period = 2 * size as isize;
+    let value = value.rem_euclid(period);
+    if value < size as isize { value } else { period - 1 - value }
 }
 
+/// The grids are flat buffers of states, stored row after row.
 pub struct Automaton {
-    grid: Vec<Cell>,
-    grid_next: Vec<Cell>,
+    grid: Vec<usize>,
+    grid_next: Vec<usize>,
+    grid_previous: Vec<usize>, // Only used by second-order automatons and automatons with memory
+    data: Vec<u8>, // Only used if the rules declare a data channel
+    data_next: Vec<u8>,
+    ages: Vec<u32>, // The number of ticks each cell has spent in its state, only used if a state has a color gradient
+
     rules: Rules,
+    regions: Vec<Region>,
+    agents: Vec<Agent>,
+    distances: Vec<Vec<u8>>, // Distance field of each state used by a distance condition, empty for the other states
+    distances_iteration: Option<usize>, // The iteration the distance fields were computed for
+    topology: Topology,
+    iteration: usize,
+    seed: u64,
+    traced_cell: Option<(usize, usize)>,
+    trace: Vec<String>,
+    shared_grid: Option<SharedGrid>, // Once shared, the grid is published after each tick
+    transition_cache: Option<TransitionCache>, // If set, the outcomes of the transitions are memoized across the ticks
+    transition_table: Option<TransitionTable>, // Set if the rules only count the neighbors, see TransitionTable
 }
 
 impl Automaton {
     pub fn new(rules: Rules) -> Automaton {
-        let size = &rules.world_size;
-        let states = &rules.states;
+        Self::with_seed(rules, rand::random())
+    }
 
-        // Initialize grid with default state.
-        let default_state = rules.states.iter()
-            .find(|s| match s.distribution {
-                StateDistribution::Default => true,
-                _ => false
-            })
-            .unwrap().id;
-        let mut grid = Vec::new();
-        for i in 0..(size.0 * size.1) {
-            grid.push(Cell{
-                state: default_state,
-                index_in_grid: i,
-                position: get_position(i, *size)
-            });
-        }
+    /// Creates the automaton with an initial distribution of the states and random conditions draws derived from the seed.
+    /// Panics if the world has no cell, which the semantic analysis refuses.
+    pub fn with_seed(rules: Rules, seed: u64) -> Automaton {
+        let (width, height) = rules.grid_size();
+        assert!(width > 0 && height > 0, "The world must have at least a cell in each dimension, but its size is ({}, {}).", width, height);
+        let mut automaton = Automaton {
+            grid: Vec::new(),
+            grid_next: Vec::new(),
+            grid_previous: Vec::new(),
+            data: Vec::new(),
+            data_next: Vec::new(),
+            ages: Vec::new(),
+            topology: Topology::new(rules.grid_size()),
+            regions: Vec::new(),
+            agents: Vec::new(),
+            distances: Vec::new(),
+            distances_iteration: None,
+            rules,
+            iteration: 0,
+            seed,
+            traced_cell: None,
+            trace: Vec::new(),
+            shared_grid: None,
+            transition_cache: None,
+            transition_table: None,
+        };
+        automaton.transition_table = TransitionTable::new(&automaton.rules);
+        automaton.reset(seed);
+        automaton
+    }
+
+    /// Returns an estimate in bytes of the memory an automaton with the rules needs for its cells : the grids of states,
+    /// the data channel and the distance fields. The estimate can be made before creating the automaton.
+    pub fn estimate_memory(rules: &Rules) -> usize {
+        let (width, height) = rules.grid_size();
+        let grids = 2 + (rules.second_order || rules.memory) as usize + rules.initial_grid.is_some() as usize;
+        let data = if rules.data_channel.is_some() { 2 } else { 0 };
+        let ages = if rules.has_gradients() { size_of::<u32>() } else { 0 };
+        let bytes_per_cell = grids * size_of::<usize>() + data + ages + rules.distance_states().len();
+        width.saturating_mul(height).saturating_mul(bytes_per_cell)
+    }
+
+    /// Returns the memory in bytes allocated for the cells, see `estimate_memory`.
+    pub fn memory_usage(&self) -> usize {
+        let grids = [&self.grid, &self.grid_next, &self.grid_previous].iter().map(|grid| grid.capacity()).sum::<usize>()
+            + self.rules.initial_grid.as_ref().map_or(0, Vec::capacity);
+        let bytes = self.data.capacity() + self.data_next.capacity() + self.distances.iter().map(Vec::capacity).sum::<usize>();
+        grids * size_of::<usize>() + self.ages.capacity() * size_of::<u32>() + bytes
+    }
 
-        // Add the states that have a proportion distribution.
-        Self::add_p_distribution_states(states, &mut grid, *size);
+    /// Reinitializes the automaton at its first iteration, as declared in the rules, with a new seed.
+    /// The regions and agents added since the creation are removed.
+    pub fn reset(&mut self, seed: u64) {
+        let rules = &self.rules;
+        let size = &rules.grid_size();
+        let states = &rules.states;
+        // The initial distribution uses a stream of random numbers that the ticks don't use.
+        let mut rng = CounterRng::new(seed, u64::MAX, 0);
 
-        // Add the states that have a box distribution.
-        Self::add_box_distribution_states(states, &mut grid, *size);
+        let mut grid = match &rules.initial_grid {
+            // The initial grid read from a file replaces the distributions.
+            Some(grid) => grid.clone(),
+            None => {
+                // Give each cell a state that has a proportion distribution. The default state gets the remaining proportion.
+                let mut grid = Self::p_distribution_grid(states, rules.default_state(), *size, &mut rng);
 
-        // Add the states that have a quantity distribution. They can overwrite states without a quantity distribution.
-        Self::add_q_distribution_states(states, &mut grid, *size);
+                // Add the states that have a box, a circle or a line distribution.
+                Self::add_shape_distribution_states(states, &mut grid, *size);
 
-        let grid_next = grid.clone();
+                // Add the states that have an image distribution, on the pixels of their color.
+                Self::add_image_distribution_states(states, &mut grid, *size);
 
-        Automaton {
-            grid,
-            grid_next,
-            rules,
+                // Add the states that have a quantity distribution. They can overwrite states without a quantity distribution.
+                Self::add_q_distribution_states(states, &mut grid, *size, &mut rng);
+                grid
+            }
+        };
+        // The cells of the pattern replace the cells given by the distributions or the initial grid.
+        for ((x, y), state) in &rules.initial_cells {
+            grid[y * size.0 + x] = *state;
         }
+
+        self.grid_next = grid.clone();
+        // The grid before the first iteration is considered identical to the initial grid.
+        self.grid_previous = if rules.second_order || rules.memory { grid.clone() } else { Vec::new() };
+        self.data = if rules.data_channel.is_some() { vec![0; grid.len()] } else { Vec::new() };
+        self.data_next = self.data.clone();
+        self.ages = if rules.has_gradients() { vec![0; grid.len()] } else { Vec::new() };
+        self.grid = grid;
+        self.regions = rules.regions.clone();
+        self.agents = rules.agents.clone();
+        self.distances_iteration = None;
+        self.iteration = 0;
+        self.seed = seed;
+        self.publish();
+        self.trace.clear();
     }
 
-    fn add_p_distribution_states(states: &[State], grid: &mut Vec<Cell>, size: (usize, usize)) {
-        let mut rng = rand::thread_rng();
+    fn p_distribution_grid(states: &[State], default_state: usize, size: (usize, usize), rng: &mut CounterRng) -> Vec<usize> {
+        let mut grid = vec![0; size.0 * size.1];
         for x in 0..size.0 {
             for y in 0..size.1 {
                 let index = get_index((x as isize, y as isize), size);
@@ -68,30 +201,54 @@ impl Automaton {
                     if let StateDistribution::Proportion(p) = state.distribution {
                         upper_bound += p;
                         if r_p >= lower_bound && r_p < upper_bound {
-                            grid[index].state = i;
+                            grid[index] = i;
                         }
                         lower_bound = upper_bound;
                     }
                 }
+                // The ranges of the proportions follow each other from 0, so the cells out of them get the remaining proportion.
+                if r_p >= upper_bound {
+                    grid[index] = default_state;
+                }
             }
         }
+        grid
     }
 
-    fn add_box_distribution_states(states: &[State], grid: &mut Vec<Cell>, size: (usize, usize)) {
+    fn add_shape_distribution_states(states: &[State], grid: &mut [usize], size: (usize, usize)) {
         for (i, state) in states.iter().enumerate() {
-            if let StateDistribution::Box(x_box, y_box, width, height) = state.distribution {
-                for x in x_box..(x_box + width) {
-                    for y in y_box..(y_box + height) {
-                        let index = get_index((x as isize, y as isize), size);
-                        grid[index].state = i;
+            let cells = match state.distribution {
+                StateDistribution::Box(x_box, y_box, width, height) =>
+                    (x_box..(x_box + width)).flat_map(|x| (y_box..(y_box + height)).map(move |y| (x, y))).collect(),
+                StateDistribution::Circle(x_center, y_center, radius) => circle_cells((x_center, y_center), radius),
+                StateDistribution::Line(x1, y1, x2, y2) => line_cells((x1, y1), (x2, y2)),
+                _ => Vec::new()
+            };
+            for (x, y) in cells {
+                let index = get_index((x as isize, y as isize), size);
+                grid[index] = i;
+            }
+        }
+    }
+
+    fn add_image_distribution_states(states: &[State], grid: &mut [usize], size: (usize, usize)) {
+        for (i, state) in states.iter().enumerate() {
+            if let StateDistribution::Image(file_name) = &state.distribution {
+                // The size of the image was checked by the semantic analysis.
+                if let Ok((image_size, pixels)) = load_image(file_name) {
+                    if image_size == size {
+                        for (cell, pixel) in grid.iter_mut().zip(pixels) {
+                            if pixel == state.color {
+                                *cell = i;
+                            }
+                        }
                     }
                 }
             }
         }
     }
 
-    fn add_q_distribution_states(states: &[State], grid: &mut Vec<Cell>, size: (usize, usize)) {
-        let mut rng = rand::thread_rng();
+    fn add_q_distribution_states(states: &[State], grid: &mut [usize], size: (usize, usize), rng: &mut CounterRng) {
         let mut positions_used = Vec::new();
         for (i, state) in states.iter().enumerate() {
             if let StateDistribution::Quantity(q) = state.distribution {
@@ -100,7 +257,7 @@ impl Automaton {
                     let pos = (rng.gen_range(0, size.0), rng.gen_range(0, size.1));
                     if !positions_used.contains(&pos) {
                         let index = get_index((pos.0 as isize, pos.1 as isize), size);
-                        grid[index].state = i;
+                        grid[index] = i;
                         positions_used.push(pos);
                         c += 1;
                     }
@@ -110,78 +267,808 @@ impl Automaton {
     }
 
     pub fn tick(&mut self) {
-        let rules = &self.rules;
-        let grid = &self.grid;
+        self.update_distances();
+        let previous_states = if self.rules.memory { &self.grid_previous[..] } else { &[] };
+        let grid = GridView { states: &self.grid, previous_states, data: &self.data, distances: &self.distances, topology: &self.topology, boundary: self.rules.boundary, layer_height: self.rules.world_size.1 };
+        match &mut self.transition_cache {
+            Some(cache) => {
+                let outcomes = Self::apply_cached_rules(&self.rules, grid, cache, &mut self.grid_next);
+                cache.extend(outcomes);
+            },
+            None => match &self.transition_table {
+                Some(table) => Self::apply_table_rules(&self.rules, grid, table, &mut self.grid_next),
+                None => Self::apply_rules(&self.rules, grid, self.seed, self.iteration, &mut self.grid_next, &mut self.data_next)
+            }
+        }
 
-        self.grid_next.par_iter_mut().for_each(|cell| {
-            let mut rng = rand::thread_rng();
-            for (state_origin, state_destination, conditions) in &rules.transitions {
-                if state_origin == &grid[cell.index_in_grid].state && rules.evaluate_conditions(grid, cell.position, conditions, &mut rng) {
-                    cell.state = *state_destination;
-                    break;
+        // The traced cell is evaluated again, sequentially, to record why it took its new state. It draws the same random
+        // numbers as the first evaluation, but only the record is kept, so the trace never changes the simulation.
+        if let Some(position) = self.traced_cell {
+            let index = self.topology.index((position.0 as isize, position.1 as isize));
+            let mut rng = CounterRng::new(self.seed, self.iteration as u64, index as u64);
+            self.trace = self.rules.trace_transitions(grid, position, self.iteration, &mut rng);
+        }
+
+        if self.rules.transitions.iter().any(|(_, _, _, effects)| !effects.neighbors.is_empty()) {
+            Self::apply_effects(&self.rules, grid, self.seed, self.iteration, &mut self.grid_next);
+        }
+
+        if self.rules.transitions.iter().any(|(_, _, _, effects)| effects.swap.is_some()) {
+            Self::apply_swaps(&self.rules, grid, self.seed, self.iteration, &mut self.grid_next, &mut self.data_next);
+        }
+
+        if self.rules.noise > 0.0 {
+            Self::apply_noise(&self.rules, self.seed, self.iteration, &mut self.grid_next);
+        }
+
+        if !self.agents.is_empty() {
+            Self::move_agents(&self.rules, &mut self.agents, &mut self.grid_next, &self.topology);
+        }
+
+        for region in &self.regions {
+            apply_region(region, &self.grid, &mut self.grid_next, &self.topology);
+        }
+
+        if self.rules.second_order {
+            // Fredkin's construction : the new state is the state given by the rules minus the previous state.
+            subtract_states(&mut self.grid_next, &self.grid_previous, self.rules.states.len());
+        }
+        for ((age, state), next_state) in self.ages.iter_mut().zip(&self.grid).zip(&self.grid_next) {
+            *age = if state == next_state { age.saturating_add(1) } else { 0 };
+        }
+        if self.rules.second_order || self.rules.memory {
+            std::mem::swap(&mut self.grid_previous, &mut self.grid);
+        }
+        std::mem::swap(&mut self.grid, &mut self.grid_next);
+        std::mem::swap(&mut self.data, &mut self.data_next);
+        self.iteration += 1;
+        self.publish();
+    }
+
+    /// Steps the automaton one iteration backward. Only second-order automatons are reversible.
+    pub fn tick_backward(&mut self) -> Result<(), String> {
+        if !self.rules.second_order {
+            return Err("Only second-order automatons can be stepped backward.".to_string());
+        }
+        if self.iteration == 0 {
+            return Err("The automaton is already at its first iteration.".to_string());
+        }
+
+        // If next = rules(current) - previous, then previous = rules(current) - next.
+        let distances = distance_fields(&self.rules, &self.grid_previous, &self.topology);
+        let grid = GridView { states: &self.grid_previous, previous_states: &[], data: &[], distances: &distances, topology: &self.topology, boundary: self.rules.boundary, layer_height: self.rules.world_size.1 };
+        Self::apply_rules(&self.rules, grid, self.seed, self.iteration - 1, &mut self.grid_next, &mut []);
+        subtract_states(&mut self.grid_next, &self.grid, self.rules.states.len());
+        // The ages can't be rewound : the cells that change start again at 0, the others keep their age.
+        for ((age, state), previous_state) in self.ages.iter_mut().zip(&self.grid).zip(&self.grid_previous) {
+            if state != previous_state {
+                *age = 0;
+            }
+        }
+        std::mem::swap(&mut self.grid, &mut self.grid_previous);
+        std::mem::swap(&mut self.grid_previous, &mut self.grid_next);
+        self.iteration -= 1;
+        self.publish();
+        Ok(())
+    }
+
+    /// Computes the state given by the rules for each cell of the grid, and its data if the rules declare a data channel.
+    fn apply_rules(rules: &Rules, grid: GridView, seed: u64, iteration: usize, grid_next: &mut [usize], data_next: &mut [u8]) {
+        // Each rayon task processes a whole row, which avoids false sharing between tasks and per-cell task overhead.
+        // The `bench` command measures the throughput of the ticks.
+        let width = rules.world_size.0;
+        if data_next.is_empty() {
+            grid_next.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+                for (x, next_state) in row.iter_mut().enumerate() {
+                    let mut rng = CounterRng::new(seed, iteration as u64, (y * width + x) as u64);
+                    *next_state = rules.next_state(grid, (x, y), &mut rng).0;
+                }
+            });
+        } else {
+            grid_next.par_chunks_mut(width).zip(data_next.par_chunks_mut(width)).enumerate().for_each(|(y, (row, data_row))| {
+                for (x, (next_state, next_data)) in row.iter_mut().zip(data_row.iter_mut()).enumerate() {
+                    let index = y * width + x;
+                    let mut rng = CounterRng::new(seed, iteration as u64, index as u64);
+                    let (state, set_data) = rules.next_state(grid, (x, y), &mut rng);
+                    *next_state = state;
+                    *next_data = set_data.unwrap_or(grid.data[index]);
+                }
+            });
+        }
+    }
+
+    /// Same as `apply_rules`, but the new states are looked up in the transition table instead of evaluating the conditions.
+    fn apply_table_rules(rules: &Rules, grid: GridView, table: &TransitionTable, grid_next: &mut [usize]) {
+        let width = rules.world_size.0;
+        grid_next.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+            for (x, next_state) in row.iter_mut().enumerate() {
+                *next_state = table.next_state(rules, grid, (x, y));
+            }
+        });
+    }
+
+    /// Same as `apply_rules`, but the new states are looked up in the cache. Returns the outcomes computed for each row,
+    /// by the key of the cells they depend on, which the cache doesn't have yet.
+    fn apply_cached_rules(rules: &Rules, grid: GridView, cache: &TransitionCache, grid_next: &mut [usize]) -> Vec<HashMap<u64, usize>> {
+        let width = rules.world_size.0;
+        grid_next.par_chunks_mut(width).enumerate().map(|(y, row)| {
+            let mut computed = HashMap::new();
+            for (x, next_state) in row.iter_mut().enumerate() {
+                let key = cache.key(rules, grid, (x, y));
+                *next_state = match cache.outcomes.get(&key).or_else(|| computed.get(&key)) {
+                    Some(state) => *state,
+                    None => {
+                        // The conditions of cached rules don't draw random numbers.
+                        let state = rules.next_state(grid, (x, y), &mut CounterRng::new(0, 0, 0)).0;
+                        computed.insert(key, state);
+                        state
+                    }
+                };
+            }
+            computed
+        }).collect()
+    }
+
+    /// Memoizes the new state of the cells by the states of the cells their transitions depend on, which speeds up the
+    /// rules whose conditions are costly to evaluate on worlds with repetitive local configurations. The rules must be
+    /// deterministic : their conditions can only test the states of the cell and of its neighbors, and they have no data channel.
+    pub fn enable_transition_cache(&mut self) -> Result<(), String> {
+        self.transition_cache = Some(TransitionCache::new(&self.rules)?);
+        Ok(())
+    }
+
+    /// Returns whether the new states are looked up in a transition table, which is the case for the deterministic rules
+    /// whose conditions only count the neighbors in each state.
+    pub fn has_transition_table(&self) -> bool {
+        self.transition_table.is_some()
+    }
+
+    /// Returns the number of outcomes in the transition cache, None if it is not enabled.
+    pub fn transition_cache_size(&self) -> Option<usize> {
+        self.transition_cache.as_ref().map(|cache| cache.outcomes.len())
+    }
+
+    /// Computes the distance fields needed by the distance conditions, if they are not up to date.
+    fn update_distances(&mut self) {
+        if self.distances_iteration != Some(self.iteration) {
+            self.distances = distance_fields(&self.rules, &self.grid, &self.topology);
+            self.distances_iteration = Some(self.iteration);
+        }
+    }
+
+    /// Returns the distance from the cell to the closest cell in the state, or none if no distance condition refers to the state.
+    pub fn get_distance(&mut self, state: usize, x: isize, y: isize) -> Option<u8> {
+        self.update_distances();
+        self.distances.get(state)?.get(self.topology.index((x, y))).copied()
+    }
+
+    /// Returns the data of the cell, or none if the rules don't declare a data channel.
+    pub fn get_data(&self, x: isize, y: isize) -> Option<u8> {
+        self.data.get(self.topology.index((x, y))).copied()
+    }
+
+    /// Writes the effects of the triggered transitions into the neighbors, once all the cells are updated.
+    /// An effect overrides the state the target cell got from its own transitions. If several effects target the same cell,
+    /// the cells are processed row after row and the last one wins, so the result doesn't depend on the number of threads.
+    fn apply_effects(rules: &Rules, grid: GridView, seed: u64, iteration: usize, grid_next: &mut [usize]) {
+        let width = rules.world_size.0;
+        for (index, state) in grid.states.iter().enumerate() {
+            // Only the cells that can trigger a transition with effects are evaluated again.
+            if !rules.transitions.iter().any(|(origin, _, _, effects)| origin == state && !effects.neighbors.is_empty()) {
+                continue;
+            }
+            let (x, y) = (index % width, index / width);
+            let mut rng = CounterRng::new(seed, iteration as u64, index as u64);
+            if let Some((_, _, _, effects)) = rules.triggered_transition(grid, (x, y), &mut rng) {
+                for (neighbor, neighbor_state) in &effects.neighbors {
+                    // The effects on the cells outside of the world are lost.
+                    if let Some(neighbor_index) = rules.boundary.index(grid.topology, get_neighbor_position((x as isize, y as isize), *neighbor)) {
+                        grid_next[neighbor_index] = *neighbor_state;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Exchanges the states of the cells that triggered a swap transition and their neighbors, once all the cells are updated.
+    /// A cell takes part in at most one swap, so the quantity of each state is conserved. The swaps are resolved row after row :
+    /// a swap is rejected if the cell or its neighbor already takes part in a swap, so the first one requested wins.
+    fn apply_swaps(rules: &Rules, grid: GridView, seed: u64, iteration: usize, grid_next: &mut [usize], data_next: &mut [u8]) {
+        let width = rules.world_size.0;
+        let mut swapped = vec![false; grid.states.len()];
+        for (index, state) in grid.states.iter().enumerate() {
+            // Only the cells that can trigger a swap transition are evaluated again.
+            if swapped[index] || !rules.transitions.iter().any(|(origin, _, _, effects)| origin == state && effects.swap.is_some()) {
+                continue;
+            }
+            let (x, y) = (index % width, index / width);
+            let mut rng = CounterRng::new(seed, iteration as u64, index as u64);
+            if let Some((_, _, _, effects)) = rules.triggered_transition(grid, (x, y), &mut rng) {
+                let neighbor_index = effects.swap.and_then(|neighbor| rules.boundary.index(grid.topology, get_neighbor_position((x as isize, y as isize), neighbor)));
+                if let Some(neighbor_index) = neighbor_index {
+                    if !swapped[neighbor_index] && neighbor_index != index {
+                        swapped[index] = true;
+                        swapped[neighbor_index] = true;
+                        grid_next[index] = grid.states[neighbor_index];
+                        grid_next[neighbor_index] = grid.states[index];
+                        if !data_next.is_empty() {
+                            data_next[index] = grid.data[neighbor_index];
+                            data_next[neighbor_index] = grid.data[index];
+                        }
+                    }
                 }
             }
+        }
+    }
+
+    /// Each agent in turn applies the first rule that matches the new state of its cell and its mode, then moves forward.
+    fn move_agents(rules: &Rules, agents: &mut [Agent], grid_next: &mut [usize], topology: &Topology) {
+        let width = rules.world_size.0;
+        for agent in agents {
+            let (x, y) = (agent.position.0 as isize, agent.position.1 as isize);
+            let index = topology.index((x, y));
+            let rule = rules.agent_rules.iter()
+                .find(|r| rules.is_state(grid_next[index], r.cell_state) && r.mode.is_none_or(|mode| mode == agent.mode));
+            if let Some(rule) = rule {
+                agent.heading = agent.heading.turn(rule.turn);
+                grid_next[index] = rule.new_cell_state;
+                agent.mode = rule.next_mode.unwrap_or(agent.mode);
+            }
+            let next_index = topology.index(match agent.heading {
+                Heading::Up => (x, y - 1),
+                Heading::Right => (x + 1, y),
+                Heading::Down => (x, y + 1),
+                Heading::Left => (x - 1, y)
+            });
+            agent.position = (next_index % width, next_index / width);
+        }
+    }
+
+    /// Returns the agents, with their position and heading at the current iteration.
+    pub fn get_agents(&self) -> &[Agent] {
+        &self.agents
+    }
+
+    /// Flips each cell to a random user state with the probability given by the "noise" directive.
+    fn apply_noise(rules: &Rules, seed: u64, iteration: usize, grid_next: &mut [usize]) {
+        let state_count = rules.user_states().len();
+        grid_next.par_iter_mut().enumerate().for_each(|(index, state)| {
+            let mut rng = CounterRng::new(seed ^ NOISE_SEED_SALT, iteration as u64, index as u64);
+            if rng.gen::<f64>() < rules.noise {
+                *state = rng.gen_range(0, state_count);
+            }
         });
+    }
+
+    /// Marks a rectangular region whose cells are never updated.
+    pub fn add_frozen_region(&mut self, x: usize, y: usize, width: usize, height: usize) -> Result<(), String> {
+        self.add_region(Region { x, y, width, height, kind: RegionKind::Frozen })
+    }
+
+    /// Marks a rectangular region whose cells are reset to the given state at each tick.
+    pub fn add_source_region(&mut self, x: usize, y: usize, width: usize, height: usize, state: usize) -> Result<(), String> {
+        if state >= self.rules.states.len() {
+            return Err(format!("The state {} doesn't exist.", state));
+        }
+        self.add_region(Region { x, y, width, height, kind: RegionKind::Source(state) })
+    }
+
+    fn add_region(&mut self, region: Region) -> Result<(), String> {
+        if self.rules.second_order {
+            return Err("A second-order automaton cannot have frozen or source regions, because it must be reversible.".to_string());
+        }
+        self.regions.push(region);
+        Ok(())
+    }
+
+    /// Removes all the frozen and source regions, including the ones declared in the rules.
+    pub fn clear_regions(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Sets the seed from which the random conditions draws are derived.
+    /// With the same seed, the random conditions give the same results whatever the number of threads.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Selects a cell whose transitions evaluation is recorded at each tick, or none to stop tracing.
+    pub fn set_traced_cell(&mut self, position: Option<(usize, usize)>) {
+        self.traced_cell = position;
+        self.trace.clear();
+    }
 
-        for index in 0..self.grid.len() {
-            self.grid[index].state = self.grid_next[index].state;
+    /// Returns the record of the traced cell's transitions evaluation during the last tick.
+    pub fn get_trace(&self) -> &[String] {
+        &self.trace
+    }
+
+    /// Returns the whole state of the automaton, with the iteration the run is to stop at.
+    pub fn checkpoint(&self, stop_iteration: Option<usize>) -> Checkpoint {
+        Checkpoint {
+            world_size: self.rules.grid_size(),
+            state_count: self.rules.states.len(),
+            seed: self.seed,
+            iteration: self.iteration,
+            stop_iteration,
+            grid: self.grid.clone(),
+            grid_previous: self.grid_previous.clone(),
+            data: self.data.clone(),
+            agents: self.agents.clone(),
+            regions: self.regions.clone(),
+            manifest: None
+        }
+    }
+
+    /// Puts the automaton back in the state of the checkpoint, which must have been made with the same rules.
+    pub fn restore(&mut self, checkpoint: &Checkpoint) -> Result<(), String> {
+        if checkpoint.world_size != self.rules.grid_size() || checkpoint.state_count != self.rules.states.len() {
+            return Err(format!("The checkpoint was made with a world of size {:?} and {} states, but the rules have a world of size {:?} and {} states.",
+                               checkpoint.world_size, checkpoint.state_count, self.rules.grid_size(), self.rules.states.len()));
+        }
+        let cell_count = self.grid.len();
+        if checkpoint.grid.len() != cell_count || checkpoint.grid_previous.len() != self.grid_previous.len() || checkpoint.data.len() != self.data.len()
+            || checkpoint.grid.iter().chain(&checkpoint.grid_previous).any(|state| *state >= self.rules.states.len()) {
+            return Err("The checkpoint doesn't match the rules.".to_string());
+        }
+        self.grid = checkpoint.grid.clone();
+        self.grid_next = checkpoint.grid.clone();
+        self.grid_previous = checkpoint.grid_previous.clone();
+        self.data = checkpoint.data.clone();
+        self.data_next = checkpoint.data.clone();
+        // The checkpoints don't store the ages, the cells start again at 0.
+        self.ages.iter_mut().for_each(|age| *age = 0);
+        self.agents = checkpoint.agents.clone();
+        self.regions = checkpoint.regions.clone();
+        self.distances_iteration = None;
+        self.iteration = checkpoint.iteration;
+        self.seed = checkpoint.seed;
+        self.trace.clear();
+        self.publish();
+        Ok(())
+    }
+
+    /// Gives new rules to the running automaton, like after an edition of the rules file. The cells keep their states,
+    /// matched by name, and the cells counting down a delay go back to the state of the delay. The cells in a state
+    /// that the new rules don't declare take the default state, and a warning is returned for each of these states that has cells.
+    /// The regions are the ones of the new rules, and the agents are kept if the new rules have as many of them.
+    pub fn replace_rules(&mut self, rules: Rules) -> Result<Vec<String>, String> {
+        if rules.grid_size() != self.rules.grid_size() {
+            return Err(format!("The new rules have a world of size {:?}, but the running world has the size {:?}.",
+                               rules.grid_size(), self.rules.grid_size()));
+        }
+        let default_state = rules.default_state();
+        let new_states: Vec<Option<usize>> = self.rules.states.iter()
+            .map(|state| rules.user_states().iter().position(|new_state| new_state.name == state.name))
+            .collect();
+        let mut warnings: Vec<String> = self.rules.user_states().iter().zip(&new_states).zip(self.state_counts())
+            .filter(|((_, new_state), count)| new_state.is_none() && *count > 0)
+            .map(|((state, _), _)| format!("The state \"{}\" is not in the new rules, its cells are now in the default state \"{}\".",
+                                      state.name, rules.states[default_state].name))
+            .collect();
+        let remap = |grid: &[usize]| grid.iter().map(|state| new_states[*state].unwrap_or(default_state)).collect::<Vec<_>>();
+        let grid = remap(&self.grid);
+        self.grid_previous = match (rules.second_order || rules.memory, self.grid_previous.is_empty()) {
+            (false, _) => Vec::new(),
+            (true, true) => grid.clone(),
+            (true, false) => remap(&self.grid_previous)
+        };
+        if rules.data_channel.is_none() {
+            self.data = Vec::new();
+        } else if self.data.is_empty() {
+            self.data = vec![0; grid.len()];
         }
+        self.data_next = self.data.clone();
+        if !rules.has_gradients() {
+            self.ages = Vec::new();
+        } else if self.ages.is_empty() {
+            self.ages = vec![0; grid.len()];
+        }
+        self.grid_next = grid.clone();
+        self.grid = grid;
+        self.regions = rules.regions.clone();
+        if self.agents.len() != rules.agents.len() {
+            self.agents = rules.agents.clone();
+        }
+        self.rules = rules;
+        self.transition_table = TransitionTable::new(&self.rules);
+        self.distances_iteration = None;
+        self.trace.clear();
+        if self.transition_cache.is_some() {
+            self.transition_cache = match TransitionCache::new(&self.rules) {
+                Ok(cache) => Some(cache),
+                Err(error) => {
+                    warnings.push(format!("The transition cache is disabled. Cause : {}", error));
+                    None
+                }
+            };
+        }
+        self.publish();
+        Ok(warnings)
+    }
+
+    /// Returns a copy of the grid at the current iteration.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            seed: self.seed,
+            iteration: self.iteration,
+            world_size: self.rules.grid_size(),
+            grid: self.grid.clone()
+        }
+    }
+
+    /// Returns a handle to read the last completed generation from other threads, without blocking the ticks.
+    /// From then on, a snapshot of the grid is published after each tick.
+    pub fn share_grid(&mut self) -> SharedGrid {
+        let snapshot = self.snapshot();
+        self.shared_grid.get_or_insert_with(|| SharedGrid::new(snapshot)).clone()
+    }
+
+    fn publish(&self) {
+        if let Some(shared_grid) = &self.shared_grid {
+            shared_grid.publish(self.snapshot());
+        }
+    }
+
+    /// Returns the states of the cells, row after row.
+    pub fn get_grid(&self) -> &[usize] {
+        &self.grid
+    }
+
+    /// Returns a hash of what the next iterations depend on : the states of the cells, their previous states if the rules
+    /// refer to them, their data and the agents. Two iterations with the same hash are followed by the same evolution, unless
+    /// the rules draw random numbers.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (&self.grid, &self.grid_previous, &self.data, &self.agents).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_iteration(&self) -> usize {
+        self.iteration
     }
 
     pub fn get_state(&self, x: isize, y: isize) -> usize {
-        self.grid[get_index((x, y), self.rules.world_size)].state
+        self.grid[self.topology.index((x, y))]
+    }
+
+    /// Returns the number of ticks the cell has spent in its state, always 0 if no state has a color gradient.
+    pub fn get_age(&self, x: isize, y: isize) -> usize {
+        self.ages.get(self.topology.index((x, y))).map_or(0, |age| *age as usize)
+    }
+
+    /// Returns the index in `get_colors` of the color the cell is shown with, which depends on its age if its state has a gradient.
+    pub fn get_color_index(&self, x: isize, y: isize) -> usize {
+        let index = self.topology.index((x, y));
+        match self.ages.get(index) {
+            Some(age) => self.rules.color_index(self.grid[index], *age as usize),
+            None => self.grid[index]
+        }
+    }
+
+    /// Sets the state of a cell, which keeps its data. The next tick starts from the new state.
+    pub fn set_cell(&mut self, x: usize, y: usize, state: usize) -> Result<(), String> {
+        let (width, height) = self.rules.grid_size();
+        if x >= width || y >= height {
+            return Err(format!("The cell ({}, {}) is outside of the world.", x, y));
+        }
+        if state >= self.rules.states.len() {
+            return Err(format!("The state {} doesn't exist.", state));
+        }
+        self.grid[y * width + x] = state;
+        if let Some(age) = self.ages.get_mut(y * width + x) {
+            *age = 0;
+        }
+        self.distances_iteration = None;
+        Ok(())
+    }
+
+    /// Returns the most frequent state of each block of `factor` x `factor` cells of a layer, row after row of blocks.
+    /// The lowest state wins the ties, and the blocks at the right and bottom borders only count the cells of the world.
+    pub fn dominant_states(&self, factor: usize, layer: usize) -> Vec<usize> {
+        let (width, height) = self.rules.world_size;
+        let state_count = self.rules.states.len();
+        let layer_grid = &self.grid[layer * width * height..(layer + 1) * width * height];
+        let blocks_width = width.div_ceil(factor);
+        let mut blocks = vec![0; blocks_width * (height.div_ceil(factor))];
+        blocks.par_chunks_mut(blocks_width).zip(layer_grid.par_chunks(width * factor)).for_each(|(block_row, rows)| {
+            let mut counts = vec![0; blocks_width * state_count];
+            for row in rows.chunks(width) {
+                for (x, state) in row.iter().enumerate() {
+                    counts[x / factor * state_count + state] += 1;
+                }
+            }
+            for (block, block_counts) in block_row.iter_mut().zip(counts.chunks(state_count)) {
+                // The last maximum is kept, so the states are reversed for the lowest state to win.
+                *block = block_counts.iter().enumerate().rev().max_by_key(|(_, count)| **count).map_or(0, |(state, _)| state);
+            }
+        });
+        blocks
+    }
+
+    /// Returns the position and the state of each cell, row after row.
+    pub fn cells(&self) -> impl Iterator<Item = ((usize, usize), usize)> + '_ {
+        let width = self.rules.world_size.0;
+        self.grid.iter().enumerate().map(move |(index, state)| ((index % width, index / width), *state))
+    }
+
+    /// Writes the world in a RLE file, or in a Life 1.06 file if its extension is `.lif`, to open it with Golly or other tools.
+    pub fn export_rle(&self, file_name: &str) -> Result<(), String> {
+        RlePattern::from_automaton(self).save(file_name)
+    }
+
+    /// Returns the number of cells in each state, implicit states included.
+    pub fn raw_state_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.rules.states.len()];
+        for state in &self.grid {
+            counts[*state] += 1;
+        }
+        counts
     }
 
+    /// Returns the number of cells in each state declared by the user.
+    /// The cells in an implicit state are counted in the state the implicit state belongs to.
+    pub fn state_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.rules.user_states().len()];
+        for (state, count) in self.raw_state_counts().iter().enumerate() {
+            counts[self.rules.parent_state(state)] += count;
+        }
+        counts
+    }
+
+    pub fn get_rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    /// Returns the colors of the states, followed by the colors of their gradients.
     pub fn get_colors(&self) -> Vec<(u8, u8, u8)> {
-        self.rules.states.iter().map(|s| s.color).collect::<Vec<_>>()
+        self.rules.colors()
     }
 }
 
 impl Rules {
-    fn evaluate_conditions(&self, grid: &[Cell], position: (usize, usize), conditions: &[Vec<Condition>], rng: &mut ThreadRng) -> bool {
-        match conditions.iter().find(|conjunction| self.evaluate_conjunction(grid, position, conjunction, rng)) {
-            Some(_) => true,
-            _ => false
+    /// Returns the new state of the cell given by the first transition triggered, and the data this transition sets.
+    fn next_state(&self, grid: GridView, position: (usize, usize), rng: &mut CounterRng) -> (usize, Option<u8>) {
+        match self.triggered_transition(grid, position, rng) {
+            Some((_, _, _, Effects { vote: Some(tie_break), set_data, .. })) => (self.vote(grid, position, *tie_break, rng), *set_data),
+            Some((_, state_destination, _, effects)) => (*state_destination, effects.set_data),
+            None => (grid.states[position.1 * grid.topology.size().0 + position.0], None)
+        }
+    }
+
+    /// Returns the new state of the center cell of a square of cells, given row after row, whose side is `neighborhood_side`.
+    /// In a 3D world, the cells are a cube, given layer after layer.
+    /// On a torus of this side, the neighbors of the center cell are distinct cells, so the neighborhood is evaluated as a tiny grid.
+    /// The previous iteration is considered identical to the neighborhood, and the data is 0.
+    pub fn evaluate_neighborhood(&self, cells: &[usize], rng: &mut CounterRng) -> usize {
+        let side = self.neighborhood_side();
+        let topology = Topology::new((side, cells.len() / side));
+        let data = if self.data_channel.is_some() { vec![0; cells.len()] } else { Vec::new() };
+        let distances = distance_fields(self, cells, &topology);
+        let grid = GridView { states: cells, previous_states: cells, data: &data, distances: &distances, topology: &topology, boundary: self.boundary, layer_height: side };
+        let center = cells.len() / 2;
+        self.next_state(grid, (center % side, center / side), rng).0
+    }
+
+    /// Returns the most common state among the neighbors of the cell, the implicit states being counted in their parent state.
+    fn vote(&self, grid: GridView, (x, y): (usize, usize), tie_break: TieBreak, rng: &mut CounterRng) -> usize {
+        let mut counts = vec![0; self.user_states().len()];
+        for offset in &self.neighbor_offsets {
+            counts[self.parent_state(grid.neighbor_state((x as isize, y as isize), *offset))] += 1;
+        }
+        self.elect(&counts, grid.state((x as isize, y as isize)), tie_break, rng)
+    }
+
+    /// Returns the state with the most neighbors, given the number of neighbors in each user state, for a cell in the state.
+    fn elect(&self, counts: &[usize], state: usize, tie_break: TieBreak, rng: &mut CounterRng) -> usize {
+        let highest = counts.iter().copied().max().unwrap_or(0);
+        let elected: Vec<usize> = (0..counts.len()).filter(|state| counts[*state] == highest).collect();
+        let state = self.parent_state(state);
+        match tie_break {
+            TieBreak::Keep if elected.contains(&state) => state,
+            TieBreak::Random if elected.len() > 1 => elected[rng.gen_range(0, elected.len())],
+            _ => elected[0]
         }
     }
 
-    fn evaluate_conjunction(&self, grid: &[Cell], position: (usize, usize), conjunction: &[Condition], rng: &mut ThreadRng) -> bool {
-        match conjunction.iter().find(|condition| !self.evaluate_condition(grid, position, condition, rng)) {
-            Some(_) => false,
-            _ => true
+    /// The side of the smallest square, or cube in a 3D world, that contains the cell and its neighbors.
+    pub fn neighborhood_side(&self) -> usize {
+        2 * self.neighborhood.radius() + 1
+    }
+
+    /// The number of cells of the smallest square, or cube in a 3D world, that contains the cell and its neighbors.
+    pub fn neighborhood_cell_count(&self) -> usize {
+        self.neighborhood_side().pow(if self.depth > 1 { 3 } else { 2 })
+    }
+
+    /// Returns the first transition triggered for the cell, if any.
+    fn triggered_transition(&self, grid: GridView, (x, y): (usize, usize), rng: &mut CounterRng) -> Option<&Transition> {
+        let state = grid.states[y * grid.topology.size().0 + x];
+        self.transitions.iter().enumerate()
+            .find(|(index, (state_origin, _, conditions, _))| *state_origin == state && self.evaluate_conditions(grid, (x, y), *index, conditions, rng))
+            .map(|(_, transition)| transition)
+    }
+
+    /// Evaluates the transitions that can apply to the cell, and records which conditions passed or failed. The evaluation
+    /// stops where `triggered_transition` stops, so it draws the same random numbers : the conditions after the first failed
+    /// condition of a conjunction are not evaluated, nor recorded.
+    fn trace_transitions(&self, grid: GridView, position: (usize, usize), iteration: usize, rng: &mut CounterRng) -> Vec<String> {
+        let state = grid.state((position.0 as isize, position.1 as isize));
+        let mut trace = vec![format!("Iteration {}, cell ({}, {}) is in state {}.", iteration, position.0, position.1, self.state_label(state))];
+
+        for (transition, (state_origin, state_destination, conditions, effects)) in self.transitions.iter().enumerate().filter(|(_, (origin, _, _, _))| *origin == state) {
+            let mut triggered = false;
+            for (i, conjunction) in conditions.iter().enumerate() {
+                let mut results = Vec::new();
+                let mut conjunction_passed = true;
+                for (j, condition) in conjunction.iter().enumerate() {
+                    let (passed, explanation) = self.trace_condition(grid, position, condition, condition_key(transition, i, j), rng);
+                    results.push(format!("{} : {}", explanation, if passed { "passed" } else { "failed" }));
+                    if !passed {
+                        conjunction_passed = false;
+                        break;
+                    }
+                }
+                trace.push(format!("  Transition {} -> {}, conjunction {} : {}.",
+                                   self.state_label(*state_origin), self.state_label(*state_destination), i + 1, results.join(", ")));
+                if conjunction_passed {
+                    triggered = true;
+                    break;
+                }
+            }
+            if triggered {
+                if let Some(tie_break) = effects.vote {
+                    let elected = self.vote(grid, position, tie_break, rng);
+                    trace.push(format!("  Transition {} -> vote is triggered, the neighbors elect {}.", self.state_label(*state_origin), self.state_label(elected)));
+                    return trace;
+                }
+                trace.push(format!("  Transition {} -> {} is triggered.", self.state_label(*state_origin), self.state_label(*state_destination)));
+                return trace;
+            }
         }
+        trace.push("  No transition is triggered, the state is unchanged.".to_string());
+        trace
+    }
+
+    /// The implicit states have the same name as their parent state, so the state index is added.
+    fn state_label(&self, state: usize) -> String {
+        format!("{} (#{})", self.states[state].name, state)
+    }
+
+    fn trace_condition(&self, grid: GridView, position: (usize, usize), condition: &Condition, condition_key: u64, rng: &mut CounterRng) -> (bool, String) {
+        let passed = self.evaluate_condition(grid, position, condition, condition_key, rng);
+        let explanation = match condition {
+            Condition::QuantityCondition(state, comp, quantity, counted) => {
+                let count = self.count_state(grid, position, *state, *counted);
+                match counted {
+                    CountedCells::Neighbors => format!("{} {} {} (count is {})", self.states[*state].name, comp, quantity, count),
+                    CountedCells::NeighborsAndSelf => format!("count9({}) {} {} (count is {})", self.states[*state].name, comp, quantity, count)
+                }
+            },
+            Condition::DataCondition(comp, value) => {
+                let data = grid.data[grid.topology.index((position.0 as isize, position.1 as isize))];
+                format!("{} {} {} (it is {})", self.data_channel.as_deref().unwrap_or_default(), comp, value, data)
+            },
+            Condition::DistanceCondition(state, comp, distance) => {
+                let actual = grid.distances[*state][grid.topology.index((position.0 as isize, position.1 as isize))];
+                format!("distance({}) {} {} (it is {})", self.states[*state].name, comp, distance, actual)
+            },
+            Condition::NeighborCondition(neighbor, state) => {
+                let neighbor_state = grid.state(get_neighbor_position((position.0 as isize, position.1 as isize), *neighbor));
+                format!("{:?} is {} (it is {})", neighbor, self.states[*state].name, self.states[neighbor_state].name)
+            },
+            Condition::PreviousNeighborCondition(neighbor, state) => {
+                let neighbor_state = grid.previous_state(get_neighbor_position((position.0 as isize, position.1 as isize), *neighbor));
+                format!("{:?} was {} (it was {})", neighbor, self.states[*state].name, self.states[neighbor_state].name)
+            },
+            Condition::PreviousStateCondition(state) => {
+                let previous_state = grid.previous_state((position.0 as isize, position.1 as isize));
+                format!("was {} (it was {})", self.states[*state].name, self.states[previous_state].name)
+            },
+            Condition::RandomCondition(proportion, RandomDraw::Cell) => format!("rand {}", proportion),
+            Condition::RandomCondition(proportion, RandomDraw::Tick) => format!("rand {} per tick", proportion),
+            Condition::RandomCondition(proportion, RandomDraw::Block(side)) => format!("rand {} per block {}", proportion, side),
+            Condition::True => "true".to_string()
+        };
+        (passed, explanation)
     }
 
-    fn evaluate_condition(&self, grid: &[Cell], position: (usize, usize), condition: &Condition, rng: &mut ThreadRng) -> bool {
+    fn evaluate_conditions(&self, grid: GridView, position: (usize, usize), transition: usize, conditions: &[Vec<Condition>], rng: &mut CounterRng) -> bool {
+        conditions.iter().enumerate().any(|(index, conjunction)| self.evaluate_conjunction(grid, position, (transition, index), conjunction, rng))
+    }
+
+    fn evaluate_conjunction(&self, grid: GridView, position: (usize, usize), (transition, conjunction_index): (usize, usize),
+                            conjunction: &[Condition], rng: &mut CounterRng) -> bool {
+        conjunction.iter().enumerate()
+            .all(|(index, condition)| self.evaluate_condition(grid, position, condition, condition_key(transition, conjunction_index, index), rng))
+    }
+
+    /// The `condition_key` identifies the condition in the rules, so its shared draws are independent of those of the other conditions.
+    fn evaluate_condition(&self, grid: GridView, position: (usize, usize), condition: &Condition, condition_key: u64, rng: &mut CounterRng) -> bool {
         match condition {
-            Condition::QuantityCondition(state, comp, quantity) => {
-                let count = self.count_state_in_neighborhood(grid, position, *state);
-                Self::evaluate_quantity_condition(count, *comp, *quantity)
+            Condition::QuantityCondition(state, comp, quantity, counted) => {
+                let count = self.count_state(grid, position, *state, *counted);
+                comp.evaluate(count, *quantity)
+            },
+            Condition::DataCondition(comp, value) => {
+                comp.evaluate(grid.data[grid.topology.index((position.0 as isize, position.1 as isize))], *value)
+            },
+            Condition::DistanceCondition(state, comp, distance) => {
+                comp.evaluate(grid.distances[*state][grid.topology.index((position.0 as isize, position.1 as isize))], *distance)
             },
             Condition::NeighborCondition(neighbor, state) => {
                 let (x, y) = (position.0 as isize, position.1 as isize);
-                let index = Self::get_index_of_neighbor((x, y), *neighbor, self.world_size);
-                self.is_state(grid[index].state, *state)
+                self.is_state(grid.state(get_neighbor_position((x, y), *neighbor)), *state)
             },
-            Condition::RandomCondition(proportion) => {
-                let r: f64 = rng.gen();
+            Condition::PreviousNeighborCondition(neighbor, state) => {
+                let (x, y) = (position.0 as isize, position.1 as isize);
+                self.is_state(grid.previous_state(get_neighbor_position((x, y), *neighbor)), *state)
+            },
+            Condition::PreviousStateCondition(state) => {
+                self.is_state(grid.previous_state((position.0 as isize, position.1 as isize)), *state)
+            },
+            Condition::RandomCondition(proportion, draw) => {
+                let r: f64 = match draw {
+                    RandomDraw::Cell => rng.gen(),
+                    RandomDraw::Tick => rng.shared(condition_key, u64::MAX).gen(),
+                    RandomDraw::Block(side) => rng.shared(condition_key, (((position.0 / side) as u64) << 32) | (position.1 / side) as u64).gen()
+                };
                 r < *proportion
             },
             Condition::True => true
         }
     }
 
-    fn count_state_in_neighborhood(&self, grid: &[Cell], (x, y): (usize, usize), state: usize) -> u8 {
-        let mut count: u8 = 0;
-        for u in -1..2 {
-            for v in -1..2 {
-                if u != 0 || v != 0 {
-                    let position = (x as isize + u, y as isize + v);
-                    let index = get_index(position, self.world_size);
-                    if self.is_state(grid[index].state, state) {
-                        count += 1;
-                    }
+    /// Returns the new state of a cell in the state, given the number of its neighbors in each state, for the rules of a
+    /// transition table. Same as `next_state`, whose conditions would count the neighbors.
+    fn next_state_from_counts(&self, state: usize, counts: &[usize]) -> usize {
+        let count_state = |counted_state: usize, counted: CountedCells| {
+            let neighbors: usize = (0..counts.len()).filter(|s| self.is_state(*s, counted_state)).map(|s| counts[s]).sum();
+            let count = neighbors.min(u8::MAX as usize) as u8;
+            match counted {
+                CountedCells::Neighbors => count,
+                CountedCells::NeighborsAndSelf => count.saturating_add(self.is_state(state, counted_state) as u8)
+            }
+        };
+        let triggered = self.transitions.iter().find(|(state_origin, _, conditions, _)| {
+            *state_origin == state && conditions.iter().any(|conjunction| conjunction.iter().all(|condition| match condition {
+                Condition::QuantityCondition(counted_state, comp, quantity, counted) => comp.evaluate(count_state(*counted_state, *counted), *quantity),
+                // The rules of a transition table have no other conditions.
+                _ => true
+            }))
+        });
+        match triggered {
+            Some((_, _, _, Effects { vote: Some(tie_break), .. })) => {
+                let mut parent_counts = vec![0; self.user_states().len()];
+                for (s, count) in counts.iter().enumerate() {
+                    parent_counts[self.parent_state(s)] += count;
                 }
+                // The ties of the rules of a transition table are not broken at random.
+                self.elect(&parent_counts, state, *tie_break, &mut CounterRng::new(0, 0, 0))
+            },
+            Some((_, state_destination, _, _)) => *state_destination,
+            None => state
+        }
+    }
+
+    /// Counts the counted cells in the state, the cell itself included for a "count9" condition.
+    fn count_state(&self, grid: GridView, (x, y): (usize, usize), state: usize, counted: CountedCells) -> u8 {
+        let count = self.count_state_in_neighborhood(grid, (x, y), state);
+        match counted {
+            CountedCells::Neighbors => count,
+            CountedCells::NeighborsAndSelf => count.saturating_add(self.is_state(grid.state((x as isize, y as isize)), state) as u8)
+        }
+    }
+
+    fn count_state_in_neighborhood(&self, grid: GridView, (x, y): (usize, usize), state: usize) -> u8 {
+        let mut count: u8 = 0;
+        for offset in &self.neighbor_offsets {
+            if self.is_state(grid.neighbor_state((x as isize, y as isize), *offset), state) {
+                count += 1;
             }
         }
         count
@@ -191,56 +1078,791 @@ impl Rules {
         if state == other_state {
             return true;
         }
-        if let Some(range) = &self.implicit_state_ranges[other_state] {
-            return state >= range.start && state < range.len;
+        self.implicit_state_ranges[other_state].iter().any(|range| state >= range.start && state < range.len)
+    }
+}
+
+/// Subtracts the states of the second grid from the states of the first one, modulo the number of states.
+fn subtract_states(grid: &mut [usize], other_grid: &[usize], states_count: usize) {
+    grid.par_iter_mut().zip(other_grid.par_iter()).for_each(|(state, other_state)| {
+        *state = (*state + states_count - *other_state) % states_count;
+    });
+}
+
+/// Identifies a condition by its transition, its conjunction and its position in the conjunction, for the shared draws.
+fn condition_key(transition: usize, conjunction: usize, condition: usize) -> u64 {
+    ((transition as u64) << 32) | ((conjunction as u64) << 16) | condition as u64
+}
+
+/// Computes the distance field of each state used by a distance condition, indexed by state.
+fn distance_fields(rules: &Rules, grid: &[usize], topology: &Topology) -> Vec<Vec<u8>> {
+    let mut fields = vec![Vec::new(); rules.states.len()];
+    for state in rules.distance_states() {
+        fields[state] = distance_field(rules, grid, topology, state);
+    }
+    fields
+}
+
+/// Computes the distance from each cell to the closest cell in the state, with a breadth-first search from all these cells.
+/// The distance is the number of moves to a neighbor cell, with the neighborhood of the rules : the Chebyshev distance with
+/// the Moore neighborhood of radius 1, the Manhattan distance with the Von Neumann one. It is capped at 255, which also means
+/// "no such cell". The distance conditions are refused in a 3D world, so the neighbors are in the layer of the cell.
+fn distance_field(rules: &Rules, grid: &[usize], topology: &Topology, state: usize) -> Vec<u8> {
+    let width = topology.size().0;
+    let mut field = vec![u8::MAX; grid.len()];
+    let mut queue = VecDeque::new();
+    for (index, cell_state) in grid.iter().enumerate() {
+        if rules.is_state(*cell_state, state) {
+            field[index] = 0;
+            queue.push_back(index);
         }
-        false
     }
+    while let Some(index) = queue.pop_front() {
+        let distance = field[index];
+        if distance == u8::MAX - 1 {
+            continue;
+        }
+        let (x, y) = ((index % width) as isize, (index / width) as isize);
+        for (u, v, _) in &rules.neighbor_offsets {
+            let neighbor_index = match rules.boundary.index(topology, (x + u, y + v)) {
+                Some(neighbor_index) => neighbor_index,
+                None => continue
+            };
+            if field[neighbor_index] == u8::MAX {
+                field[neighbor_index] = distance + 1;
+                queue.push_back(neighbor_index);
+            }
+        }
+    }
+    field
+}
 
-    fn evaluate_quantity_condition(count: u8, comp: ComparisonOperator, quantity: u8) -> bool {
-        match comp {
-            ComparisonOperator::Greater => count > quantity,
-            ComparisonOperator::Lesser => count < quantity,
-            ComparisonOperator::GreaterOrEqual => count >= quantity,
-            ComparisonOperator::LesserOrEqual => count <= quantity,
-            ComparisonOperator::Equal => count == quantity,
-            ComparisonOperator::Different => count != quantity
+fn get_neighbor_position((x, y): (isize, isize), neighbor: NeighborCell) -> (isize, isize) {
+    let (u, v) = neighbor.offset();
+    (x + u, y + v)
+}
+
+/// Overrides the states given by the rules in the region. Regions that go past the grid's borders are wrapped around.
+fn apply_region(region: &Region, grid: &[usize], grid_next: &mut [usize], topology: &Topology) {
+    for x in region.x..(region.x + region.width) {
+        for y in region.y..(region.y + region.height) {
+            let index = topology.index((x as isize, y as isize));
+            grid_next[index] = match region.kind {
+                RegionKind::Frozen => grid[index],
+                RegionKind::Source(state) => state
+            };
         }
     }
+}
 
-    fn get_index_of_neighbor((x, y): (isize, isize), neighbor: NeighborCell, size: (usize, usize)) -> usize {
-        let neighbor_position = match neighbor {
-            NeighborCell::A => (x - 1, y - 1),
-            NeighborCell::B => (x, y - 1),
-            NeighborCell::C => (x + 1, y - 1),
-            NeighborCell::D => (x - 1, y),
-            NeighborCell::E => (x + 1, y),
-            NeighborCell::F => (x - 1, y + 1),
-            NeighborCell::G => (x, y + 1),
-            NeighborCell::H => (x + 1, y + 1)
-        };
-        get_index(neighbor_position, size)
+/// The outcomes of the transitions of deterministic rules, by the states of the cell, of its neighborhood and of the neighbors
+/// tested by the neighbor conditions. These states are packed in the key of an outcome, with the bits of a state each.
+struct TransitionCache {
+    neighbors: Vec<NeighborCell>, // The neighbors tested by the neighbor conditions
+    bits: u32,
+    outcomes: HashMap<u64, usize>
+}
+
+impl TransitionCache {
+    fn new(rules: &Rules) -> Result<TransitionCache, String> {
+        if rules.data_channel.is_some() {
+            return Err("The transitions of rules with a data channel cannot be cached.".to_string());
+        }
+        let mut neighbors: Vec<NeighborCell> = Vec::new();
+        for (_, _, conditions, effects) in &rules.transitions {
+            for condition in conditions.iter().flatten() {
+                match condition {
+                    Condition::QuantityCondition(..) | Condition::True => {},
+                    Condition::NeighborCondition(neighbor, _) => if !neighbors.contains(neighbor) {
+                        neighbors.push(*neighbor);
+                    },
+                    _ => return Err("The transitions can only be cached if their conditions only test the states of the cell and of its neighbors.".to_string())
+                }
+            }
+            if effects.vote == Some(TieBreak::Random) {
+                return Err("The transitions cannot be cached, because a vote breaks its ties at random.".to_string());
+            }
+        }
+        let bits = usize::BITS - (rules.states.len() - 1).max(1).leading_zeros();
+        let cell_count = 1 + rules.neighbor_offsets.len() + neighbors.len();
+        if cell_count as u32 * bits > u64::BITS {
+            return Err(format!("The states of {} cells, among {} states, are too many to be cached.", cell_count, rules.states.len()));
+        }
+        Ok(TransitionCache { neighbors, bits, outcomes: HashMap::new() })
+    }
+
+    fn key(&self, rules: &Rules, grid: GridView, (x, y): (usize, usize)) -> u64 {
+        let position = (x as isize, y as isize);
+        let mut key = grid.state(position) as u64;
+        for offset in &rules.neighbor_offsets {
+            key = key << self.bits | grid.neighbor_state(position, *offset) as u64;
+        }
+        for neighbor in &self.neighbors {
+            key = key << self.bits | grid.state(get_neighbor_position(position, *neighbor)) as u64;
+        }
+        key
+    }
+
+    fn extend(&mut self, computed: Vec<HashMap<u64, usize>>) {
+        let count: usize = computed.iter().map(HashMap::len).sum();
+        if self.outcomes.len() + count > TRANSITION_CACHE_CAPACITY {
+            self.outcomes.clear();
+        }
+        for outcomes in computed {
+            self.outcomes.extend(outcomes.into_iter().take(TRANSITION_CACHE_CAPACITY - self.outcomes.len()));
+        }
     }
 }
 
-fn get_position(index: usize, size: (usize, usize)) -> (usize, usize) {
-    assert!(index < size.0 * size.1,
-            "The index {} is too big to be located in the matrix of size ({},{}).", index, size.0, size.1);
-    (index % size.0, index / size.0)
+/// The new states of deterministic rules whose conditions only count the neighbors in each state, by the state of the cell
+/// and the number of its neighbors in each state, computed once for all. The counts are packed in the index of an entry,
+/// in base "number of neighbors + 1" : the count of the last state isn't, as the counts add up to the number of neighbors.
+struct TransitionTable {
+    weights: Vec<usize>, // The weight of a neighbor in each state in the index of an entry
+    stride: usize, // The number of entries for each state of the cell
+    entries: Vec<usize>
 }
 
-fn get_index((x, y): (isize, isize), size: (usize, usize)) -> usize {
-    tore_correction(y, size.1) * size.0 + tore_correction(x, size.0)
+impl TransitionTable {
+    /// Returns None if the rules test more than the number of neighbors in each state, draw random numbers, have a data
+    /// channel, or need more entries than the capacity of a table. These rules are interpreted.
+    fn new(rules: &Rules) -> Option<TransitionTable> {
+        let counted_only = rules.transitions.iter().all(|(_, _, conditions, effects)| {
+            effects.vote != Some(TieBreak::Random)
+                && conditions.iter().flatten().all(|condition| matches!(condition, Condition::QuantityCondition(..) | Condition::True))
+        });
+        let neighbor_count = rules.neighbor_offsets.len();
+        if !counted_only || rules.data_channel.is_some() || neighbor_count > u8::MAX as usize {
+            return None;
+        }
+        let state_count = rules.states.len();
+        let stride = (neighbor_count + 1).checked_pow(state_count as u32 - 1)?;
+        if stride.checked_mul(state_count)? > TRANSITION_TABLE_CAPACITY {
+            return None;
+        }
+        let mut weights: Vec<usize> = (0..state_count - 1).map(|state| (neighbor_count + 1).pow(state as u32)).collect();
+        weights.push(0);
+
+        let mut entries = vec![0; stride * state_count];
+        let mut counts = vec![0; state_count];
+        for index in 0..stride {
+            for state in 0..state_count - 1 {
+                counts[state] = index / weights[state] % (neighbor_count + 1);
+            }
+            let counted: usize = counts[..state_count - 1].iter().sum();
+            // The entries whose counts add up to more than the number of neighbors are never looked up.
+            if counted > neighbor_count {
+                continue;
+            }
+            counts[state_count - 1] = neighbor_count - counted;
+            for state in 0..state_count {
+                entries[state * stride + index] = rules.next_state_from_counts(state, &counts);
+            }
+        }
+        Some(TransitionTable { weights, stride, entries })
+    }
+
+    fn next_state(&self, rules: &Rules, grid: GridView, (x, y): (usize, usize)) -> usize {
+        let position = (x as isize, y as isize);
+        let index: usize = rules.neighbor_offsets.iter().map(|offset| self.weights[grid.neighbor_state(position, *offset)]).sum();
+        self.entries[grid.states[y * grid.topology.size().0 + x] * self.stride + index]
+    }
+}
+
+/// A read-only view of a grid, with the topology that locates its cells.
+#[derive(Clone, Copy)]
+struct GridView<'a> {
+    states: &'a [usize],
+    previous_states: &'a [usize], // Empty unless the automaton has memory
+    data: &'a [u8], // Empty unless the rules declare a data channel
+    distances: &'a [Vec<u8>], // Indexed by state, see distance_fields
+    topology: &'a Topology,
+    boundary: BoundaryMode,
+    layer_height: usize, // The height of a layer of a 3D world, the height of the grid for a 2D world
+}
+
+impl<'a> GridView<'a> {
+    fn state(&self, position: (isize, isize)) -> usize {
+        self.boundary.state(self.states, self.topology, position)
+    }
+
+    fn previous_state(&self, position: (isize, isize)) -> usize {
+        self.boundary.state(self.previous_states, self.topology, position)
+    }
+
+    /// Returns the state of the neighbor at the (x, y, layer) offset. The layers of a 3D world are stacked in the grid,
+    /// and a neighbor in the same layer or in another one wraps around within its layer.
+    fn neighbor_state(&self, (x, y): (isize, isize), (u, v, w): (isize, isize, isize)) -> usize {
+        let grid_height = self.topology.size().1;
+        if self.layer_height == grid_height {
+            return self.state((x + u, y + v));
+        }
+        let height = self.layer_height as isize;
+        let layer = (y.div_euclid(height) + w).rem_euclid((grid_height / self.layer_height) as isize);
+        self.states[self.topology.index((x + u, layer * height + (y + v).rem_euclid(height)))]
+    }
+}
+
+/// Returns the cells of the disc, whose distance to the center is at most the radius.
+fn circle_cells((x_center, y_center): (usize, usize), radius: usize) -> Vec<(usize, usize)> {
+    (x_center - radius..=x_center + radius)
+        .flat_map(|x| (y_center - radius..=y_center + radius).map(move |y| (x, y)))
+        .filter(|(x, y)| x.abs_diff(x_center).pow(2) + y.abs_diff(y_center).pow(2) <= radius.pow(2))
+        .collect()
 }
 
-/// The world is a tore, so the value range can be )-inf; +inf(, and it will be mapped to (0; upper_bound-1).
-fn tore_correction(value: isize, upper_bound: usize) -> usize {
-    if value >= 0 {
-        (value as usize) % upper_bound
-    } else {
-        // don't question my magic
-        let signed_upper_bound = upper_bound as isize;
-        let corrected = (signed_upper_bound + (value % signed_upper_bound)) % signed_upper_bound;
-        corrected as usize
+/// Returns the cells of the segment between the two ends, drawn with Bresenham's algorithm : each cell touches the next one.
+fn line_cells(start: (usize, usize), end: (usize, usize)) -> Vec<(usize, usize)> {
+    let (dx, dy) = (end.0.abs_diff(start.0) as isize, -(end.1.abs_diff(start.1) as isize));
+    let step = |from: usize, to: usize| if from < to { 1 } else { -1 };
+    let (step_x, step_y) = (step(start.0, end.0), step(start.1, end.1));
+    let (mut x, mut y) = (start.0 as isize, start.1 as isize);
+    let mut error = dx + dy;
+    let mut cells = vec![start];
+    while (x, y) != (end.0 as isize, end.1 as isize) {
+        let double_error = 2 * error;
+        if double_error >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if double_error <= dx {
+            error += dx;
+            y += step_y;
+        }
+        cells.push((x as usize, y as usize));
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::automaton::{circle_cells, line_cells, Automaton, BoundaryMode};
+    use crate::compiler::semantic::{parse, parse_str};
+    use crate::compiler::parser::{Heading, Neighborhood};
+
+    static GAME_OF_LIFE_FILE: &str = "resources/game_of_life.txt";
+    static SECOND_ORDER_FILE: &str = "resources/second_order_life.txt";
+    static MEMORY_FILE: &str = "resources/tests/semantic_memory.txt";
+    static NOISE_FILE: &str = "resources/tests/automaton_noise.txt";
+    static REGIONS_FILE: &str = "resources/tests/semantic_regions.txt";
+    static DATA_FILE: &str = "resources/tests/semantic_data.txt";
+    static EFFECTS_FILE: &str = "resources/tests/semantic_effects.txt";
+    static LANGTON_ANT_FILE: &str = "resources/langton_ant.txt";
+    static DISTANCE_FILE: &str = "resources/tests/semantic_distance.txt";
+    static SWAP_FILE: &str = "resources/tests/semantic_swap.txt";
+    static SWAP_COLLISION_FILE: &str = "resources/tests/automaton_swap_collision.txt";
+    static VIRUS_FILE: &str = "resources/virus.txt";
+    static BOUNDARY_FILE: &str = "resources/tests/automaton_boundary.txt";
+    static VON_NEUMANN_FILE: &str = "resources/tests/automaton_von_neumann.txt";
+    static INIT_CSV_FILE: &str = "resources/tests/semantic_init_csv.txt";
+    static IMAGE_FILE: &str = "resources/tests/semantic_image.txt";
+    static PATTERN_FILE: &str = "resources/tests/semantic_pattern.txt";
+    static THREE_DIMENSIONAL_FILE: &str = "resources/tests/automaton_3d.txt";
+    static COUNT9_FILE: &str = "resources/tests/automaton_count9.txt";
+    static VOTE_FILE: &str = "resources/tests/automaton_vote.txt";
+    static SHARED_DRAWS_FILE: &str = "resources/tests/automaton_shared_draws.txt";
+    static INDEPENDENT_DRAWS_FILE: &str = "resources/tests/automaton_independent_draws.txt";
+    static SHAPES_FILE: &str = "resources/tests/automaton_shapes.txt";
+    static GRADIENT_FILE: &str = "resources/tests/automaton_gradient.txt";
+    static DELAY_FILE: &str = "resources/tests/semantic_delay.txt";
+    static TRACE_RANDOM_FILE: &str = "resources/tests/automaton_trace_random.txt";
+    static DETERMINISTIC_GAME_OF_LIFE_FILE: &str = "resources/deterministic_game_of_life.txt";
+
+    #[test]
+    fn same_seed_gives_same_evolution_whatever_the_threads() {
+        let run = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            pool.install(|| {
+                let mut automaton = Automaton::with_seed(parse(VIRUS_FILE).unwrap(), 1234);
+                for _ in 0..30 {
+                    automaton.tick();
+                }
+                automaton.grid.clone()
+            })
+        };
+        let grid = run(1);
+        assert_eq!(run(4), grid);
+        let mut other_seed = Automaton::with_seed(parse(VIRUS_FILE).unwrap(), 4321);
+        for _ in 0..30 {
+            other_seed.tick();
+        }
+        assert_ne!(other_seed.grid, grid);
+    }
+
+    #[test]
+    fn second_order_tick_backward_restores_grid() {
+        let mut automaton = Automaton::new(parse(SECOND_ORDER_FILE).unwrap());
+        let initial_grid = automaton.grid.clone();
+        for _ in 0..20 {
+            automaton.tick();
+        }
+        assert_ne!(automaton.grid, initial_grid);
+        for _ in 0..20 {
+            automaton.tick_backward().unwrap();
+        }
+        assert_eq!(automaton.grid, initial_grid);
+        assert_eq!(automaton.get_iteration(), 0);
+        assert!(automaton.tick_backward().is_err());
+    }
+
+    #[test]
+    fn first_order_tick_backward_fails() {
+        let mut automaton = Automaton::new(parse(GAME_OF_LIFE_FILE).unwrap());
+        automaton.tick();
+        assert!(automaton.tick_backward().is_err());
+    }
+
+    #[test]
+    fn memory_retains_previous_grid() {
+        let mut automaton = Automaton::new(parse(MEMORY_FILE).unwrap());
+        for _ in 0..3 {
+            let grid = automaton.grid.clone();
+            automaton.tick();
+            assert_eq!(automaton.grid_previous, grid);
+        }
+    }
+
+    #[test]
+    fn noise_flips_cells() {
+        let mut automaton = Automaton::new(parse(NOISE_FILE).unwrap());
+        assert_eq!(automaton.raw_state_counts(), vec![400, 0]);
+        automaton.tick();
+        let counts = automaton.raw_state_counts();
+        assert!(counts[1] > 0 && counts[1] < 400);
+    }
+
+    #[test]
+    fn regions_override_transitions() {
+        let mut automaton = Automaton::new(parse(REGIONS_FILE).unwrap());
+        automaton.add_frozen_region(90, 0, 10, 10).unwrap();
+        let frozen_before: Vec<usize> = (40..50).flat_map(|x| (20..30).map(move |y| (x, y))).map(|(x, y)| automaton.get_state(x, y)).collect();
+        let added_before = automaton.get_state(95, 5);
+        for _ in 0..10 {
+            automaton.tick();
+            for y in 0..50 {
+                assert_eq!(automaton.get_state(0, y), 2);
+            }
+        }
+        let frozen_after: Vec<usize> = (40..50).flat_map(|x| (20..30).map(move |y| (x, y))).map(|(x, y)| automaton.get_state(x, y)).collect();
+        assert_eq!(frozen_before, frozen_after);
+        assert_eq!(automaton.get_state(95, 5), added_before);
+    }
+
+    #[test]
+    fn transitions_set_and_test_data() {
+        let mut automaton = Automaton::new(parse(DATA_FILE).unwrap());
+        let hot = (0..20).flat_map(|x| (0..20).map(move |y| (x, y))).find(|(x, y)| automaton.get_state(*x, *y) == 1).unwrap();
+        assert_eq!(automaton.get_data(hot.0, hot.1), Some(0));
+        automaton.tick();
+        assert_eq!(automaton.get_data(hot.0, hot.1), Some(3));
+        assert_eq!(automaton.get_data(hot.0 + 1, hot.1), Some(1));
+        automaton.tick();
+        assert_eq!(automaton.get_state(hot.0, hot.1), 0);
+        assert_eq!(automaton.get_state(hot.0 + 1, hot.1), 1);
+    }
+
+    #[test]
+    fn effects_write_into_neighbors() {
+        let mut automaton = Automaton::new(parse(EFFECTS_FILE).unwrap());
+        let (x, y) = (0..10).flat_map(|x| (0..10).map(move |y| (x, y))).find(|(x, y)| automaton.get_state(*x, *y) == 1).unwrap();
+        for i in 1..=3 {
+            automaton.tick();
+            assert_eq!(automaton.get_state(x, y + i), 1);
+            assert_eq!(automaton.raw_state_counts(), vec![99, 1]);
+        }
+    }
+
+    #[test]
+    fn swaps_conserve_states() {
+        let mut automaton = Automaton::new(parse(SWAP_FILE).unwrap());
+        automaton.tick();
+        for x in 0..10 {
+            assert_eq!((automaton.get_state(x, 0), automaton.get_state(x, 1), automaton.get_state(x, 2)), (1, 0, 1));
+        }
+        for _ in 0..10 {
+            automaton.tick();
+            assert_eq!(automaton.raw_state_counts(), vec![80, 20]);
+        }
+    }
+
+    #[test]
+    fn swap_collision_first_cell_wins() {
+        let mut automaton = Automaton::new(parse(SWAP_COLLISION_FILE).unwrap());
+        automaton.tick();
+        assert_eq!(automaton.get_state(3, 1), 1);
+        assert_eq!(automaton.get_state(2, 0), 0);
+        assert_eq!(automaton.get_state(4, 0), 2);
+    }
+
+    #[test]
+    fn langton_ant_moves_and_flips_cells() {
+        let mut automaton = Automaton::new(parse(LANGTON_ANT_FILE).unwrap());
+        for _ in 0..4 {
+            automaton.tick();
+        }
+        assert_eq!(automaton.get_agents()[0].position, (50, 50));
+        assert_eq!(automaton.raw_state_counts()[1], 4);
+        automaton.tick();
+        assert_eq!(automaton.get_agents()[0].position, (49, 50));
+        assert_eq!(automaton.get_agents()[0].heading, Heading::Left);
+        assert_eq!(automaton.get_state(50, 50), 0);
+    }
+
+    #[test]
+    fn boundary_modes_change_the_border_neighbors() {
+        // A vertical blinker on the left border of the world.
+        let alive_cells = |boundary| {
+            let mut rules = parse(BOUNDARY_FILE).unwrap();
+            assert_eq!(rules.boundary, BoundaryMode::Wall(0));
+            rules.boundary = boundary;
+            let mut automaton = Automaton::with_seed(rules, 0);
+            automaton.tick();
+            automaton.get_grid().iter().enumerate().filter(|(_, state)| **state == 1).map(|(index, _)| (index % 5, index / 5)).collect::<Vec<_>>()
+        };
+        assert_eq!(alive_cells(BoundaryMode::Wrap), vec![(0, 2), (1, 2), (4, 2)]);
+        assert_eq!(alive_cells(BoundaryMode::Wall(0)), vec![(0, 2), (1, 2)]);
+        // The column outside of the world is a copy of the first column.
+        assert_eq!(alive_cells(BoundaryMode::Mirror), vec![(0, 1), (1, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn initial_grid_replaces_the_distributions() {
+        let initial_grid = vec![0, 1, 0, 0, 1, 0, 1, 0, 1, 1, 0, 1];
+        for seed in 0..3 {
+            let mut automaton = Automaton::with_seed(parse(INIT_CSV_FILE).unwrap(), seed);
+            assert_eq!(automaton.get_grid(), &initial_grid[..]);
+            automaton.tick();
+            automaton.reset(seed + 1);
+            assert_eq!(automaton.get_grid(), &initial_grid[..]);
+        }
+    }
+
+    #[test]
+    fn image_distribution_places_the_pixels_of_the_state_colors() {
+        let automaton = Automaton::with_seed(parse(IMAGE_FILE).unwrap(), 0);
+        assert_eq!(automaton.get_grid(), &[0, 1, 0, 2, 1, 1, 0, 0, 2, 0, 1, 0][..]);
+    }
+
+    #[test]
+    fn ages_count_the_ticks_in_the_state() {
+        let mut automaton = Automaton::with_seed(parse(GRADIENT_FILE).unwrap(), 0);
+        for tick in 1..7 {
+            automaton.tick();
+            // The center of the blinker stays alive, its ends change at each tick.
+            assert_eq!(automaton.get_age(2, 2), tick);
+            assert_eq!(automaton.get_age(2, 1), 0);
+            assert_eq!(automaton.get_age(1, 2), 0);
+        }
+        assert_eq!(automaton.get_age(0, 0), 6);
+        // The center has the last color of the gradient, and the dead cells have no gradient.
+        assert_eq!(automaton.get_color_index(2, 2), 5);
+        assert_eq!(automaton.get_colors()[5], (0, 255, 0));
+        assert_eq!(automaton.get_color_index(0, 0), 0);
+
+        automaton.set_cell(2, 2, 0).unwrap();
+        assert_eq!(automaton.get_age(2, 2), 0);
+        automaton.reset(0);
+        assert_eq!(automaton.get_age(0, 0), 0);
+        assert_eq!(Automaton::with_seed(parse(GAME_OF_LIFE_FILE).unwrap(), 0).get_age(0, 0), 0);
+    }
+
+    #[test]
+    fn circles_and_lines_are_rasterized() {
+        assert_eq!(circle_cells((3, 3), 0), vec![(3, 3)]);
+        assert_eq!(circle_cells((3, 3), 1), vec![(2, 3), (3, 2), (3, 3), (3, 4), (4, 3)]);
+        assert_eq!(line_cells((0, 0), (4, 2)), vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2)]);
+        assert_eq!(line_cells((2, 4), (2, 1)), vec![(2, 4), (2, 3), (2, 2), (2, 1)]);
+
+        let automaton = Automaton::with_seed(parse(SHAPES_FILE).unwrap(), 0);
+        assert_eq!(automaton.state_counts(), vec![9 * 7 - 13 - 9, 13, 9]);
+        assert_eq!(automaton.get_grid()[6 * 9..], [2; 9]);
+        assert_eq!(automaton.get_state(4, 1), 1);
+        assert_eq!(automaton.get_state(5, 1), 0);
+    }
+
+    #[test]
+    fn pattern_cells_are_placed_over_the_distributions() {
+        let mut automaton = Automaton::with_seed(parse(PATTERN_FILE).unwrap(), 0);
+        let alive_cells = |automaton: &Automaton| automaton.cells().filter(|(_, state)| *state == 1).map(|(position, _)| position).collect::<Vec<_>>();
+        assert_eq!(alive_cells(&automaton), vec![(2, 1), (3, 2), (1, 3), (2, 3), (3, 3)]);
+        // The glider moves by a cell diagonally every 4 ticks.
+        for _ in 0..4 {
+            automaton.tick();
+        }
+        assert_eq!(alive_cells(&automaton), vec![(3, 2), (4, 3), (2, 4), (3, 4), (4, 4)]);
+    }
+
+    #[test]
+    fn quantity_conditions_count_the_neighborhood() {
+        let excited_cells = |neighborhood: Option<Neighborhood>| {
+            let mut rules = parse(VON_NEUMANN_FILE).unwrap();
+            if let Some(neighborhood) = neighborhood {
+                rules.neighborhood = neighborhood;
+                rules.neighbor_offsets = neighborhood.offsets(false);
+            }
+            let mut automaton = Automaton::with_seed(rules, 0);
+            automaton.tick();
+            automaton.get_grid().iter().enumerate().filter(|(_, state)| **state == 1).map(|(index, _)| (index % 5, index / 5)).collect::<Vec<_>>()
+        };
+        assert_eq!(excited_cells(None), vec![(2, 1), (1, 2), (3, 2), (2, 3)]);
+        // All the cells within 2 cells of the center, but the refractory cell in the corner and the center.
+        assert_eq!(excited_cells(Some(Neighborhood::Moore(2))).len(), 23);
+    }
+
+    #[test]
+    fn distance_conditions_use_distance_field() {
+        let mut automaton = Automaton::new(parse(DISTANCE_FILE).unwrap());
+        assert_eq!(automaton.get_distance(2, 8, 5), Some(3));
+        assert_eq!(automaton.get_distance(2, 3, 3), Some(2));
+        assert_eq!(automaton.get_distance(2, 19, 5), Some(6));
+        assert_eq!(automaton.get_distance(0, 5, 5), None);
+        automaton.tick();
+        assert_eq!(automaton.raw_state_counts(), vec![375, 24, 1]);
+    }
+
+    #[test]
+    fn default_state_gets_the_remaining_proportion() {
+        for (proportion, dead) in [("0.2", 0.8), ("0.75", 0.25)] {
+            let text = fs::read_to_string(GAME_OF_LIFE_FILE).unwrap().replace("proportion 0.2", &format!("proportion {}", proportion));
+            let counts = Automaton::with_seed(parse_str(&text).unwrap(), 9).state_counts();
+            let dead_share = counts[0] as f64 / (counts[0] + counts[1]) as f64;
+            assert!((dead_share - dead).abs() < 0.02, "{} : {}", proportion, dead_share);
+        }
+    }
+
+    #[test]
+    fn distances_follow_the_neighborhood() {
+        let distance = |neighborhood: Neighborhood, (x, y): (isize, isize)| {
+            let mut rules = parse(DISTANCE_FILE).unwrap();
+            rules.neighborhood = neighborhood;
+            rules.neighbor_offsets = neighborhood.offsets(false);
+            Automaton::new(rules).get_distance(2, x, y)
+        };
+        // The fire is at (5, 5).
+        assert_eq!(distance(Neighborhood::Moore(1), (8, 8)), Some(3));
+        assert_eq!(distance(Neighborhood::VonNeumann(1), (8, 8)), Some(6));
+        assert_eq!(distance(Neighborhood::VonNeumann(1), (5, 8)), Some(3));
+        assert_eq!(distance(Neighborhood::Moore(2), (8, 8)), Some(2));
+        assert_eq!(distance(Neighborhood::VonNeumann(2), (8, 8)), Some(3));
+    }
+
+    #[test]
+    fn quantity_conditions_count_the_neighbors_in_the_adjacent_layers() {
+        let mut automaton = Automaton::with_seed(parse(THREE_DIMENSIONAL_FILE).unwrap(), 0);
+        // The 4 layers of 4 x 4 cells are stacked in the grid, and a cell is at (x, y, layer).
+        let alive_cells = |automaton: &Automaton| automaton.get_grid().iter().enumerate()
+            .filter(|(_, state)| **state == 1)
+            .map(|(index, _)| (index % 4, index / 4 % 4, index / 16))
+            .collect::<Vec<_>>();
+        let (x, y, z) = alive_cells(&automaton)[0];
+        automaton.tick();
+        let mut expected = (-1..=1).flat_map(|u| (-1..=1).flat_map(move |v| (-1..=1).map(move |w| (u, v, w))))
+            .map(|(u, v, w): (isize, isize, isize)| ((x as isize + u).rem_euclid(4) as usize, (y as isize + v).rem_euclid(4) as usize, (z as isize + w).rem_euclid(4) as usize))
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|&(x, y, z)| (z, y, x));
+        assert_eq!(alive_cells(&automaton), expected);
+    }
+
+    #[test]
+    fn count9_conditions_count_the_cell_itself() {
+        let mut automaton = Automaton::with_seed(parse(COUNT9_FILE).unwrap(), 0);
+        for ((x, y), _) in automaton.cells().collect::<Vec<_>>() {
+            automaton.set_cell(x, y, 0).unwrap();
+        }
+        // A plus sign : its center has 4 white neighbors, and is the fifth white cell of its block.
+        for (x, y) in [(2, 2), (1, 2), (3, 2), (2, 1), (2, 3)].iter() {
+            automaton.set_cell(*x, *y, 1).unwrap();
+        }
+        automaton.tick();
+        assert_eq!(automaton.get_state(2, 2), 1);
+        assert_eq!(automaton.get_state(1, 2), 0);
+        assert_eq!(automaton.get_state(1, 1), 0);
+    }
+
+    #[test]
+    fn vote_transitions_take_the_most_common_state() {
+        // 4 white cells and 5 red cells : a white cell sees 5 red neighbors, and a red cell sees a tie of 4 red and 4 white.
+        let vote = |rules| {
+            let mut automaton = Automaton::with_seed(rules, 0);
+            for ((x, y), _) in automaton.cells().collect::<Vec<_>>() {
+                automaton.set_cell(x, y, if x + 3 * y < 4 { 1 } else { 2 }).unwrap();
+            }
+            automaton.tick();
+            automaton.get_grid().to_vec()
+        };
+        assert_eq!(vote(parse(VOTE_FILE).unwrap()), vec![2; 9]);
+
+        let lowest = fs::read_to_string(VOTE_FILE).unwrap().replace("(red, vote,", "(red, vote lowest,");
+        assert_eq!(vote(parse_str(&lowest).unwrap()), vec![2, 2, 2, 2, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn replace_rules_keeps_the_cells_by_state_name() {
+        let mut automaton = Automaton::with_seed(parse(GAME_OF_LIFE_FILE).unwrap(), 0);
+        automaton.tick();
+        let grid = automaton.get_grid().to_vec();
+        let text = fs::read_to_string(GAME_OF_LIFE_FILE).unwrap();
+
+        // A new state is declared first, and the alive cells don't die anymore.
+        let immortal = text.replace("states {", "states {\n    (ghost, 0, 0, 255, proportion 0.0),")
+            .replace("(alive, dead, alive < 2 || alive > 3),", "");
+        assert_eq!(automaton.replace_rules(parse_str(&immortal).unwrap()), Ok(Vec::new()));
+        assert_eq!(automaton.get_grid(), grid.iter().map(|state| state + 1).collect::<Vec<_>>().as_slice());
+        let alive_count = automaton.state_counts()[2];
+        automaton.tick();
+        assert!(automaton.state_counts()[2] >= alive_count);
+
+        let renamed = text.replace("alive", "living");
+        assert_eq!(automaton.replace_rules(parse_str(&renamed).unwrap()),
+                   Ok(vec!["The state \"alive\" is not in the new rules, its cells are now in the default state \"dead\".".to_string()]));
+        assert_eq!(automaton.state_counts(), vec![grid.len(), 0]);
+
+        let resized = text.replacen("size (", "size (1", 1);
+        assert!(automaton.replace_rules(parse_str(&resized).unwrap()).is_err());
+    }
+
+    #[test]
+    fn memory_estimate_matches_the_allocations() {
+        let rules = parse(GAME_OF_LIFE_FILE).unwrap();
+        let estimate = Automaton::estimate_memory(&rules);
+        assert_eq!(estimate, 200 * 50 * 2 * 8);
+        let mut automaton = Automaton::with_seed(rules, 0);
+        automaton.tick();
+        assert_eq!(automaton.memory_usage(), estimate);
+
+        for file_name in [MEMORY_FILE, DATA_FILE, DISTANCE_FILE].iter() {
+            let rules = parse(file_name).unwrap();
+            let estimate = Automaton::estimate_memory(&rules);
+            let mut automaton = Automaton::with_seed(rules, 0);
+            automaton.tick();
+            assert_eq!(automaton.memory_usage(), estimate, "{}", file_name);
+        }
+    }
+
+    #[test]
+    fn set_cell_changes_the_next_tick() {
+        let mut automaton = Automaton::with_seed(parse(GAME_OF_LIFE_FILE).unwrap(), 0);
+        for ((x, y), _) in automaton.cells().collect::<Vec<_>>() {
+            automaton.set_cell(x, y, 0).unwrap();
+        }
+        // A blinker.
+        for x in 4..7 {
+            automaton.set_cell(x, 5, 1).unwrap();
+        }
+        assert_eq!(automaton.state_counts(), vec![automaton.get_grid().len() - 3, 3]);
+        automaton.tick();
+        let alive_cells = automaton.cells().filter(|(_, state)| *state == 1).map(|(position, _)| position).collect::<Vec<_>>();
+        assert_eq!(alive_cells, vec![(5, 4), (5, 5), (5, 6)]);
+
+        let (width, height) = automaton.get_rules().world_size;
+        assert_eq!(automaton.set_cell(width, 0, 1), Err(format!("The cell ({}, 0) is outside of the world.", width)));
+        assert_eq!(automaton.set_cell(0, height - 1, 2), Err("The state 2 doesn't exist.".to_string()));
+    }
+
+    #[test]
+    fn transition_cache_gives_the_same_evolution() {
+        for file_name in [GAME_OF_LIFE_FILE, VON_NEUMANN_FILE, THREE_DIMENSIONAL_FILE, BOUNDARY_FILE, COUNT9_FILE, VOTE_FILE, EFFECTS_FILE].iter() {
+            let mut automaton = Automaton::with_seed(parse(file_name).unwrap(), 3);
+            let mut cached = Automaton::with_seed(parse(file_name).unwrap(), 3);
+            cached.enable_transition_cache().unwrap();
+            for _ in 0..10 {
+                automaton.tick();
+                cached.tick();
+                assert_eq!(cached.get_grid(), automaton.get_grid(), "{}", file_name);
+            }
+            assert!(cached.transition_cache_size().unwrap() > 0);
+        }
+        let mut automaton = Automaton::with_seed(parse(VIRUS_FILE).unwrap(), 3);
+        assert_eq!(automaton.enable_transition_cache(),
+                   Err("The transitions can only be cached if their conditions only test the states of the cell and of its neighbors.".to_string()));
+        assert_eq!(automaton.transition_cache_size(), None);
+    }
+
+    #[test]
+    fn tracing_a_cell_does_not_change_the_simulation() {
+        let run = |traced_cell| {
+            let mut automaton = Automaton::with_seed(parse(TRACE_RANDOM_FILE).unwrap(), 11);
+            automaton.set_traced_cell(traced_cell);
+            for _ in 0..10 {
+                automaton.tick();
+            }
+            assert_eq!(automaton.get_trace().is_empty(), traced_cell.is_none());
+            automaton.grid
+        };
+        let grid = run(None);
+        for x in 0..20 {
+            for y in 0..20 {
+                assert_eq!(run(Some((x, y))), grid, "traced cell ({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn transition_table_gives_the_same_evolution() {
+        for file_name in [GAME_OF_LIFE_FILE, SECOND_ORDER_FILE, VON_NEUMANN_FILE, THREE_DIMENSIONAL_FILE, BOUNDARY_FILE, COUNT9_FILE, VOTE_FILE, EFFECTS_FILE, DELAY_FILE].iter() {
+            let mut automaton = Automaton::with_seed(parse(file_name).unwrap(), 3);
+            assert!(automaton.has_transition_table(), "{}", file_name);
+            let mut interpreted = Automaton::with_seed(parse(file_name).unwrap(), 3);
+            interpreted.transition_table = None;
+            for _ in 0..10 {
+                automaton.tick();
+                interpreted.tick();
+                assert_eq!(automaton.get_grid(), interpreted.get_grid(), "{}", file_name);
+            }
+        }
+        for file_name in [VIRUS_FILE, DATA_FILE, DISTANCE_FILE, MEMORY_FILE, SHARED_DRAWS_FILE].iter() {
+            assert!(!Automaton::with_seed(parse(file_name).unwrap(), 3).has_transition_table(), "{}", file_name);
+        }
+        // 11 states of 8 neighbors would need too many entries.
+        assert!(!Automaton::with_seed(parse(DETERMINISTIC_GAME_OF_LIFE_FILE).unwrap(), 3).has_transition_table());
+    }
+
+    #[test]
+    fn shared_draws_strike_the_cells_together() {
+        // With a draw per tick, the struck cells are all the cells or none of them.
+        let mut automaton = Automaton::with_seed(parse(SHARED_DRAWS_FILE).unwrap(), 5);
+        let mut struck_counts = Vec::new();
+        for _ in 0..20 {
+            automaton.tick();
+            struck_counts.push(automaton.state_counts()[1]);
+        }
+        assert!(struck_counts.iter().all(|count| *count == 0 || *count == 96));
+        assert!(struck_counts.contains(&0) && struck_counts.contains(&96));
+
+        // With a draw per block, the struck cells are whole blocks of 4 x 4 cells, but not all the blocks.
+        let text = fs::read_to_string(SHARED_DRAWS_FILE).unwrap().replace("per tick", "per block 4");
+        let mut automaton = Automaton::with_seed(parse_str(&text).unwrap(), 5);
+        let mut partial = false;
+        for _ in 0..20 {
+            automaton.tick();
+            let grid = automaton.get_grid();
+            assert!(grid.iter().enumerate().all(|(index, state)| *state == grid[(index / 12 / 4 * 4) * 12 + index % 12 / 4 * 4]));
+            let struck = automaton.state_counts()[1];
+            partial |= struck != 0 && struck != 96;
+        }
+        assert!(partial);
+    }
+
+    #[test]
+    fn shared_draws_of_different_conditions_are_independent() {
+        let struck = |text: &str| {
+            let mut automaton = Automaton::with_seed(parse_str(text).unwrap(), 5);
+            let mut struck = Vec::new();
+            for _ in 0..40 {
+                automaton.tick();
+                let counts = automaton.state_counts();
+                struck.push((counts[2] > 0, counts[3] > 0));
+            }
+            struck
+        };
+        let text = fs::read_to_string(INDEPENDENT_DRAWS_FILE).unwrap();
+        let struck_together = struck(&text);
+        assert!(struck_together.iter().any(|(a, b)| a != b));
+
+        // Two draws of the same conjunction pass together less often than one of them.
+        let conjunction = text.replace("(b, b_hit, rand 0.5 per tick)", "(b, b_hit, rand 0.5 per tick && rand 0.5 per tick)");
+        let ticks = struck(&conjunction).iter().filter(|(_, b)| *b).count();
+        assert!(ticks < struck_together.iter().filter(|(_, b)| *b).count());
     }
 }
@@ -0,0 +1,163 @@
+//! This module measures the speed of the engine on rules files, for the `bench` command : the ticks per second, the latency
+//! of the ticks and the memory of the cells. The results are printed as lines, or written as JSON to compare the versions
+//! of the engine and catch the regressions.
+
+use std::time::{Duration, Instant};
+use crate::automaton::Automaton;
+use crate::compiler::error::CompileError;
+use crate::compiler::semantic::{parse_with_options, CompilerOptions};
+use crate::manifest::engine_variant;
+
+/// The latency of the ticks : the median, the 90th and 99th percentiles, and the longest tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Latencies {
+    pub median: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration
+}
+
+impl Latencies {
+    /// Takes the percentiles of the durations of the ticks, sorted from the shortest to the longest.
+    pub fn from_sorted(durations: &[Duration]) -> Latencies {
+        Latencies {
+            median: percentile(durations, 50.0),
+            p90: percentile(durations, 90.0),
+            p99: percentile(durations, 99.0),
+            max: durations.last().copied().unwrap_or_default()
+        }
+    }
+}
+
+/// Returns the duration under which `p` percent of the sorted durations are, with the nearest-rank method.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.saturating_sub(1)).copied().unwrap_or_default()
+}
+
+/// The measures of the ticks of a rules file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchResult {
+    pub file_name: String,
+    pub cells: usize, // The number of cells of the world, the layers of a 3D world included
+    pub ticks: usize,
+    pub duration: Duration, // The time taken by all the ticks
+    pub latencies: Latencies,
+    pub memory: usize // The memory allocated for the cells at the end, in bytes
+}
+
+impl BenchResult {
+    pub fn ticks_per_second(&self) -> f64 {
+        if self.duration.as_nanos() == 0 { 0.0 } else { self.ticks as f64 / self.duration.as_secs_f64() }
+    }
+
+    /// Returns the measures in a line, like
+    /// "resources/game_of_life.txt : 1000 ticks of 10000 cells, 850.2 ticks/s, latency median 1.102 ms, p90 1.300 ms, p99 2.012 ms, max 3.400 ms, 0.2 MB".
+    pub fn line(&self) -> String {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        format!("{} : {} ticks of {} cells, {:.1} ticks/s, latency median {:.3} ms, p90 {:.3} ms, p99 {:.3} ms, max {:.3} ms, {:.1} MB",
+                self.file_name, self.ticks, self.cells, self.ticks_per_second(), millis(self.latencies.median), millis(self.latencies.p90),
+                millis(self.latencies.p99), millis(self.latencies.max), self.memory as f64 / (1024.0 * 1024.0))
+    }
+
+    fn to_json(&self) -> String {
+        let micros = |duration: Duration| duration.as_secs_f64() * 1_000_000.0;
+        format!("{{\"file\": {}, \"cells\": {}, \"ticks\": {}, \"seconds\": {:.6}, \"ticks_per_second\": {:.3}, \
+                 \"latency_us\": {{\"median\": {:.3}, \"p90\": {:.3}, \"p99\": {:.3}, \"max\": {:.3}}}, \"memory_bytes\": {}}}",
+                json_string(&self.file_name), self.cells, self.ticks, self.duration.as_secs_f64(), self.ticks_per_second(),
+                micros(self.latencies.median), micros(self.latencies.p90), micros(self.latencies.p99), micros(self.latencies.max), self.memory)
+    }
+}
+
+/// Runs the ticks of the rules file from the initial grid given by the seed, and measures each one. Nothing is printed.
+pub fn bench_file(file_name: &str, ticks: usize, seed: u64, options: &CompilerOptions) -> Result<BenchResult, Vec<CompileError>> {
+    let rules = parse_with_options(file_name, options)?;
+    let (width, height) = rules.grid_size();
+    let mut automaton = Automaton::with_seed(rules, seed);
+    let mut durations = Vec::with_capacity(ticks);
+    let start = Instant::now();
+    for _ in 0..ticks {
+        let tick_start = Instant::now();
+        automaton.tick();
+        durations.push(tick_start.elapsed());
+    }
+    let duration = start.elapsed();
+    durations.sort();
+    Ok(BenchResult {
+        file_name: file_name.to_string(),
+        cells: width * height,
+        ticks,
+        duration,
+        latencies: Latencies::from_sorted(&durations),
+        memory: automaton.memory_usage()
+    })
+}
+
+/// Returns the results as a JSON document, with the version and the variant of the engine that made them.
+pub fn to_json(results: &[BenchResult], seed: u64) -> String {
+    let results = results.iter().map(|result| format!("    {}", result.to_json())).collect::<Vec<_>>();
+    format!("{{\n  \"version\": {},\n  \"variant\": {},\n  \"seed\": {},\n  \"results\": [\n{}\n  ]\n}}\n",
+            json_string(env!("CARGO_PKG_VERSION")), json_string(engine_variant()), seed, results.join(",\n"))
+}
+
+/// Returns the text as a JSON string, between quotes.
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c)
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::bench::{bench_file, json_string, percentile, to_json, BenchResult, Latencies};
+    use crate::compiler::semantic::CompilerOptions;
+
+    #[test]
+    fn percentiles_of_the_ticks() {
+        let durations: Vec<Duration> = (1..=200).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&durations, 50.0), Duration::from_millis(100));
+        assert_eq!(percentile(&durations, 99.0), Duration::from_millis(198));
+        assert_eq!(Latencies::from_sorted(&durations).max, Duration::from_millis(200));
+        assert_eq!(Latencies::from_sorted(&[]), Latencies::default());
+        assert_eq!(percentile(&[Duration::from_millis(3)], 90.0), Duration::from_millis(3));
+    }
+
+    #[test]
+    fn bench_measures_each_tick() {
+        let result = bench_file("resources/game_of_life.txt", 20, 7, &CompilerOptions::default()).unwrap();
+        assert_eq!((result.ticks, result.cells), (20, 200 * 50));
+        let latencies = result.latencies;
+        assert!(latencies.median <= latencies.p90 && latencies.p90 <= latencies.p99 && latencies.p99 <= latencies.max);
+        assert!(latencies.max <= result.duration);
+        assert!(result.ticks_per_second() > 0.0);
+        assert!(result.memory >= 2 * 200 * 50 * std::mem::size_of::<usize>());
+        assert!(bench_file("resources/tests/missing.txt", 20, 7, &CompilerOptions::default()).is_err());
+    }
+
+    #[test]
+    fn results_in_json() {
+        let result = BenchResult {
+            file_name: "rules \"v2\".txt".to_string(),
+            cells: 100,
+            ticks: 4,
+            duration: Duration::from_millis(2),
+            latencies: Latencies { median: Duration::from_micros(400), p90: Duration::from_micros(600), p99: Duration::from_micros(700), max: Duration::from_micros(700) },
+            memory: 1600
+        };
+        let json = to_json(&[result.clone(), result], 42);
+        assert!(json.contains("\"seed\": 42,"));
+        assert!(json.contains("{\"file\": \"rules \\\"v2\\\".txt\", \"cells\": 100, \"ticks\": 4, \"seconds\": 0.002000, \"ticks_per_second\": 2000.000, \
+                               \"latency_us\": {\"median\": 400.000, \"p90\": 600.000, \"p99\": 700.000, \"max\": 700.000}, \"memory_bytes\": 1600},\n"));
+        assert_eq!(json_string("a\\b\n\t"), "\"a\\\\b\\n\\u0009\"");
+    }
+}
@@ -4,28 +4,376 @@ extern crate log;
 use std::{
     env,
     process,
+    str::FromStr,
 };
 
 use mutations::executor::{
+    analyze,
+    check,
+    verify,
+    document_rules,
+    rename_state_in_file,
+    export_palette,
+    generate_benchmark,
     execute,
+    search_patterns,
+    render_run,
+    evolve_rules,
+    bench,
+    BenchConf,
+    SearchConf,
+    EvolveRulesConf,
+    RenderConf,
     Conf,
-    MaxIterationCount
+    MaxIterationCount,
+    StopCondition,
+    PopulationThreshold
 };
+use mutations::compiler::parser::ComparisonOperator;
+use mutations::compiler::semantic::{CompilerOptions, Lint, LintLevel};
+use mutations::compiler::messages::Catalog;
+use mutations::terminal::{ColorDepth, Charset};
+use mutations::generator::GeneratorConf;
+use mutations::profile::{load_profile, CONFIG_FILE};
 
 fn main() {
     env_logger::init();
 
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        error!("USAGE : <automaton_file_path>");
-        process::exit(1);
-    }
-    let file_name = &args[1];
-
-    execute(&Conf {
-        file_name,
-        with_display: true,
-        iteration_delay: 10,
-        max_iteration_count: MaxIterationCount::Infinite,
-    });
+    if args.len() == 3 && args[1] == "analyze" {
+        analyze(&args[2], &compiler_options());
+        return;
+    }
+    if args.len() >= 3 && args[1] == "check" {
+        match parse_check_options(&args[2..]) {
+            Ok((file_names, compiler_options)) => if !check(&file_names, &compiler_options) {
+                process::exit(1);
+            },
+            Err(error) => {
+                error!("{}", error);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if args.len() >= 3 && args[1] == "bench" {
+        match parse_bench_options(&args[2..]) {
+            Ok(conf) => if !bench(&conf) {
+                process::exit(1);
+            },
+            Err(error) => {
+                error!("{}", error);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if args.len() >= 4 && args[1] == "verify" {
+        let file_names: Vec<&str> = args[3..].iter().map(String::as_str).collect();
+        if !verify(&args[2], &file_names) {
+            process::exit(1);
+        }
+        return;
+    }
+    if args.len() == 5 && args[1] == "rename-state" {
+        if !rename_state_in_file(&args[4], &args[2], &args[3]) {
+            process::exit(1);
+        }
+        return;
+    }
+    if (args.len() == 3 || args.len() == 4) && args[1] == "doc" {
+        document_rules(&args[2], args.get(3).map(String::as_str));
+        return;
+    }
+    if args.len() == 5 && args[1] == "palette" && args[2] == "save" {
+        export_palette(&args[3], &args[4]);
+        return;
+    }
+    if (args.len() == 6 || args.len() == 7) && args[1] == "gen-bench" {
+        let numbers: Result<Vec<usize>, _> = args[3..6].iter().map(|arg| arg.parse()).collect();
+        let seed = args.get(6).map_or(Ok(rand::random()), |seed| seed.parse());
+        match (numbers.as_deref(), seed) {
+            (Ok(&[states, transitions, condition_complexity]), Ok(seed)) => {
+                generate_benchmark(&args[2], &GeneratorConf { states, transitions, condition_complexity, ..GeneratorConf::default() }, seed);
+                return;
+            },
+            _ => {
+                error!("The number of states, the number of transitions, the condition complexity and the seed must be positive integers.");
+                process::exit(1);
+            }
+        }
+    }
+    if args.len() == 8 && args[1] == "search" {
+        match (args[3].parse(), args[4].parse(), args[6].parse(), args[7].parse()) {
+            (Ok(runs), Ok(ticks), Ok(min_population), Ok(max_population)) => {
+                search_patterns(&SearchConf { file_name: &args[2], runs, ticks, state: &args[5], min_population, max_population });
+                return;
+            },
+            _ => {
+                error!("The number of runs, the number of ticks and the population bounds must be positive integers.");
+                process::exit(1);
+            }
+        }
+    }
+    if (args.len() == 6 || args.len() == 7) && args[1] == "render" {
+        let numbers: Result<Vec<usize>, _> = args[4..6].iter().map(|arg| arg.parse()).collect();
+        let seed = args.get(6).map_or(Ok(rand::random()), |seed| seed.parse());
+        match (numbers.as_deref(), seed) {
+            (Ok(&[ticks, scale]), Ok(seed)) => {
+                render_run(&RenderConf { file_name: &args[2], output: &args[3], ticks, scale, seed });
+                return;
+            },
+            _ => {
+                error!("The number of ticks, the scale and the seed must be positive integers.");
+                process::exit(1);
+            }
+        }
+    }
+    if (args.len() == 7 || args.len() == 8) && args[1] == "evolve" {
+        let numbers: Result<Vec<usize>, _> = args[5..7].iter().map(|arg| arg.parse()).collect();
+        let seed = args.get(7).map_or(Ok(rand::random()), |seed| seed.parse());
+        match (numbers.as_deref(), seed) {
+            (Ok(&[generations, ticks]), Ok(seed)) => {
+                evolve_rules(&EvolveRulesConf { file_name: &args[2], output: &args[3], metric: &args[4], generations, ticks, seed });
+                return;
+            },
+            _ => {
+                error!("The number of generations, the number of ticks and the seed must be positive integers.");
+                process::exit(1);
+            }
+        }
+    }
+    let (profile_file, args) = match expand_profile(&args[1..]) {
+        Ok(expanded) => expanded,
+        Err(error) => {
+            error!("{}", error);
+            process::exit(1);
+        }
+    };
+    let conf = match parse_run_options(&args, profile_file.as_deref()) {
+        Ok(conf) => conf,
+        Err(error) => {
+            error!("{}", error);
+            error!("USAGE : [run] <automaton_file_path> [overlay_file_path]... [options] | analyze <automaton_file_path> \
+                | verify <manifest_file_path> <automaton_file_path>... \
+                | doc <automaton_file_path> [output_file_path] \
+                | rename-state <old_name> <new_name> <automaton_file_path> \
+                | check <automaton_file_path>... [--deny warnings|<lint>, --allow <lint>, --warn <lint>] \
+                | search <automaton_file_path> <runs> <ticks> <state> <min_population> <max_population> \
+                | palette save|load <automaton_file_path> <palette_file_path> \
+                | gen-bench <output_file_path> <states> <transitions> <condition_complexity> [seed] \
+                | bench <automaton_file_path>... [--ticks <count>, --seed <seed>, --json <json_file_path>] \
+                | render <automaton_file_path> <output_path> <ticks> <scale> [seed] \
+                | evolve <automaton_file_path> <output_directory> activity|diversity|<state> <generations> <ticks> [seed]");
+            error!("OPTIONS : --headless, --delay <milliseconds>, --max-iterations <count>, --stop-when-stable <period>, --stop-when <state><operator><cells>[%], --seed <seed>, \
+                --fps <frames_per_second>, --tps <ticks_per_second>, --skip-frames, --show-wrap, --layer <z>, --downscale <block_side>, --palette <palette_file_path>, --pattern <rle_file_path>, --pattern-at <x>,<y>, --pattern-states <state>,..., --raw-statistics, --delay-gradient, \
+                --trace <x>,<y>, --metrics <state>, --monitor <ticks>, --max-period <ticks>, --tag <x>,<y>,<width>,<height>, --summary <iterations>, \
+                --colors truecolor|256|16, --ascii, --unicode, --png <directory>, --png-scale <pixels>, --sdl, --record <animation_file_path>, --crossfade <frames>, \
+                --checkpoint <checkpoint_file_path>, --resume <checkpoint_file_path>, --event-log <event_log_file_path>, --population-csv <csv_file_path>, --population-interval <iterations>, --memory-limit <megabytes>, --size <width>,<height>, --profile <name>, --cache, --manifest <manifest_file_path>, --embed-manifest, --hud, \
+                --deny warnings|<lint>, --allow <lint>, --warn <lint>");
+            process::exit(1);
+        }
+    };
+    execute(&conf);
+}
+
+/// Replaces `--profile <name>` with the options of the profile, read from the configuration file.
+/// The options after `--profile` override the profile, and the automaton file of the command line replaces the profile's one.
+fn expand_profile(args: &[String]) -> Result<(Option<String>, Vec<String>), String> {
+    match args.iter().position(|arg| arg == "--profile") {
+        Some(index) => {
+            let name = args.get(index + 1).ok_or("The option --profile expects a value.")?;
+            let profile = load_profile(CONFIG_FILE, name)?;
+            let expanded = args[..index].iter().chain(&profile.options).chain(&args[index + 2..]).cloned().collect();
+            Ok((profile.file, expanded))
+        },
+        None => Ok((None, args.to_vec()))
+    }
+}
+
+/// Parses the automaton file path and the options of a run, optionally preceded by `run`.
+/// The default file is used if the arguments have no automaton file path.
+/// `palette load <automaton> <palette>` is the same as `<automaton> --palette <palette>`.
+fn parse_run_options<'a>(args: &'a [String], default_file: Option<&'a str>) -> Result<Conf<'a>, String> {
+    let mut conf = Conf { compiler_options: compiler_options(), ..Conf::default() };
+    let args = match args {
+        [command, action, file_name, palette] if command == "palette" && action == "load" => {
+            conf.palette = Some(palette.as_str());
+            std::slice::from_ref(file_name)
+        },
+        [command, args @ ..] if command == "run" => args,
+        _ => args
+    };
+    let mut args = args.iter();
+    let mut file_name = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().map(String::as_str).ok_or(format!("The option {} expects a value.", arg));
+        match arg.as_str() {
+            "--headless" => conf.with_display = false,
+            "--delay" => conf.iteration_delay = parse_number(arg, value()?)?,
+            "--max-iterations" => conf.max_iteration_count = MaxIterationCount::Finite(parse_number(arg, value()?)?),
+            "--stop-when-stable" => conf.stop_condition = StopCondition::OnStabilization { max_period: parse_number(arg, value()?)? },
+            "--stop-when" => conf.stop_condition = parse_population_condition(arg, value()?)?,
+            "--seed" => conf.seed = Some(parse_number(arg, value()?)?),
+            "--fps" => conf.target_fps = Some(parse_number(arg, value()?)?),
+            "--tps" => conf.target_tps = Some(parse_number(arg, value()?)?),
+            "--memory-limit" => conf.memory_limit = parse_number(arg, value()?)?,
+            "--cache" => conf.transition_cache = true,
+            "--manifest" => conf.manifest = Some(value()?),
+            "--embed-manifest" => conf.embed_manifest = true,
+            "--hud" => conf.hud = true,
+            "--skip-frames" => conf.skip_frames_when_behind = true,
+            "--show-wrap" => conf.show_wrap = true,
+            "--layer" => conf.layer = parse_number(arg, value()?)?,
+            "--downscale" => conf.downscale = Some(parse_number(arg, value()?)?),
+            "--summary" => conf.summary_interval = match parse_number(arg, value()?)? {
+                0 => return Err(format!("The option {} expects a positive integer, found 0.", arg)),
+                interval => Some(interval)
+            },
+            "--colors" => conf.color_depth = Some(match value()? {
+                "truecolor" => ColorDepth::TrueColor,
+                "256" => ColorDepth::Ansi256,
+                "16" => ColorDepth::Ansi16,
+                colors => return Err(format!("The option {} expects \"truecolor\", \"256\" or \"16\", found {}.", arg, colors))
+            }),
+            "--ascii" => conf.charset = Some(Charset::Ascii),
+            "--unicode" => conf.charset = Some(Charset::Block),
+            "--png" => conf.png_frames = Some(value()?),
+            "--png-scale" => conf.png_scale = parse_number(arg, value()?)?,
+            "--sdl" => conf.sdl_window = true,
+            "--record" => conf.record = Some(value()?),
+            "--crossfade" => conf.crossfade = parse_number(arg, value()?)?,
+            "--resume" => conf.resume = Some(value()?),
+            "--checkpoint" => conf.checkpoint = Some(value()?),
+            "--event-log" => conf.event_log = Some(value()?),
+            "--population-csv" => conf.population_csv = Some(value()?),
+            "--population-interval" => conf.population_interval = parse_number(arg, value()?)?,
+            "--palette" => conf.palette = Some(value()?),
+            "--pattern" => conf.pattern = Some(value()?),
+            "--pattern-at" => conf.pattern_position = match parse_numbers(arg, value()?)?[..] {
+                [x, y] => Some((x, y)),
+                _ => return Err(format!("The option {} expects a position like 10,20.", arg))
+            },
+            "--pattern-states" => conf.pattern_states = value()?.split(',').collect(),
+            "--raw-statistics" => conf.raw_statistics = true,
+            "--delay-gradient" => conf.compiler_options.delay_color_gradient = true,
+            "--deny" | "--allow" | "--warn" => parse_lint_option(arg, value()?, &mut conf.compiler_options)?,
+            "--trace" => conf.traced_cell = match parse_numbers(arg, value()?)?[..] {
+                [x, y] => Some((x, y)),
+                _ => return Err(format!("The option {} expects a position like 10,20.", arg))
+            },
+            "--size" => conf.compiler_options.world_size = match parse_numbers(arg, value()?)?[..] {
+                [width, height] if width > 0 && height > 0 => Some((width, height)),
+                _ => return Err(format!("The option {} expects a size like 100,50.", arg))
+            },
+            "--metrics" => conf.analyzed_state = Some(value()?),
+            "--monitor" => conf.monitor_window = Some(parse_number(arg, value()?)?),
+            "--max-period" => conf.max_period = Some(parse_number(arg, value()?)?),
+            "--tag" => conf.tagged_region = match parse_numbers(arg, value()?)?[..] {
+                [x, y, width, height] => Some((x, y, width, height)),
+                _ => return Err(format!("The option {} expects a rectangle like 10,20,5,5.", arg))
+            },
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}.", arg)),
+            _ if file_name.is_none() => file_name = Some(arg.as_str()),
+            // The next rules files are merged over the first one.
+            _ => conf.overrides.push(arg.as_str())
+        }
+    }
+    conf.file_name = file_name.or(default_file).ok_or("The automaton file path is missing.")?;
+    Ok(conf)
+}
+
+fn parse_number<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("The option {} expects a positive integer, found {}.", option, value))
+}
+
+/// Parses a condition on the population of a state, like `fire==0` or `infected>50%`.
+fn parse_population_condition(option: &str, value: &str) -> Result<StopCondition, String> {
+    let error = || format!("The option {} expects a state, a comparison operator and a number of cells or a percentage, like fire==0 or infected>50%, found {}.", option, value);
+    let start = value.find(|c| "<>=!".contains(c)).ok_or_else(error)?;
+    let length = if value[start + 1..].starts_with('=') { 2 } else { 1 };
+    let comparison = ComparisonOperator::from_symbol(&value[start..start + length]).ok_or_else(error)?;
+    let number = &value[start + length..];
+    let threshold = match number.strip_suffix('%') {
+        Some(percent) => PopulationThreshold::Percent(percent.parse().map_err(|_| error())?),
+        None => PopulationThreshold::Cells(number.parse().map_err(|_| error())?)
+    };
+    Ok(StopCondition::Population { state: value[..start].to_string(), comparison, value: threshold })
+}
+
+fn parse_numbers(option: &str, value: &str) -> Result<Vec<usize>, String> {
+    value.split(',').map(|number| parse_number(option, number)).collect()
+}
+
+/// Parses the files and the lint options of `check`, like `check a.txt b.txt --deny warnings`.
+fn parse_check_options(args: &[String]) -> Result<(Vec<&str>, CompilerOptions), String> {
+    let mut compiler_options = compiler_options();
+    let mut file_names = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--deny" | "--allow" | "--warn" => {
+                let value = args.next().ok_or(format!("The option {} expects a value.", arg))?;
+                parse_lint_option(arg, value, &mut compiler_options)?;
+            },
+            option if option.starts_with("--") => return Err(format!("Unknown option {}.", option)),
+            file_name => file_names.push(file_name)
+        }
+    }
+    if file_names.is_empty() {
+        return Err("The check command expects at least one automaton file.".to_string());
+    }
+    Ok((file_names, compiler_options))
+}
+
+/// Parses the automaton files and the options of the bench command. The files run for 1000 ticks with the seed 0 by default.
+fn parse_bench_options(args: &[String]) -> Result<BenchConf<'_>, String> {
+    let mut conf = BenchConf { file_names: Vec::new(), ticks: 1000, seed: 0, json: None };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().map(String::as_str).ok_or(format!("The option {} expects a value.", arg));
+        match arg.as_str() {
+            "--ticks" => conf.ticks = parse_number(arg, value()?)?,
+            "--seed" => conf.seed = parse_number(arg, value()?)?,
+            "--json" => conf.json = Some(value()?),
+            option if option.starts_with("--") => return Err(format!("Unknown option {}.", option)),
+            file_name => conf.file_names.push(file_name)
+        }
+    }
+    if conf.file_names.is_empty() {
+        return Err("The bench command expects at least one automaton file.".to_string());
+    }
+    Ok(conf)
+}
+
+/// Sets the level of a lint, or denies all the warnings, with `--deny`, `--allow` or `--warn`.
+fn parse_lint_option(arg: &str, value: &str, compiler_options: &mut CompilerOptions) -> Result<(), String> {
+    match (arg, value) {
+        ("--deny", "warnings") => compiler_options.deny_warnings = true,
+        (_, lint) => {
+            let lint = Lint::from_name(lint).ok_or(format!("The option {} expects a lint among {}, found {}.",
+                arg, Lint::ALL.iter().map(|lint| lint.name()).collect::<Vec<_>>().join(", "), lint))?;
+            let level = match arg {
+                "--allow" => LintLevel::Allow,
+                "--warn" => LintLevel::Warn,
+                _ => LintLevel::Deny
+            };
+            compiler_options.lint_levels.push((lint, level));
+        }
+    }
+    Ok(())
+}
+
+/// The compiler messages are translated with the catalog given by the MUTATIONS_CATALOG environment variable, if set.
+fn compiler_options() -> CompilerOptions {
+    match env::var("MUTATIONS_CATALOG") {
+        Ok(file_name) => match Catalog::load(&file_name) {
+            Ok(catalog) => CompilerOptions { catalog: Some(catalog), ..CompilerOptions::default() },
+            Err(error) => {
+                error!("{}", error);
+                process::exit(1);
+            }
+        },
+        Err(_) => CompilerOptions::default()
+    }
 }
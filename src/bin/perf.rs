@@ -10,5 +10,7 @@ fn main() {
         with_display: false,
         iteration_delay: 0,
         max_iteration_count: MaxIterationCount::Finite(5000),
+        downscale: Some(1),
+        ..Conf::default()
     });
 }
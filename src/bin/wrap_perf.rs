@@ -0,0 +1,41 @@
+use std::hint::black_box;
+use std::time::Instant;
+
+use mutations::topology::{WrapTable, tore_correction};
+
+/// Compares the computed toroidal correction with the precomputed wrap table, on the neighborhood lookups of a 200x50 world.
+fn main() {
+    let size: (usize, usize) = black_box((200, 50));
+    let (table_x, table_y) = (WrapTable::new(size.0), WrapTable::new(size.1));
+    let iterations = 200;
+
+    let start = Instant::now();
+    let mut checksum = 0;
+    for _ in 0..iterations {
+        for y in 0..size.1 as isize {
+            for x in 0..size.0 as isize {
+                for (u, v) in &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                    checksum += tore_correction(y + v, size.1) * size.0 + tore_correction(x + u, size.0);
+                }
+            }
+        }
+    }
+    let computed_duration = start.elapsed();
+
+    let start = Instant::now();
+    let mut table_checksum = 0;
+    for _ in 0..iterations {
+        for y in 0..size.1 as isize {
+            for x in 0..size.0 as isize {
+                for (u, v) in &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                    table_checksum += table_y.wrap(y + v) * size.0 + table_x.wrap(x + u);
+                }
+            }
+        }
+    }
+    let table_duration = start.elapsed();
+
+    assert_eq!(checksum, table_checksum);
+    println!("tore_correction : {:?}", computed_duration);
+    println!("wrap table : {:?}", table_duration);
+}
@@ -2,74 +2,422 @@ use crate::automaton::Automaton;
 use crate::inputs::{Direction, Zoom};
 
 const TRANSLATION_OFFSET: usize = 5;
-const ZOOM_FACTOR: f64 = 1.2;
+pub const DEFAULT_VIEWPORT: (usize, usize) = (200, 50); // The field of view when the size of the terminal is unknown
+const MAX_MAGNIFICATION: usize = 8; // The side of the block of characters showing a cell, when the camera is zoomed in the most
+const WRAP_TINT: (u8, u8, u8) = (0, 0, 160); // Blended with the colors of the cells seen beyond the world's borders
 
+/// The pixels captured by the camera, column after column, with their colors : the displays draw them as they are.
+#[derive(Clone)]
 pub struct Image {
-    pub grid: Vec<Vec<usize>>,
-    pub colors: Vec<(u8, u8, u8)>   // 16M color
+    pub grid: Vec<Vec<(u8, u8, u8)>> // The color of each pixel, 16M color
 }
 
 impl Image {
-    fn new(size: (f64, f64), automaton: &Automaton) -> Image {
+    fn new(size: (usize, usize)) -> Image {
         Image {
-            grid: vec![vec![0; size.1 as usize]; size.0 as usize],
-            colors: automaton.get_colors()
+            grid: vec![vec![(0, 0, 0); size.1]; size.0]
         }
     }
 
-    fn resize(&mut self, new_size: (f64, f64)) {
-        self.grid = vec![vec![0; new_size.1 as usize]; new_size.0 as usize];
+    /// Captures the cells of the given layer, the layers of a 3D world being stacked in the grid.
+    /// Each cell is shown by a block of `magnification` x `magnification` pixels, with the color of its state and its age.
+    /// The colors are taken from the rules at each capture, since they change when the rules are reloaded.
+    fn capture(&mut self, camera_pos: (isize, isize), layer: usize, magnification: usize, automaton: &Automaton, show_wrap: bool) {
+        let colors = automaton.get_colors();
+        let (width, height) = automaton.get_rules().world_size;
+        let layer_top = (layer * height) as isize;
+        for (x_c, column) in self.grid.iter_mut().enumerate() {
+            for (y_c, pixel) in column.iter_mut().enumerate() {
+                let x = (x_c / magnification) as isize + camera_pos.0;
+                let y = (y_c / magnification) as isize + camera_pos.1;
+                *pixel = colors[automaton.get_color_index(x, layer_top + y.rem_euclid(height as isize))];
+                let wrapped = x < 0 || y < 0 || x >= width as isize || y >= height as isize;
+                if show_wrap && wrapped {
+                    *pixel = tint(*pixel);
+                }
+            }
+        }
     }
 
-    fn capture(&mut self, camera_pos: (isize, isize), automaton: &Automaton) {
+    /// Captures a pixel per block of `factor` x `factor` cells, with the color of the most frequent state of the block,
+    /// whatever the age of its cells.
+    fn capture_blocks(&mut self, camera_pos: (isize, isize), layer: usize, factor: usize, automaton: &Automaton, show_wrap: bool) {
+        let colors = automaton.get_colors();
+        let (width, height) = automaton.get_rules().world_size;
+        let blocks = automaton.dominant_states(factor, layer);
+        let blocks_size = ((width.div_ceil(factor)) as isize, (height.div_ceil(factor)) as isize);
+        let first_block = (camera_pos.0.div_euclid(factor as isize), camera_pos.1.div_euclid(factor as isize));
         for (x_c, column) in self.grid.iter_mut().enumerate() {
             for (y_c, pixel) in column.iter_mut().enumerate() {
-                let x = x_c as isize + camera_pos.0;
-                let y = y_c as isize + camera_pos.1;
-                *pixel = automaton.get_state(x, y);
+                let x = x_c as isize + first_block.0;
+                let y = y_c as isize + first_block.1;
+                *pixel = colors[blocks[(y.rem_euclid(blocks_size.1) * blocks_size.0 + x.rem_euclid(blocks_size.0)) as usize]];
+                let wrapped = x < 0 || y < 0 || x >= blocks_size.0 || y >= blocks_size.1;
+                if show_wrap && wrapped {
+                    *pixel = tint(*pixel);
+                }
             }
         }
     }
 }
 
+/// Returns the color blended half and half with the wrap tint.
+fn tint((r, g, b): (u8, u8, u8)) -> (u8, u8, u8) {
+    let blend = |component: u8, tint: u8| ((component as u16 + tint as u16) / 2) as u8;
+    (blend(r, WRAP_TINT.0), blend(g, WRAP_TINT.1), blend(b, WRAP_TINT.2))
+}
+
 /// The camera's (0,0) position is at the upper-left of the field of view.
 pub struct Camera {
     position: (isize, isize),
-    size: (f64, f64), // The size is stored as floating-point number because it makes zooming more consistent
+    size: (usize, usize), // The number of columns and rows of the field of view
+    show_wrap: bool, // If true, the cells seen beyond the world's borders are tinted, since the world wraps around
+    layer: usize, // The slice of a 3D world seen by the camera, 0 for a 2D world
+    scale: usize, // The side of the block of cells shown by a pixel
+    magnification: usize, // The side of the block of pixels showing a cell, more than 1 only when the scale is 1
     image: Image
 }
 
 impl Camera {
-    pub fn new(x: isize, y: isize, automaton: &Automaton) -> Camera {
-        let size = (200.0, 50.0);
+    /// Creates a camera whose field of view has the given number of columns and rows, and shows the whole world in its center :
+    /// a large world is shown with blocks of cells per pixel, and a small one with blocks of pixels per cell.
+    pub fn fit(automaton: &Automaton, viewport: (usize, usize)) -> Camera {
+        let mut camera = Camera::with_size(0, 0, viewport);
+        let (width, height) = automaton.get_rules().world_size;
+        camera.scale = camera.fitting_scale(automaton);
+        if camera.scale == 1 {
+            camera.magnification = (viewport.0 / width.max(1)).min(viewport.1 / height.max(1)).clamp(1, MAX_MAGNIFICATION);
+        }
+        // The margins around the world, in pixels, converted to cells.
+        let margin = |pixels: usize, cells: usize| {
+            let shown = cells.div_ceil(camera.scale) * camera.magnification;
+            (pixels.saturating_sub(shown) / 2 * camera.scale / camera.magnification) as isize
+        };
+        camera.position = (-margin(viewport.0, width), -margin(viewport.1, height));
+        camera
+    }
+
+    /// Creates a camera whose field of view has the given number of columns and rows, at least one of each.
+    pub fn with_size(x: isize, y: isize, (width, height): (usize, usize)) -> Camera {
+        let size = (width.max(1), height.max(1));
         Camera {
             position: (x, y),
             size,
-            image: Image::new(size, automaton)
+            show_wrap: false,
+            layer: 0,
+            scale: 1,
+            magnification: 1,
+            image: Image::new(size)
         }
     }
 
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    /// Changes the number of columns and rows of the field of view, like when the terminal is resized.
+    /// The zoom is kept, and the center of the field of view stays on the same cells.
+    pub fn resize(&mut self, (width, height): (usize, usize)) {
+        let half_view = self.half_view();
+        let center = (self.position.0 + half_view.0, self.position.1 + half_view.1);
+        let size = (width.max(1), height.max(1));
+        self.size = size;
+        self.image = Image::new(size);
+        let half_view = self.half_view();
+        self.position = (center.0 - half_view.0, center.1 - half_view.1);
+    }
+
+    /// Tints the wrapped copies of the world, so that the patterns re-entering from the other side are told apart.
+    pub fn show_wrap(&mut self) {
+        self.show_wrap = true;
+    }
+
+    /// Shows the given layer of a 3D world. The layer wraps around the depth of the world.
+    pub fn set_layer(&mut self, layer: isize, automaton: &Automaton) {
+        self.layer = layer.rem_euclid(automaton.get_rules().depth as isize) as usize;
+    }
+
+    pub fn change_layer(&mut self, delta: isize, automaton: &Automaton) {
+        self.set_layer(self.layer as isize + delta, automaton);
+    }
+
+    pub fn get_layer(&self) -> usize {
+        self.layer
+    }
+
+    /// Shows a block of `scale` x `scale` cells per pixel, so that a world larger than the field of view fits in it.
+    pub fn set_scale(&mut self, scale: usize) {
+        self.scale = scale.max(1);
+        self.magnification = 1;
+    }
+
+    /// Returns the smallest scale that shows a whole layer of the world in the field of view.
+    pub fn fitting_scale(&self, automaton: &Automaton) -> usize {
+        let (width, height) = automaton.get_rules().world_size;
+        let (columns, rows) = (self.size.0.max(1), self.size.1.max(1));
+        width.div_ceil(columns).max(height.div_ceil(rows))
+    }
+
     pub fn capture(&mut self, automaton: &Automaton) -> &Image {
-        self.image.capture(self.position, automaton);
+        if self.scale > 1 {
+            return self.capture_scaled(automaton, self.scale);
+        }
+        self.image.capture(self.position, self.layer, self.magnification, automaton, self.show_wrap);
         &self.image
     }
 
+    /// Captures an image whose pixels show the most frequent state of blocks of `factor` x `factor` cells.
+    /// The blocks are aggregated by the automaton, instead of sampling a cell per pixel.
+    pub fn capture_scaled(&mut self, automaton: &Automaton, factor: usize) -> &Image {
+        self.image.capture_blocks(self.position, self.layer, factor, automaton, self.show_wrap);
+        &self.image
+    }
+
+    /// Returns the cells of the world shown by a pixel of the field of view, none if the pixel is outside of it.
+    /// A pixel shows a block of cells when the camera is zoomed out, and the copies of the world beyond its borders wrap around.
+    pub fn cells_at(&self, (x_c, y_c): (usize, usize), automaton: &Automaton) -> Vec<(usize, usize)> {
+        if x_c >= self.image.grid.len() || self.image.grid.first().is_none_or(|column| y_c >= column.len()) {
+            return Vec::new();
+        }
+        let (width, height) = automaton.get_rules().world_size;
+        let factor = self.scale as isize;
+        let (x_c, y_c) = (x_c / self.magnification, y_c / self.magnification);
+        let blocks_size = (width.div_ceil(self.scale) as isize, height.div_ceil(self.scale) as isize);
+        let block = ((x_c as isize + self.position.0.div_euclid(factor)).rem_euclid(blocks_size.0) as usize,
+                     (y_c as isize + self.position.1.div_euclid(factor)).rem_euclid(blocks_size.1) as usize);
+        let layer_top = self.layer * height;
+        let rows = block.1 * self.scale..((block.1 + 1) * self.scale).min(height);
+        (block.0 * self.scale..((block.0 + 1) * self.scale).min(width))
+            .flat_map(|x| rows.clone().map(move |y| (x, layer_top + y)))
+            .collect()
+    }
+
     pub fn translate(&mut self, direction: &Direction) {
+        // The camera moves by the same number of characters whatever the zoom.
+        let offset = (TRANSLATION_OFFSET * self.scale).div_ceil(self.magnification) as isize;
         match direction {
-            Direction::Left => { self.position.0 -= TRANSLATION_OFFSET as isize; }
-            Direction::Right => { self.position.0 += TRANSLATION_OFFSET as isize; }
-            Direction::Up => { self.position.1 -= TRANSLATION_OFFSET as isize; }
-            Direction::Down => { self.position.1 += TRANSLATION_OFFSET as isize; }
+            Direction::Left => { self.position.0 -= offset; }
+            Direction::Right => { self.position.0 += offset; }
+            Direction::Up => { self.position.1 -= offset; }
+            Direction::Down => { self.position.1 += offset; }
         }
     }
 
+    /// Zooms out by showing larger blocks of cells per pixel, or zooms in by showing a cell with a larger block of pixels
+    /// once each pixel shows a single cell. The field of view keeps its size, and its center stays on the same cells.
     pub fn zoom(&mut self, zoom: &Zoom) {
-        let factor = match zoom {
-            Zoom::In => 1.0 / ZOOM_FACTOR,
-            Zoom::Out => ZOOM_FACTOR
-        };
-        self.size.0 *= factor;
-        self.size.1 *= factor;
-        self.image.resize(self.size);
+        let half_view = self.half_view();
+        let center = (self.position.0 + half_view.0, self.position.1 + half_view.1);
+        match zoom {
+            Zoom::In if self.scale > 1 => self.scale -= 1,
+            Zoom::In => self.magnification = (self.magnification + 1).min(MAX_MAGNIFICATION),
+            Zoom::Out if self.magnification > 1 => self.magnification -= 1,
+            Zoom::Out => self.scale += 1
+        }
+        let half_view = self.half_view();
+        self.position = (center.0 - half_view.0, center.1 - half_view.1);
+    }
+
+    /// Returns true if the field of view is wider or higher than a layer of the world, whose wrapped copies are then seen.
+    pub fn sees_beyond_the_world(&self, automaton: &Automaton) -> bool {
+        let (width, height) = automaton.get_rules().world_size;
+        let cells = |pixels: usize| (pixels * self.scale).div_ceil(self.magnification);
+        cells(self.size.0) > width || cells(self.size.1) > height
+    }
+
+    /// Returns the half of the width and the height of the field of view, in cells.
+    fn half_view(&self) -> (isize, isize) {
+        let half = |pixels: usize| (pixels * self.scale / self.magnification / 2) as isize;
+        (half(self.size.0), half(self.size.1))
+    }
+
+    /// Describes the zoom, like "blocks of 3 x 3 cells per character".
+    pub fn zoom_description(&self) -> String {
+        match (self.scale, self.magnification) {
+            (1, 1) => "a cell per character".to_string(),
+            (1, magnification) => format!("a cell per block of {} x {} characters", magnification, magnification),
+            (scale, _) => format!("blocks of {} x {} cells per character", scale, scale)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::Automaton;
+    use crate::camera::{tint, Camera};
+    use crate::inputs::Zoom;
+    use crate::compiler::semantic::parse;
+
+    #[test]
+    fn show_wrap_tints_the_wrapped_cells() {
+        let automaton = Automaton::with_seed(parse("resources/tests/automaton_boundary.txt").unwrap(), 0);
+        let (width, height) = automaton.get_rules().world_size;
+        let colors = automaton.get_colors();
+        let mut camera = Camera::with_size(-2, 1, (width, height));
+        assert!(camera.capture(&automaton).grid.iter().flatten().all(|color| colors.contains(color)));
+
+        camera.show_wrap();
+        let image = camera.capture(&automaton);
+        for (x, column) in image.grid.iter().enumerate() {
+            for (y, color) in column.iter().enumerate() {
+                let state_color = colors[automaton.get_state(x as isize - 2, y as isize + 1)];
+                let wrapped = x < 2 || y + 1 >= height;
+                assert_eq!(*color, if wrapped { tint(state_color) } else { state_color });
+            }
+        }
+    }
+
+    #[test]
+    fn capture_shows_the_age_of_the_cells() {
+        let mut automaton = Automaton::with_seed(parse("resources/tests/automaton_gradient.txt").unwrap(), 0);
+        automaton.tick();
+        automaton.tick();
+        let mut camera = Camera::with_size(0, 0, (5, 8));
+        camera.show_wrap();
+        let image = camera.capture(&automaton);
+        // The center of the blinker is alive since 2 ticks, like its wrapped copy on the last row.
+        assert_eq!(image.grid[2][2], (0, 153, 0));
+        assert_eq!(image.grid[2][1], (0, 51, 0));
+        assert_eq!(image.grid[2][7], tint((0, 153, 0)));
+    }
+
+    #[test]
+    fn capture_shows_a_layer_of_a_3d_world() {
+        let automaton = Automaton::with_seed(parse("resources/tests/automaton_3d.txt").unwrap(), 0);
+        let (width, height) = automaton.get_rules().world_size;
+        let mut camera = Camera::with_size(0, 0, (width, height + 1));
+        camera.change_layer(-1, &automaton);
+        assert_eq!(camera.get_layer(), 3);
+        let colors = automaton.get_colors();
+        let image = camera.capture(&automaton).clone();
+        for (x, column) in image.grid.iter().enumerate() {
+            for (y, color) in column.iter().enumerate() {
+                assert_eq!(*color, colors[automaton.get_state(x as isize, (3 * height + y % height) as isize)]);
+            }
+        }
+        camera.set_layer(5, &automaton);
+        assert_eq!(camera.get_layer(), 1);
+    }
+
+    #[test]
+    fn capture_scaled_shows_the_dominant_states() {
+        let mut automaton = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 0);
+        let (width, height) = automaton.get_rules().world_size;
+        for ((x, y), _) in automaton.cells().collect::<Vec<_>>() {
+            // Three cells out of four are alive in the blocks of the even columns, one in the others.
+            let alive = (x % 2 == 0 && y % 2 == 0) || (x / 2 % 2 == 0 && (x % 2 == 0 || y % 2 == 0));
+            automaton.set_cell(x, y, alive as usize).unwrap();
+        }
+        let mut camera = Camera::with_size(0, 0, (width / 2, height / 2));
+        camera.set_scale(2);
+        let image = camera.capture(&automaton);
+        let colors = automaton.get_colors();
+        assert!(image.grid.iter().enumerate().all(|(x, column)| column.iter().all(|color| *color == colors[(x % 2 == 0) as usize])));
+        assert_eq!(automaton.dominant_states(3, 0).len(), width.div_ceil(3) * height.div_ceil(3));
+    }
+
+    #[test]
+    fn fitting_scale_shows_the_whole_world() {
+        let automaton = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 0);
+        let (width, height) = automaton.get_rules().world_size;
+        assert_eq!(Camera::with_size(0, 0, (width, height)).fitting_scale(&automaton), 1);
+        assert_eq!(Camera::with_size(0, 0, (width / 2, height)).fitting_scale(&automaton), 2);
+        assert_eq!(Camera::with_size(0, 0, (width, height / 3 - 1)).fitting_scale(&automaton), 4);
+    }
+
+    #[test]
+    fn cells_at_follows_the_position_the_scale_and_the_layer() {
+        let automaton = Automaton::with_seed(parse("resources/tests/automaton_3d.txt").unwrap(), 0);
+        let mut camera = Camera::with_size(-1, 2, (6, 6));
+        assert_eq!(camera.cells_at((0, 0), &automaton), vec![(3, 2)]);
+        assert_eq!(camera.cells_at((2, 3), &automaton), vec![(1, 1)]);
+        assert!(camera.cells_at((6, 0), &automaton).is_empty());
+        camera.set_layer(2, &automaton);
+        assert_eq!(camera.cells_at((2, 3), &automaton), vec![(1, 9)]);
+
+        let automaton = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 0);
+        let mut camera = Camera::with_size(0, 0, (100, 25));
+        camera.set_scale(3);
+        assert_eq!(camera.cells_at((1, 2), &automaton), vec![(3, 6), (3, 7), (3, 8), (4, 6), (4, 7), (4, 8), (5, 6), (5, 7), (5, 8)]);
+        // The blocks of the last row are cut by the bottom border of the world.
+        assert_eq!(camera.cells_at((0, 16), &automaton), vec![(0, 48), (0, 49), (1, 48), (1, 49), (2, 48), (2, 49)]);
+    }
+
+    #[test]
+    fn zoom_scales_the_cells_and_keeps_the_field_of_view() {
+        let automaton = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 0);
+        let mut camera = Camera::with_size(10, 10, (20, 10));
+        camera.zoom(&Zoom::In);
+        camera.zoom(&Zoom::In);
+        assert_eq!(camera.zoom_description(), "a cell per block of 3 x 3 characters");
+        // The center of the field of view stays on the cell (20, 15).
+        assert_eq!(camera.position, (17, 14));
+        let colors = automaton.get_colors();
+        let image = camera.capture(&automaton).clone();
+        assert_eq!((image.grid.len(), image.grid[0].len()), (20, 10));
+        for (x, column) in image.grid.iter().enumerate() {
+            for (y, color) in column.iter().enumerate() {
+                assert_eq!(*color, colors[automaton.get_state(17 + x as isize / 3, 14 + y as isize / 3)]);
+            }
+        }
+        assert_eq!(camera.cells_at((5, 4), &automaton), vec![(18, 15)]);
+
+        for _ in 0..4 {
+            camera.zoom(&Zoom::Out);
+        }
+        assert_eq!(camera.zoom_description(), "blocks of 3 x 3 cells per character");
+        let zoomed_out = camera.capture(&automaton).clone();
+        let first_block = (camera.position.0.div_euclid(3), camera.position.1.div_euclid(3));
+        let blocks = automaton.dominant_states(3, 0);
+        let blocks_size = (200_usize.div_ceil(3) as isize, 50_usize.div_ceil(3) as isize);
+        assert!(zoomed_out.grid.iter().enumerate().all(|(x, column)| column.iter().enumerate().all(|(y, color)| {
+            let block = ((first_block.0 + x as isize).rem_euclid(blocks_size.0), (first_block.1 + y as isize).rem_euclid(blocks_size.1));
+            *color == colors[blocks[(block.1 * blocks_size.0 + block.0) as usize]]
+        })));
+    }
+
+    #[test]
+    fn resize_keeps_the_center_of_the_field_of_view() {
+        let automaton = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 0);
+        let mut camera = Camera::with_size(10, 10, (20, 10));
+        camera.resize((40, 21));
+        assert_eq!(camera.size(), (40, 21));
+        assert_eq!(camera.position, (0, 5));
+        let image = camera.capture(&automaton);
+        assert_eq!((image.grid.len(), image.grid[0].len()), (40, 21));
+        assert_eq!(camera.cells_at((20, 10), &automaton), vec![(20, 15)]);
+    }
+
+    #[test]
+    fn field_of_view_larger_than_the_world() {
+        let automaton = Automaton::with_seed(parse("resources/tests/automaton_3d.txt").unwrap(), 0);
+        let mut camera = Camera::with_size(0, 0, (0, 0));
+        assert_eq!(camera.size(), (1, 1));
+        assert!(!camera.sees_beyond_the_world(&automaton));
+        camera.resize((5, 4));
+        assert!(camera.sees_beyond_the_world(&automaton));
+        // The copies of the world of 4 x 4 cells repeat it.
+        let image = camera.capture(&automaton);
+        assert_eq!(image.grid[4], image.grid[0]);
+        camera.set_scale(2);
+        camera.resize((2, 2));
+        assert!(!camera.sees_beyond_the_world(&automaton));
+    }
+
+    #[test]
+    fn fit_centers_the_whole_world() {
+        // A world of 4 x 4 cells is shown with blocks of 3 x 3 characters, in the center of a field of view of 20 x 14.
+        let automaton = Automaton::with_seed(parse("resources/tests/automaton_3d.txt").unwrap(), 0);
+        let camera = Camera::fit(&automaton, (20, 14));
+        assert_eq!(camera.zoom_description(), "a cell per block of 3 x 3 characters");
+        assert_eq!(camera.position, (-1, 0));
+        assert_eq!(camera.cells_at((4, 1), &automaton), vec![(0, 0)]);
+
+        // A world of 200 x 50 cells is shown with blocks of 4 x 4 cells, with 2 columns on each side.
+        let automaton = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 0);
+        let camera = Camera::fit(&automaton, (54, 13));
+        assert_eq!(camera.zoom_description(), "blocks of 4 x 4 cells per character");
+        assert_eq!(camera.position, (-8, 0));
+        assert_eq!(camera.cells_at((2, 0), &automaton)[0], (0, 0));
     }
 }
@@ -0,0 +1,61 @@
+//! This module draws the images captured by the camera in a canvas of a web page, with the `wasm` feature.
+
+use wasm_bindgen::{Clamped, JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
+use crate::camera::Image;
+use crate::display::{Display, Status};
+
+/// Draws each image with a pixel of the canvas per pixel of the image, the canvas taking the size of the image.
+/// The page scales the canvas with its style, like `width: 100%; image-rendering: pixelated;`.
+pub struct CanvasDisplay {
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    pixels: Vec<u8> // The RGBA pixels of the last image, row after row
+}
+
+impl CanvasDisplay {
+    /// Draws in the canvas of the page with the given id.
+    pub fn new(canvas_id: &str) -> Result<CanvasDisplay, String> {
+        let document = web_sys::window().and_then(|window| window.document()).ok_or("The page has no document.")?;
+        let canvas = document.get_element_by_id(canvas_id)
+            .ok_or(format!("The page has no element with the id \"{}\".", canvas_id))?
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| format!("The element \"{}\" is not a canvas.", canvas_id))?;
+        let context = canvas.get_context("2d")
+            .map_err(|error| format!("{:?}", error))?
+            .ok_or(format!("The canvas \"{}\" has no 2D context.", canvas_id))?
+            .dyn_into::<CanvasRenderingContext2d>()
+            .map_err(|_| format!("The context of the canvas \"{}\" is not a 2D context.", canvas_id))?;
+        Ok(CanvasDisplay { canvas, context, pixels: Vec::new() })
+    }
+
+    fn draw(&mut self, image: &Image) -> Result<(), JsValue> {
+        let (width, height) = (image.grid.len(), image.grid.first().map_or(0, Vec::len));
+        if (self.canvas.width(), self.canvas.height()) != (width as u32, height as u32) {
+            self.canvas.set_width(width as u32);
+            self.canvas.set_height(height as u32);
+        }
+        self.pixels.clear();
+        for y in 0..height {
+            for x in 0..width {
+                let (r, g, b) = image.grid[x][y];
+                self.pixels.extend_from_slice(&[r, g, b, 255]);
+            }
+        }
+        let image_data = ImageData::new_with_u8_clamped_array_and_sh(Clamped(&self.pixels), width as u32, height as u32)?;
+        self.context.put_image_data(&image_data, 0.0, 0.0)
+    }
+}
+
+impl Display for CanvasDisplay {
+    fn init(&mut self) {}
+
+    /// The status is not drawn : the page shows it where it wants, see `WebAutomaton::status`.
+    fn render(&mut self, image: &Image, _status: Option<&Status>) {
+        if let Err(error) = self.draw(image) {
+            error!("Cannot draw the image in the canvas. Cause : {:?}", error);
+        }
+    }
+
+    fn clean(&mut self) {}
+}
@@ -0,0 +1,170 @@
+//! This module saves the whole state of an automaton in a binary file, so that a run can be resumed later.
+//!
+//! The random draws are derived from the seed and the iteration, so restoring them restores the random number generator.
+
+use std::fs;
+use crate::compiler::parser::Heading;
+use crate::compiler::semantic::{Agent, Region, RegionKind};
+
+/// The first bytes of a checkpoint file, with the version of the format.
+const MAGIC: &[u8; 8] = b"MUTCKPT1";
+
+/// The state of an automaton at some iteration, and the iteration the run was to stop at.
+#[derive(Debug, PartialEq)]
+pub struct Checkpoint {
+    pub world_size: (usize, usize),
+    pub state_count: usize, // The number of states of the rules, implicit states included
+    pub seed: u64,
+    pub iteration: usize,
+    pub stop_iteration: Option<usize>, // None if the run was to go on forever
+    pub grid: Vec<usize>,
+    pub grid_previous: Vec<usize>, // Empty if the automaton has no memory and is not second-order
+    pub data: Vec<u8>, // Empty if the rules don't declare a data channel
+    pub agents: Vec<Agent>,
+    pub regions: Vec<Region>,
+    pub manifest: Option<String> // The manifest of the run, if it is embedded
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint as a sequence of little-endian integers.
+    pub fn save(&self, file_name: &str) -> Result<(), String> {
+        let mut bytes = MAGIC.to_vec();
+        let mut write = |value: u64| bytes.extend_from_slice(&value.to_le_bytes());
+        write(self.world_size.0 as u64);
+        write(self.world_size.1 as u64);
+        write(self.state_count as u64);
+        write(self.seed);
+        write(self.iteration as u64);
+        write(self.stop_iteration.map_or(u64::MAX, |iteration| iteration as u64));
+        for grid in &[&self.grid, &self.grid_previous] {
+            write(grid.len() as u64);
+            grid.iter().for_each(|state| write(*state as u64));
+        }
+        write(self.data.len() as u64);
+        self.data.iter().for_each(|value| write(*value as u64));
+        write(self.agents.len() as u64);
+        for agent in &self.agents {
+            let heading = match agent.heading {
+                Heading::Up => 0,
+                Heading::Right => 1,
+                Heading::Down => 2,
+                Heading::Left => 3
+            };
+            IntoIterator::into_iter([agent.position.0 as u64, agent.position.1 as u64, heading, agent.mode as u64]).for_each(&mut write);
+        }
+        write(self.regions.len() as u64);
+        for region in &self.regions {
+            // A source region is written with its state + 1, a frozen region with 0.
+            let kind = match region.kind {
+                RegionKind::Frozen => 0,
+                RegionKind::Source(state) => state as u64 + 1
+            };
+            IntoIterator::into_iter([region.x as u64, region.y as u64, region.width as u64, region.height as u64, kind]).for_each(&mut write);
+        }
+        // The manifest is written last, as its length followed by its text padded to a multiple of 8 bytes.
+        if let Some(manifest) = &self.manifest {
+            write(manifest.len() as u64);
+            bytes.extend_from_slice(manifest.as_bytes());
+            bytes.resize(bytes.len().div_ceil(8) * 8, 0);
+        }
+        fs::write(file_name, bytes).map_err(|error| format!("Cannot write the checkpoint {}. Cause : {:?}", file_name, error))
+    }
+
+    pub fn load(file_name: &str) -> Result<Checkpoint, String> {
+        let bytes = fs::read(file_name).map_err(|error| format!("Cannot read the checkpoint {}. Cause : {:?}", file_name, error))?;
+        if !bytes.starts_with(MAGIC) {
+            return Err(format!("The file {} is not a checkpoint.", file_name));
+        }
+        let mut values = bytes[MAGIC.len()..].chunks(8);
+        let mut read = || match values.next() {
+            Some(chunk) if chunk.len() == 8 => {
+                let mut value = [0; 8];
+                value.copy_from_slice(chunk);
+                Ok(u64::from_le_bytes(value) as usize)
+            },
+            _ => Err(format!("The checkpoint {} is truncated.", file_name))
+        };
+        let world_size = (read()?, read()?);
+        let state_count = read()?;
+        let seed = read()? as u64;
+        let iteration = read()?;
+        let stop_iteration = Some(read()?).filter(|iteration| *iteration as u64 != u64::MAX);
+        let grid = (0..read()?).map(|_| read()).collect::<Result<Vec<_>, _>>()?;
+        let grid_previous = (0..read()?).map(|_| read()).collect::<Result<Vec<_>, _>>()?;
+        let data = (0..read()?).map(|_| read().map(|value| value as u8)).collect::<Result<Vec<_>, _>>()?;
+        let mut agents = Vec::new();
+        for _ in 0..read()? {
+            let position = (read()?, read()?);
+            let heading = match read()? {
+                0 => Heading::Up,
+                1 => Heading::Right,
+                2 => Heading::Down,
+                _ => Heading::Left
+            };
+            agents.push(Agent { position, heading, mode: read()? });
+        }
+        let mut regions = Vec::new();
+        for _ in 0..read()? {
+            let (x, y, width, height) = (read()?, read()?, read()?, read()?);
+            let kind = match read()? {
+                0 => RegionKind::Frozen,
+                state => RegionKind::Source(state - 1)
+            };
+            regions.push(Region { x, y, width, height, kind });
+        }
+        // The checkpoints made without a manifest end with the regions.
+        let manifest = match read() {
+            Ok(length) => {
+                let text: Vec<u8> = values.take(length.div_ceil(8)).flatten().copied().take(length).collect();
+                if text.len() != length {
+                    return Err(format!("The checkpoint {} is truncated.", file_name));
+                }
+                Some(String::from_utf8(text).map_err(|_| format!("The manifest of the checkpoint {} is not valid UTF-8.", file_name))?)
+            },
+            Err(_) => None
+        };
+        Ok(Checkpoint { world_size, state_count, seed, iteration, stop_iteration, grid, grid_previous, data, agents, regions, manifest })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::automaton::Automaton;
+    use crate::checkpoint::Checkpoint;
+    use crate::compiler::semantic::parse;
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let file_name = std::env::temp_dir().join("mutations_checkpoint.bin");
+        let file_name = file_name.to_str().unwrap();
+        for rules_file in &["resources/virus.txt", "resources/langton_ant.txt", "resources/tests/semantic_memory.txt"] {
+            let mut automaton = Automaton::with_seed(parse(rules_file).unwrap(), 7);
+            automaton.add_frozen_region(1, 2, 3, 4).ok();
+            for _ in 0..10 {
+                automaton.tick();
+            }
+            let checkpoint = automaton.checkpoint(Some(30));
+            checkpoint.save(file_name).unwrap();
+            assert_eq!(Checkpoint::load(file_name).unwrap(), checkpoint);
+
+            let mut resumed = Automaton::with_seed(parse(rules_file).unwrap(), 8);
+            resumed.restore(&Checkpoint::load(file_name).unwrap()).unwrap();
+            for _ in 0..10 {
+                automaton.tick();
+                resumed.tick();
+            }
+            assert_eq!(resumed.get_iteration(), 20);
+            assert_eq!(resumed.get_grid(), automaton.get_grid());
+            assert_eq!(resumed.get_agents().len(), automaton.get_agents().len());
+        }
+        fs::remove_file(file_name).unwrap();
+    }
+
+    #[test]
+    fn restore_checks_the_rules() {
+        let automaton = Automaton::with_seed(parse("resources/virus.txt").unwrap(), 7);
+        let mut other = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 7);
+        assert!(other.restore(&automaton.checkpoint(None)).is_err());
+    }
+}
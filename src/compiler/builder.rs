@@ -0,0 +1,360 @@
+//! This module creates rules without a file : the states, the transitions and the directives are given by a program,
+//! and the rules are checked like the rules of a file.
+//!
+//! ```
+//! use mutations::compiler::builder::{Condition, RulesBuilder};
+//! use mutations::compiler::parser::ComparisonOperator::{Equal, Greater, Lesser};
+//! use mutations::compiler::semantic::StateDistribution;
+//!
+//! let rules = RulesBuilder::new(200, 50)
+//!     .state("dead", (0, 0, 0))
+//!     .distributed_state("alive", (255, 255, 255), StateDistribution::Proportion(0.2))
+//!     .transition("alive", "dead", Condition::quantity("alive", Lesser, 2).or(Condition::quantity("alive", Greater, 3)))
+//!     .transition("dead", "alive", Condition::quantity("alive", Equal, 3))
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(rules.states.len(), 2);
+//! ```
+
+use crate::compiler::error::CompileError;
+use crate::compiler::parser::{Ast, AgentRuleNode, BooleanOperator, CellNode, ComparisonOperator, ConditionNode, CountedCells, DirectiveNode, Gradient, Neighborhood,
+                              NeighborCell, NextConditionNode, RandomDraw, StateDistributionNode, StateNode, TieBreak, TransitionAnnotations, TransitionNode};
+use crate::compiler::semantic::{analyze, CompilerOptions, Rules, StateDistribution};
+
+/// A term of a condition, like `alive > 3`.
+enum Term {
+    Quantity(String, ComparisonOperator, u8, CountedCells),
+    Data(ComparisonOperator, u8),
+    Distance(String, ComparisonOperator, u8),
+    Neighbor(NeighborCell, String),
+    PreviousNeighbor(NeighborCell, String),
+    PreviousState(String),
+    Random(f64, RandomDraw),
+    True
+}
+
+/// The condition of a transition : terms combined with `and` and `or`, where `and` takes precedence, like in a rules file.
+pub struct Condition {
+    terms: Vec<(BooleanOperator, Term)> // The operator of the first term is ignored
+}
+
+impl Condition {
+    fn term(term: Term) -> Condition {
+        Condition { terms: vec![(BooleanOperator::And, term)] }
+    }
+
+    /// The number of neighbors in the state, like `alive > 3`.
+    pub fn quantity(state: &str, operator: ComparisonOperator, quantity: u8) -> Condition {
+        Condition::term(Term::Quantity(state.to_string(), operator, quantity, CountedCells::Neighbors))
+    }
+
+    /// The number of cells in the state among the neighbors and the cell itself, like `count9(alive) > 4`.
+    pub fn quantity_with_self(state: &str, operator: ComparisonOperator, quantity: u8) -> Condition {
+        Condition::term(Term::Quantity(state.to_string(), operator, quantity, CountedCells::NeighborsAndSelf))
+    }
+
+    /// The data of the cell, which needs a `data` directive.
+    pub fn data(operator: ComparisonOperator, value: u8) -> Condition {
+        Condition::term(Term::Data(operator, value))
+    }
+
+    /// The distance to the closest cell in the state, like `distance(fire) < 3`.
+    pub fn distance(state: &str, operator: ComparisonOperator, distance: u8) -> Condition {
+        Condition::term(Term::Distance(state.to_string(), operator, distance))
+    }
+
+    /// The state of a neighbor, like `G is dead`.
+    pub fn neighbor(cell: NeighborCell, state: &str) -> Condition {
+        Condition::term(Term::Neighbor(cell, state.to_string()))
+    }
+
+    /// The state of a neighbor at the previous iteration, like `G was dead`, which needs a `memory` directive.
+    pub fn previous_neighbor(cell: NeighborCell, state: &str) -> Condition {
+        Condition::term(Term::PreviousNeighbor(cell, state.to_string()))
+    }
+
+    /// The state of the cell at the previous iteration, like `was dead`, which needs a `memory` directive.
+    pub fn previous_state(state: &str) -> Condition {
+        Condition::term(Term::PreviousState(state.to_string()))
+    }
+
+    /// True with the probability, like `rand 0.1`.
+    pub fn random(probability: f64) -> Condition {
+        Condition::term(Term::Random(probability, RandomDraw::Cell))
+    }
+
+    /// True with the probability, with a draw shared by the cells of the iteration or of a block, like `rand 0.1 per tick`.
+    pub fn shared_random(probability: f64, draw: RandomDraw) -> Condition {
+        Condition::term(Term::Random(probability, draw))
+    }
+
+    /// Always true. It can't be combined with other terms.
+    pub fn always() -> Condition {
+        Condition::term(Term::True)
+    }
+
+    pub fn and(self, other: Condition) -> Condition {
+        self.combine(BooleanOperator::And, other)
+    }
+
+    pub fn or(self, other: Condition) -> Condition {
+        self.combine(BooleanOperator::Or, other)
+    }
+
+    fn combine(mut self, operator: BooleanOperator, other: Condition) -> Condition {
+        let mut terms = other.terms.into_iter();
+        if let Some((_, first)) = terms.next() {
+            self.terms.push((operator, first));
+        }
+        self.terms.extend(terms);
+        self
+    }
+}
+
+/// A transition to a destination state, a swap with a neighbor, or a vote of the neighbors.
+enum Destination {
+    State(String),
+    Swap(NeighborCell),
+    Vote(TieBreak)
+}
+
+/// A state given to the builder : its name, its color, its gradient and its distribution.
+type StateDeclaration = (String, (u8, u8, u8), Option<Gradient>, StateDistribution);
+
+/// Creates rules step by step. The errors are reported by `build`, with the messages of the compiler.
+pub struct RulesBuilder {
+    world_size: (usize, usize),
+    depth: usize,
+    directives: Vec<DirectiveNode>,
+    states: Vec<StateDeclaration>,
+    transitions: Vec<(String, Destination, Condition, TransitionAnnotations)>,
+    agent_rules: Vec<AgentRuleNode>,
+    initial_cells: Vec<CellNode>
+}
+
+impl RulesBuilder {
+    pub fn new(width: usize, height: usize) -> RulesBuilder {
+        RulesBuilder { world_size: (width, height), depth: 1, directives: Vec::new(), states: Vec::new(), transitions: Vec::new(), agent_rules: Vec::new(), initial_cells: Vec::new() }
+    }
+
+    /// Makes the world 3D, with the number of layers.
+    pub fn depth(mut self, depth: usize) -> RulesBuilder {
+        self.depth = depth;
+        self
+    }
+
+    /// Adds a directive, like `DirectiveNode::Memory`. The file paths are used as they are.
+    pub fn directive(mut self, directive: DirectiveNode) -> RulesBuilder {
+        self.directives.push(directive);
+        self
+    }
+
+    /// Adds the default state, which fills the cells left by the distributions of the other states.
+    pub fn state(self, name: &str, color: (u8, u8, u8)) -> RulesBuilder {
+        self.distributed_state(name, color, StateDistribution::Default)
+    }
+
+    pub fn distributed_state(mut self, name: &str, color: (u8, u8, u8), distribution: StateDistribution) -> RulesBuilder {
+        self.states.push((name.to_string(), color, None, distribution));
+        self
+    }
+
+    /// Fades the color of the state declared last to the end color while its cells stay in it, over the given number of ticks.
+    pub fn gradient(mut self, end: (u8, u8, u8), ticks: usize) -> RulesBuilder {
+        if let Some(state) = self.states.last_mut() {
+            state.2 = Some(Gradient { end, ticks });
+        }
+        self
+    }
+
+    pub fn transition(self, origin: &str, destination: &str, condition: Condition) -> RulesBuilder {
+        self.annotated_transition(origin, destination, condition, TransitionAnnotations::default())
+    }
+
+    /// Adds a transition with annotations, like a delay, a priority or effects.
+    pub fn annotated_transition(mut self, origin: &str, destination: &str, condition: Condition, annotations: TransitionAnnotations) -> RulesBuilder {
+        self.transitions.push((origin.to_string(), Destination::State(destination.to_string()), condition, annotations));
+        self
+    }
+
+    /// Adds a transition that exchanges the states of the cell and the neighbor.
+    pub fn swap(mut self, origin: &str, neighbor: NeighborCell, condition: Condition, annotations: TransitionAnnotations) -> RulesBuilder {
+        self.transitions.push((origin.to_string(), Destination::Swap(neighbor), condition, annotations));
+        self
+    }
+
+    /// Adds a transition to the most common state of the neighbors.
+    pub fn vote(mut self, origin: &str, tie_break: TieBreak, condition: Condition, annotations: TransitionAnnotations) -> RulesBuilder {
+        self.transitions.push((origin.to_string(), Destination::Vote(tie_break), condition, annotations));
+        self
+    }
+
+    pub fn agent_rule(mut self, rule: AgentRuleNode) -> RulesBuilder {
+        self.agent_rules.push(rule);
+        self
+    }
+
+    /// Places a cell in the state at the first iteration, like a cell of the "pattern" section.
+    pub fn cell(mut self, x: usize, y: usize, state: &str) -> RulesBuilder {
+        self.initial_cells.push(((x, y), state.to_string()));
+        self
+    }
+
+    pub fn build(self) -> Result<Rules, Vec<CompileError>> {
+        self.build_with_options(&CompilerOptions::default())
+    }
+
+    /// Checks the rules like the rules of a file, with the given options.
+    pub fn build_with_options(self, options: &CompilerOptions) -> Result<Rules, Vec<CompileError>> {
+        let errors = self.control_conditions();
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(CompileError::semantic).collect());
+        }
+        analyze(&self.into_ast(), options)
+    }
+
+    /// Reports the errors that the parser reports for a file, since the conditions are not parsed.
+    fn control_conditions(&self) -> Vec<String> {
+        let data_channel = self.directives.iter().any(|d| matches!(d, DirectiveNode::Data(_)));
+        let neighbor_count = self.directives.iter().find_map(|d| match d {
+            DirectiveNode::Neighborhood(neighborhood) => Some(*neighborhood),
+            _ => None
+        }).unwrap_or(Neighborhood::Moore(1)).offsets(self.depth > 1).len();
+        let mut errors = Vec::new();
+        for (origin, _, condition, annotations) in &self.transitions {
+            for (_, term) in &condition.terms {
+                match term {
+                    Term::Quantity(state, _, quantity, CountedCells::Neighbors) if *quantity as usize > neighbor_count =>
+                        errors.push(format!("The condition on \"{}\" of a transition from \"{}\" counts {} neighbors, but there are {} neighbors.", state, origin, quantity, neighbor_count)),
+                    Term::Quantity(state, _, quantity, CountedCells::NeighborsAndSelf) if *quantity as usize > neighbor_count + 1 =>
+                        errors.push(format!("The condition on \"{}\" of a transition from \"{}\" counts {} cells, but there are {} cells with the cell itself.", state, origin, quantity, neighbor_count + 1)),
+                    Term::Data(_, _) if !data_channel =>
+                        errors.push(format!("A transition from \"{}\" has a data condition, but there is no \"data\" directive.", origin)),
+                    Term::Random(probability, _) if !(0.0..=1.0).contains(probability) =>
+                        errors.push(format!("A transition from \"{}\" has a random condition of probability {}, which is not between 0 and 1.", origin, probability)),
+                    _ => {}
+                }
+            }
+            if annotations.set_data.is_some() && !data_channel {
+                errors.push(format!("A transition from \"{}\" sets the data, but there is no \"data\" directive.", origin));
+            }
+        }
+        errors
+    }
+
+    /// Chains the transitions then the states, from the last to the first, like the parser does.
+    fn into_ast(self) -> Ast {
+        let mut next_transition = TransitionNode::End;
+        for (origin, destination, condition, annotations) in self.transitions.into_iter().rev() {
+            let mut next_condition = NextConditionNode::NextTransition(annotations, Box::new(next_transition));
+            let mut terms = condition.terms.into_iter().rev().peekable();
+            let mut first_condition = None;
+            while let Some((operator, term)) = terms.next() {
+                let node = match term {
+                    Term::Quantity(state, comparison, quantity, counted) => ConditionNode::QuantityCondition(state, comparison, quantity, counted, next_condition),
+                    Term::Data(comparison, value) => ConditionNode::DataCondition(comparison, value, next_condition),
+                    Term::Distance(state, comparison, distance) => ConditionNode::DistanceCondition(state, comparison, distance, next_condition),
+                    Term::Neighbor(cell, state) => ConditionNode::NeighborCondition(cell, state, next_condition),
+                    Term::PreviousNeighbor(cell, state) => ConditionNode::PreviousNeighborCondition(cell, state, next_condition),
+                    Term::PreviousState(state) => ConditionNode::PreviousStateCondition(state, next_condition),
+                    Term::Random(probability, draw) => ConditionNode::RandomCondition(probability, draw, next_condition),
+                    Term::True => ConditionNode::True(next_condition)
+                };
+                if terms.peek().is_none() {
+                    first_condition = Some(node);
+                    break;
+                }
+                next_condition = NextConditionNode::NextCondition(operator, Box::new(node));
+            }
+            let condition = Box::new(first_condition.expect("A condition has at least one term."));
+            next_transition = match destination {
+                Destination::State(destination) => TransitionNode::Transition(origin, destination, condition),
+                Destination::Swap(neighbor) => TransitionNode::Swap(origin, neighbor, condition),
+                Destination::Vote(tie_break) => TransitionNode::Vote(origin, tie_break, condition)
+            };
+        }
+        let mut next_state = StateNode::Next(next_transition);
+        for (name, (r, g, b), gradient, distribution) in self.states.into_iter().rev() {
+            let next = Box::new(next_state);
+            let distribution = match distribution {
+                StateDistribution::Proportion(proportion) => StateDistributionNode::Proportion(proportion, next),
+                StateDistribution::Quantity(quantity) => StateDistributionNode::Quantity(quantity, next),
+                StateDistribution::Box(x, y, width, height) => StateDistributionNode::Box(x, y, width, height, next),
+                StateDistribution::Circle(x, y, radius) => StateDistributionNode::Circle(x, y, radius, next),
+                StateDistribution::Line(x1, y1, x2, y2) => StateDistributionNode::Line(x1, y1, x2, y2, next),
+                StateDistribution::Image(file_name) => StateDistributionNode::Image(file_name, next),
+                StateDistribution::Default => StateDistributionNode::Default(next)
+            };
+            next_state = StateNode::State(name, r, g, b, gradient, distribution);
+        }
+        Ast { world_size: self.world_size, depth: self.depth, directives: self.directives, first_state: next_state, agent_rules: self.agent_rules, initial_cells: self.initial_cells }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod tests {
+    use crate::compiler::builder::{Condition, RulesBuilder};
+    use crate::compiler::parser::ComparisonOperator::{Equal, Greater, Lesser};
+    use crate::compiler::parser::{CountedCells, DirectiveNode, NeighborCell};
+    use crate::compiler::semantic::{parse, Condition as RuleCondition, StateDistribution};
+
+    #[test]
+    fn build_the_game_of_life() {
+        let built = RulesBuilder::new(200, 50)
+            .state("dead", (0, 0, 0))
+            .distributed_state("alive", (255, 255, 255), StateDistribution::Proportion(0.2))
+            .transition("alive", "dead", Condition::quantity("alive", Lesser, 2).or(Condition::quantity("alive", Greater, 3)))
+            .transition("dead", "alive", Condition::quantity("alive", Equal, 3))
+            .build()
+            .unwrap();
+        let parsed = parse("resources/game_of_life.txt").unwrap();
+        assert_eq!(format!("{:?}", built.states), format!("{:?}", parsed.states));
+        assert_eq!(format!("{:?}", built.transitions), format!("{:?}", parsed.transitions));
+        assert_eq!(built.world_size, parsed.world_size);
+    }
+
+    #[test]
+    fn build_combines_the_terms_like_a_file() {
+        let rules = RulesBuilder::new(10, 10)
+            .directive(DirectiveNode::Memory)
+            .state("dead", (0, 0, 0))
+            .distributed_state("alive", (255, 255, 255), StateDistribution::Quantity(3))
+            .transition("dead", "alive", Condition::neighbor(NeighborCell::B, "alive")
+                .and(Condition::previous_state("dead").or(Condition::random(0.5)))
+                .and(Condition::quantity("alive", Greater, 1)))
+            .cell(4, 5, "alive")
+            .build()
+            .unwrap();
+        assert_eq!(rules.initial_cells, vec![((4, 5), 1)]);
+        // "B is alive && was dead || random 0.5 && alive > 1"
+        match &rules.transitions[0].2[..] {
+            [first, second] => {
+                assert!(matches!(first[..], [RuleCondition::NeighborCondition(NeighborCell::B, 1), RuleCondition::PreviousStateCondition(0)]));
+                assert!(matches!(second[..], [RuleCondition::RandomCondition(_, _), RuleCondition::QuantityCondition(1, Greater, 1, CountedCells::Neighbors)]));
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn build_errors_fails() {
+        let errors = RulesBuilder::new(10, 10)
+            .state("dead", (0, 0, 0))
+            .transition("dead", "alive", Condition::quantity("dead", Greater, 9))
+            .transition("dead", "dead", Condition::data(Equal, 1))
+            .build()
+            .unwrap_err();
+        assert_eq!(errors, vec![
+            "The condition on \"dead\" of a transition from \"dead\" counts 9 neighbors, but there are 8 neighbors.",
+            "A transition from \"dead\" has a data condition, but there is no \"data\" directive."
+        ]);
+
+        let errors = RulesBuilder::new(10, 10)
+            .state("dead", (0, 0, 0))
+            .transition("dead", "alive", Condition::always())
+            .build()
+            .unwrap_err();
+        assert_eq!(errors, vec!["The transition 'dead -> alive' refers to the state \"alive\", but it's not defined."]);
+    }
+}
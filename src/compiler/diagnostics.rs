@@ -0,0 +1,109 @@
+//! This module renders the compile errors like the diagnostics of rustc : the message, then the line of the rules
+//! it refers to, with the faulty token underlined.
+//!
+//! ```text
+//! Expected an unsigned integer, found "yolo".
+//!  --> resources/tests/parser_expected_usize.txt:6:39
+//!   |
+//! 6 |     (unusedState, 255, 0, 0, quantity yolo),
+//!   |                                       ^^^^ expected an unsigned integer
+//! ```
+
+use crate::compiler::error::CompileError;
+
+/// Returns the message of the error, followed by the snippet of the source if the error has a span on one of its lines.
+pub fn render_diagnostic(error: &CompileError, file_name: &str, source: &str) -> String {
+    let span = match error.span() {
+        Some(span) => span,
+        None => return error.to_string()
+    };
+    let line = match source.lines().nth((span.line as usize).saturating_sub(1)) {
+        Some(line) => line.trim_end(),
+        None => return error.to_string()
+    };
+    // The span is in bytes, as the lexer reads the text byte by byte, and the carets are one per character.
+    let start = span.column as usize - 1;
+    let end = start + span.length.max(1) as usize;
+    let characters = |range: std::ops::Range<usize>| line.char_indices().filter(move |(index, _)| range.contains(index));
+    // The tabulations before the token are kept, so that the carets are under it whatever the width of a tabulation.
+    let indent: String = characters(0..start).map(|(_, c)| if c == '\t' { '\t' } else { ' ' }).collect();
+    let carets = characters(start..end).count().max(1);
+    let number = span.line.to_string();
+    let margin = " ".repeat(number.len());
+    let diagnostic = format!("{}\n{}--> {}:{}:{}\n{} |\n{} | {}\n{} | {}{} {}",
+                             without_position(error.message()), margin, file_name, span.line, span.column, margin, number, line, margin, indent,
+                             "^".repeat(carets), hint(error));
+    diagnostic.trim_end().to_string()
+}
+
+/// Returns the message without the position of the token, like " - line 6, column 42", which the diagnostic tells in its
+/// own way : the position of a token is the column of its last character, the diagnostic the column of its first one.
+fn without_position(message: &str) -> String {
+    let position = message.rfind(" - line ").and_then(|start| {
+        let line = &message[start + " - line ".len()..];
+        let column = line.trim_start_matches(|c: char| c.is_ascii_digit()).strip_prefix(", column ")?;
+        let end = column.trim_start_matches(|c: char| c.is_ascii_digit());
+        (end.len() < column.len()).then(|| (start, message.len() - end.len()))
+    });
+    match position {
+        Some((start, end)) => format!("{}{}", &message[..start], &message[end..]),
+        None => message.to_string()
+    }
+}
+
+/// Returns a short explanation written after the carets, like what the parser expected instead of the token.
+fn hint(error: &CompileError) -> String {
+    let message = error.message();
+    match error {
+        CompileError::Lexical { .. } if message.contains("is not closed") => "not closed".to_string(),
+        CompileError::Lexical { .. } => "not a valid token".to_string(),
+        CompileError::Syntax { .. } => match (message.strip_prefix("Expected "), message.find(", but found").or_else(|| message.find(", found"))) {
+            (Some(_), Some(end)) => format!("expected {}", &message["Expected ".len()..end]),
+            _ => "not allowed here".to_string()
+        },
+        _ => String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::compiler::diagnostics::render_diagnostic;
+    use crate::compiler::error::CompileError;
+    use crate::compiler::parser::{parse, parse_str};
+
+    #[test]
+    fn render_diagnostic_underlines_the_token() {
+        let file_name = "resources/tests/parser_expected_comparison_operator.txt";
+        let source = fs::read_to_string(file_name).unwrap();
+        let error = parse(file_name).err().unwrap();
+        // The message keeps its position, the diagnostic tells the column of the first character instead of the last one.
+        assert!(error.to_string().ends_with("found \"plouf\" - line 9, column 29."));
+        assert_eq!(render_diagnostic(&error, file_name, &source), "\
+            Expected one of \"<\", \">\", \"<=\", \">=\", \"==\", or \"!=\" tokens, found \"plouf\".\n \
+            --> resources/tests/parser_expected_comparison_operator.txt:9:25\n  \
+              |\n\
+            9 |     (alive, dead, alive plouf 2 || alive > 3),\n  \
+              |                         ^^^^^ expected one of \"<\", \">\", \"<=\", \">=\", \"==\", or \"!=\" tokens");
+
+        // The column is in bytes, the carets are under the characters.
+        let text = "size (10, 10)\nstates {\n    (dead, 0, 0, 0),\n    (alive, 255, 255, 255),\n}\n\
+                      transitions {\n    /* née */ (dead, alive, alive plouf 3),\n}\n";
+        let error = parse_str(text).err().unwrap();
+        assert_eq!(render_diagnostic(&error, "life.txt", text), "\
+            Expected one of \"<\", \">\", \"<=\", \">=\", \"==\", or \"!=\" tokens, found \"plouf\".\n \
+            --> life.txt:7:36\n  \
+              |\n\
+            7 |     /* née */ (dead, alive, alive plouf 3),\n  \
+              |                                   ^^^^^ expected one of \"<\", \">\", \"<=\", \">=\", \"==\", or \"!=\" tokens");
+
+        // The text after the position is kept.
+        let text = "size (10, 10)\nstates {\n    (dead, 0, 0, 0),\n    (alive, 255, 255, 255, quantity 1.000.000 ),\n}\n";
+        let error = parse_str(text).err().unwrap();
+        assert_eq!(render_diagnostic(&error, "life.txt", text).lines().next(),
+                   Some("Invalid token \"1.000.000\". It starts with a digit but is not a number."));
+
+        let error = CompileError::semantic("The state \"ghost\" is not defined.".to_string());
+        assert_eq!(render_diagnostic(&error, file_name, &source), "The state \"ghost\" is not defined.");
+    }
+}
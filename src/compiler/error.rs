@@ -0,0 +1,72 @@
+//! This module provides the errors of the compiler, with the part of the rules they refer to when it is known.
+//!
+//! The displayed error is the message alone, like "Expected an unsigned integer, found \"yolo\" - line 6, column 42.",
+//! the span lets the tools underline the faulty text without parsing the message.
+
+use std::fmt;
+
+/// A part of a line of the rules. The line and the column of the first character start at 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+    pub length: u32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CompileError {
+    Io { message: String }, // The rules cannot be read
+    Lexical { message: String, span: Span }, // The text is not made of valid tokens
+    Syntax { message: String, span: Span }, // The tokens don't match the grammar
+    Semantic { message: String, span: Option<Span> } // The rules are inconsistent, like a transition to an undefined state
+}
+
+impl CompileError {
+    pub fn semantic(message: String) -> CompileError {
+        CompileError::Semantic { message, span: None }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            CompileError::Io { message } | CompileError::Lexical { message, .. }
+            | CompileError::Syntax { message, .. } | CompileError::Semantic { message, .. } => message
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            CompileError::Io { .. } => None,
+            CompileError::Lexical { span, .. } | CompileError::Syntax { span, .. } => Some(*span),
+            CompileError::Semantic { span, .. } => *span
+        }
+    }
+
+    /// Returns the same error with another message, like its translation.
+    pub fn with_message(self, message: String) -> CompileError {
+        match self {
+            CompileError::Io { .. } => CompileError::Io { message },
+            CompileError::Lexical { span, .. } => CompileError::Lexical { message, span },
+            CompileError::Syntax { span, .. } => CompileError::Syntax { message, span },
+            CompileError::Semantic { span, .. } => CompileError::Semantic { message, span }
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+/// An error equals a text if its message does, like a `String` does.
+impl PartialEq<&str> for CompileError {
+    fn eq(&self, other: &&str) -> bool {
+        self.message() == *other
+    }
+}
+
+impl PartialEq<String> for CompileError {
+    fn eq(&self, other: &String) -> bool {
+        self.message() == other
+    }
+}
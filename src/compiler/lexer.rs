@@ -1,9 +1,10 @@
-/// This module provides lexical analysis functions
+//! This module provides lexical analysis functions
 
 use std::fs::File;
 use std::io;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::fmt;
+use crate::compiler::error::{CompileError, Span};
 
 static DELIMITERS: [char; 5] = ['{', '}', '(', ')', ','];
 static SINGLE_CHAR_OPERATORS: [char; 2] = ['<', '>'];
@@ -28,15 +29,28 @@ impl Token {
     }
 }
 
+impl Token {
+    /// Returns the part of the line covered by the token. The column of a token is the column of its last character.
+    pub fn span(&self) -> Span {
+        let length = self.str.chars().count() as u32;
+        Span { line: self.line, column: (self.column + 1).saturating_sub(length).max(1), length }
+    }
+}
+
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "\"{}\" - line {}, column {}", self.str, self.line, self.column)
     }
 }
 
+/// What the lexer reads the characters from. It seeks back when a character ends a token without being part of it.
+pub trait Source: Read + Seek {}
+
+impl<T: Read + Seek> Source for T {}
+
 pub struct Lexer<'a> {
-    reader: BufReader<File>,
-    file_name: &'a str,
+    reader: Box<dyn Source + 'a>,
+    file_name: &'a str, // The name of the source in the errors, the path of a file
     previous_line: u32,
     previous_column: u32,
     current_line: u32,
@@ -46,31 +60,55 @@ pub struct Lexer<'a> {
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(file_name: &str) -> Result<Lexer, io::Error> {
+    pub fn new(file_name: &str) -> Result<Lexer<'_>, io::Error> {
         let file = File::open(file_name)?;
-        let reader = BufReader::new(file);
-        Ok(Lexer {
-            reader,
-            file_name,
+        Ok(Lexer::from_reader(BufReader::new(file), file_name))
+    }
+
+    /// Reads the rules from a string instead of a file, like rules embedded in the code.
+    pub fn from_text(text: &'a str) -> Lexer<'a> {
+        Lexer::from_reader(Cursor::new(text.as_bytes()), "<text>")
+    }
+
+    pub fn from_reader(reader: impl Source + 'a, name: &'a str) -> Lexer<'a> {
+        Lexer {
+            reader: Box::new(reader),
+            file_name: name,
             previous_line: 1,
             previous_column: 0,
             current_line: 1,
             current_column: 0,
             current_char_in_token: false,
             current_char: '\n'
-        })
+        }
     }
 
-    pub fn get_next_token(&mut self) -> Result<Token, String> {
-        // Read until a not-whitespace parameter is found.
+    pub fn get_next_token(&mut self) -> Result<Token, CompileError> {
+        // Read until a not-whitespace parameter is found, skipping the comments.
         let mut c = self.read_char()?;
-        while c.is_ascii_whitespace() && c != '\u{0}' {
+        loop {
+            while c.is_ascii_whitespace() && c != '\u{0}' {
+                c = self.read_char()?;
+            }
+            if c != '/' {
+                break;
+            }
+            match self.read_char()? {
+                '/' => self.skip_line_comment()?,
+                '*' => self.skip_block_comment()?,
+                // At the end of the file, there is nothing to un-read.
+                '\u{0}' => break,
+                _ => {
+                    self.rewind_char()?;
+                    break;
+                }
+            }
             c = self.read_char()?;
         }
 
         // The token is a single delimiter character.
         if DELIMITERS.contains(&c) {
-            return Ok(Token::new(c.to_string(), &self));
+            return Ok(Token::new(c.to_string(), self));
         }
 
         // The token seems to be an operator.
@@ -78,11 +116,56 @@ impl<'a> Lexer<'a> {
             return self.get_operator_token(c);
         }
 
-        // The token should be a number or an alpha-numeric identifier (that doesn't start with a number).
+        if c == '"' {
+            return self.get_string_token();
+        }
+
+        // The ".." token separates the two ends of a color gradient.
+        if c == '.' {
+            let next = self.read_char()?;
+            if next == '.' {
+                return Ok(Token::new("..".to_string(), self));
+            }
+            if next != '\u{0}' {
+                self.rewind_char()?;
+            }
+        }
+
+        // The token should be a number, a hexadecimal color or an alpha-numeric identifier (that doesn't start with a number).
         self.get_number_or_id_token(c)
     }
 
-    fn get_operator_token(&mut self, c: char) -> Result<Token, String> {
+    /// Reads until the end of the line, or of the file.
+    fn skip_line_comment(&mut self) -> Result<(), CompileError> {
+        let mut c = self.read_char()?;
+        while c != '\n' && c != '\u{0}' {
+            c = self.read_char()?;
+        }
+        Ok(())
+    }
+
+    /// Reads until the "*/" that closes the comment. The comments can't be nested.
+    fn skip_block_comment(&mut self) -> Result<(), CompileError> {
+        let (line, column) = (self.current_line, self.current_column - 1);
+        let mut c = self.read_char()?;
+        loop {
+            match c {
+                '\u{0}' => return Err(CompileError::Lexical {
+                    message: format!("The comment starting line {}, column {} is not closed.", line, column),
+                    span: Span { line, column, length: 2 }
+                }),
+                '*' => {
+                    c = self.read_char()?;
+                    if c == '/' {
+                        return Ok(());
+                    }
+                },
+                _ => c = self.read_char()?
+            }
+        }
+    }
+
+    fn get_operator_token(&mut self, c: char) -> Result<Token, CompileError> {
         let mut token = String::new();
         token.push(c);
         let expected_char = match c {
@@ -96,28 +179,49 @@ impl<'a> Lexer<'a> {
 
         // The token is a two-characters operator
         if c2 == expected_char {
-            Ok(Token::new(token, &self))
+            Ok(Token::new(token, self))
         }
         // The token is a single character operator ('<' or '>')
         else if (c == '<' || c == '>')
             && (c2.is_ascii_whitespace()
                 || c2 == '\u{0}'
                 || c2.is_ascii_alphanumeric()
-                || DELIMITERS.contains(&c2)
-                || OPERATOR_FIRST_CHARS.contains(&c2)) {
+                || ends_token(c2)) {
             token.pop();
             self.rewind_char()?;
-            Ok(Token::new(token, &self))
+            Ok(Token::new(token, self))
         }
         // The token starts as an operator but not one
         else {
-            Err(format!("Invalid token {}. Note : recognized operators are {:?} and {:?}.", Token::new(token, &self), SINGLE_CHAR_OPERATORS, TWO_CHAR_OPERATORS))
+            let token = Token::new(token, self);
+            Err(lexical_error(&token, format!("Invalid token {}. Note : recognized operators are {:?} and {:?}.", token, SINGLE_CHAR_OPERATORS, TWO_CHAR_OPERATORS)))
         }
     }
 
-    fn get_number_or_id_token(&mut self, first_char: char) -> Result<Token, String> {
+    /// Reads until the closing double quote, on the same line. The token keeps its double quotes.
+    fn get_string_token(&mut self) -> Result<Token, CompileError> {
+        let (line, column) = (self.current_line, self.current_column);
+        let mut token = String::from("\"");
+        loop {
+            let c = self.read_char()?;
+            match c {
+                '\n' | '\u{0}' => return Err(CompileError::Lexical {
+                    message: format!("The string starting line {}, column {} is not closed.", line, column),
+                    span: Span { line, column, length: 1 }
+                }),
+                '"' => {
+                    token.push(c);
+                    return Ok(Token::new(token, self));
+                },
+                _ => token.push(c)
+            }
+        }
+    }
+
+    fn get_number_or_id_token(&mut self, first_char: char) -> Result<Token, CompileError> {
         let is_token_number = first_char.is_ascii_digit();
         let is_token_identifier = first_char.is_ascii_alphabetic();
+        let is_token_color = first_char == '#';
         let mut rewind_one_char = false;
         let mut failure = false;
 
@@ -129,7 +233,7 @@ impl<'a> Lexer<'a> {
             token.push(c);
 
             if is_token_number && !c.is_ascii_digit() {
-                if DELIMITERS.contains(&c) || OPERATOR_FIRST_CHARS.contains(&c) {
+                if ends_token(c) {
                     rewind_one_char = true;
                     break;
                 } else if c == '.' && !dot_encountered {
@@ -139,8 +243,17 @@ impl<'a> Lexer<'a> {
                 }
             }
 
-            if is_token_identifier && !c.is_ascii_alphanumeric() {
-                if DELIMITERS.contains(&c) || OPERATOR_FIRST_CHARS.contains(&c) {
+            if is_token_color && token.len() > 1 && !c.is_ascii_hexdigit() {
+                if ends_token(c) || c == '.' {
+                    rewind_one_char = true;
+                    break;
+                } else {
+                    failure = true;
+                }
+            }
+
+            if is_token_identifier && !c.is_ascii_alphanumeric() && c != '_' {
+                if ends_token(c) {
                     rewind_one_char = true;
                     break;
                 } else {
@@ -151,16 +264,18 @@ impl<'a> Lexer<'a> {
             c = self.read_char()?;
         }
 
-        // The token is not a valid number or identifier
+        // The token is not a valid number, color or identifier
         if failure {
+            let token = Token::new(token, self);
             return
-                if is_token_number { Err(format!("Invalid token {}. It starts with a digit but is not a number.", Token::new(token, &self))) }
-                else { Err(format!("Invalid token {}. It contains illegal characters.", Token::new(token, &self))) }
+                if is_token_number { Err(lexical_error(&token, format!("Invalid token {}. It starts with a digit but is not a number.", token))) }
+                else if is_token_color { Err(invalid_color(token)) }
+                else { Err(lexical_error(&token, format!("Invalid token {}. It contains illegal characters.", token))) }
         }
 
         // No token found and we reached end-of-file
         if token.is_empty() && c == '\u{0}' {
-            return Ok(Token::new(String::new(), &self))
+            return Ok(Token::new(String::new(), self))
         }
 
         // The last character is nor part of the token, we just have to un-read it and we are good.
@@ -169,11 +284,16 @@ impl<'a> Lexer<'a> {
             self.rewind_char()?;
        }
 
-        // Token is a valid number or identifier
-        Ok(Token::new(token, &self))
+        // A color has exactly six hexadecimal digits, two for each component.
+        if is_token_color && token.len() != 7 {
+            return Err(invalid_color(Token::new(token, self)));
+        }
+
+        // Token is a valid number, color or identifier
+        Ok(Token::new(token, self))
     }
 
-    fn read_char(&mut self) -> Result<char, String> {
+    fn read_char(&mut self) -> Result<char, CompileError> {
         let mut buffer = [0; 1];
         match self.reader.read(&mut buffer) {
             Ok(_) => {
@@ -190,17 +310,17 @@ impl<'a> Lexer<'a> {
                 }
             }
             Err(e) => {
-                return Err(format!("Cannot read character from file {} (line {}, column {}). Cause : {:?}",
-                                   self.file_name, self.current_line, self.current_column, e))
+                return Err(CompileError::Io { message: format!("Cannot read character from file {} (line {}, column {}). Cause : {:?}",
+                                   self.file_name, self.current_line, self.current_column, e) })
             }
         }
         Ok(buffer[0] as char)
     }
 
-    fn rewind_char(&mut self) -> Result<(), String> {
+    fn rewind_char(&mut self) -> Result<(), CompileError> {
         if let Err(error) = self.reader.seek(SeekFrom::Current(-1)) {
-            return Err(format!("Could not get token (line {}, column {}). Cause : {:?}",
-                        self.previous_line, self.previous_column, error));
+            return Err(CompileError::Io { message: format!("Could not get token (line {}, column {}). Cause : {:?}",
+                        self.previous_line, self.previous_column, error) });
         }
         if self.current_char == '\n' {
             self.current_line = self.previous_line;
@@ -211,9 +331,24 @@ impl<'a> Lexer<'a> {
     }
 }
 
+/// A delimiter, an operator or the start of a comment right after a token ends it.
+fn ends_token(c: char) -> bool {
+    DELIMITERS.contains(&c) || OPERATOR_FIRST_CHARS.contains(&c) || c == '/'
+}
+
+fn invalid_color(token: Token) -> CompileError {
+    lexical_error(&token, format!("Invalid token {}. It starts with \"#\" but is not a hexadecimal color like #FF00AA.", token))
+}
+
+fn lexical_error(token: &Token, message: String) -> CompileError {
+    CompileError::Lexical { message, span: token.span() }
+}
+
 // TODO Add tests for line and columns feature, current tests are not enough
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests {
+    use crate::compiler::error::Span;
     use crate::compiler::lexer::{Lexer, SINGLE_CHAR_OPERATORS, TWO_CHAR_OPERATORS};
 
     static BENCH_NICE_FILE: &str = "resources/tests/lexer_benchmark_nice.txt";
@@ -223,6 +358,10 @@ mod tests {
     static NB_WITH_TWO_DOTS_FILE: &str = "resources/tests/lexer_number_with_two_dots.txt";
     static NB_WITH_ALPHABETIC_FILE: &str = "resources/tests/lexer_number_with_alphabetic.txt";
     static ID_WITH_ILLEGAL_CHAR_FILE: &str = "resources/tests/lexer_id_with_illegal_char.txt";
+    static ID_WITH_UNDERSCORE_FILE: &str = "resources/tests/lexer_id_with_underscore.txt";
+    static MALFORMED_HEX_COLOR_FILE: &str = "resources/tests/lexer_malformed_hex_color.txt";
+    static STRINGS_FILE: &str = "resources/tests/lexer_strings.txt";
+    static COMMENTS_FILE: &str = "resources/tests/lexer_comments.txt";
 
     #[test]
     fn tokenize_benchmark_nice_succeeds() {
@@ -236,6 +375,13 @@ mod tests {
         check_benchmark_output(&mut lexer);
     }
 
+    #[test]
+    fn tokenize_text_succeeds() {
+        let text = std::fs::read_to_string(BENCH_UGLY_FILE).unwrap();
+        let mut lexer = Lexer::from_text(&text);
+        check_benchmark_output(&mut lexer);
+    }
+
     fn check_benchmark_output(lexer: &mut Lexer) {
         assert_eq!(lexer.get_next_token().unwrap().str, "th15I5AnAlphanum3r1cId3nt1f1er");
         assert_eq!(lexer.get_next_token().unwrap().str, "thisTooAndNextUpIsANumber");
@@ -262,6 +408,13 @@ mod tests {
         assert!(lexer.get_next_token().unwrap().str.is_empty());
    }
 
+    #[test]
+    fn tokenize_gradient() {
+        let mut lexer = Lexer::from_text("#003300..#00FF00 0 .. 1");
+        let tokens: Vec<String> = (0..6).map(|_| lexer.get_next_token().unwrap().str).collect();
+        assert_eq!(tokens, vec!["#003300", "..", "#00FF00", "0", "..", "1"]);
+    }
+
     #[test]
     fn tokenize_no_file_fails() {
         match Lexer::new(NON_EXISTING_FILE) {
@@ -274,8 +427,11 @@ mod tests {
     fn tokenize_operator_typo_fails() {
         let mut lexer = Lexer::new(OPERATOR_TYPO_FILE).unwrap();
         match lexer.get_next_token() {
-            Err(error) => assert_eq!(error, format!(
-                "Invalid token \"|-\" - line 1, column 2. Note : recognized operators are {:?} and {:?}.", SINGLE_CHAR_OPERATORS, TWO_CHAR_OPERATORS)),
+            Err(error) => {
+                assert_eq!(error, format!(
+                    "Invalid token \"|-\" - line 1, column 2. Note : recognized operators are {:?} and {:?}.", SINGLE_CHAR_OPERATORS, TWO_CHAR_OPERATORS));
+                assert_eq!(error.span(), Some(Span { line: 1, column: 1, length: 2 }));
+            },
             _ => assert!(false),
         }
         assert_eq!(lexer.get_next_token().unwrap().str, "thisTokenShouldBeReadWithoutIssues");
@@ -301,13 +457,63 @@ mod tests {
         assert_eq!(lexer.get_next_token().unwrap().str, "thisTokenShouldBeReadWithoutIssues");
     }
 
+    #[test]
+    fn tokenize_id_with_underscore_succeeds() {
+        let mut lexer = Lexer::new(ID_WITH_UNDERSCORE_FILE).unwrap();
+        assert_eq!(lexer.get_next_token().unwrap().str, "hello_world");
+        assert_eq!(lexer.get_next_token().unwrap().str, "on_fire_2");
+        assert_eq!(lexer.get_next_token().unwrap().str, "(");
+    }
+
     #[test]
     fn tokenize_id_with_illegal_char_fails() {
         let mut lexer = Lexer::new(ID_WITH_ILLEGAL_CHAR_FILE).unwrap();
         match lexer.get_next_token() {
-            Err(error) => assert_eq!(error, "Invalid token \"hello_world\" - line 1, column 11. It contains illegal characters."),
+            Err(error) => assert_eq!(error, "Invalid token \"hello$world\" - line 1, column 11. It contains illegal characters."),
+            _ => assert!(false),
+        }
+        assert_eq!(lexer.get_next_token().unwrap().str, "thisTokenShouldBeReadWithoutIssues");
+    }
+
+    #[test]
+    fn tokenize_malformed_hex_color_fails() {
+        let mut lexer = Lexer::new(MALFORMED_HEX_COLOR_FILE).unwrap();
+        match lexer.get_next_token() {
+            Err(error) => assert_eq!(error, "Invalid token \"#FF00G0\" - line 1, column 7. It starts with \"#\" but is not a hexadecimal color like #FF00AA."),
             _ => assert!(false),
         }
         assert_eq!(lexer.get_next_token().unwrap().str, "thisTokenShouldBeReadWithoutIssues");
+        match lexer.get_next_token() {
+            Err(error) => assert_eq!(error, "Invalid token \"#FF00A\" - line 3, column 6. It starts with \"#\" but is not a hexadecimal color like #FF00AA."),
+            _ => assert!(false),
+        }
+        assert_eq!(lexer.get_next_token().unwrap().str, ",");
+        assert_eq!(lexer.get_next_token().unwrap().str, "#ff00aa");
+    }
+
+    #[test]
+    fn tokenize_comments() {
+        let mut lexer = Lexer::new(COMMENTS_FILE).unwrap();
+        for (str, line, column) in [("first", 2, 5), ("second", 3, 17), ("third", 3, 27), ("/", 4, 1), ("fourth", 5, 14)] {
+            let token = lexer.get_next_token().unwrap();
+            assert_eq!((token.str.as_str(), token.line, token.column), (str, line, column));
+        }
+        match lexer.get_next_token() {
+            Err(error) => assert_eq!(error, "The comment starting line 6, column 1 is not closed."),
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn tokenize_strings() {
+        let mut lexer = Lexer::new(STRINGS_FILE).unwrap();
+        for (str, line, column) in [("init", 1, 4), ("csv", 1, 8), ("\"grids/start.csv\"", 1, 26), (",", 1, 27), ("\"with // no comment\"", 2, 20)] {
+            let token = lexer.get_next_token().unwrap();
+            assert_eq!((token.str.as_str(), token.line, token.column), (str, line, column));
+        }
+        match lexer.get_next_token() {
+            Err(error) => assert_eq!(error, "The string starting line 2, column 22 is not closed."),
+            _ => assert!(false),
+        }
     }
 }
@@ -0,0 +1,184 @@
+//! This module merges the rules of several files, so that a base world and the overrides of a scenario are kept apart :
+//! the states and the directives of the files are gathered, and the transitions of the later file come first, so that they
+//! take precedence over the transitions of the earlier one for the same cells.
+//!
+//! The files must declare the same world size. A state declared by both files must have the same color and distribution,
+//! and a directive that can appear once, like `noise` or `boundary`, the same value. The other conflicts are reported
+//! by the semantic analysis of the merged rules, like a transition to a state that no file declares.
+
+use crate::compiler::error::CompileError;
+use crate::compiler::parser::{Ast, ConditionNode, DirectiveNode, Gradient, NextConditionNode, StateDistributionNode, StateNode, TransitionNode};
+use crate::compiler::semantic::StateDistribution;
+
+/// A state declared by a file : its name, its color, its gradient and its distribution.
+type StateDeclaration = (String, (u8, u8, u8), Option<Gradient>, StateDistribution);
+
+/// Merges the rules of the overlay into the rules of the base. Returns the conflicts between the files, if any.
+pub fn merge(base: Ast, overlay: Ast) -> Result<Ast, Vec<CompileError>> {
+    let mut errors = Vec::new();
+    if (base.world_size, base.depth) != (overlay.world_size, overlay.depth) {
+        errors.push(format!("The merged files declare different world sizes, {} and {}.", size(&base), size(&overlay)));
+    }
+
+    let mut directives = base.directives;
+    for directive in overlay.directives {
+        match directive_key(&directive).and_then(|key| directives.iter().find(|other| directive_key(other) == Some(key))) {
+            Some(other) if *other == directive => {},
+            Some(_) => errors.push(format!("The merged files declare different \"{}\" directives.", directive_key(&directive).unwrap())),
+            None => directives.push(directive)
+        }
+    }
+
+    let (mut states, base_transitions) = split_states(base.first_state);
+    let (overlay_states, mut transitions) = split_states(overlay.first_state);
+    for state in overlay_states {
+        match states.iter().find(|(name, _, _, _)| *name == state.0) {
+            Some(other) if *other == state => {},
+            Some(_) => errors.push(format!("The state \"{}\" is declared with different colors or distributions in the merged files.", state.0)),
+            None => states.push(state)
+        }
+    }
+    append_transitions(&mut transitions, base_transitions);
+
+    let mut agent_rules = overlay.agent_rules;
+    agent_rules.extend(base.agent_rules);
+    // The cells of the overlay are placed last, so they replace the cells of the base at the same positions.
+    let mut initial_cells = base.initial_cells;
+    initial_cells.extend(overlay.initial_cells);
+
+    if !errors.is_empty() {
+        return Err(errors.into_iter().map(CompileError::semantic).collect());
+    }
+    Ok(Ast { world_size: base.world_size, depth: base.depth, directives, first_state: chain_states(states, transitions), agent_rules, initial_cells })
+}
+
+fn size(ast: &Ast) -> String {
+    match ast.depth {
+        1 => format!("({}, {})", ast.world_size.0, ast.world_size.1),
+        depth => format!("({}, {}, {})", ast.world_size.0, ast.world_size.1, depth)
+    }
+}
+
+/// Returns the keyword of a directive that can appear once, with the key of the metadata, or None for a repeatable directive.
+fn directive_key(directive: &DirectiveNode) -> Option<&str> {
+    match directive {
+        DirectiveNode::SecondOrder => Some("second_order"),
+        DirectiveNode::Memory => Some("memory"),
+        DirectiveNode::Noise(_) => Some("noise"),
+        DirectiveNode::Data(_) => Some("data"),
+        DirectiveNode::Boundary(_, _) => Some("boundary"),
+        DirectiveNode::Neighborhood(_) => Some("neighborhood"),
+        DirectiveNode::InitCsv(_) => Some("init"),
+        DirectiveNode::Metadata(key, _) => Some(key),
+        DirectiveNode::Frozen(..) | DirectiveNode::Source(..) | DirectiveNode::Agent(..) => None
+    }
+}
+
+/// Splits the chain of the states from the transitions that follow them.
+fn split_states(first_state: StateNode) -> (Vec<StateDeclaration>, TransitionNode) {
+    let mut states = Vec::new();
+    let mut node = first_state;
+    loop {
+        match node {
+            StateNode::State(name, r, g, b, gradient, distribution) => {
+                let (distribution, next) = match distribution {
+                    StateDistributionNode::Proportion(proportion, next) => (StateDistribution::Proportion(proportion), next),
+                    StateDistributionNode::Quantity(quantity, next) => (StateDistribution::Quantity(quantity), next),
+                    StateDistributionNode::Box(x, y, width, height, next) => (StateDistribution::Box(x, y, width, height), next),
+                    StateDistributionNode::Circle(x, y, radius, next) => (StateDistribution::Circle(x, y, radius), next),
+                    StateDistributionNode::Line(x1, y1, x2, y2, next) => (StateDistribution::Line(x1, y1, x2, y2), next),
+                    StateDistributionNode::Image(file_name, next) => (StateDistribution::Image(file_name), next),
+                    StateDistributionNode::Default(next) => (StateDistribution::Default, next)
+                };
+                states.push((name, (r, g, b), gradient, distribution));
+                node = *next;
+            },
+            StateNode::Next(transitions) => return (states, transitions)
+        }
+    }
+}
+
+/// Chains the states then the transitions, from the last state to the first, like the parser does.
+fn chain_states(states: Vec<StateDeclaration>, transitions: TransitionNode) -> StateNode {
+    let mut next_state = StateNode::Next(transitions);
+    for (name, (r, g, b), gradient, distribution) in states.into_iter().rev() {
+        let next = Box::new(next_state);
+        let distribution = match distribution {
+            StateDistribution::Proportion(proportion) => StateDistributionNode::Proportion(proportion, next),
+            StateDistribution::Quantity(quantity) => StateDistributionNode::Quantity(quantity, next),
+            StateDistribution::Box(x, y, width, height) => StateDistributionNode::Box(x, y, width, height, next),
+            StateDistribution::Circle(x, y, radius) => StateDistributionNode::Circle(x, y, radius, next),
+            StateDistribution::Line(x1, y1, x2, y2) => StateDistributionNode::Line(x1, y1, x2, y2, next),
+            StateDistribution::Image(file_name) => StateDistributionNode::Image(file_name, next),
+            StateDistribution::Default => StateDistributionNode::Default(next)
+        };
+        next_state = StateNode::State(name, r, g, b, gradient, distribution);
+    }
+    next_state
+}
+
+/// Appends the transitions after the last transition of the chain, which ends with the conditions of each transition.
+fn append_transitions(chain: &mut TransitionNode, transitions: TransitionNode) {
+    match chain {
+        TransitionNode::End => *chain = transitions,
+        TransitionNode::Transition(_, _, condition) | TransitionNode::Swap(_, _, condition) | TransitionNode::Vote(_, _, condition) =>
+            append_after_condition(condition, transitions)
+    }
+}
+
+fn append_after_condition(condition: &mut ConditionNode, transitions: TransitionNode) {
+    let next = match condition {
+        ConditionNode::QuantityCondition(_, _, _, _, next) | ConditionNode::DataCondition(_, _, next)
+        | ConditionNode::DistanceCondition(_, _, _, next) | ConditionNode::NeighborCondition(_, _, next)
+        | ConditionNode::PreviousNeighborCondition(_, _, next) | ConditionNode::PreviousStateCondition(_, next)
+        | ConditionNode::RandomCondition(_, _, next) | ConditionNode::True(next) => next
+    };
+    match next {
+        NextConditionNode::NextCondition(_, condition) => append_after_condition(condition, transitions),
+        NextConditionNode::NextTransition(_, chain) => append_transitions(chain, transitions)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod tests {
+    use crate::compiler::merge::merge;
+    use crate::compiler::parser::parse_str;
+    use crate::compiler::semantic::{analyze, CompilerOptions};
+
+    const BASE: &str = "size (20, 10)\n\
+                        noise 0.01\n\
+                        title \"Forest\"\n\
+                        states { (ground, 0, 0, 0), (tree, 0, 255, 0, proportion 0.3), }\n\
+                        transitions { (ground, tree, tree > 2), (tree, ground, rand 0.1), }";
+
+    #[test]
+    fn merge_the_states_and_the_transitions_of_the_overlay_first() {
+        let overlay = "size (20, 10)\n\
+                       noise 0.01\n\
+                       frozen 0 0 2 2\n\
+                       states { (tree, 0, 255, 0, proportion 0.3), (fire, 255, 0, 0, quantity 3), }\n\
+                       transitions { (tree, fire, fire > 0), (fire, ground, true), }";
+        let ast = merge(parse_str(BASE).unwrap(), parse_str(overlay).unwrap()).unwrap();
+        let rules = analyze(&ast, &CompilerOptions::default()).unwrap();
+        let names: Vec<&str> = rules.user_states().iter().map(|state| state.name.as_str()).collect();
+        assert_eq!(names, vec!["ground", "tree", "fire"]);
+        assert_eq!(rules.noise, 0.01);
+        assert_eq!(rules.regions.len(), 1);
+        let origins: Vec<&str> = rules.transitions.iter().map(|(origin, _, _, _)| rules.states[*origin].name.as_str()).collect();
+        assert_eq!(origins, vec!["tree", "fire", "ground", "tree"]);
+    }
+
+    #[test]
+    fn merge_conflicts_fail() {
+        let overlay = "size (20, 12)\n\
+                       noise 0.2\n\
+                       title \"Forest\"\n\
+                       states { (tree, 0, 200, 0, proportion 0.3), }\n\
+                       transitions { }";
+        let errors = merge(parse_str(BASE).unwrap(), parse_str(overlay).unwrap()).err().unwrap();
+        assert_eq!(errors, vec!["The merged files declare different world sizes, (20, 10) and (20, 12).",
+                                "The merged files declare different \"noise\" directives.",
+                                "The state \"tree\" is declared with different colors or distributions in the merged files."]);
+    }
+}
@@ -0,0 +1,148 @@
+//! This module provides the translation of the compiler errors and warnings, which are written in English.
+//!
+//! A catalog is a text file of `msgid` / `msgstr` pairs, like a gettext file. The `msgid` is an English message,
+//! in which `{}` stands for a variable part, like a state name or a line number. In the `msgstr`, `{}` stands
+//! for the next variable part and `{N}` for the Nth one, starting at 0, so that a translation can reorder them :
+//! ```text
+//! # French
+//! msgid "The state \"{}\" is not defined."
+//! msgstr "L'état \"{}\" n'est pas défini."
+//! ```
+
+use std::fs;
+
+struct Entry {
+    /// The parts of the English message around the variable parts.
+    pieces: Vec<String>,
+    translation: String
+}
+
+/// The translations of the messages. The messages that are not in the catalog are left in English.
+pub struct Catalog {
+    entries: Vec<Entry>
+}
+
+impl Catalog {
+    pub fn load(file_name: &str) -> Result<Catalog, String> {
+        match fs::read_to_string(file_name) {
+            Ok(text) => Catalog::from_text(&text),
+            Err(error) => Err(format!("Cannot read the message catalog {}. Cause : {:?}", file_name, error))
+        }
+    }
+
+    pub fn from_text(text: &str) -> Result<Catalog, String> {
+        let mut entries = Vec::new();
+        let mut message_id = None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match (line.split_once(' '), message_id.take()) {
+                (Some(("msgid", text)), None) => message_id = Some(unquote(text, index)?),
+                (Some(("msgstr", text)), Some(id)) => entries.push(Entry {
+                    pieces: id.split("{}").map(String::from).collect(),
+                    translation: unquote(text, index)?
+                }),
+                (_, Some(_)) => return Err(format!("Expected \"msgstr\", found \"{}\" - line {}.", line, index + 1)),
+                (_, None) => return Err(format!("Expected \"msgid\", found \"{}\" - line {}.", line, index + 1))
+            }
+        }
+        match message_id {
+            Some(_) => Err(String::from("Expected \"msgstr\", found the end of the catalog.")),
+            None => Ok(Catalog { entries })
+        }
+    }
+
+    /// Returns the translation of the first entry that matches the message, or the message itself if there is none.
+    pub fn translate(&self, message: &str) -> String {
+        self.entries.iter()
+            .find_map(|entry| match_pieces(&entry.pieces, message).map(|arguments| substitute(&entry.translation, &arguments)))
+            .unwrap_or_else(|| message.to_string())
+    }
+}
+
+/// Removes the quotes around the text, and unescapes the quotes and backslashes inside.
+fn unquote(text: &str, index: usize) -> Result<String, String> {
+    let text = text.trim();
+    if text.len() < 2 || !text.starts_with('"') || !text.ends_with('"') {
+        return Err(format!("Expected a quoted message, found {} - line {}.", text, index + 1));
+    }
+    Ok(text[1..text.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\"))
+}
+
+/// Returns the variable parts of the message if it matches the pieces. Each variable part is as short as possible.
+fn match_pieces(pieces: &[String], message: &str) -> Option<Vec<String>> {
+    let (first, rest) = pieces.split_first()?;
+    let mut remaining = message.strip_prefix(first.as_str())?;
+    let mut arguments = Vec::new();
+    for (i, piece) in rest.iter().enumerate() {
+        let end = if i == rest.len() - 1 {
+            remaining.strip_suffix(piece.as_str())?.len()
+        } else {
+            remaining.find(piece.as_str())?
+        };
+        arguments.push(remaining[..end].to_string());
+        remaining = &remaining[end + piece.len()..];
+    }
+    if remaining.is_empty() { Some(arguments) } else { None }
+}
+
+fn substitute(translation: &str, arguments: &[String]) -> String {
+    let mut result = String::new();
+    let mut next_argument = 0;
+    let mut rest = translation;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let placeholder = rest[start + 1..].find('}')
+            .map(|end| &rest[start + 1..start + 1 + end]);
+        let index = match placeholder {
+            Some("") => Some(next_argument),
+            Some(digits) => digits.parse::<usize>().ok(),
+            None => None
+        };
+        match (index, placeholder) {
+            (Some(index), Some(placeholder)) if index < arguments.len() => {
+                result.push_str(&arguments[index]);
+                next_argument = index + 1;
+                rest = &rest[start + placeholder.len() + 2..];
+            },
+            _ => {
+                result.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::messages::Catalog;
+
+    static CATALOG: &str = r#"
+# French
+msgid "The state \"{}\" is not defined."
+msgstr "L'état \"{}\" n'est pas défini."
+
+msgid "Expected {}, found {} - line {}, column {}."
+msgstr "Ligne {2}, colonne {3} : {0} attendu, {1} trouvé."
+"#;
+
+    #[test]
+    fn translate_messages() {
+        let catalog = Catalog::from_text(CATALOG).unwrap();
+        assert_eq!(catalog.translate("The state \"fire\" is not defined."), "L'état \"fire\" n'est pas défini.");
+        assert_eq!(catalog.translate("Expected \"states\", found \"plouf\" - line 3, column 5."),
+                   "Ligne 3, colonne 5 : \"states\" attendu, \"plouf\" trouvé.");
+        assert_eq!(catalog.translate("You should specify at least one state."), "You should specify at least one state.");
+    }
+
+    #[test]
+    fn catalog_errors() {
+        assert_eq!(Catalog::from_text("msgstr \"a\"").err().unwrap(), "Expected \"msgid\", found \"msgstr \"a\"\" - line 1.");
+        assert_eq!(Catalog::from_text("msgid \"a\"").err().unwrap(), "Expected \"msgstr\", found the end of the catalog.");
+        assert_eq!(Catalog::from_text("msgid a").err().unwrap(), "Expected a quoted message, found a - line 1.");
+    }
+}
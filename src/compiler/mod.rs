@@ -1,3 +1,9 @@
-mod lexer;
+pub(crate) mod lexer;
 pub mod parser;
+pub mod builder;
 pub mod semantic;
+pub mod messages;
+pub mod error;
+pub mod diagnostics;
+pub mod rename;
+pub mod merge;
@@ -1,8 +1,11 @@
-/// This module provides syntax analysis functions
+//! This module provides syntax analysis functions
 
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::compiler::error::CompileError;
 use crate::compiler::lexer::{Token, Lexer};
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ComparisonOperator {
     Greater,
     Lesser,
@@ -12,7 +15,48 @@ pub enum ComparisonOperator {
     Different
 }
 
-#[derive(Copy, Clone, Debug)]
+impl ComparisonOperator {
+    /// Returns the operator written with the symbol, like ">=".
+    pub fn from_symbol(symbol: &str) -> Option<ComparisonOperator> {
+        match symbol {
+            "<" => Some(ComparisonOperator::Lesser),
+            ">" => Some(ComparisonOperator::Greater),
+            "<=" => Some(ComparisonOperator::LesserOrEqual),
+            ">=" => Some(ComparisonOperator::GreaterOrEqual),
+            "==" => Some(ComparisonOperator::Equal),
+            "!=" => Some(ComparisonOperator::Different),
+            _ => None
+        }
+    }
+
+    /// Returns the result of the comparison "lhs <operator> rhs".
+    pub fn evaluate<T: PartialOrd>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            ComparisonOperator::Greater => lhs > rhs,
+            ComparisonOperator::Lesser => lhs < rhs,
+            ComparisonOperator::GreaterOrEqual => lhs >= rhs,
+            ComparisonOperator::LesserOrEqual => lhs <= rhs,
+            ComparisonOperator::Equal => lhs == rhs,
+            ComparisonOperator::Different => lhs != rhs
+        }
+    }
+}
+
+impl fmt::Display for ComparisonOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            ComparisonOperator::Greater => ">",
+            ComparisonOperator::Lesser => "<",
+            ComparisonOperator::GreaterOrEqual => ">=",
+            ComparisonOperator::LesserOrEqual => "<=",
+            ComparisonOperator::Equal => "==",
+            ComparisonOperator::Different => "!="
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum NeighborCell {
     A,
     B,
@@ -24,6 +68,51 @@ pub enum NeighborCell {
     H
 }
 
+/// The neighbor cells with their letter, their position relative to the cell as an (x, y) offset, and this position in words.
+const NEIGHBOR_CELLS: [(NeighborCell, &str, (isize, isize), &str); 8] = [
+    (NeighborCell::A, "A", (-1, -1), "up left"),
+    (NeighborCell::B, "B", (0, -1), "up"),
+    (NeighborCell::C, "C", (1, -1), "up right"),
+    (NeighborCell::D, "D", (-1, 0), "left"),
+    (NeighborCell::E, "E", (1, 0), "right"),
+    (NeighborCell::F, "F", (-1, 1), "down left"),
+    (NeighborCell::G, "G", (0, 1), "down"),
+    (NeighborCell::H, "H", (1, 1), "down right")
+];
+
+impl NeighborCell {
+    pub const ALL: [NeighborCell; 8] = [NeighborCell::A, NeighborCell::B, NeighborCell::C, NeighborCell::D,
+                                        NeighborCell::E, NeighborCell::F, NeighborCell::G, NeighborCell::H];
+
+    pub fn letter(self) -> &'static str {
+        NEIGHBOR_CELLS[self as usize].1
+    }
+
+    /// Returns the position of the neighbor relative to the cell, y going down.
+    pub fn offset(self) -> (isize, isize) {
+        NEIGHBOR_CELLS[self as usize].2
+    }
+
+    pub fn description(self) -> &'static str {
+        NEIGHBOR_CELLS[self as usize].3
+    }
+
+    pub fn from_letter(letter: &str) -> Option<NeighborCell> {
+        NEIGHBOR_CELLS.iter().find(|(_, other, _, _)| *other == letter).map(|(cell, _, _, _)| *cell)
+    }
+
+    /// Returns the accepted letters with their position, like "\"A\" (up left), \"B\" (up)".
+    pub fn letters_description() -> String {
+        NEIGHBOR_CELLS.iter().map(|(_, letter, _, description)| format!("\"{}\" ({})", letter, description)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+impl fmt::Display for NeighborCell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum BooleanOperator {
     And,
@@ -32,175 +121,632 @@ pub enum BooleanOperator {
 
 pub enum NextConditionNode {
     NextCondition(BooleanOperator, Box<ConditionNode>),
-    NextTransition(Option<usize>, Box<TransitionNode>)  // The optional usize represents the optional transition's delay
+    NextTransition(TransitionAnnotations, Box<TransitionNode>)
+}
+
+/// The optional annotations that can follow the conditions of a transition.
+#[derive(Default)]
+pub struct TransitionAnnotations {
+    pub delay: Option<usize>,
+    pub priority: Option<usize>,
+    pub set_data: Option<u8>,
+    pub effects: Vec<(NeighborCell, String)> // The states written into neighbors when the transition is triggered
+}
+
+/// The cells counted by a quantity condition.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CountedCells {
+    Neighbors, // The cells of the neighborhood, like "alive > 3"
+    NeighborsAndSelf // The cells of the neighborhood and the cell itself, like the 9 cells of "count9(alive) > 4"
 }
 
 pub enum ConditionNode {
-    QuantityCondition(String, ComparisonOperator, u8, NextConditionNode),
+    QuantityCondition(String, ComparisonOperator, u8, CountedCells, NextConditionNode),
+    DataCondition(ComparisonOperator, u8, NextConditionNode),
+    DistanceCondition(String, ComparisonOperator, u8, NextConditionNode),
     NeighborCondition(NeighborCell, String, NextConditionNode),
-    RandomCondition(f64, NextConditionNode),
+    PreviousNeighborCondition(NeighborCell, String, NextConditionNode),
+    PreviousStateCondition(String, NextConditionNode),
+    RandomCondition(f64, RandomDraw, NextConditionNode),
     True(NextConditionNode)
 }
 
+/// Which cells share the draw of a random condition.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RandomDraw {
+    Cell, // Each cell draws its own number, like "rand 0.1"
+    Tick, // All the cells share a number drawn at each iteration, like "rand 0.1 per tick"
+    Block(usize) // The cells of a square block of this side share a number drawn at each iteration, like "rand 0.1 per block 4"
+}
+
 pub enum TransitionNode {
     Transition(String, String, Box<ConditionNode>),
+    Swap(String, NeighborCell, Box<ConditionNode>), // Exchanges the states of the cell and the neighbor
+    Vote(String, TieBreak, Box<ConditionNode>), // Takes the most common state of the neighbors
     End
 }
 
+/// How a vote picks the new state of the cell when several states are the most common among its neighbors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TieBreak {
+    Keep, // The cell keeps its state if it is one of them, or else takes the first declared one
+    Lowest, // The first declared state among them
+    Random // One of them at random
+}
+
 pub enum StateDistributionNode {
     Proportion(f64, Box<StateNode>),
     Quantity(usize, Box<StateNode>),
     Box(usize, usize, usize, usize, Box<StateNode>),
+    Circle(usize, usize, usize, Box<StateNode>), // The center and the radius
+    Line(usize, usize, usize, usize, Box<StateNode>), // The two ends
+    Image(String, Box<StateNode>), // The path of the PNG file, relative to the directory of the rules file
     Default(Box<StateNode>)
 }
 
+/// The color a state fades to while its cells stay in it, from the color of the state.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Gradient {
+    pub end: (u8, u8, u8),
+    pub ticks: usize // The age of the cells shown with the end color
+}
+
 pub enum StateNode {
-    State(String, u8, u8, u8, StateDistributionNode),
+    State(String, u8, u8, u8, Option<Gradient>, StateDistributionNode),
     Next(TransitionNode)
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Hash)]
+pub enum Heading {
+    Up,
+    Right,
+    Down,
+    Left
+}
+
+/// How an agent changes its heading.
+#[derive(Copy, Clone, Debug)]
+pub enum Turn {
+    Left,
+    Right,
+    Back,
+    None
+}
+
+impl Heading {
+    /// Returns the heading after the turn.
+    pub fn turn(self, turn: Turn) -> Heading {
+        let headings = [Heading::Up, Heading::Right, Heading::Down, Heading::Left];
+        let current = headings.iter().position(|h| *h == self).unwrap();
+        let offset = match turn {
+            Turn::None => 0,
+            Turn::Right => 1,
+            Turn::Back => 2,
+            Turn::Left => 3
+        };
+        headings[(current + offset) % 4]
+    }
+}
+
+/// An agent rule : on a cell in the given state, the agent turns and writes the new state into the cell.
+/// Optionally, the rule only applies in a given mode of the agent, and changes its mode.
+pub struct AgentRuleNode {
+    pub cell_state: String,
+    pub turn: Turn,
+    pub new_cell_state: String,
+    pub mode: Option<usize>,
+    pub next_mode: Option<usize>
+}
+
+/// The cells counted by the quantity conditions, within the radius around the cell.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Neighborhood {
+    Moore(usize), // The cells of the square
+    VonNeumann(usize) // The cells within the Manhattan distance
+}
+
+impl Neighborhood {
+    pub fn radius(self) -> usize {
+        match self {
+            Neighborhood::Moore(radius) | Neighborhood::VonNeumann(radius) => radius
+        }
+    }
+
+    /// Returns the positions of the neighbors relative to the cell, as (x, y, layer) offsets.
+    /// In a 3D world, the Moore neighborhood is a cube, like the 26 cells around the cell for a radius of 1.
+    pub fn offsets(self, three_dimensional: bool) -> Vec<(isize, isize, isize)> {
+        let radius = self.radius() as isize;
+        let layers = if three_dimensional { radius } else { 0 };
+        (-layers..=layers)
+            .flat_map(|w| (-radius..=radius).flat_map(move |v| (-radius..=radius).map(move |u| (u, v, w))))
+            .filter(|(u, v, w)| (*u, *v, *w) != (0, 0, 0) && match self {
+                Neighborhood::Moore(_) => true,
+                Neighborhood::VonNeumann(_) => u.abs() + v.abs() + w.abs() <= radius
+            })
+            .collect()
+    }
+}
+
+/// What the directives tell about the conditions of the transitions.
+struct Context {
+    data_channel: Option<String>,
+    neighbor_count: usize, // The maximum of a quantity condition
+    directory: PathBuf // The directory the file paths of the states are relative to
+}
+
+/// The optional directives declared between the world size and the states.
+#[derive(PartialEq)]
+pub enum DirectiveNode {
+    SecondOrder,
+    Memory,
+    Noise(f64),
+    Frozen(usize, usize, usize, usize),
+    Source(usize, usize, usize, usize, String),
+    Data(String),
+    Agent(usize, usize, Heading),
+    Boundary(String, Option<String>), // The mode, and the state of the cells outside of the world for a wall
+    Neighborhood(Neighborhood),
+    InitCsv(String), // The path of the CSV file, relative to the directory of the rules file
+    Metadata(String, String) // The "title", "author" or "description" of the rules, and its text
+}
+
 pub struct Ast {
     pub world_size: (usize, usize),
-    pub first_state: StateNode
+    pub depth: usize, // The number of layers of a 3D world, 1 for a 2D world
+    pub directives: Vec<DirectiveNode>,
+    pub first_state: StateNode,
+    pub agent_rules: Vec<AgentRuleNode>,
+    pub initial_cells: Vec<CellNode> // The cells of the "pattern" section
 }
 
+/// A cell of the "pattern" section : its position and the name of its state.
+pub type CellNode = ((usize, usize), String);
+
 /// Parses the file to create an AST that matches the automaton description language grammar.
 /// If an error occurs, the parsing is stopped and the error is returned.
 ///
 /// Two main types of errors can be generated :
 ///     - lexical error if the error occurred in the lexical analyzer (lexer)
 ///     - syntax error if the file does not match the grammar
-pub fn parse(file_name: &str) -> Result<Ast, String> {
-    let mut lexer: Lexer;
-    match Lexer::new(file_name) {
-        Ok(l) => { lexer = l; },
-        Err(io_error) => { return Err(format!("Cannot parse file {}. Cause : {:?}", file_name, io_error)); }
+pub fn parse(file_name: &str) -> Result<Ast, CompileError> {
+    let lexer = match Lexer::new(file_name) {
+        Ok(lexer) => lexer,
+        Err(io_error) => { return Err(CompileError::Io { message: format!("Cannot parse file {}. Cause : {:?}", file_name, io_error) }); }
     };
+    parse_lexer(lexer, Path::new(file_name).parent().unwrap_or_else(|| Path::new("")))
+}
+
+/// Same as `parse`, but the rules are read from the text. The file paths of the directives are relative to the current directory.
+pub fn parse_str(text: &str) -> Result<Ast, CompileError> {
+    parse_lexer(Lexer::from_text(text), Path::new(""))
+}
 
+/// Parses the tokens of the lexer. The file paths of the directives are relative to the directory.
+fn parse_lexer(mut lexer: Lexer, directory: &Path) -> Result<Ast, CompileError> {
     expect(&mut lexer, vec!["size"])?;
     expect(&mut lexer, vec!["("])?;
-    let width = expect_usize(&mut lexer)?;
+    let width = expect_positive_usize(&mut lexer)?;
     expect(&mut lexer, vec![","])?;
-    let height = expect_usize(&mut lexer)?;
-    expect(&mut lexer, vec![")"])?;
-    expect(&mut lexer, vec!["states"])?;
+    let height = expect_positive_usize(&mut lexer)?;
+    // A 3D world has a third dimension, its number of layers.
+    let depth = if expect(&mut lexer, vec![",", ")"])? == "," {
+        let depth = expect_positive_usize(&mut lexer)?;
+        expect(&mut lexer, vec![")"])?;
+        depth
+    } else {
+        1
+    };
+    let directives = parse_directives(&mut lexer, directory)?;
+    let context = Context {
+        data_channel: directives.iter().find_map(|d| match d {
+            DirectiveNode::Data(name) => Some(name.clone()),
+            _ => None
+        }),
+        neighbor_count: directives.iter().find_map(|d| match d {
+            DirectiveNode::Neighborhood(neighborhood) => Some(*neighborhood),
+            _ => None
+        }).unwrap_or(Neighborhood::Moore(1)).offsets(depth > 1).len(),
+        directory: directory.to_path_buf()
+    };
     expect(&mut lexer, vec!["{"])?;
-    let first_state = parse_state(&mut lexer)?;
+    let first_state = parse_state(&mut lexer, &context)?;
+    // The optional sections follow the transitions : the agent rules, then the pattern.
+    let mut token = lexer.get_next_token()?;
+    let agent_rules = if token.str == "agents" {
+        let agent_rules = parse_agent_rules(&mut lexer)?;
+        token = lexer.get_next_token()?;
+        agent_rules
+    } else {
+        Vec::new()
+    };
+    let initial_cells = if token.str == "pattern" { parse_initial_cells(&mut lexer)? } else { Vec::new() };
     Ok(Ast {
         world_size: (width, height),
-        first_state
+        depth,
+        directives,
+        first_state,
+        agent_rules,
+        initial_cells
     })
 }
 
-fn parse_state(lexer: &mut Lexer) -> Result<StateNode, String> {
+/// Parses the directives until the "states" keyword. The file paths are relative to the directory.
+fn parse_directives(lexer: &mut Lexer, directory: &Path) -> Result<Vec<DirectiveNode>, CompileError> {
+    let mut directives = Vec::new();
+    // The token read after a directive whose last parameter is optional.
+    let mut next_token = None;
+    loop {
+        let token = match next_token.take() {
+            Some(token) => token,
+            None => lexer.get_next_token()?
+        };
+        let token = check(token, vec!["states", "second_order", "memory", "noise", "frozen", "source", "data", "agent", "boundary", "neighborhood", "init",
+                                   "title", "author", "description"])?;
+        match token.as_str() {
+            "states" => return Ok(directives),
+            "second_order" => directives.push(DirectiveNode::SecondOrder),
+            "memory" => directives.push(DirectiveNode::Memory),
+            "noise" => directives.push(DirectiveNode::Noise(expect_proportion(lexer)?)),
+            "data" => directives.push(DirectiveNode::Data(expect_identifier(lexer)?)),
+            "neighborhood" => {
+                let kind = expect(lexer, vec!["moore", "von_neumann"])?;
+                let token = lexer.get_next_token()?;
+                let radius = match token.str.parse::<usize>() {
+                    Ok(0) => return Err(syntax_error(&token, format!("Expected an unsigned integer > 0, found {}.", token))),
+                    Ok(radius) => radius,
+                    Err(_) => {
+                        next_token = Some(token);
+                        1
+                    }
+                };
+                directives.push(DirectiveNode::Neighborhood(if kind == "moore" { Neighborhood::Moore(radius) } else { Neighborhood::VonNeumann(radius) }));
+            },
+            "init" => {
+                expect(lexer, vec!["csv"])?;
+                let path = directory.join(expect_string(lexer, "a file path")?);
+                directives.push(DirectiveNode::InitCsv(path.to_string_lossy().into_owned()));
+            },
+            "title" | "author" | "description" => directives.push(DirectiveNode::Metadata(token.clone(), expect_string(lexer, "a text")?)),
+            "boundary" => {
+                let mode = expect(lexer, vec!["wrap", "wall", "mirror"])?;
+                let state = if mode == "wall" { Some(expect_identifier(lexer)?) } else { None };
+                directives.push(DirectiveNode::Boundary(mode, state));
+            },
+            "agent" => {
+                let (x, y) = (expect_usize(lexer)?, expect_usize(lexer)?);
+                let heading = match expect(lexer, vec!["up", "right", "down", "left"])?.as_str() {
+                    "up" => Heading::Up,
+                    "right" => Heading::Right,
+                    "down" => Heading::Down,
+                    _ => Heading::Left
+                };
+                directives.push(DirectiveNode::Agent(x, y, heading));
+            },
+            _ => {
+                let (x, y) = (expect_usize(lexer)?, expect_usize(lexer)?);
+                let (width, height) = (expect_positive_usize(lexer)?, expect_positive_usize(lexer)?);
+                if token == "frozen" {
+                    directives.push(DirectiveNode::Frozen(x, y, width, height));
+                } else {
+                    directives.push(DirectiveNode::Source(x, y, width, height, expect_identifier(lexer)?));
+                }
+            }
+        }
+    }
+}
+
+fn parse_state(lexer: &mut Lexer, context: &Context) -> Result<StateNode, CompileError> {
     let token = expect(lexer, vec!["(", "}"])?;
     if token == "(" {
         let state_name = expect_identifier(lexer)?;
         expect(lexer, vec![","])?;
-        let red = expect_u8(lexer)?;
-        expect(lexer, vec![","])?;
-        let green = expect_u8(lexer)?;
-        expect(lexer, vec![","])?;
-        let blue = expect_u8(lexer)?;
-        Ok(StateNode::State(state_name, red, green, blue, parse_state_distribution(lexer)?))
+        let (red, green, blue) = parse_color(lexer)?;
+        // The color can fade to another one over the ticks, like "#003300..#00FF00 over 20".
+        let mut token = lexer.get_next_token()?;
+        let gradient = if token.str == ".." {
+            let end = parse_color(lexer)?;
+            expect(lexer, vec!["over"])?;
+            let ticks = expect_positive_usize(lexer)?;
+            token = lexer.get_next_token()?;
+            Some(Gradient { end, ticks })
+        } else {
+            None
+        };
+        Ok(StateNode::State(state_name, red, green, blue, gradient, parse_state_distribution(token, lexer, context)?))
     } else {
         expect(lexer, vec!["transitions"])?;
         expect(lexer, vec!["{"])?;
-        Ok(StateNode::Next(parse_transitions(lexer)?))
+        Ok(StateNode::Next(parse_transitions(lexer, context)?))
+    }
+}
+
+/// The color is either three integers between 0 and 255, like "255, 0, 170", or a hexadecimal color like "#FF00AA".
+fn parse_color(lexer: &mut Lexer) -> Result<(u8, u8, u8), CompileError> {
+    let token = lexer.get_next_token()?;
+    if token.str.starts_with('#') {
+        // The lexer checked that the token has six hexadecimal digits.
+        let component = |i: usize| u8::from_str_radix(&token.str[i..i + 2], 16).unwrap();
+        return Ok((component(1), component(3), component(5)));
     }
+    let red = to_u8(token)?;
+    expect(lexer, vec![","])?;
+    let green = expect_u8(lexer)?;
+    expect(lexer, vec![","])?;
+    let blue = expect_u8(lexer)?;
+    Ok((red, green, blue))
 }
 
-fn parse_state_distribution(lexer: &mut Lexer) -> Result<StateDistributionNode, String> {
-    let token = expect(lexer, vec![")", ","])?;
+/// Parses the distribution of a state, from the token that follows its color.
+fn parse_state_distribution(token: Token, lexer: &mut Lexer, context: &Context) -> Result<StateDistributionNode, CompileError> {
+    let token = check(token, vec![")", ","])?;
     if token == ")" {
         expect(lexer, vec![","])?;
-        Ok(StateDistributionNode::Default(Box::new(parse_state(lexer)?)))
+        Ok(StateDistributionNode::Default(Box::new(parse_state(lexer, context)?)))
     } else {
-        let token2 = expect(lexer, vec!["proportion", "quantity", "box"])?;
+        let token2 = expect(lexer, vec!["proportion", "quantity", "box", "circle", "line", "from_image"])?;
         if token2 == "proportion" {
             let proportion = expect_proportion(lexer)?;
             expect(lexer, vec![")"])?;
             expect(lexer, vec![","])?;
-            Ok(StateDistributionNode::Proportion(proportion, Box::new(parse_state(lexer)?)))
+            Ok(StateDistributionNode::Proportion(proportion, Box::new(parse_state(lexer, context)?)))
         } else if token2 == "quantity" {
             let quantity = expect_usize(lexer)?;
             expect(lexer, vec![")"])?;
             expect(lexer, vec![","])?;
-            Ok(StateDistributionNode::Quantity(quantity, Box::new(parse_state(lexer)?)))
+            Ok(StateDistributionNode::Quantity(quantity, Box::new(parse_state(lexer, context)?)))
+        } else if token2 == "circle" {
+            let (x, y, radius) = (expect_usize(lexer)?, expect_usize(lexer)?, expect_usize(lexer)?);
+            expect(lexer, vec![")"])?;
+            expect(lexer, vec![","])?;
+            Ok(StateDistributionNode::Circle(x, y, radius, Box::new(parse_state(lexer, context)?)))
+        } else if token2 == "line" {
+            let (x1, y1) = (expect_usize(lexer)?, expect_usize(lexer)?);
+            let (x2, y2) = (expect_usize(lexer)?, expect_usize(lexer)?);
+            expect(lexer, vec![")"])?;
+            expect(lexer, vec![","])?;
+            Ok(StateDistributionNode::Line(x1, y1, x2, y2, Box::new(parse_state(lexer, context)?)))
+        } else if token2 == "from_image" {
+            let path = context.directory.join(expect_string(lexer, "a file path")?);
+            expect(lexer, vec![")"])?;
+            expect(lexer, vec![","])?;
+            Ok(StateDistributionNode::Image(path.to_string_lossy().into_owned(), Box::new(parse_state(lexer, context)?)))
         } else {
             let (x, y) = (expect_usize(lexer)?, expect_usize(lexer)?);
             let (width, height) = (expect_positive_usize(lexer)?, expect_positive_usize(lexer)?);
             expect(lexer, vec![")"])?;
             expect(lexer, vec![","])?;
-            Ok(StateDistributionNode::Box(x, y, width, height, Box::new(parse_state(lexer)?)))
+            Ok(StateDistributionNode::Box(x, y, width, height, Box::new(parse_state(lexer, context)?)))
         }
     }
 }
 
-fn parse_transitions(lexer: &mut Lexer) -> Result<TransitionNode, String> {
+fn parse_transitions(lexer: &mut Lexer, context: &Context) -> Result<TransitionNode, CompileError> {
     let token = expect(lexer, vec!["(", "}"])?;
     if token == "(" {
         let initial_state_name = expect_identifier(lexer)?;
         expect(lexer, vec![","])?;
         let next_state_name = expect_identifier(lexer)?;
+        if next_state_name == "swap" {
+            let neighbor_cell = expect_neighbor_cell(lexer)?;
+            expect(lexer, vec![","])?;
+            return Ok(TransitionNode::Swap(initial_state_name, neighbor_cell, Box::new(parse_condition(lexer, context)?)));
+        }
+        if next_state_name == "vote" {
+            // The tie-break is optional, the cell keeps its state by default.
+            let tie_break = match expect(lexer, vec![",", "keep", "lowest", "random"])?.as_str() {
+                "," => TieBreak::Keep,
+                tie_break => {
+                    expect(lexer, vec![","])?;
+                    match tie_break {
+                        "keep" => TieBreak::Keep,
+                        "lowest" => TieBreak::Lowest,
+                        _ => TieBreak::Random
+                    }
+                }
+            };
+            return Ok(TransitionNode::Vote(initial_state_name, tie_break, Box::new(parse_condition(lexer, context)?)));
+        }
         expect(lexer, vec![","])?;
-        Ok(TransitionNode::Transition(initial_state_name, next_state_name, Box::new(parse_condition(lexer)?)))
+        Ok(TransitionNode::Transition(initial_state_name, next_state_name, Box::new(parse_condition(lexer, context)?)))
     }
     else {
         Ok(TransitionNode::End)
     }
 }
 
-fn parse_condition(lexer: &mut Lexer) -> Result<ConditionNode, String> {
+/// The words a condition can start with, besides the letters of the neighbor cells, the states and the data channel.
+pub static CONDITION_KEYWORDS: [&str; 5] = ["true", "rand", "was", "count9", "distance"];
+
+fn parse_condition(lexer: &mut Lexer, context: &Context) -> Result<ConditionNode, CompileError> {
     let token = lexer.get_next_token()?;
     if token.str == "true" {
-        Ok(ConditionNode::True(parse_next_condition(lexer)?))
+        Ok(ConditionNode::True(parse_next_condition(lexer, context)?))
     }
     else if token.str == "rand" {
         let proportion = expect_proportion(lexer)?;
-        Ok(ConditionNode::RandomCondition(proportion, parse_next_condition(lexer)?))
+        let token = lexer.get_next_token()?;
+        if token.str == "per" {
+            let draw = match expect(lexer, vec!["tick", "block"])?.as_str() {
+                "tick" => RandomDraw::Tick,
+                _ => RandomDraw::Block(expect_positive_usize(lexer)?)
+            };
+            Ok(ConditionNode::RandomCondition(proportion, draw, parse_next_condition(lexer, context)?))
+        }
+        else {
+            Ok(ConditionNode::RandomCondition(proportion, RandomDraw::Cell, continue_condition(token, lexer, context)?))
+        }
+    }
+    else if token.str == "was" {
+        let state_name = expect_identifier(lexer)?;
+        Ok(ConditionNode::PreviousStateCondition(state_name, parse_next_condition(lexer, context)?))
     }
     else if let Some(neighbor_cell) = to_neighbor_cell(&token) {
-        expect(lexer, vec!["is"])?;
+        let verb = expect(lexer, vec!["is", "was"])?;
         let state_name = expect_identifier(lexer)?;
-        Ok(ConditionNode::NeighborCondition(neighbor_cell, state_name, parse_next_condition(lexer)?))
+        if verb == "is" {
+            Ok(ConditionNode::NeighborCondition(neighbor_cell, state_name, parse_next_condition(lexer, context)?))
+        } else {
+            Ok(ConditionNode::PreviousNeighborCondition(neighbor_cell, state_name, parse_next_condition(lexer, context)?))
+        }
+    }
+    else if token.str == "count9" {
+        expect(lexer, vec!["("])?;
+        let state_name = expect_identifier(lexer)?;
+        expect(lexer, vec![")"])?;
+        let comparison_operator = expect_comparison_operator(lexer)?;
+        let number = expect_neighbor_number(lexer, context.neighbor_count + 1)?;
+        Ok(ConditionNode::QuantityCondition(state_name, comparison_operator, number, CountedCells::NeighborsAndSelf, parse_next_condition(lexer, context)?))
+    }
+    else if token.str == "distance" {
+        expect(lexer, vec!["("])?;
+        let state_name = expect_identifier(lexer)?;
+        expect(lexer, vec![")"])?;
+        let comparison_operator = expect_comparison_operator(lexer)?;
+        let distance = expect_u8(lexer)?;
+        Ok(ConditionNode::DistanceCondition(state_name, comparison_operator, distance, parse_next_condition(lexer, context)?))
+    }
+    else if context.data_channel.as_deref() == Some(token.str.as_str()) {
+        let comparison_operator = expect_comparison_operator(lexer)?;
+        let value = expect_u8(lexer)?;
+        Ok(ConditionNode::DataCondition(comparison_operator, value, parse_next_condition(lexer, context)?))
     }
     else if is_identifier(&token) {
         let comparison_operator = expect_comparison_operator(lexer)?;
-        let number = expect_neighbor_number(lexer)?;
-        Ok(ConditionNode::QuantityCondition(token.str, comparison_operator, number, parse_next_condition(lexer)?))
+        let number = expect_neighbor_number(lexer, context.neighbor_count)?;
+        Ok(ConditionNode::QuantityCondition(token.str, comparison_operator, number, CountedCells::Neighbors, parse_next_condition(lexer, context)?))
     }
     else {
-        Err(format!("Expected either token \"true\", token \"rand\", a neighbor cell identifier \
-            (one of \"A\", \"B\", \"C\", \"D\", \"E\", \"F\", \"H\"), or an alphanumeric identifier, but found {}.", token))
+        Err(syntax_error(&token, format!("Expected either token \"true\", token \"rand\", token \"was\", a neighbor cell identifier \
+            (one of {}), or an alphanumeric identifier, but found {}.", NeighborCell::letters_description(), token)))
     }
 }
 
-fn parse_next_condition(lexer: &mut Lexer) -> Result<NextConditionNode, String> {
+fn parse_next_condition(lexer: &mut Lexer, context: &Context) -> Result<NextConditionNode, CompileError> {
     let token = lexer.get_next_token()?;
+    continue_condition(token, lexer, context)
+}
+
+/// Parses what follows a condition, from its first token.
+fn continue_condition(token: Token, lexer: &mut Lexer, context: &Context) -> Result<NextConditionNode, CompileError> {
     if let Some(boolean_operator) = to_boolean_operator(&token) {
-        Ok(NextConditionNode::NextCondition(boolean_operator, Box::new(parse_condition(lexer)?)))
+        Ok(NextConditionNode::NextCondition(boolean_operator, Box::new(parse_condition(lexer, context)?)))
     }
     else if token.str == "," {
-        expect(lexer, vec!["delay"])?;
-        let delay = expect_delay(lexer)?;
-        expect(lexer, vec![")"])?;
+        let annotations = parse_transition_annotations(lexer, context)?;
         expect(lexer, vec![","])?;
-        Ok(NextConditionNode::NextTransition(Some(delay), Box::new(parse_transitions(lexer)?)))
+        Ok(NextConditionNode::NextTransition(annotations, Box::new(parse_transitions(lexer, context)?)))
     }
     else if token.str == ")" {
         expect(lexer, vec![","])?;
-        Ok(NextConditionNode::NextTransition(None, Box::new(parse_transitions(lexer)?)))
+        Ok(NextConditionNode::NextTransition(TransitionAnnotations::default(), Box::new(parse_transitions(lexer, context)?)))
     }
     else {
-        Err(format!("Expected either a boolean operator, a \",\" or a \")\" token, found {}.", token))
+        Err(syntax_error(&token, format!("Expected either a boolean operator, a \",\" or a \")\" token, found {}.", token)))
+    }
+}
+
+/// Parses the agent rules, after the "agents" keyword.
+fn parse_agent_rules(lexer: &mut Lexer) -> Result<Vec<AgentRuleNode>, CompileError> {
+    let mut rules = Vec::new();
+    expect(lexer, vec!["{"])?;
+    while expect(lexer, vec!["(", "}"])? == "(" {
+        let cell_state = expect_identifier(lexer)?;
+        expect(lexer, vec![","])?;
+        let turn = match expect(lexer, vec!["left", "right", "back", "none"])?.as_str() {
+            "left" => Turn::Left,
+            "right" => Turn::Right,
+            "back" => Turn::Back,
+            _ => Turn::None
+        };
+        expect(lexer, vec![","])?;
+        let new_cell_state = expect_identifier(lexer)?;
+        let mut rule = AgentRuleNode { cell_state, turn, new_cell_state, mode: None, next_mode: None };
+        while expect(lexer, vec![",", ")"])? == "," {
+            if expect(lexer, vec!["mode", "next"])? == "mode" {
+                rule.mode = Some(expect_usize(lexer)?);
+            } else {
+                rule.next_mode = Some(expect_usize(lexer)?);
+            }
+        }
+        expect(lexer, vec![","])?;
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+/// Parses the cells of the pattern, after the "pattern" keyword, like "(10, 5) alive,".
+fn parse_initial_cells(lexer: &mut Lexer) -> Result<Vec<CellNode>, CompileError> {
+    let mut cells = Vec::new();
+    expect(lexer, vec!["{"])?;
+    while expect(lexer, vec!["(", "}"])? == "(" {
+        let x = expect_usize(lexer)?;
+        expect(lexer, vec![","])?;
+        let y = expect_usize(lexer)?;
+        expect(lexer, vec![")"])?;
+        cells.push(((x, y), expect_identifier(lexer)?));
+        expect(lexer, vec![","])?;
+    }
+    Ok(cells)
+}
+
+/// Parses the annotations of a transition, until the closing parenthesis of the transition.
+fn parse_transition_annotations(lexer: &mut Lexer, context: &Context) -> Result<TransitionAnnotations, CompileError> {
+    let mut annotations = TransitionAnnotations::default();
+    loop {
+        let token = lexer.get_next_token()?;
+        match token.str.as_str() {
+            "delay" if annotations.delay.is_none() => {
+                annotations.delay = Some(expect_delay(lexer)?);
+            },
+            "priority" if annotations.priority.is_none() => {
+                annotations.priority = Some(expect_usize(lexer)?);
+            },
+            "set" if annotations.set_data.is_none() && context.data_channel.is_some() => {
+                expect(lexer, context.data_channel.as_deref().into_iter().collect())?;
+                annotations.set_data = Some(expect_u8(lexer)?);
+            },
+            "effect" => {
+                annotations.effects.push(parse_effect(lexer)?);
+            },
+            "delay" | "priority" => {
+                return Err(syntax_error(&token, format!("The transition annotation {} is specified twice.", token)));
+            },
+            "set" if context.data_channel.is_some() => {
+                return Err(syntax_error(&token, format!("The transition annotation {} is specified twice.", token)));
+            },
+            _ => {
+                return Err(syntax_error(&token, format!("Expected \"delay\" or \"priority\" or \"effect\"{}, found {}.",
+                    if context.data_channel.is_some() { " or \"set\"" } else { "" }, token)));
+            }
+        }
+        if expect(lexer, vec![",", ")"])? == ")" {
+            return Ok(annotations);
+        }
     }
 }
 
+/// Parses an effect annotation such as "effect G becomes fire", after the "effect" keyword.
+fn parse_effect(lexer: &mut Lexer) -> Result<(NeighborCell, String), CompileError> {
+    let neighbor_cell = expect_neighbor_cell(lexer)?;
+    expect(lexer, vec!["becomes"])?;
+    Ok((neighbor_cell, expect_identifier(lexer)?))
+}
+
+/// Return the next token translated into a neighbor cell if possible, or raises an error.
+fn expect_neighbor_cell(lexer: &mut Lexer) -> Result<NeighborCell, CompileError> {
+    let token = lexer.get_next_token()?;
+    to_neighbor_cell(&token).ok_or_else(|| syntax_error(&token, format!(
+        "Expected a neighbor cell identifier (one of {}), found {}.", NeighborCell::letters_description(), token)))
+}
+
 /// Return the next token if it's one of the expected tokens, or raises an error.
-fn expect(lexer: &mut Lexer, expected: Vec<&str>) -> Result<String, String> {
+fn expect(lexer: &mut Lexer, expected: Vec<&str>) -> Result<String, CompileError> {
+    check(lexer.get_next_token()?, expected)
+}
+
+/// Return the token if it's one of the expected tokens, or raises an error.
+fn check(token: Token, expected: Vec<&str>) -> Result<String, CompileError> {
     let mut expected_as_sentence = String::new();
-    let token = lexer.get_next_token()?;
     for (i, item) in expected.iter().enumerate() {
         if token.str == *item {
             return Ok(token.str);
@@ -208,103 +754,111 @@ fn expect(lexer: &mut Lexer, expected: Vec<&str>) -> Result<String, String> {
         if i != 0 {
             expected_as_sentence.push_str(" or ");
         }
-        expected_as_sentence.push_str("\"");
+        expected_as_sentence.push('"');
         expected_as_sentence.push_str(item);
-        expected_as_sentence.push_str("\"");
+        expected_as_sentence.push('"');
     }
-    Err(format!("Expected {}, found {}.", expected_as_sentence, token))
+    Err(syntax_error(&token, format!("Expected {}, found {}.", expected_as_sentence, token)))
 }
 
 /// Return the next token if it's an alphanumeric identifier, or raises an error.
-fn expect_identifier(lexer: &mut Lexer) -> Result<String, String> {
+fn expect_identifier(lexer: &mut Lexer) -> Result<String, CompileError> {
     let token = lexer.get_next_token()?;
     if is_identifier(&token) {
         Ok(token.str)
     }
     else {
-        Err(format!("Expected an alphanumeric identifier, found {}.", token))
+        Err(syntax_error(&token, format!("Expected an alphanumeric identifier, found {}.", token)))
+    }
+}
+
+/// Returns the content of a string token, without its double quotes.
+/// Returns the text between the double quotes of the token. `what` tells what the text is, like "a file path".
+fn expect_string(lexer: &mut Lexer, what: &str) -> Result<String, CompileError> {
+    let token = lexer.get_next_token()?;
+    if token.str.starts_with('"') {
+        Ok(token.str[1..token.str.len() - 1].to_string())
+    }
+    else {
+        Err(syntax_error(&token, format!("Expected {} between double quotes, found {}.", what, token)))
     }
 }
 
 fn is_identifier(token: & Token) -> bool {
-    token.str.chars().all(|c| c.is_ascii_alphanumeric())
+    token.str.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
         && !token.str.chars().all(|c| c.is_ascii_digit())
 }
 
 /// Return the next token translated into an integer between 0 and 255 if possible, or raises an error.
-fn expect_u8(lexer: &mut Lexer) -> Result<u8, String> {
-    let token = lexer.get_next_token()?;
+fn expect_u8(lexer: &mut Lexer) -> Result<u8, CompileError> {
+    to_u8(lexer.get_next_token()?)
+}
+
+fn to_u8(token: Token) -> Result<u8, CompileError> {
     match token.str.parse::<u8>() {
         Ok(number) => Ok(number),
-        Err(_) => Err(format!("Expected an integer between 0 and 255, found {}.", token))
+        Err(_) => Err(syntax_error(&token, format!("Expected an integer between 0 and 255, found {}.", token)))
     }
 }
 
-/// Return the next token translated into an integer between 0 and 8 if possible, or raises an error.
-fn expect_neighbor_number(lexer: &mut Lexer) -> Result<u8, String> {
+/// Return the next token translated into an integer between 0 and the number of neighbors if possible, or raises an error.
+fn expect_neighbor_number(lexer: &mut Lexer, neighbor_count: usize) -> Result<u8, CompileError> {
     let token = lexer.get_next_token()?;
     if let Ok(number) = token.str.parse::<u8>() {
-        if number <= 8 {
+        if number as usize <= neighbor_count {
             return Ok(number);
         }
     }
-    Err(format!("Expected an integer between 0 and 8, found {}.", token))
+    Err(syntax_error(&token, format!("Expected an integer between 0 and {}, found {}.", neighbor_count.min(u8::MAX as usize), token)))
 }
 
 /// Return the next token translated into a floating number between 0 and 1 if possible, or raises an error.
-fn expect_proportion(lexer: &mut Lexer) -> Result<f64, String> {
+fn expect_proportion(lexer: &mut Lexer) -> Result<f64, CompileError> {
     let token = lexer.get_next_token()?;
     if let Ok(number) = token.str.parse::<f64>() {
-        if number >= 0.0 && number <= 1.0 {
+        if (0.0..=1.0).contains(&number) {
             return Ok(number)
         }
     }
-    Err(format!("Expected a floating number between 0 and 1, found {}.", token))
+    Err(syntax_error(&token, format!("Expected a floating number between 0 and 1, found {}.", token)))
 }
 
 /// Return the next token translated into an unsigned integer if possible, or raises an error.
-fn expect_usize(lexer: &mut Lexer) -> Result<usize, String> {
+fn expect_usize(lexer: &mut Lexer) -> Result<usize, CompileError> {
     let token = lexer.get_next_token()?;
     match token.str.parse::<usize>() {
         Ok(number) => Ok(number),
-        Err(_) => Err(format!("Expected an unsigned integer, found {}.", token))
+        Err(_) => Err(syntax_error(&token, format!("Expected an unsigned integer, found {}.", token)))
     }
 }
 
 /// Return the next token translated into a strictly positive unsigned integer if possible, or raises an error.
-fn expect_positive_usize(lexer: &mut Lexer) -> Result<usize, String> {
+fn expect_positive_usize(lexer: &mut Lexer) -> Result<usize, CompileError> {
     let token = lexer.get_next_token()?;
     if let Ok(number) = token.str.parse::<usize>() {
         if number > 0 {
             return Ok(number);
         }
     }
-    Err(format!("Expected an unsigned integer > 0, found {}.", token))
+    Err(syntax_error(&token, format!("Expected an unsigned integer > 0, found {}.", token)))
 }
 
 /// Return the next token translated into a integer > 1 if possible, or raises an error.
-fn expect_delay(lexer: &mut Lexer) -> Result<usize, String> {
+fn expect_delay(lexer: &mut Lexer) -> Result<usize, CompileError> {
     let token = lexer.get_next_token()?;
     if let Ok(number) = token.str.parse::<usize>() {
         if number > 1 {
             return Ok(number);
         }
     }
-    Err(format!("Expected an integer greater than 1, found {}.", token))
+    Err(syntax_error(&token, format!("Expected an integer greater than 1, found {}.", token)))
 }
 
 /// Return a comparison operator if the next token represents one, or raises an error.
-fn expect_comparison_operator(lexer: &mut Lexer) -> Result<ComparisonOperator, String> {
+fn expect_comparison_operator(lexer: &mut Lexer) -> Result<ComparisonOperator, CompileError> {
     let token = lexer.get_next_token()?;
-    match token.str.as_str() {
-        "<" => Ok(ComparisonOperator::Lesser),
-        ">" => Ok(ComparisonOperator::Greater),
-        "<=" => Ok(ComparisonOperator::LesserOrEqual),
-        ">=" => Ok(ComparisonOperator::GreaterOrEqual),
-        "==" => Ok(ComparisonOperator::Equal),
-        "!=" => Ok(ComparisonOperator::Different),
-        _ => Err(format!("Expected one of \"<\", \">\", \"<=\", \">=\", \"==\", or \"!=\" tokens, found {}.", token))
-    }
+    ComparisonOperator::from_symbol(&token.str)
+        .ok_or_else(|| syntax_error(&token, format!("Expected one of \"<\", \">\", \"<=\", \">=\", \"==\", or \"!=\" tokens, found {}.", token)))
 }
 
 /// Translate the token into a boolean operator, if possible.
@@ -316,25 +870,22 @@ fn to_boolean_operator(token: & Token) -> Option<BooleanOperator> {
     }
 }
 
+fn syntax_error(token: &Token, message: String) -> CompileError {
+    CompileError::Syntax { message, span: token.span() }
+}
+
 /// Translate the token into a neighbor cell identifier, if possible.
 fn to_neighbor_cell(token: & Token) -> Option<NeighborCell> {
-    match token.str.as_str() {
-        "A" => Some(NeighborCell::A),
-        "B" => Some(NeighborCell::B),
-        "C" => Some(NeighborCell::C),
-        "D" => Some(NeighborCell::D),
-        "E" => Some(NeighborCell::E),
-        "F" => Some(NeighborCell::F),
-        "G" => Some(NeighborCell::G),
-        "H" => Some(NeighborCell::H),
-        _ => None
-    }
+    NeighborCell::from_letter(&token.str)
 }
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests {
+    use crate::compiler::error::{CompileError, Span};
     use crate::compiler::parser::parse;
 
+    static ANNOTATION_TWICE_FILE: &str = "resources/tests/parser_annotation_twice.txt";
     static BENCHMARK_FILE: &str = "resources/tests/compiler_benchmark.txt";
     static NON_EXISTING_FILE: &str = "resources/tests/does_not_exist.txt";
     static COND_ERROR_FILE: &str = "resources/tests/parser_condition_error.txt";
@@ -349,6 +900,15 @@ mod tests {
     static EXPECT_USIZE_FILE: &str = "resources/tests/parser_expected_usize.txt";
     static NEXT_COND_ERROR_FILE: &str = "resources/tests/parser_next_condition_error.txt";
     static NO_STATES_FILE: &str = "resources/tests/parser_no_states_keyword.txt";
+    static NEIGHBORHOOD_QUANTITY_FILE: &str = "resources/tests/parser_neighborhood_quantity.txt";
+
+    #[test]
+    fn parse_annotation_twice_fails() {
+        match parse(ANNOTATION_TWICE_FILE) {
+            Err(error) => assert_eq!(error, "The transition annotation \"priority\" - line 9, column 71 is specified twice."),
+            _ => assert!(false)
+        }
+    }
 
     #[test]
     fn parse_benchmark_succeeds() {
@@ -362,8 +922,8 @@ mod tests {
     fn parse_non_existing_file_fails() {
          match parse(NON_EXISTING_FILE) {
             Err(error) => {
-                assert!(error.contains("Cannot parse file resources/tests/does_not_exist.txt. Cause : "));
-                assert!(error.contains("No such file or directory"));
+                assert!(error.message().contains("Cannot parse file resources/tests/does_not_exist.txt. Cause : "));
+                assert!(error.message().contains("No such file or directory"));
             },
             _ => assert!(false)
         }
@@ -372,8 +932,9 @@ mod tests {
     #[test]
     fn parse_condition_error_fails() {
         match parse(COND_ERROR_FILE) {
-            Err(error) => assert_eq!(error, "Expected either token \"true\", token \"rand\", a neighbor cell identifier \
-            (one of \"A\", \"B\", \"C\", \"D\", \"E\", \"F\", \"H\"), or an alphanumeric identifier, but found \"3153\" - line 9, column 22."),
+            Err(error) => assert_eq!(error, "Expected either token \"true\", token \"rand\", token \"was\", a neighbor cell identifier \
+            (one of \"A\" (up left), \"B\" (up), \"C\" (up right), \"D\" (left), \"E\" (right), \"F\" (down left), \"G\" (down), \"H\" (down right)), \
+            or an alphanumeric identifier, but found \"3153\" - line 9, column 22."),
             _ => assert!(false)
         }
     }
@@ -381,7 +942,10 @@ mod tests {
     #[test]
     fn parse_expect_comp_operator_fails() {
         match parse(EXPECT_COMP_OP_FILE) {
-            Err(error) => assert_eq!(error, "Expected one of \"<\", \">\", \"<=\", \">=\", \"==\", or \"!=\" tokens, found \"plouf\" - line 9, column 29."),
+            Err(error) => {
+                assert_eq!(error, "Expected one of \"<\", \">\", \"<=\", \">=\", \"==\", or \"!=\" tokens, found \"plouf\" - line 9, column 29.");
+                assert!(matches!(error, CompileError::Syntax { span: Span { line: 9, column: 25, length: 5 }, .. }));
+            },
             _ => assert!(false)
         }
     }
@@ -405,7 +969,7 @@ mod tests {
     #[test]
     fn parse_expect_is_token_fails() {
          match parse(EXPECT_IS_FILE) {
-            Err(error) => assert_eq!(error, "Expected \"is\", found \"plouf\" - line 10, column 39."),
+            Err(error) => assert_eq!(error, "Expected \"is\" or \"was\", found \"plouf\" - line 10, column 39."),
             _ => assert!(false)
         }
     }
@@ -450,6 +1014,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_neighborhood_quantity_fails() {
+        match parse(NEIGHBORHOOD_QUANTITY_FILE) {
+            Err(error) => assert_eq!(error, "Expected an integer between 0 and 4, found \"5\" - line 11, column 34."),
+            _ => assert!(false)
+        }
+    }
+
     #[test]
     fn parse_next_condition_error_fails() {
          match parse(NEXT_COND_ERROR_FILE) {
@@ -461,7 +1033,7 @@ mod tests {
     #[test]
     fn parse_no_states_keyword_fails() {
          match parse(NO_STATES_FILE) {
-            Err(error) => assert_eq!(error, "Expected \"states\", found \"plouf\" - line 3, column 5."),
+            Err(error) => assert_eq!(error, "Expected \"states\" or \"second_order\" or \"memory\" or \"noise\" or \"frozen\" or \"source\" or \"data\" or \"agent\" or \"boundary\" or \"neighborhood\" or \"init\" or \"title\" or \"author\" or \"description\", found \"plouf\" - line 3, column 5."),
             _ => assert!(false)
         }
     }
@@ -0,0 +1,72 @@
+//! This module renames a state in the text of the rules, from the tokens of the lexer rather than with a find-and-replace :
+//! the comments, the strings, the neighbor letters and the data channel are left as they are, even if they have the name.
+
+use crate::compiler::error::CompileError;
+use crate::compiler::lexer::{Lexer, Token};
+
+/// Returns the text of the rules with the state renamed everywhere it is referred to : its declaration, the transitions,
+/// the conditions, the effects, the directives and the agent rules. The state is expected to be declared by the rules.
+pub fn rename_state(text: &str, old_name: &str, new_name: &str) -> Result<String, CompileError> {
+    let mut lexer = Lexer::from_text(text);
+    let mut tokens: Vec<Token> = Vec::new();
+    loop {
+        let token = lexer.get_next_token()?;
+        if token.str.is_empty() {
+            break;
+        }
+        tokens.push(token);
+    }
+    let mut lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+    // The tokens of a line are replaced from the last one, so that the columns of the others are still right.
+    for (index, token) in tokens.iter().enumerate().rev() {
+        let previous = index.checked_sub(1).map(|index| tokens[index].str.as_str());
+        let next = tokens.get(index + 1).map(|token| token.str.as_str());
+        // A neighbor letter comes before "is" or "was", or after "effect" or "swap", and the data channel after "data" or "set".
+        let is_state = token.str == old_name
+            && !matches!(next, Some("is") | Some("was"))
+            && !matches!(previous, Some("effect") | Some("swap") | Some("data") | Some("set"));
+        if is_state {
+            // The column is the one of the last byte of the token, starting at 1, as the lexer reads the text byte by byte.
+            let end = token.column as usize;
+            lines[token.line as usize - 1].replace_range(end - token.str.len()..end, new_name);
+        }
+    }
+    Ok(lines.join("\n") + if text.ends_with('\n') { "\n" } else { "" })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::compiler::rename::rename_state;
+    use crate::compiler::semantic::parse_str;
+
+    #[test]
+    fn rename_state_everywhere_it_is_referred_to() {
+        let text = fs::read_to_string("resources/virus.txt").unwrap();
+        let renamed = rename_state(&text, "infected", "sick").unwrap();
+        // The description keeps the name, it is a string.
+        let description = text.lines().find(|line| line.starts_with("description")).unwrap();
+        assert_eq!(renamed.lines().find(|line| line.starts_with("description")), Some(description));
+        assert_eq!(renamed.matches("sick").count(), text.matches("infected").count() - description.matches("infected").count());
+        let rules = parse_str(&renamed).unwrap();
+        assert_eq!(rules.user_states()[1].name, "sick");
+
+        // The neighbor letters, the comments and the strings keep the name.
+        let text = "size (10, 10)\n\
+                    title \"The G cells\"\n\
+                    states {\n    (empty, 0, 0, 0), // G falls\n    (G, 255, 200, 100, quantity 10),\n}\n\
+                    transitions {\n    (G, swap G, G is empty && empty > 2),\n    (empty, G, G is G, effect G becomes G),\n}\n";
+        assert_eq!(rename_state(text, "G", "sand").unwrap(), "size (10, 10)\n\
+                    title \"The G cells\"\n\
+                    states {\n    (empty, 0, 0, 0), // G falls\n    (sand, 255, 200, 100, quantity 10),\n}\n\
+                    transitions {\n    (sand, swap G, G is empty && empty > 2),\n    (empty, sand, G is sand, effect G becomes sand),\n}\n");
+    }
+
+    #[test]
+    fn rename_state_after_non_ascii_characters() {
+        let text = "states {\n    (dead, 0, 0, 0),\n    (alive, 255, 255, 255),\n}\n\
+                    transitions {\n    /* née */ (dead, alive, alive == 3),\n    (alive, dead, alive < 2 || alive > 3), // déjà mort\n}\n";
+        assert_eq!(rename_state(text, "alive", "living").unwrap(), "states {\n    (dead, 0, 0, 0),\n    (living, 255, 255, 255),\n}\n\
+                    transitions {\n    /* née */ (dead, living, living == 3),\n    (living, dead, living < 2 || living > 3), // déjà mort\n}\n");
+    }
+}
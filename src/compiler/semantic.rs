@@ -1,13 +1,24 @@
-/// This module provides semantic analysis functions
+//! This module provides semantic analysis functions
 
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use crate::compiler::error::CompileError;
 use crate::compiler::parser;
 use crate::compiler::parser::*;
+use crate::compiler::merge::merge;
+use crate::compiler::messages::Catalog;
+use crate::automaton::BoundaryMode;
+use crate::interop::load_image;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum StateDistribution {
     Proportion(f64),
     Quantity(usize),
     Box(usize, usize, usize, usize),
+    Circle(usize, usize, usize), // The disc of the cells whose distance to the center is at most the radius
+    Line(usize, usize, usize, usize), // The cells of the segment between the two ends
+    Image(String), // The path of a PNG file of the size of the world, whose pixels of the color of the state are its cells
     Default
 }
 
@@ -16,6 +27,7 @@ pub struct State {
     pub id: usize,
     pub name: String,
     pub color: (u8, u8, u8), // 16M color
+    pub gradient: Option<Gradient>, // If set, the color fades to another one while the cells stay in the state
     pub distribution: StateDistribution
 }
 
@@ -30,16 +42,235 @@ pub struct Rules {
     pub world_size: (usize, usize),
     pub states: Vec<State>,
     pub transitions: Vec<Transition>,
-    pub implicit_state_ranges: Vec<Option<ImplicitStateRange>>
+    pub implicit_state_ranges: Vec<Vec<ImplicitStateRange>>, // The ranges of the implicit states of each state, one per delayed transition
+    pub second_order: bool, // If true, the next state of a cell also depends on its previous state, which makes the automaton reversible
+    pub memory: bool, // If true, the conditions can refer to the previous iteration
+    pub noise: f64, // Probability for each cell to be flipped to a random state after each tick
+    pub regions: Vec<Region>,
+    pub data_channel: Option<String>, // Name of the auxiliary value stored in each cell, if declared
+    pub agents: Vec<Agent>, // The agents at the first iteration
+    pub agent_rules: Vec<AgentRule>,
+    pub boundary: BoundaryMode, // How the cells at the borders see the neighbors outside of the world
+    pub neighborhood: Neighborhood, // The cells counted by the quantity conditions
+    pub neighbor_offsets: Vec<(isize, isize, isize)>, // The positions of the neighbors relative to the cell, computed from the neighborhood
+    pub depth: usize, // The number of layers of a 3D world, 1 for a 2D world
+    pub initial_grid: Option<Vec<usize>>, // If set, the state of each cell at the first iteration, row after row and layer after layer, instead of the distributions
+    pub initial_cells: Vec<((usize, usize), usize)>, // The cells placed at the first iteration over the distributions, in the order of the file
+    pub metadata: Vec<(String, String)>, // The title, author and description of the rules, in the order of the file
+    pub warnings: Vec<String>
+}
+
+impl Rules {
+    /// Returns the index of the default state, the one without a distribution specified.
+    pub fn default_state(&self) -> usize {
+        self.states.iter()
+            .find(|s| matches!(s.distribution, StateDistribution::Default))
+            .unwrap().id
+    }
+
+    /// Returns the width and the height of the grid. The layers of a 3D world are stacked in the grid, the first layer at the top.
+    pub fn grid_size(&self) -> (usize, usize) {
+        (self.world_size.0, self.world_size.1 * self.depth)
+    }
+
+    /// Returns the states declared by the user, without the implicit states created for delayed transitions.
+    pub fn user_states(&self) -> &[State] {
+        &self.states[..self.implicit_state_ranges.len()]
+    }
+
+    /// Returns the user-visible state that an implicit state belongs to, or the state itself if it's not implicit.
+    pub fn parent_state(&self, state: usize) -> usize {
+        self.implicit_state_ranges.iter()
+            .position(|ranges| ranges.iter().any(|range| state >= range.start && state < range.len))
+            .unwrap_or(state)
+    }
+
+    /// Returns the states whose distance field is needed by the conditions.
+    pub fn distance_states(&self) -> Vec<usize> {
+        let mut distance_states: Vec<usize> = self.transitions.iter()
+            .flat_map(|(_, _, conditions, _)| conditions.iter().flatten())
+            .filter_map(|c| match c {
+                Condition::DistanceCondition(state, _, _) => Some(*state),
+                _ => None
+            })
+            .collect();
+        distance_states.sort_unstable();
+        distance_states.dedup();
+        distance_states
+    }
+
+    /// Returns the proportion of cells that are left to the default state once the proportion distributions are applied.
+    pub fn default_proportion(&self) -> f64 {
+        1.0 - proportions_sum(&self.states)
+    }
+
+    /// Returns the colors the cells are shown with : the color of each state, then the colors of the gradients,
+    /// a color per tick of age for each state that has a gradient.
+    pub fn colors(&self) -> Vec<(u8, u8, u8)> {
+        let mut colors: Vec<_> = self.states.iter().map(|s| s.color).collect();
+        for state in &self.states {
+            if let Some(gradient) = state.gradient {
+                colors.extend((1..=gradient.ticks).map(|age| interpolate_color(state.color, gradient.end, age as f64 / gradient.ticks as f64)));
+            }
+        }
+        colors
+    }
+
+    /// Returns the number of colors, see `colors`.
+    pub fn color_count(&self) -> usize {
+        self.states.len() + self.states.iter().filter_map(|s| s.gradient).map(|g| g.ticks).sum::<usize>()
+    }
+
+    /// Returns the index in the colors of a cell in the state for the given number of ticks.
+    pub fn color_index(&self, state: usize, age: usize) -> usize {
+        match self.states[state].gradient {
+            Some(gradient) if age > 0 => {
+                let previous_ticks: usize = self.states[..state].iter().filter_map(|s| s.gradient).map(|g| g.ticks).sum();
+                self.states.len() + previous_ticks + age.min(gradient.ticks) - 1
+            },
+            _ => state
+        }
+    }
+
+    /// Returns true if a state has a gradient, so that the age of the cells is needed.
+    pub fn has_gradients(&self) -> bool {
+        self.states.iter().any(|s| s.gradient.is_some())
+    }
+}
+
+/// Options that tune the semantic analysis.
+pub struct CompilerOptions {
+    /// A warning is emitted if the proportion left to the default state is lower than this value.
+    pub default_proportion_warning_threshold: f64,
+    /// If true, the colors of the implicit states created by a delayed transition are interpolated
+    /// from the origin state's color toward the destination state's color.
+    pub delay_color_gradient: bool,
+    /// If set, the errors and warnings are translated with this catalog.
+    pub catalog: Option<Catalog>,
+    /// If true, the warnings are errors, except those of the lints explicitly allowed.
+    pub deny_warnings: bool,
+    /// The level of some lints, the last level given for a lint wins.
+    pub lint_levels: Vec<(Lint, LintLevel)>,
+    /// If set, the world has this size instead of the size declared by the rules.
+    pub world_size: Option<(usize, usize)>
+}
+
+impl CompilerOptions {
+    pub fn lint_level(&self, lint: Lint) -> LintLevel {
+        match self.lint_levels.iter().rev().find(|(other, _)| *other == lint) {
+            Some((_, level)) => *level,
+            None if self.deny_warnings => LintLevel::Deny,
+            None => LintLevel::Warn
+        }
+    }
 }
 
-pub type Transition = (usize, usize, Vec<Vec<Condition>>);
+impl Default for CompilerOptions {
+    fn default() -> CompilerOptions {
+        CompilerOptions {
+            default_proportion_warning_threshold: 0.01,
+            delay_color_gradient: false,
+            catalog: None,
+            deny_warnings: false,
+            lint_levels: Vec::new(),
+            world_size: None
+        }
+    }
+}
+
+/// The kinds of warnings of the semantic analysis, which can be allowed or denied by name.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lint {
+    SmallDefaultProportion,
+    OverlappingTransitions,
+    ImpossibleCondition,
+    ShadowedTransition,
+    NeighborOutsideNeighborhood,
+    SmallWorld
+}
+
+impl Lint {
+    pub const ALL: [Lint; 6] = [Lint::SmallDefaultProportion, Lint::OverlappingTransitions, Lint::ImpossibleCondition, Lint::ShadowedTransition,
+                                Lint::NeighborOutsideNeighborhood, Lint::SmallWorld];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::SmallDefaultProportion => "small-default-proportion",
+            Lint::OverlappingTransitions => "overlapping-transitions",
+            Lint::ImpossibleCondition => "impossible-condition",
+            Lint::ShadowedTransition => "shadowed-transition",
+            Lint::NeighborOutsideNeighborhood => "neighbor-outside-neighborhood",
+            Lint::SmallWorld => "small-world"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+}
+
+/// What is done with the warnings of a lint : they are dropped, kept as warnings, or turned into errors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny
+}
+
+/// A rectangular area of the grid whose cells are not updated by the transitions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub kind: RegionKind
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegionKind {
+    Frozen, // The cells keep their state
+    Source(usize) // The cells are reset to this state at each tick
+}
+
+/// A mobile agent that moves on the grid and interacts with the cells, like Langton's ant.
+#[derive(Clone, Copy, Debug, PartialEq, Hash)]
+pub struct Agent {
+    pub position: (usize, usize),
+    pub heading: Heading,
+    pub mode: usize // The internal state of the agent, used by turmites
+}
+
+#[derive(Clone, Debug)]
+pub struct AgentRule {
+    pub cell_state: usize,
+    pub turn: Turn,
+    pub new_cell_state: usize,
+    pub mode: Option<usize>, // The rule applies to the agents in any mode if not specified
+    pub next_mode: Option<usize>
+}
+
+/// The origin state, the destination state, the conditions as a disjunction of conjunctions, and the effects.
+pub type Transition = (usize, usize, Vec<Vec<Condition>>, Effects);
+
+/// What a transition does besides changing the state of the cell.
+#[derive(Clone, Debug, Default)]
+pub struct Effects {
+    pub set_data: Option<u8>, // The value written in the data channel of the cell
+    pub neighbors: Vec<(NeighborCell, usize)>, // The states written into neighbors, after all the cells are updated
+    pub swap: Option<NeighborCell>, // The neighbor the cell exchanges its state with, after all the cells are updated
+    pub vote: Option<TieBreak> // If set, the cell takes the most common state of its neighbors instead of the destination state
+}
 
 #[derive(Clone, Debug)]
 pub enum Condition {
-    QuantityCondition(usize, ComparisonOperator, u8),
+    QuantityCondition(usize, ComparisonOperator, u8, CountedCells),
+    DataCondition(ComparisonOperator, u8), // Compares the value in the data channel of the cell
+    DistanceCondition(usize, ComparisonOperator, u8), // Compares the distance to the closest cell in the state
     NeighborCondition(NeighborCell, usize),
-    RandomCondition(f64),
+    PreviousNeighborCondition(NeighborCell, usize), // The state of the neighbor at the previous iteration
+    PreviousStateCondition(usize), // The state of the cell itself at the previous iteration
+    RandomCondition(f64, RandomDraw),
     True
 }
 
@@ -47,32 +278,153 @@ pub enum Condition {
 ///
 /// If it finds a lexical or syntax error, the parsing is stopped and the error is returned.
 /// Otherwise, it performs a semantic analysis. If the semantic analysis fails, returns the list of semantic errors.
-pub fn parse(file_name: &str) -> Result<Rules, Vec<String>> {
-    match parser::parse(file_name) {
-        Ok(ast) => semantic_analysis(&ast),
+pub fn parse(file_name: &str) -> Result<Rules, Vec<CompileError>> {
+    parse_with_options(file_name, &CompilerOptions::default())
+}
+
+/// Same as `parse`, but the semantic analysis is tuned with the given options.
+pub fn parse_with_options(file_name: &str, options: &CompilerOptions) -> Result<Rules, Vec<CompileError>> {
+    let result = match parser::parse(file_name) {
+        Ok(ast) => semantic_analysis(&ast, options),
+        Err(error) => Err(vec![error])
+    };
+    translate(result, options)
+}
+
+/// Same as `parse`, but the rules are read from the text, like rules embedded in the code.
+pub fn parse_str(text: &str) -> Result<Rules, Vec<CompileError>> {
+    parse_str_with_options(text, &CompilerOptions::default())
+}
+
+pub fn parse_str_with_options(text: &str, options: &CompilerOptions) -> Result<Rules, Vec<CompileError>> {
+    let result = match parser::parse_str(text) {
+        Ok(ast) => semantic_analysis(&ast, options),
         Err(error) => Err(vec![error])
+    };
+    translate(result, options)
+}
+
+/// Same as `parse`, but the rules are read until the end of the reader, like the standard input or a network stream.
+pub fn parse_reader(mut reader: impl Read, options: &CompilerOptions) -> Result<Rules, Vec<CompileError>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text).map_err(|error| vec![CompileError::Io { message: format!("Cannot read the rules. Cause : {:?}", error) }])?;
+    parse_str_with_options(&text, options)
+}
+
+/// Same as `parse_with_options`, but the rules of the files are merged, each file overriding the previous ones (see `merge`).
+/// The errors come with the file they were found in, or without a file for the errors of the merged rules.
+pub fn parse_files_with_options<'a>(file_names: &[&'a str], options: &CompilerOptions) -> Result<Rules, (Option<&'a str>, Vec<CompileError>)> {
+    let mut merged: Option<Ast> = None;
+    for file_name in file_names {
+        let ast = parser::parse(file_name).map_err(|error| (Some(*file_name), translate_errors(vec![error], options)))?;
+        merged = Some(match merged {
+            Some(base) => merge(base, ast).map_err(|errors| (None, translate_errors(errors, options)))?,
+            None => ast
+        });
+    }
+    match merged {
+        Some(ast) => analyze(&ast, options).map_err(|errors| (file_names.first().filter(|_| file_names.len() == 1).copied(), errors)),
+        None => Err((None, vec![CompileError::Io { message: "No rules file to parse.".to_string() }]))
+    }
+}
+
+/// Checks an AST built without a file, like by the rules builder, and creates the rules.
+pub fn analyze(ast: &Ast, options: &CompilerOptions) -> Result<Rules, Vec<CompileError>> {
+    translate(semantic_analysis(ast, options), options)
+}
+
+/// Translates the errors and the warnings with the catalog of the options, if any.
+fn translate(result: Result<Rules, Vec<CompileError>>, options: &CompilerOptions) -> Result<Rules, Vec<CompileError>> {
+    match &options.catalog {
+        Some(catalog) => match result {
+            Ok(mut rules) => {
+                rules.warnings = rules.warnings.iter().map(|warning| catalog.translate(warning)).collect();
+                Ok(rules)
+            },
+            Err(errors) => Err(translate_errors(errors, options))
+        },
+        None => result
+    }
+}
+
+fn translate_errors(errors: Vec<CompileError>, options: &CompilerOptions) -> Vec<CompileError> {
+    match &options.catalog {
+        Some(catalog) => errors.into_iter().map(|error| {
+            let message = catalog.translate(error.message());
+            error.with_message(message)
+        }).collect(),
+        None => errors
     }
 }
 
-fn semantic_analysis(ast: & Ast) -> Result<Rules, Vec<String>> {
+fn semantic_analysis(ast: & Ast, options: &CompilerOptions) -> Result<Rules, Vec<CompileError>> {
     let mut errors = Vec::new();
+    let mut warnings = Vec::new();
 
     if let StateNode::Next(_) = ast.first_state {
         errors.push("You should specify at least one state.".to_string());
     }
 
+    let world_size = options.world_size.unwrap_or(ast.world_size);
+    // The size given by the options may have no cell, unlike the size declared by the rules.
+    if world_size.0 == 0 || world_size.1 == 0 {
+        return Err(vec![CompileError::semantic(format!("The world size ({}, {}) must have at least a cell in each dimension.", world_size.0, world_size.1))]);
+    }
     let (mut states, mut implicit_state_ranges, first_transition_node) = construct_states(&ast.first_state);
-    control_states_distribution(&states, &ast.world_size, &mut errors);
-    let (transitions, mut implicit_states) = construct_transitions(first_transition_node, &states, &mut implicit_state_ranges, &mut errors);
+    control_states_distribution(&states, &world_size, ast.depth, options, &mut errors, &mut warnings);
+    let (transitions, priorities, mut implicit_states) = construct_transitions(first_transition_node, &states, &mut implicit_state_ranges, options, &mut errors);
+    let neighborhood = construct_neighborhood(&ast.directives, ast.depth > 1, &mut errors);
+    let neighbor_offsets = neighborhood.offsets(ast.depth > 1);
+    control_transitions_overlap(&transitions, &priorities, &states, neighbor_offsets.len(), &mut warnings);
+    let transitions = sort_by_priority(transitions, priorities);
+    control_unreachable_conditions(&transitions, &states, neighbor_offsets.len(), &mut warnings);
+    control_neighbors_in_neighborhood(&transitions, &states, neighborhood, &neighbor_offsets, &mut warnings);
+    let boundary = construct_boundary(&ast.directives, &states, &mut errors);
+    control_world_size(world_size, ast.depth, neighborhood, boundary, &mut warnings);
+    let warnings = apply_lint_levels(warnings, options, &mut errors);
+    let second_order = ast.directives.iter().any(|d| matches!(d, DirectiveNode::SecondOrder));
+    if second_order {
+        control_second_order(&transitions, &implicit_states, &mut errors);
+    }
+    let memory = ast.directives.iter().any(|d| matches!(d, DirectiveNode::Memory));
+    if !memory {
+        control_no_previous_condition(&transitions, &mut errors);
+    }
+    if second_order && memory {
+        errors.push("The \"second_order\" and \"memory\" directives cannot be used together.".to_string());
+    }
+    let noise = construct_noise(&ast.directives, second_order, &mut errors);
+    let regions = construct_regions(&ast.directives, &states, second_order, &mut errors);
+    let data_channel = construct_data_channel(&ast.directives, &states, second_order, &mut errors);
+    let (agents, agent_rules) = construct_agents(ast, world_size, &states, second_order, &mut errors);
+    let metadata = construct_metadata(&ast.directives, &mut errors);
+    let initial_grid = construct_initial_grid(&ast.directives, &states, (world_size.0, world_size.1 * ast.depth), &mut errors);
+    let initial_cells = construct_initial_cells(&ast.initial_cells, &states, (world_size.0, world_size.1 * ast.depth), &mut errors);
+    if ast.depth > 1 {
+        control_three_dimensional(&transitions, &agents, boundary, &mut errors);
+    }
     states.append(&mut implicit_states);
 
     match errors.len() {
-        0 => Ok(Rules { world_size: ast.world_size, states, transitions, implicit_state_ranges }),
-        _ => Err(errors)
+        0 => Ok(Rules { world_size, states, transitions, implicit_state_ranges, second_order, memory, noise, regions, data_channel, agents, agent_rules, boundary, neighborhood, neighbor_offsets, depth: ast.depth, initial_grid, initial_cells, metadata, warnings }),
+        _ => Err(errors.into_iter().map(CompileError::semantic).collect())
+    }
+}
+
+/// Drops the warnings of the allowed lints, and moves those of the denied lints to the errors.
+fn apply_lint_levels(warnings: Vec<(Lint, String)>, options: &CompilerOptions, errors: &mut Vec<String>) -> Vec<String> {
+    let mut kept = Vec::new();
+    for (lint, warning) in warnings {
+        match options.lint_level(lint) {
+            LintLevel::Allow => {},
+            LintLevel::Warn => kept.push(warning),
+            LintLevel::Deny => errors.push(warning)
+        }
     }
+    kept
 }
 
-fn construct_states(ast: & StateNode) -> (Vec<State>, Vec<Option<ImplicitStateRange>>, & TransitionNode) {
+fn construct_states(ast: & StateNode) -> (Vec<State>, Vec<Vec<ImplicitStateRange>>, & TransitionNode) {
     let mut curr_state_node = ast;
     let first_transition_node: &TransitionNode;
     let mut states = Vec::new();
@@ -80,25 +432,29 @@ fn construct_states(ast: & StateNode) -> (Vec<State>, Vec<Option<ImplicitStateRa
     let mut id = 0;
     loop {
         match curr_state_node {
-            StateNode::State(name, red, green, blue, state_distribution_node) => {
+            StateNode::State(name, red, green, blue, gradient, state_distribution_node) => {
                 let (distribution, state_node) = match state_distribution_node {
                     StateDistributionNode::Proportion(proportion, state_node) => (StateDistribution::Proportion(*proportion), state_node.as_ref()),
                     StateDistributionNode::Quantity(quantity, state_node) => (StateDistribution::Quantity(*quantity), state_node.as_ref()),
                     StateDistributionNode::Box(x, y, width, height, state_node) => (StateDistribution::Box(*x, *y, *width, *height), state_node.as_ref()),
+                    StateDistributionNode::Circle(x, y, radius, state_node) => (StateDistribution::Circle(*x, *y, *radius), state_node.as_ref()),
+                    StateDistributionNode::Line(x1, y1, x2, y2, state_node) => (StateDistribution::Line(*x1, *y1, *x2, *y2), state_node.as_ref()),
+                    StateDistributionNode::Image(file_name, state_node) => (StateDistribution::Image(file_name.clone()), state_node.as_ref()),
                     StateDistributionNode::Default(state_node) => (StateDistribution::Default, state_node.as_ref())
                 };
                 states.push(State {
                     id,
                     name: name.clone(),
                     color: (*red, *green, *blue),
+                    gradient: *gradient,
                     distribution
                 });
-                implicit_state_range.push(None);
+                implicit_state_range.push(Vec::new());
                 id += 1;
                 curr_state_node = state_node;
             },
             StateNode::Next(t) => {
-                first_transition_node = &t;
+                first_transition_node = t;
                 break;
             }
         }
@@ -106,41 +462,59 @@ fn construct_states(ast: & StateNode) -> (Vec<State>, Vec<Option<ImplicitStateRa
     (states, implicit_state_range, first_transition_node)
 }
 
-fn control_states_distribution(states: &[State], world_size: &(usize, usize), errors: &mut Vec<String>) {
-    let proportions_sum = states.iter().fold(0.0, |sum, s|
+fn proportions_sum(states: &[State]) -> f64 {
+    states.iter().fold(0.0, |sum, s|
         sum + match s.distribution {
             StateDistribution::Proportion(p) => p,
             _ => 0.0
-        });
+        })
+}
+
+fn control_states_distribution(states: &[State],
+                               world_size: &(usize, usize),
+                               depth: usize,
+                               options: &CompilerOptions,
+                               errors: &mut Vec<String>,
+                               warnings: &mut Vec<(Lint, String)>) {
+    let proportions_sum = proportions_sum(states);
     if proportions_sum >= 1.0 {
         errors.push(format!("The sum of state's proportions must be lesser than 1.0, but it is currently {}.", proportions_sum));
     }
 
-    let default_count = states.iter().filter(|s|
-        match s.distribution {
-            StateDistribution::Default => true,
-            _ => false
-        }).count();
+    let default_count = states.iter().filter(|s| matches!(s.distribution, StateDistribution::Default)).count();
     if default_count != 1 {
         errors.push(format!(
             "There must be exactly one default state (without a distribution specified), but there are currently {} of such states.",
             default_count));
     }
 
+    // The default state gets the remaining proportion of the cells.
+    let default_proportion = 1.0 - proportions_sum;
+    if proportions_sum < 1.0 && default_count == 1 && default_proportion < options.default_proportion_warning_threshold {
+        let default_state = states.iter().find(|s| matches!(s.distribution, StateDistribution::Default)).unwrap();
+        warnings.push((Lint::SmallDefaultProportion, format!(
+            "The default state \"{}\" only gets the remaining proportion {:.4}, which is lesser than {}.",
+            default_state.name, default_proportion, options.default_proportion_warning_threshold)));
+    }
+
     let quantities_sum = states.iter().fold(0, |sum, s|
         sum + match s.distribution {
             StateDistribution::Quantity(q) => q,
             _ => 0
         });
-    let q_max= world_size.0 * world_size.1;
-    if quantities_sum > q_max {
+    let q_max= world_size.0 * world_size.1 * depth;
+    if quantities_sum > q_max && depth > 1 {
+        errors.push(format!(
+            "The sum of state's quantities is {}, but the world cannot hold that, its size is only {} * {} * {} = {}.",
+            quantities_sum, world_size.0, world_size.1, depth, q_max));
+    } else if quantities_sum > q_max {
         errors.push(format!(
             "The sum of state's quantities is {}, but the world cannot hold that, its size is only {} * {} = {}.",
             quantities_sum, world_size.0, world_size.1, q_max));
     }
 
     for state in states.iter() {
-        if let StateDistribution::Box(_, _, width, height) = state.distribution {
+        if let StateDistribution::Box(x, y, width, height) = state.distribution {
             // Note : No need to check if size are 0 because it's already done in syntax analysis.
             if width > world_size.0 {
                 errors.push(format!(
@@ -152,203 +526,824 @@ fn control_states_distribution(states: &[State], world_size: &(usize, usize), er
                     "For state \"{}\", box height cannot be {} because it is greater than the world's height ({})",
                     state.name, height, world_size.1));
             }
+            // The box doesn't wrap around the world. The layers of a 3D world are stacked, like in the grid.
+            if width <= world_size.0 && height <= world_size.1 && (x + width > world_size.0 || y + height > world_size.1 * depth) {
+                errors.push(format!(
+                    "For state \"{}\", the box of {} x {} cells at {}, {} goes beyond the world of {} x {} cells.",
+                    state.name, width, height, x, y, world_size.0, world_size.1 * depth));
+            }
+        }
+        // Like the boxes, the circles and the lines don't wrap around the world.
+        match state.distribution {
+            StateDistribution::Circle(x, y, radius) if x < radius || y < radius || x + radius >= world_size.0 || y + radius >= world_size.1 * depth =>
+                errors.push(format!(
+                    "For state \"{}\", the circle of radius {} at {}, {} goes beyond the world of {} x {} cells.",
+                    state.name, radius, x, y, world_size.0, world_size.1 * depth)),
+            StateDistribution::Line(x1, y1, x2, y2) if x1.max(x2) >= world_size.0 || y1.max(y2) >= world_size.1 * depth =>
+                errors.push(format!(
+                    "For state \"{}\", the line from {}, {} to {}, {} goes beyond the world of {} x {} cells.",
+                    state.name, x1, y1, x2, y2, world_size.0, world_size.1 * depth)),
+            _ => {}
+        }
+        if let StateDistribution::Image(file_name) = &state.distribution {
+            // The layers of a 3D world are stacked in the image, like in the grid.
+            match load_image(file_name) {
+                Ok((size, _)) if size != (world_size.0, world_size.1 * depth) => errors.push(format!(
+                    "For state \"{}\", the image {} has {} x {} pixels, but the world has {} x {} cells.",
+                    state.name, file_name, size.0, size.1, world_size.0, world_size.1 * depth)),
+                Ok(_) => {},
+                Err(error) => errors.push(error)
+            }
         }
     }
 }
 
 fn construct_transitions(first_transition_node: &TransitionNode,
                          states: &[State],
-                         implicit_state_ranges: &mut Vec<Option<ImplicitStateRange>>,
-                         errors: &mut Vec<String>) -> (Vec<Transition>, Vec<State>) {
+                         implicit_state_ranges: &mut [Vec<ImplicitStateRange>],
+                         options: &CompilerOptions,
+                         errors: &mut Vec<String>) -> (Vec<Transition>, Vec<usize>, Vec<State>) {
     let mut curr_transition_node = first_transition_node;
     let mut transitions = Vec::new();
+    let mut priorities = Vec::new();
     let mut implicit_states = Vec::new();
 
-    while let TransitionNode::Transition(state_origin_name, state_destination_name, condition_node) = curr_transition_node {
-        let state_origin = match get_state_index(state_origin_name, &states) {
+    loop {
+        let (state_origin_name, state_destination_name, swap, vote, condition_node) = match curr_transition_node {
+            TransitionNode::Transition(origin, destination, condition_node) => (origin, destination, None, None, condition_node),
+            // The cell keeps its state, the exchange is done once all the cells are updated.
+            TransitionNode::Swap(origin, cell, condition_node) => (origin, origin, Some(*cell), None, condition_node),
+            // The destination is the state elected by the neighbors, the origin state stands for it.
+            TransitionNode::Vote(origin, tie_break, condition_node) => (origin, origin, None, Some(*tie_break), condition_node),
+            TransitionNode::End => break
+        };
+        let state_origin = match get_state_index(state_origin_name, states) {
             Some(index) => index,
             _ => {
                 errors.push(transition_undefined_state_error(state_origin_name, state_destination_name, state_origin_name));
                 0   // whatever the number here is, it won't be used because an error occurred
             }
         };
-        let state_destination = match get_state_index(state_destination_name, &states) {
+        let state_destination = match get_state_index(state_destination_name, states) {
             Some(index) => index,
             _ => {
                 errors.push(transition_undefined_state_error(state_origin_name, state_destination_name, state_destination_name));
                 0   // whatever the number here is, it won't be used because an error occurred
             }
         };
-        let (transition_node, processed_condition, transition_delay) = construct_condition(condition_node, &states, errors);
+        let (transition_node, processed_condition, annotations) = construct_condition(condition_node, states, errors);
         curr_transition_node = transition_node;
+        let transition_delay = annotations.delay.unwrap_or(0);
+        let priority = annotations.priority.unwrap_or(0);
+        let mut effects = construct_effects(annotations, states, errors);
+        effects.swap = swap;
+        effects.vote = vote;
+        if swap.is_some() && transition_delay > 1 {
+            errors.push(format!("The swap transition of the state \"{}\" cannot have a delay.", state_origin_name));
+        }
+        if vote.is_some() && transition_delay > 1 {
+            errors.push(format!("The vote transition of the state \"{}\" cannot have a delay.", state_origin_name));
+        }
 
         let states_number = states.len() + implicit_states.len();
         if transition_delay > 1 {
             // Intermediary states and transitions are created automatically when a transition has a delay.
             // This way the cell will "slide" along the states sled and it will looks like it stayed in the same state for several iterations.
-            transitions.push((state_origin, states_number, processed_condition));
-            implicit_states.push(State {
-                id: states_number,
-                name: states[state_origin].name.clone(),
-                color: states[state_origin].color,
-                distribution: StateDistribution::Quantity(0),
-            });
-            for i in 0..transition_delay - 2 {
-                transitions.push((states_number + i, states_number + i + 1, vec![vec![Condition::True]; 1]));
+            transitions.push((state_origin, states_number, processed_condition, Effects::default()));
+            priorities.push(priority);
+            for i in 0..transition_delay - 1 {
+                if i > 0 {
+                    transitions.push((states_number + i - 1, states_number + i, vec![vec![Condition::True]; 1], Effects::default()));
+                    priorities.push(0);
+                }
+                let color = if options.delay_color_gradient {
+                    let t = (i + 1) as f64 / transition_delay as f64;
+                    interpolate_color(states[state_origin].color, states[state_destination].color, t)
+                } else {
+                    states[state_origin].color
+                };
                 implicit_states.push(State {
-                    id: states_number + i + 1,
+                    id: states_number + i,
                     name: states[state_origin].name.clone(),
-                    color: states[state_origin].color,
+                    color,
+                    gradient: None,
                     distribution: StateDistribution::Quantity(0),
                 });
             }
-            transitions.push((states_number + transition_delay - 2, state_destination, vec![vec![Condition::True]; 1]));
-            implicit_state_ranges[state_origin] = Some(ImplicitStateRange {
+            transitions.push((states_number + transition_delay - 2, state_destination, vec![vec![Condition::True]; 1], effects));
+            priorities.push(0);
+            implicit_state_ranges[state_origin].push(ImplicitStateRange {
                 start: states_number,
                 len: states_number + transition_delay - 1
             });
         } else {
-            transitions.push((state_origin, state_destination, processed_condition));
+            transitions.push((state_origin, state_destination, processed_condition, effects));
+            priorities.push(priority);
         }
     }
-    (transitions, implicit_states)
+    (transitions, priorities, implicit_states)
 }
 
-fn get_state_index(state_name: &str, states: &[State]) -> Option<usize> {
-    states.iter().position(|s| s.name == state_name)
+/// A second-order automaton can only be stepped backward if its transitions are deterministic and don't use implicit states.
+fn control_second_order(transitions: &[Transition], implicit_states: &[State], errors: &mut Vec<String>) {
+    let has_random_condition = transitions.iter()
+        .flat_map(|(_, _, conditions, _)| conditions.iter().flatten())
+        .any(|c| matches!(c, Condition::RandomCondition(_, _)));
+    if has_random_condition {
+        errors.push("A second-order automaton cannot have random conditions, because it must be reversible.".to_string());
+    }
+    if !implicit_states.is_empty() {
+        errors.push("A second-order automaton cannot have delayed transitions, because it must be reversible.".to_string());
+    }
+    if transitions.iter().any(|(_, _, _, effects)| !effects.neighbors.is_empty()) {
+        errors.push("A second-order automaton cannot have effects on neighbors, because it must be reversible.".to_string());
+    }
+    if transitions.iter().any(|(_, _, _, effects)| effects.swap.is_some()) {
+        errors.push("A second-order automaton cannot have swap transitions, because it must be reversible.".to_string());
+    }
+    if transitions.iter().any(|(_, _, _, effects)| effects.vote == Some(TieBreak::Random)) {
+        errors.push("A second-order automaton cannot have votes with random ties, because it must be reversible.".to_string());
+    }
 }
 
-fn construct_condition<'a>(root_condition_node: &'a ConditionNode,
-                       states: &[State],
-                       errors: &mut Vec<String>) -> (&'a TransitionNode, Vec<Vec<Condition>>, usize) {
-    let mut processed_condition = Vec::new();
-    let mut curr_condition_conjunction = Vec::new();
-    let mut curr_condition_node = root_condition_node;
+/// Returns the noise probability, or 0 if the "noise" directive is not declared.
+fn construct_noise(directives: &[DirectiveNode], second_order: bool, errors: &mut Vec<String>) -> f64 {
+    let noises: Vec<f64> = directives.iter()
+        .filter_map(|d| match d {
+            DirectiveNode::Noise(p) => Some(*p),
+            _ => None
+        })
+        .collect();
+    if noises.len() > 1 {
+        errors.push("The \"noise\" directive is declared more than once.".to_string());
+    }
+    if second_order && !noises.is_empty() {
+        errors.push("A second-order automaton cannot have noise, because it must be reversible.".to_string());
+    }
+    noises.first().copied().unwrap_or(0.0)
+}
 
-    let next_transition_node: &TransitionNode;
-    let transition_delay: usize;
-    loop {
-        let (condition, next_condition_node) = match curr_condition_node {
-            ConditionNode::QuantityCondition(state_name, comp_op, quantity, next_condition_node) => {
-                let state = match get_state_index(state_name, states) {
-                    Some(index) => index,
-                    _ => {
-                        errors.push(condition_undefined_state_error(state_name));
-                        0   // whatever the number here is, it won't be used because an error occurred
-                    }
-                };
-                (Condition::QuantityCondition(state, *comp_op, *quantity), next_condition_node)
+fn construct_regions(directives: &[DirectiveNode], states: &[State], second_order: bool, errors: &mut Vec<String>) -> Vec<Region> {
+    let mut regions = Vec::new();
+    for directive in directives {
+        match directive {
+            DirectiveNode::Frozen(x, y, width, height) => {
+                regions.push(Region { x: *x, y: *y, width: *width, height: *height, kind: RegionKind::Frozen });
             },
-            ConditionNode::NeighborCondition(cell, state_name, next_condition_node) => {
+            DirectiveNode::Source(x, y, width, height, state_name) => {
                 let state = match get_state_index(state_name, states) {
                     Some(index) => index,
                     _ => {
-                        errors.push(condition_undefined_state_error(state_name));
+                        errors.push(format!("A source region refers to the state \"{}\", but it's not defined.", state_name));
                         0   // whatever the number here is, it won't be used because an error occurred
                     }
                 };
-                (Condition::NeighborCondition(*cell, state), next_condition_node)
+                regions.push(Region { x: *x, y: *y, width: *width, height: *height, kind: RegionKind::Source(state) });
             },
-            ConditionNode::RandomCondition(proportion, next_condition_node) => {
-                (Condition::RandomCondition(*proportion), next_condition_node)
-            },
-            ConditionNode::True(next_condition_node) => {
-               (Condition::True, next_condition_node)
-            }
-        };
-
-        curr_condition_conjunction.push(condition);
-
-        let condition_is_true = if let ConditionNode::True(_) = curr_condition_node { true } else { false };
-        let conditions_before = curr_condition_conjunction.len() > 1 || !processed_condition.is_empty();
-        let conditions_after = if let NextConditionNode::NextCondition(_,_) = next_condition_node { true } else { false };
-        if condition_is_true && (conditions_before || conditions_after) {
-            errors.push(condition_true_error());
+            _ => {}
         }
+    }
+    if second_order && !regions.is_empty() {
+        errors.push("A second-order automaton cannot have frozen or source regions, because it must be reversible.".to_string());
+    }
+    regions
+}
 
-        match next_condition_node {
-            NextConditionNode::NextCondition(bool_op, condition_node) => {
-                curr_condition_node = condition_node.as_ref();
-                if let BooleanOperator::Or = bool_op {
-                    processed_condition.push(curr_condition_conjunction);
-                    curr_condition_conjunction = Vec::new();
-                }
-            },
-            NextConditionNode::NextTransition(opt_delay, t) => {
-                transition_delay = if let Some(delay) = opt_delay { *delay } else { 0 };
-                next_transition_node = t.as_ref();
-                if !curr_condition_conjunction.is_empty() {
-                     processed_condition.push(curr_condition_conjunction);
+fn construct_effects(annotations: &TransitionAnnotations, states: &[State], errors: &mut Vec<String>) -> Effects {
+    let neighbors = annotations.effects.iter()
+        .map(|(cell, state_name)| {
+            let state = match get_state_index(state_name, states) {
+                Some(index) => index,
+                _ => {
+                    errors.push(format!("An effect refers to the state \"{}\", but it's not defined.", state_name));
+                    0   // whatever the number here is, it won't be used because an error occurred
                 }
-                break;
+            };
+            (*cell, state)
+        })
+        .collect();
+    Effects { set_data: annotations.set_data, neighbors, swap: None, vote: None }
+}
+
+fn construct_agents(ast: &Ast, world_size: (usize, usize), states: &[State], second_order: bool, errors: &mut Vec<String>) -> (Vec<Agent>, Vec<AgentRule>) {
+    let mut agents = Vec::new();
+    for directive in &ast.directives {
+        if let DirectiveNode::Agent(x, y, heading) = directive {
+            if *x >= world_size.0 || *y >= world_size.1 {
+                errors.push(format!("The agent at ({}, {}) is outside of the world.", x, y));
             }
+            agents.push(Agent { position: (*x, *y), heading: *heading, mode: 0 });
         }
     }
-    (next_transition_node, processed_condition, transition_delay)
+
+    let mut agent_rules = Vec::new();
+    for rule in &ast.agent_rules {
+        let mut get_state = |state_name: &str| match get_state_index(state_name, states) {
+            Some(index) => index,
+            _ => {
+                errors.push(format!("An agent rule refers to the state \"{}\", but it's not defined.", state_name));
+                0   // whatever the number here is, it won't be used because an error occurred
+            }
+        };
+        agent_rules.push(AgentRule {
+            cell_state: get_state(&rule.cell_state),
+            turn: rule.turn,
+            new_cell_state: get_state(&rule.new_cell_state),
+            mode: rule.mode,
+            next_mode: rule.next_mode
+        });
+    }
+
+    if second_order && !agents.is_empty() {
+        errors.push("A second-order automaton cannot have agents, because it must be reversible.".to_string());
+    }
+    (agents, agent_rules)
 }
 
-fn transition_undefined_state_error(state_origin: &str,
-                                    state_destination: &str,
-                                    undefined: &str) -> String {
-    format!("The transition '{} -> {}' refers to the state \"{}\", but it's not defined.", state_origin, state_destination, undefined)
+fn construct_metadata(directives: &[DirectiveNode], errors: &mut Vec<String>) -> Vec<(String, String)> {
+    let mut metadata: Vec<(String, String)> = Vec::new();
+    for directive in directives {
+        if let DirectiveNode::Metadata(key, text) = directive {
+            if metadata.iter().any(|(other, _)| other == key) {
+                errors.push(format!("The \"{}\" directive is declared more than once.", key));
+            }
+            metadata.push((key.clone(), text.clone()));
+        }
+    }
+    metadata
 }
 
-fn condition_undefined_state_error(state_name: &str) -> String {
-    format!("A condition refers to the state \"{}\", but it's not defined.", state_name)
+/// The name of the data channel must differ from the words a condition can start with, or its conditions would be ambiguous.
+fn construct_data_channel(directives: &[DirectiveNode], states: &[State], second_order: bool, errors: &mut Vec<String>) -> Option<String> {
+    let channels: Vec<&String> = directives.iter()
+        .filter_map(|d| match d {
+            DirectiveNode::Data(name) => Some(name),
+            _ => None
+        })
+        .collect();
+    if channels.len() > 1 {
+        errors.push("The \"data\" directive is declared more than once.".to_string());
+    }
+    if second_order && !channels.is_empty() {
+        errors.push("A second-order automaton cannot have a data channel, because it must be reversible.".to_string());
+    }
+    for name in &channels {
+        if get_state_index(name, states).is_some() {
+            errors.push(format!("The data channel \"{}\" has the name of a state.", name));
+        }
+        if CONDITION_KEYWORDS.contains(&name.as_str()) {
+            errors.push(format!("The data channel \"{}\" has the name of a keyword, one of {}.", name, CONDITION_KEYWORDS.join(", ")));
+        }
+        if NeighborCell::from_letter(name).is_some() {
+            errors.push(format!("The data channel \"{}\" has the name of a neighbor cell, one of {}.", name, NeighborCell::letters_description()));
+        }
+    }
+    channels.first().map(|name| name.to_string())
 }
 
-fn condition_true_error() -> String {
-    "The \"true\" condition should not be alone, not combined with other conditions.".to_string()
+fn construct_neighborhood(directives: &[DirectiveNode], three_dimensional: bool, errors: &mut Vec<String>) -> Neighborhood {
+    let neighborhoods: Vec<Neighborhood> = directives.iter()
+        .filter_map(|d| match d {
+            DirectiveNode::Neighborhood(neighborhood) => Some(*neighborhood),
+            _ => None
+        })
+        .collect();
+    if neighborhoods.len() > 1 {
+        errors.push("The \"neighborhood\" directive is declared more than once.".to_string());
+    }
+    let neighborhood = neighborhoods.first().copied().unwrap_or(Neighborhood::Moore(1));
+    let neighbor_count = neighborhood.offsets(three_dimensional).len();
+    if neighbor_count > u8::MAX as usize {
+        errors.push(format!("The neighborhood has {} neighbors, but a quantity condition can count at most {}.", neighbor_count, u8::MAX));
+    }
+    neighborhood
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::compiler::semantic::parse;
+fn construct_boundary(directives: &[DirectiveNode], states: &[State], errors: &mut Vec<String>) -> BoundaryMode {
+    let boundaries: Vec<(&String, &Option<String>)> = directives.iter()
+        .filter_map(|d| match d {
+            DirectiveNode::Boundary(mode, state) => Some((mode, state)),
+            _ => None
+        })
+        .collect();
+    if boundaries.len() > 1 {
+        errors.push("The \"boundary\" directive is declared more than once.".to_string());
+    }
+    match boundaries.first() {
+        Some((mode, _)) if mode.as_str() == "mirror" => BoundaryMode::Mirror,
+        Some((_, Some(state_name))) => match get_state_index(state_name, states) {
+            Some(state) => BoundaryMode::Wall(state),
+            None => {
+                errors.push(format!("The boundary wall refers to the state \"{}\", but it's not defined.", state_name));
+                BoundaryMode::Wrap
+            }
+        },
+        _ => BoundaryMode::Wrap
+    }
+}
 
-    static BENCHMARK_FILE: &str = "resources/tests/compiler_benchmark.txt";
-    static BOX_ERRORS_FILE: &str = "resources/tests/semantic_box_errors.txt";
-    static CONDITION_UNDEFINED_STATE_FILE: &str = "resources/tests/semantic_condition_undefined_state.txt";
-    static NO_STATES_FILE: &str = "resources/tests/semantic_no_states.txt";
-    static QUANTITIES_TOO_MUCH_FILE: &str = "resources/tests/semantic_quantities_too_much.txt";
-    static SEVERAL_ERRORS_FILE: &str = "resources/tests/semantic_several_errors.txt";
-    static TRANSITION_UNDEFINED_STATE_FILE: &str = "resources/tests/semantic_transition_undefined_state.txt";
-    static TRUE_ERROR_FILE: &str = "resources/tests/semantic_true_error.txt";
-    static TWO_DEFAULT_STATES_FILE: &str = "resources/tests/semantic_two_default_states.txt";
-    static WRONG_PROPORTIONS_FILE: &str = "resources/tests/semantic_wrong_proportions.txt";
+/// The layers of a 3D world are stacked, so the positions in a layer are only supported in the first layer, for the boxes and the regions.
+/// The conditions and features that refer to a neighbor of the cell in its layer are not supported.
+fn control_three_dimensional(transitions: &[Transition], agents: &[Agent], boundary: BoundaryMode, errors: &mut Vec<String>) {
+    let conditions = || transitions.iter().flat_map(|(_, _, conditions, _)| conditions.iter().flatten());
+    if conditions().any(|c| matches!(c, Condition::NeighborCondition(_, _) | Condition::PreviousNeighborCondition(_, _))) {
+        errors.push("The conditions on a neighbor, like \"G is dead\", are not supported in a 3D world.".to_string());
+    }
+    if conditions().any(|c| matches!(c, Condition::DistanceCondition(_, _, _))) {
+        errors.push("The distance conditions are not supported in a 3D world.".to_string());
+    }
+    if transitions.iter().any(|(_, _, _, effects)| !effects.neighbors.is_empty() || effects.swap.is_some()) {
+        errors.push("The effects and the swaps are not supported in a 3D world.".to_string());
+    }
+    if !agents.is_empty() {
+        errors.push("The agents are not supported in a 3D world.".to_string());
+    }
+    if boundary != BoundaryMode::Wrap {
+        errors.push("A 3D world wraps around, the \"boundary\" directive is not supported.".to_string());
+    }
+}
 
-    #[test]
-    fn parse_benchmark_succeeds() {
-        match parse(BENCHMARK_FILE) {
-            Ok(_) => assert!(true),
-            _ => assert!(false)
+/// Reads the CSV file of the "init" directive : a line per row of the world, and a state name or id per cell, separated by commas.
+/// The layers of a 3D world come one after the other.
+fn construct_initial_grid(directives: &[DirectiveNode], states: &[State], world_size: (usize, usize), errors: &mut Vec<String>) -> Option<Vec<usize>> {
+    let files: Vec<&String> = directives.iter()
+        .filter_map(|d| match d {
+            DirectiveNode::InitCsv(file_name) => Some(file_name),
+            _ => None
+        })
+        .collect();
+    if files.len() > 1 {
+        errors.push("The \"init\" directive is declared more than once.".to_string());
+    }
+    let file_name = files.first()?;
+    let text = match fs::read_to_string(file_name) {
+        Ok(text) => text,
+        Err(error) => {
+            errors.push(format!("Cannot read the initial grid {}. Cause : {:?}", file_name, error));
+            return None;
+        }
+    };
+    let rows: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    if rows.len() != world_size.1 {
+        errors.push(format!("The initial grid {} has {} rows, but the world has {} rows.", file_name, rows.len(), world_size.1));
+        return None;
+    }
+    let mut grid = Vec::with_capacity(world_size.0 * world_size.1);
+    for (y, row) in rows.iter().enumerate() {
+        let cells: Vec<&str> = row.split(',').map(str::trim).collect();
+        if cells.len() != world_size.0 {
+            errors.push(format!("The row {} of the initial grid {} has {} cells, but the world has {} columns.", y + 1, file_name, cells.len(), world_size.0));
+            return None;
+        }
+        for (x, cell) in cells.iter().enumerate() {
+            match get_state_index(cell, states).or_else(|| cell.parse().ok().filter(|id| *id < states.len())) {
+                Some(state) => grid.push(state),
+                None => {
+                    errors.push(format!("The cell \"{}\" at row {}, column {} of the initial grid {} is neither a state name nor a state id.", cell, y + 1, x + 1, file_name));
+                    return None;
+                }
+            }
         }
     }
+    Some(grid)
+}
 
-    #[test]
-    fn parse_box_distribution_several_errors_fails() {
-        match parse(BOX_ERRORS_FILE) {
-            Err(errors) => {
-                assert_eq!(errors.len(), 2);
-                assert_eq!(errors[0], "For state \"test\", box width cannot be 300 because it is greater than the world's width (200)");
-                assert_eq!(errors[1], "For state \"test\", box height cannot be 60 because it is greater than the world's height (50)");
-            },
-            _ => assert!(false)
+/// Returns the cells of the "pattern" section with the index of their state. The layers of a 3D world are stacked, like in the grid.
+fn construct_initial_cells(cells: &[CellNode], states: &[State], world_size: (usize, usize), errors: &mut Vec<String>) -> Vec<((usize, usize), usize)> {
+    let mut initial_cells = Vec::new();
+    for ((x, y), state_name) in cells {
+        if *x >= world_size.0 || *y >= world_size.1 {
+            errors.push(format!("The cell ({}, {}) of the pattern is outside of the world of {} x {} cells.", x, y, world_size.0, world_size.1));
         }
+        match get_state_index(state_name, states) {
+            Some(state) => initial_cells.push(((*x, *y), state)),
+            None => errors.push(format!("The cell ({}, {}) of the pattern refers to the state \"{}\", but it's not defined.", x, y, state_name))
+        }
+    }
+    initial_cells
+}
+
+/// The conditions can refer to the previous iteration only if it is retained, with the "memory" directive.
+fn control_no_previous_condition(transitions: &[Transition], errors: &mut Vec<String>) {
+    let has_previous_condition = transitions.iter()
+        .flat_map(|(_, _, conditions, _)| conditions.iter().flatten())
+        .any(|c| matches!(c, Condition::PreviousNeighborCondition(_, _) | Condition::PreviousStateCondition(_)));
+    if has_previous_condition {
+        errors.push("A condition refers to the previous iteration, but the \"memory\" directive is not declared.".to_string());
     }
+}
 
-    #[test]
-    fn parse_condition_undefined_state_fails() {
-        match parse(CONDITION_UNDEFINED_STATE_FILE) {
-            Err(errors) => {
-                assert_eq!(errors.len(), 1);
-                assert_eq!(errors[0], "A condition refers to the state \"happy\", but it\'s not defined.");
-            },
-            _ => assert!(false)
+/// Warns about the transitions that have the same origin and priority, and whose conditions can be true at the same time.
+/// For those transitions, only the order in the file decides which one is triggered.
+fn control_transitions_overlap(transitions: &[Transition], priorities: &[usize], states: &[State], neighbor_count: usize, warnings: &mut Vec<(Lint, String)>) {
+    for (i, (origin, destination, conditions, _)) in transitions.iter().enumerate() {
+        // The transitions between implicit states can't overlap, there is only one per implicit state.
+        if *origin >= states.len() {
+            continue;
+        }
+        for (j, (other_origin, other_destination, other_conditions, _)) in transitions.iter().enumerate().skip(i + 1) {
+            if origin == other_origin && priorities[i] == priorities[j] && conditions_can_overlap(conditions, other_conditions, neighbor_count) {
+                warnings.push((Lint::OverlappingTransitions, format!(
+                    "The transitions '{} -> {}' and '{} -> {}' have the same priority and can both be triggered, \
+                    so the first one declared is used. Consider using \"priority\" to make the order explicit.",
+                    states[*origin].name, states[final_destination(*destination, transitions, states)].name,
+                    states[*other_origin].name, states[final_destination(*other_destination, transitions, states)].name)));
+            }
         }
     }
+}
 
-    #[test]
-    fn parse_no_states_fails() {
+/// Warns about the conditions that can never be true, and the transitions that can never be triggered
+/// because a previous transition of the same state is always triggered.
+fn control_unreachable_conditions(transitions: &[Transition], states: &[State], neighbor_count: usize, warnings: &mut Vec<(Lint, String)>) {
+    let name = |(origin, destination, _, _): &Transition| format!("'{} -> {}'",
+        states[*origin].name, states[final_destination(*destination, transitions, states)].name);
+    for (i, transition) in transitions.iter().enumerate() {
+        let (origin, _, conditions, _) = transition;
+        if *origin >= states.len() {
+            continue;
+        }
+        for condition in conditions.iter().flatten() {
+            match condition {
+                Condition::QuantityCondition(state, comp, quantity, CountedCells::Neighbors)
+                    if !(0..=neighbor_count).any(|count| comp.evaluate(count as u8, *quantity)) => {
+                    warnings.push((Lint::ImpossibleCondition, format!("The condition \"{} {} {}\" of the transition {} can never be true, because a cell has {} neighbors.",
+                                          states[*state].name, comp, quantity, name(transition), neighbor_count)));
+                },
+                Condition::QuantityCondition(state, comp, quantity, CountedCells::NeighborsAndSelf)
+                    if !(0..=neighbor_count + 1).any(|count| comp.evaluate(count as u8, *quantity)) => {
+                    warnings.push((Lint::ImpossibleCondition, format!("The condition \"count9({}) {} {}\" of the transition {} can never be true, because a cell and its neighbors are {} cells.",
+                                          states[*state].name, comp, quantity, name(transition), neighbor_count + 1)));
+                },
+                Condition::RandomCondition(proportion, _) if *proportion <= 0.0 => {
+                    warnings.push((Lint::ImpossibleCondition, format!("The condition \"rand {}\" of the transition {} can never be true.", proportion, name(transition))));
+                },
+                _ => {}
+            }
+        }
+        let always_triggered = conditions.iter().any(|conjunction| conjunction.iter().all(|condition| match condition {
+            Condition::True => true,
+            Condition::RandomCondition(proportion, _) => *proportion >= 1.0,
+            _ => false
+        }));
+        if always_triggered {
+            for shadowed in transitions[i + 1..].iter().filter(|(other_origin, _, _, _)| other_origin == origin) {
+                warnings.push((Lint::ShadowedTransition, format!("The transition {} can never be triggered, because the transition {} is always triggered before.",
+                                      name(shadowed), name(transition))));
+            }
+        }
+    }
+}
+
+/// Warns about the neighbor cells referred to by the transitions that are not in the neighborhood, like the diagonal cells
+/// of a Von Neumann neighborhood of radius 1. Those cells are still read, but aren't counted by the quantity conditions.
+fn control_neighbors_in_neighborhood(transitions: &[Transition], states: &[State], neighborhood: Neighborhood,
+                                     neighbor_offsets: &[(isize, isize, isize)], warnings: &mut Vec<(Lint, String)>) {
+    let kind = match neighborhood {
+        Neighborhood::Moore(_) => "moore",
+        Neighborhood::VonNeumann(_) => "von_neumann"
+    };
+    for (origin, destination, conditions, effects) in transitions {
+        let neighbors = conditions.iter().flatten()
+            .filter_map(|condition| match condition {
+                Condition::NeighborCondition(neighbor, _) | Condition::PreviousNeighborCondition(neighbor, _) => Some(*neighbor),
+                _ => None
+            })
+            .chain(effects.neighbors.iter().map(|(neighbor, _)| *neighbor))
+            .chain(effects.swap);
+        let mut reported: Vec<&str> = Vec::new();
+        for neighbor in neighbors {
+            let (u, v) = neighbor.offset();
+            if neighbor_offsets.contains(&(u, v, 0)) || reported.contains(&neighbor.letter()) {
+                continue;
+            }
+            reported.push(neighbor.letter());
+            warnings.push((Lint::NeighborOutsideNeighborhood, format!(
+                "The transition '{} -> {}' refers to the neighbor \"{}\" ({}), which is not in the \"{} {}\" neighborhood.",
+                states[first_origin(*origin, transitions, states)].name, states[final_destination(*destination, transitions, states)].name,
+                neighbor, neighbor.description(), kind, neighborhood.radius())));
+        }
+    }
+}
+
+/// A wrapping world narrower than the neighborhood is allowed, but its cells see the same neighbors several times,
+/// or themselves, like the single cell of a 1 x 1 world.
+fn control_world_size(world_size: (usize, usize), depth: usize, neighborhood: Neighborhood, boundary: BoundaryMode, warnings: &mut Vec<(Lint, String)>) {
+    let span = 2 * neighborhood.radius() + 1;
+    let narrow = world_size.0 < span || world_size.1 < span || (depth > 1 && depth < span);
+    if narrow && boundary == BoundaryMode::Wrap {
+        let size = match depth {
+            1 => format!("{} x {}", world_size.0, world_size.1),
+            depth => format!("{} x {} x {}", world_size.0, world_size.1, depth)
+        };
+        warnings.push((Lint::SmallWorld, format!(
+            "The world of {} cells is narrower than the neighborhood, which spans {} cells : as the world wraps around, its cells count some neighbors several times, or themselves.",
+            size, span)));
+    }
+}
+
+/// The origin of the last transition of a delay is an implicit state, this follows the implicit states back to the declared origin.
+fn first_origin(origin: usize, transitions: &[Transition], states: &[State]) -> usize {
+    let mut origin = origin;
+    while origin >= states.len() {
+        origin = transitions.iter().find(|(_, destination, _, _)| *destination == origin).unwrap().0;
+    }
+    origin
+}
+
+/// The destination of a delayed transition is an implicit state, this follows the implicit states until the declared destination.
+fn final_destination(destination: usize, transitions: &[Transition], states: &[State]) -> usize {
+    let mut destination = destination;
+    while destination >= states.len() {
+        destination = transitions.iter().find(|(origin, _, _, _)| *origin == destination).unwrap().1;
+    }
+    destination
+}
+
+/// Returns false only if the conditions can be proven to never be true at the same time.
+fn conditions_can_overlap(conditions: &[Vec<Condition>], other_conditions: &[Vec<Condition>], neighbor_count: usize) -> bool {
+    conditions.iter().any(|c| other_conditions.iter().any(|o| conjunctions_can_overlap(c, o, neighbor_count)))
+}
+
+fn conjunctions_can_overlap(conjunction: &[Condition], other_conjunction: &[Condition], neighbor_count: usize) -> bool {
+    // For each state and counted cells, whether each count satisfies the conditions.
+    let mut possible_counts: HashMap<(usize, CountedCells), Vec<bool>> = HashMap::new();
+    let mut neighbor_states: HashMap<usize, usize> = HashMap::new();
+    for condition in conjunction.iter().chain(other_conjunction) {
+        match condition {
+            Condition::QuantityCondition(state, comp, quantity, counted) => {
+                let counts = possible_counts.entry((*state, *counted)).or_insert_with(|| vec![true; counted_cells(*counted, neighbor_count) + 1]);
+                for (count, possible) in counts.iter_mut().enumerate() {
+                    *possible &= comp.evaluate(count as u8, *quantity);
+                }
+            },
+            Condition::NeighborCondition(cell, state) => {
+                if let Some(other_state) = neighbor_states.insert(*cell as usize, *state) {
+                    if other_state != *state {
+                        return false;
+                    }
+                }
+            },
+            Condition::DataCondition(_, _)
+            | Condition::DistanceCondition(_, _, _)
+            | Condition::PreviousNeighborCondition(_, _)
+            | Condition::PreviousStateCondition(_)
+            | Condition::RandomCondition(_, _)
+            | Condition::True => {}
+        }
+    }
+    possible_counts.values().all(|counts| counts.contains(&true))
+}
+
+/// Returns the number of cells counted by a quantity condition.
+fn counted_cells(counted: CountedCells, neighbor_count: usize) -> usize {
+    match counted {
+        CountedCells::Neighbors => neighbor_count,
+        CountedCells::NeighborsAndSelf => neighbor_count + 1
+    }
+}
+
+/// Orders the transitions by decreasing priority. Transitions with the same priority keep the order of the file.
+fn sort_by_priority(transitions: Vec<Transition>, priorities: Vec<usize>) -> Vec<Transition> {
+    let mut prioritized = transitions.into_iter().zip(priorities).collect::<Vec<_>>();
+    prioritized.sort_by(|(_, p1), (_, p2)| p2.cmp(p1));
+    prioritized.into_iter().map(|(t, _)| t).collect()
+}
+
+/// Linear interpolation between two colors, t = 0 gives the first color and t = 1 the second one.
+fn interpolate_color(from: (u8, u8, u8), to: (u8, u8, u8), t: f64) -> (u8, u8, u8) {
+    let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    (lerp(from.0, to.0), lerp(from.1, to.1), lerp(from.2, to.2))
+}
+
+fn get_state_index(state_name: &str, states: &[State]) -> Option<usize> {
+    states.iter().position(|s| s.name == state_name)
+}
+
+fn construct_condition<'a>(root_condition_node: &'a ConditionNode,
+                       states: &[State],
+                       errors: &mut Vec<String>) -> (&'a TransitionNode, Vec<Vec<Condition>>, &'a TransitionAnnotations) {
+    let mut processed_condition = Vec::new();
+    let mut curr_condition_conjunction = Vec::new();
+    let mut curr_condition_node = root_condition_node;
+
+    let next_transition_node: &TransitionNode;
+    let annotations: &TransitionAnnotations;
+    loop {
+        let (condition, next_condition_node) = match curr_condition_node {
+            ConditionNode::QuantityCondition(state_name, comp_op, quantity, counted, next_condition_node) => {
+                let state = match get_state_index(state_name, states) {
+                    Some(index) => index,
+                    _ => {
+                        errors.push(condition_undefined_state_error(state_name));
+                        0   // whatever the number here is, it won't be used because an error occurred
+                    }
+                };
+                (Condition::QuantityCondition(state, *comp_op, *quantity, *counted), next_condition_node)
+            },
+            ConditionNode::DataCondition(comp_op, value, next_condition_node) => {
+                (Condition::DataCondition(*comp_op, *value), next_condition_node)
+            },
+            ConditionNode::DistanceCondition(state_name, comp_op, distance, next_condition_node) => {
+                let state = match get_state_index(state_name, states) {
+                    Some(index) => index,
+                    _ => {
+                        errors.push(condition_undefined_state_error(state_name));
+                        0   // whatever the number here is, it won't be used because an error occurred
+                    }
+                };
+                (Condition::DistanceCondition(state, *comp_op, *distance), next_condition_node)
+            },
+            ConditionNode::NeighborCondition(cell, state_name, next_condition_node) => {
+                let state = match get_state_index(state_name, states) {
+                    Some(index) => index,
+                    _ => {
+                        errors.push(condition_undefined_state_error(state_name));
+                        0   // whatever the number here is, it won't be used because an error occurred
+                    }
+                };
+                (Condition::NeighborCondition(*cell, state), next_condition_node)
+            },
+            ConditionNode::PreviousNeighborCondition(cell, state_name, next_condition_node) => {
+                let state = match get_state_index(state_name, states) {
+                    Some(index) => index,
+                    _ => {
+                        errors.push(condition_undefined_state_error(state_name));
+                        0   // whatever the number here is, it won't be used because an error occurred
+                    }
+                };
+                (Condition::PreviousNeighborCondition(*cell, state), next_condition_node)
+            },
+            ConditionNode::PreviousStateCondition(state_name, next_condition_node) => {
+                let state = match get_state_index(state_name, states) {
+                    Some(index) => index,
+                    _ => {
+                        errors.push(condition_undefined_state_error(state_name));
+                        0   // whatever the number here is, it won't be used because an error occurred
+                    }
+                };
+                (Condition::PreviousStateCondition(state), next_condition_node)
+            },
+            ConditionNode::RandomCondition(proportion, draw, next_condition_node) => {
+                (Condition::RandomCondition(*proportion, *draw), next_condition_node)
+            },
+            ConditionNode::True(next_condition_node) => {
+               (Condition::True, next_condition_node)
+            }
+        };
+
+        curr_condition_conjunction.push(condition);
+
+        let condition_is_true = matches!(curr_condition_node, ConditionNode::True(_));
+        let conditions_before = curr_condition_conjunction.len() > 1 || !processed_condition.is_empty();
+        let conditions_after = matches!(next_condition_node, NextConditionNode::NextCondition(_,_));
+        if condition_is_true && (conditions_before || conditions_after) {
+            errors.push(condition_true_error());
+        }
+
+        match next_condition_node {
+            NextConditionNode::NextCondition(bool_op, condition_node) => {
+                curr_condition_node = condition_node.as_ref();
+                if let BooleanOperator::Or = bool_op {
+                    processed_condition.push(curr_condition_conjunction);
+                    curr_condition_conjunction = Vec::new();
+                }
+            },
+            NextConditionNode::NextTransition(transition_annotations, t) => {
+                annotations = transition_annotations;
+                next_transition_node = t.as_ref();
+                if !curr_condition_conjunction.is_empty() {
+                     processed_condition.push(curr_condition_conjunction);
+                }
+                break;
+            }
+        }
+    }
+    (next_transition_node, processed_condition, annotations)
+}
+
+fn transition_undefined_state_error(state_origin: &str,
+                                    state_destination: &str,
+                                    undefined: &str) -> String {
+    format!("The transition '{} -> {}' refers to the state \"{}\", but it's not defined.", state_origin, state_destination, undefined)
+}
+
+fn condition_undefined_state_error(state_name: &str) -> String {
+    format!("A condition refers to the state \"{}\", but it's not defined.", state_name)
+}
+
+fn condition_true_error() -> String {
+    "The \"true\" condition should not be alone, not combined with other conditions.".to_string()
+}
+
+#[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
+mod tests {
+    use std::fs;
+    use crate::compiler::semantic::{parse, parse_with_options, parse_str, parse_str_with_options, parse_reader, CompilerOptions, Lint, LintLevel, RegionKind, Condition, StateDistribution};
+    use crate::compiler::parser::{ComparisonOperator, CountedCells, Gradient, NeighborCell, RandomDraw, TieBreak};
+    use crate::compiler::messages::Catalog;
+
+    static BENCHMARK_FILE: &str = "resources/tests/compiler_benchmark.txt";
+    static BOX_ERRORS_FILE: &str = "resources/tests/semantic_box_errors.txt";
+    static CONDITION_UNDEFINED_STATE_FILE: &str = "resources/tests/semantic_condition_undefined_state.txt";
+    static NO_STATES_FILE: &str = "resources/tests/semantic_no_states.txt";
+    static VIRUS_FILE: &str = "resources/virus.txt";
+    static LINTS_FILE: &str = "resources/tests/semantic_lints.txt";
+    static HEX_COLOR_FILE: &str = "resources/tests/semantic_hex_color.txt";
+    static FRENCH_CATALOG_FILE: &str = "resources/i18n/fr.po";
+    static DELAY_FILE: &str = "resources/tests/semantic_delay.txt";
+    static SECOND_ORDER_FILE: &str = "resources/tests/semantic_second_order.txt";
+    static SECOND_ORDER_ERRORS_FILE: &str = "resources/tests/semantic_second_order_errors.txt";
+    static MEMORY_FILE: &str = "resources/tests/semantic_memory.txt";
+    static MISSING_MEMORY_FILE: &str = "resources/tests/semantic_missing_memory.txt";
+    static NOISE_FILE: &str = "resources/tests/semantic_noise.txt";
+    static NOISE_ERRORS_FILE: &str = "resources/tests/semantic_noise_errors.txt";
+    static REGIONS_FILE: &str = "resources/tests/semantic_regions.txt";
+    static REGIONS_UNDEFINED_STATE_FILE: &str = "resources/tests/semantic_regions_undefined_state.txt";
+    static DATA_FILE: &str = "resources/tests/semantic_data.txt";
+    static DATA_NAME_CLASH_FILE: &str = "resources/tests/semantic_data_name_clash.txt";
+    static BOUNDARY_ERRORS_FILE: &str = "resources/tests/semantic_boundary_errors.txt";
+    static INIT_CSV_FILE: &str = "resources/tests/semantic_init_csv.txt";
+    static THREE_DIMENSIONAL_FILE: &str = "resources/life_3d.txt";
+    static THREE_DIMENSIONAL_ERRORS_FILE: &str = "resources/tests/semantic_3d_errors.txt";
+    static INIT_CSV_ERRORS_FILE: &str = "resources/tests/semantic_init_csv_errors.txt";
+    static IMAGE_FILE: &str = "resources/tests/semantic_image.txt";
+    static PATTERN_FILE: &str = "resources/tests/semantic_pattern.txt";
+    static NEIGHBORHOOD_ERRORS_FILE: &str = "resources/tests/semantic_neighborhood_errors.txt";
+    static NEIGHBOR_OUTSIDE_NEIGHBORHOOD_FILE: &str = "resources/tests/semantic_neighbor_outside_neighborhood.txt";
+    static EFFECTS_FILE: &str = "resources/tests/semantic_effects.txt";
+    static EFFECTS_UNDEFINED_STATE_FILE: &str = "resources/tests/semantic_effects_undefined_state.txt";
+    static SWAP_FILE: &str = "resources/tests/semantic_swap.txt";
+    static AGENTS_FILE: &str = "resources/langton_ant.txt";
+    static AGENTS_ERRORS_FILE: &str = "resources/tests/semantic_agents_undefined_state.txt";
+    static DISTANCE_FILE: &str = "resources/tests/semantic_distance.txt";
+    static SMALL_DEFAULT_PROPORTION_FILE: &str = "resources/tests/semantic_small_default_proportion.txt";
+    static PRIORITIES_FILE: &str = "resources/tests/semantic_priorities.txt";
+    static QUANTITIES_TOO_MUCH_FILE: &str = "resources/tests/semantic_quantities_too_much.txt";
+    static SEVERAL_ERRORS_FILE: &str = "resources/tests/semantic_several_errors.txt";
+    static TRANSITION_UNDEFINED_STATE_FILE: &str = "resources/tests/semantic_transition_undefined_state.txt";
+    static TRUE_ERROR_FILE: &str = "resources/tests/semantic_true_error.txt";
+    static TWO_DEFAULT_STATES_FILE: &str = "resources/tests/semantic_two_default_states.txt";
+    static WRONG_PROPORTIONS_FILE: &str = "resources/tests/semantic_wrong_proportions.txt";
+
+    #[test]
+    fn parse_benchmark_succeeds() {
+        match parse(BENCHMARK_FILE) {
+            Ok(_) => assert!(true),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_box_distribution_several_errors_fails() {
+        match parse(BOX_ERRORS_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 2);
+                assert_eq!(errors[0], "For state \"test\", box width cannot be 300 because it is greater than the world's width (200)");
+                assert_eq!(errors[1], "For state \"test\", box height cannot be 60 because it is greater than the world's height (50)");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_box_distribution_outside_of_the_world_fails() {
+        let rules = |size: &str, distribution: &str| format!("size {} states {{ (dead, 0, 0, 0), (wall, 100, 100, 100, {}), }} \
+            transitions {{ }}", size, distribution);
+        assert!(parse_str(&rules("(10, 5)", "box 0 0 10 5")).is_ok());
+        assert_eq!(parse_str(&rules("(10, 5)", "box 8 1 3 4")).unwrap_err(),
+                   vec!["For state \"wall\", the box of 3 x 4 cells at 8, 1 goes beyond the world of 10 x 5 cells."]);
+        // The box of the second layer of a 3D world.
+        assert!(parse_str(&rules("(10, 5, 2)", "box 0 5 10 5")).is_ok());
+        assert!(parse_str(&rules("(10, 5, 2)", "box 0 6 10 5")).is_err());
+    }
+
+    #[test]
+    fn parse_circle_and_line_distributions() {
+        let rules = |distribution: &str| parse_str(&format!("size (10, 5) states {{ (dead, 0, 0, 0), (wave, 255, 200, 0, {}), }} \
+            transitions {{ }}", distribution));
+        assert_eq!(rules("circle 4 2 2").unwrap().states[1].distribution, StateDistribution::Circle(4, 2, 2));
+        assert_eq!(rules("line 0 4 9 0").unwrap().states[1].distribution, StateDistribution::Line(0, 4, 9, 0));
+        assert_eq!(rules("circle 8 2 2").unwrap_err(),
+                   vec!["For state \"wave\", the circle of radius 2 at 8, 2 goes beyond the world of 10 x 5 cells."]);
+        assert_eq!(rules("line 0 5 9 0").unwrap_err(),
+                   vec!["For state \"wave\", the line from 0, 5 to 9, 0 goes beyond the world of 10 x 5 cells."]);
+    }
+
+    #[test]
+    fn parse_condition_undefined_state_fails() {
+        match parse(CONDITION_UNDEFINED_STATE_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0], "A condition refers to the state \"happy\", but it\'s not defined.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_no_states_fails() {
         match parse(NO_STATES_FILE) {
             Err(errors) => {
                 assert_eq!(errors.len(), 2);
@@ -359,6 +1354,502 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_lints_warnings() {
+        match parse(LINTS_FILE) {
+            Ok(rules) => assert_eq!(rules.warnings, vec![
+                "The transition 'ghost -> alive' can never be triggered, because the transition 'ghost -> dead' is always triggered before.",
+                "The condition \"alive > 8\" of the transition 'alive -> dead' can never be true, because a cell has 8 neighbors.",
+                "The condition \"rand 0\" of the transition 'alive -> dead' can never be true."
+            ]),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_neighbor_outside_neighborhood_warnings() {
+        match parse(NEIGHBOR_OUTSIDE_NEIGHBORHOOD_FILE) {
+            Ok(rules) => assert_eq!(rules.warnings, vec![
+                "The transition 'dead -> alive' refers to the neighbor \"A\" (up left), which is not in the \"von_neumann 1\" neighborhood.",
+                "The transition 'alive -> dead' refers to the neighbor \"H\" (down right), which is not in the \"von_neumann 1\" neighborhood.",
+                "The transition 'fire -> fire' refers to the neighbor \"C\" (up right), which is not in the \"von_neumann 1\" neighborhood."
+            ]),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_degenerate_world_sizes() {
+        let rules = |size: &str, directives: &str| format!("size {}\n{}\nstates {{ (dead, 0, 0, 0), (alive, 255, 255, 255, quantity 1), }}\n\
+                                                            transitions {{ (dead, alive, alive == 3), }}", size, directives);
+        let errors = parse_str(&rules("(0, 10)", "")).err().unwrap();
+        assert_eq!(errors[0], "Expected an unsigned integer > 0, found \"0\" - line 1, column 7.");
+        let options = CompilerOptions { world_size: Some((5, 0)), ..CompilerOptions::default() };
+        let errors = parse_str_with_options(&rules("(5, 5)", ""), &options).err().unwrap();
+        assert_eq!(errors, vec!["The world size (5, 0) must have at least a cell in each dimension."]);
+
+        assert_eq!(parse_str(&rules("(1, 1)", "")).unwrap().warnings, vec![
+            "The world of 1 x 1 cells is narrower than the neighborhood, which spans 3 cells : as the world wraps around, its cells count some neighbors several times, or themselves."
+        ]);
+        assert_eq!(parse_str(&rules("(8, 4)", "neighborhood moore 2")).unwrap().warnings.len(), 1);
+        assert!(parse_str(&rules("(8, 5)", "neighborhood moore 2")).unwrap().warnings.is_empty());
+        // The cells of a walled world don't see the other side of the world.
+        assert!(parse_str(&rules("(1, 1)", "boundary wall dead")).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn parse_str_and_reader_like_a_file() {
+        let text = fs::read_to_string("resources/game_of_life.txt").unwrap();
+        let rules = parse("resources/game_of_life.txt").unwrap();
+        for other in [parse_str(&text).unwrap(), parse_reader(text.as_bytes(), &CompilerOptions::default()).unwrap()].iter() {
+            assert_eq!(other.world_size, rules.world_size);
+            assert_eq!(other.states.iter().map(|state| &state.name).collect::<Vec<_>>(), rules.states.iter().map(|state| &state.name).collect::<Vec<_>>());
+            assert_eq!(other.transitions.len(), rules.transitions.len());
+        }
+        assert_eq!(parse_str("size (10, 10)\n\nstates {\n    (dead, 0, 0, 0)\n").unwrap_err(),
+                   vec!["Expected \",\", found \"\" - line 5, column 0."]);
+    }
+
+    #[test]
+    fn parse_count9_conditions() {
+        let rules = parse("resources/tests/automaton_count9.txt").unwrap();
+        assert!(matches!(rules.transitions[0].2[0][..], [Condition::QuantityCondition(1, ComparisonOperator::GreaterOrEqual, 5, CountedCells::NeighborsAndSelf)]));
+        let rules = parse_str("size (10, 10)\nstates {\n    (dead, 0, 0, 0),\n}\ntransitions {\n    (dead, dead, count9(dead) > 9),\n}\n").unwrap();
+        assert_eq!(rules.warnings, vec!["The condition \"count9(dead) > 9\" of the transition 'dead -> dead' can never be true, because a cell and its neighbors are 9 cells."]);
+        assert_eq!(parse_str("size (10, 10)\nstates {\n    (dead, 0, 0, 0),\n}\ntransitions {\n    (dead, dead, count9(dead) > 10),\n}\n").unwrap_err(),
+                   vec!["Expected an integer between 0 and 9, found \"10\" - line 6, column 34."]);
+    }
+
+    #[test]
+    fn parse_world_size_override() {
+        let options = CompilerOptions { world_size: Some((200, 60)), ..CompilerOptions::default() };
+        assert_eq!(parse_with_options("resources/langton_ant.txt", &options).unwrap().world_size, (200, 60));
+        let options = CompilerOptions { world_size: Some((40, 40)), ..CompilerOptions::default() };
+        assert_eq!(parse_with_options("resources/langton_ant.txt", &options).unwrap_err(), vec!["The agent at (50, 50) is outside of the world."]);
+    }
+
+    #[test]
+    fn parse_lints_levels() {
+        let options = CompilerOptions { lint_levels: vec![(Lint::ImpossibleCondition, LintLevel::Allow)], ..CompilerOptions::default() };
+        match parse_with_options(LINTS_FILE, &options) {
+            Ok(rules) => assert_eq!(rules.warnings.len(), 1),
+            _ => assert!(false)
+        }
+
+        let options = CompilerOptions { deny_warnings: true, lint_levels: vec![(Lint::ShadowedTransition, LintLevel::Allow)], ..CompilerOptions::default() };
+        match parse_with_options(LINTS_FILE, &options) {
+            Err(errors) => assert_eq!(errors.len(), 2),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_with_catalog_translates_errors() {
+        let catalog = Catalog::load(FRENCH_CATALOG_FILE).unwrap();
+        let options = CompilerOptions { catalog: Some(catalog), ..CompilerOptions::default() };
+        match parse_with_options(NO_STATES_FILE, &options) {
+            Err(errors) => {
+                assert_eq!(errors[0], "Il faut déclarer au moins un état.");
+                assert_eq!(errors[1], "There must be exactly one default state (without a distribution specified), but there are currently 0 of such states.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_init_csv() {
+        match parse(INIT_CSV_FILE) {
+            Ok(rules) => assert_eq!(rules.initial_grid, Some(vec![0, 1, 0, 0, 1, 0, 1, 0, 1, 1, 0, 1])),
+            _ => assert!(false)
+        }
+        let options = CompilerOptions { world_size: Some((4, 4)), ..CompilerOptions::default() };
+        assert_eq!(parse_with_options(INIT_CSV_FILE, &options).unwrap_err(),
+                   vec!["The initial grid resources/tests/semantic_init_csv.csv has 3 rows, but the world has 4 rows."]);
+    }
+
+    #[test]
+    fn parse_init_csv_errors_fails() {
+        match parse(INIT_CSV_ERRORS_FILE) {
+            Err(errors) => assert_eq!(errors, vec![
+                "The \"init\" directive is declared more than once.",
+                "The cell \"zombie\" at row 2, column 3 of the initial grid resources/tests/semantic_init_csv_errors.csv is neither a state name nor a state id."
+            ]),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_image_distribution() {
+        let rules = parse(IMAGE_FILE).unwrap();
+        assert_eq!(rules.states[1].distribution, StateDistribution::Image("resources/tests/semantic_image.png".to_string()));
+        assert_eq!(rules.states[2].distribution, StateDistribution::Image("resources/tests/semantic_image.png".to_string()));
+
+        let options = CompilerOptions { world_size: Some((4, 4)), ..CompilerOptions::default() };
+        assert_eq!(parse_with_options(IMAGE_FILE, &options).unwrap_err(), vec![
+            "For state \"alive\", the image resources/tests/semantic_image.png has 4 x 3 pixels, but the world has 4 x 4 cells.",
+            "For state \"wall\", the image resources/tests/semantic_image.png has 4 x 3 pixels, but the world has 4 x 4 cells."
+        ]);
+        let errors = parse_str("size (4, 3) states { (dead, 0, 0, 0), (alive, 255, 255, 255, from_image \"missing.png\"), } \
+            transitions { (dead, alive, alive == 3), }").unwrap_err();
+        assert!(errors[0].to_string().contains("Cannot read the image missing.png."));
+    }
+
+    #[test]
+    fn parse_pattern_section() {
+        let rules = parse(PATTERN_FILE).unwrap();
+        assert_eq!(rules.initial_cells, vec![((2, 1), 1), ((3, 2), 1), ((1, 3), 1), ((2, 3), 1), ((3, 3), 1)]);
+
+        // The pattern follows the agent rules.
+        let text = "size (4, 3) states { (white, 255, 255, 255), (black, 0, 0, 0, quantity 0), } transitions { } \
+            agents { (white, right, black), } pattern { (1, 2) black, (4, 0) black, (0, 0) grey, }";
+        assert_eq!(parse_str(text).unwrap_err(), vec![
+            "The cell (4, 0) of the pattern is outside of the world of 4 x 3 cells.",
+            "The cell (0, 0) of the pattern refers to the state \"grey\", but it's not defined."
+        ]);
+    }
+
+    #[test]
+    fn parse_color_gradients() {
+        let rules = parse_str("size (4, 3) states { (dead, 0, 0, 0), (young, #003300..#00FF00 over 4, quantity 1), \
+            (old, 0, 0, 0 .. 255, 255, 255 over 1, quantity 1), } transitions { }").unwrap();
+        assert_eq!(rules.states[1].gradient, Some(Gradient { end: (0, 255, 0), ticks: 4 }));
+        assert_eq!(rules.colors(), vec![(0, 0, 0), (0, 51, 0), (0, 0, 0), (0, 102, 0), (0, 153, 0), (0, 204, 0), (0, 255, 0), (255, 255, 255)]);
+        assert_eq!(rules.color_count(), 8);
+        assert_eq!((0..6).map(|age| rules.color_index(1, age)).collect::<Vec<_>>(), vec![1, 3, 4, 5, 6, 6]);
+        assert_eq!((0..3).map(|age| rules.color_index(2, age)).collect::<Vec<_>>(), vec![2, 7, 7]);
+        assert_eq!(rules.color_index(0, 10), 0);
+
+        assert!(parse_str("size (4, 3) states { (dead, 0, 0, 0), (alive, #003300..#00FF00 over 0), } transitions { }").is_err());
+    }
+
+    #[test]
+    fn parse_hex_colors() {
+        match parse(HEX_COLOR_FILE) {
+            Ok(rules) => assert_eq!(rules.states.iter().map(|s| s.color).collect::<Vec<_>>(), vec![(0, 0, 0), (255, 0, 170), (12, 34, 56)]),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_delay_copies_origin_color() {
+        match parse(DELAY_FILE) {
+            Ok(rules) => {
+                assert_eq!(rules.states.len(), 5);
+                assert!(rules.states[2..].iter().all(|s| s.color == (255, 0, 0) && s.name == "burning"));
+                assert_eq!(rules.user_states().len(), 2);
+                assert_eq!((0..5).map(|s| rules.parent_state(s)).collect::<Vec<_>>(), vec![0, 1, 1, 1, 1]);
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_several_delays_from_the_same_state() {
+        match parse(VIRUS_FILE) {
+            Ok(rules) => {
+                // The infected state has a delay of 15 and a delay of 10, so 14 + 9 implicit states.
+                assert_eq!(rules.states.len(), 4 + 14 + 9);
+                assert!((4..rules.states.len()).all(|s| rules.parent_state(s) == 1));
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_delay_with_color_gradient_succeeds() {
+        let options = CompilerOptions { delay_color_gradient: true, ..CompilerOptions::default() };
+        match parse_with_options(DELAY_FILE, &options) {
+            Ok(rules) => {
+                assert_eq!(rules.states.len(), 5);
+                assert_eq!(rules.states[2].color, (191, 0, 0));
+                assert_eq!(rules.states[3].color, (128, 0, 0));
+                assert_eq!(rules.states[4].color, (64, 0, 0));
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_memory_succeeds() {
+        match parse(MEMORY_FILE) {
+            Ok(rules) => assert!(rules.memory),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_missing_memory_fails() {
+        match parse(MISSING_MEMORY_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0], "A condition refers to the previous iteration, but the \"memory\" directive is not declared.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_noise_succeeds() {
+        match parse(NOISE_FILE) {
+            Ok(rules) => assert_eq!(rules.noise, 0.001),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_boundary_errors_fails() {
+        match parse(BOUNDARY_ERRORS_FILE) {
+            Err(errors) => assert_eq!(errors, vec![
+                "The \"boundary\" directive is declared more than once.",
+                "The boundary wall refers to the state \"ghost\", but it's not defined."
+            ]),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_neighborhood_errors_fails() {
+        match parse(NEIGHBORHOOD_ERRORS_FILE) {
+            Err(errors) => assert_eq!(errors, vec![
+                "The \"neighborhood\" directive is declared more than once.",
+                "The neighborhood has 440 neighbors, but a quantity condition can count at most 255."
+            ]),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_three_dimensional() {
+        match parse(THREE_DIMENSIONAL_FILE) {
+            Ok(rules) => {
+                assert_eq!((rules.world_size, rules.depth), ((100, 40), 20));
+                assert_eq!(rules.grid_size(), (100, 800));
+                assert_eq!(rules.neighbor_offsets.len(), 26);
+                assert_eq!(rules.neighborhood_cell_count(), 27);
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_three_dimensional_errors_fails() {
+        match parse(THREE_DIMENSIONAL_ERRORS_FILE) {
+            Err(errors) => assert_eq!(errors, vec![
+                "The conditions on a neighbor, like \"G is dead\", are not supported in a 3D world.",
+                "The distance conditions are not supported in a 3D world.",
+                "The effects and the swaps are not supported in a 3D world.",
+                "The agents are not supported in a 3D world.",
+                "A 3D world wraps around, the \"boundary\" directive is not supported."
+            ]),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_noise_errors_fails() {
+        match parse(NOISE_ERRORS_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 2);
+                assert_eq!(errors[0], "The \"noise\" directive is declared more than once.");
+                assert_eq!(errors[1], "A second-order automaton cannot have noise, because it must be reversible.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_regions_succeeds() {
+        match parse(REGIONS_FILE) {
+            Ok(rules) => {
+                assert_eq!(rules.regions.len(), 2);
+                assert!(matches!(rules.regions[0].kind, RegionKind::Frozen));
+                assert!(matches!(rules.regions[1].kind, RegionKind::Source(2)));
+                assert_eq!((rules.regions[1].x, rules.regions[1].y, rules.regions[1].width, rules.regions[1].height), (0, 0, 1, 50));
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_regions_undefined_state_fails() {
+        match parse(REGIONS_UNDEFINED_STATE_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0], "A source region refers to the state \"lava\", but it's not defined.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_data_succeeds() {
+        match parse(DATA_FILE) {
+            Ok(rules) => {
+                assert_eq!(rules.data_channel.as_deref(), Some("heat"));
+                assert!(matches!(rules.transitions[0].2[0][0], Condition::DataCondition(ComparisonOperator::GreaterOrEqual, 3)));
+                assert_eq!(rules.transitions[0].3.set_data, None);
+                assert_eq!(rules.transitions[1].3.set_data, Some(3));
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_data_name_clash_fails() {
+        match parse(DATA_NAME_CLASH_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0], "The data channel \"alive\" has the name of a state.");
+            },
+            _ => assert!(false)
+        }
+        let rules = |channel: &str| format!("size (10, 10) data {} states {{ (dead, 0, 0, 0), (alive, 255, 255, 255, quantity 1), }} \
+            transitions {{ (alive, dead, alive < 2), }}", channel);
+        assert!(parse_str(&rules("heat")).is_ok());
+        assert_eq!(parse_str(&rules("rand")).unwrap_err(),
+                   vec!["The data channel \"rand\" has the name of a keyword, one of true, rand, was, count9, distance."]);
+        assert_eq!(parse_str(&rules("distance")).unwrap_err().len(), 1);
+        assert!(parse_str(&rules("C")).unwrap_err()[0].to_string().starts_with("The data channel \"C\" has the name of a neighbor cell, one of \"A\" (up left)"));
+    }
+
+    #[test]
+    fn parse_effects_succeeds() {
+        match parse(EFFECTS_FILE) {
+            Ok(rules) => {
+                let neighbors = &rules.transitions[0].3.neighbors;
+                assert_eq!(neighbors.len(), 2);
+                assert!(matches!(neighbors[0], (NeighborCell::G, 1)));
+                assert!(matches!(neighbors[1], (NeighborCell::B, 0)));
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_effects_undefined_state_fails() {
+        match parse(EFFECTS_UNDEFINED_STATE_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0], "An effect refers to the state \"smoke\", but it's not defined.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_swap_succeeds() {
+        match parse(SWAP_FILE) {
+            Ok(rules) => {
+                assert_eq!((rules.transitions[0].0, rules.transitions[0].1), (1, 1));
+                assert!(matches!(rules.transitions[0].3.swap, Some(NeighborCell::G)));
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_vote_succeeds() {
+        let rules = parse("resources/tests/automaton_vote.txt").unwrap();
+        assert_eq!((rules.transitions[1].0, rules.transitions[1].1), (1, 1));
+        assert_eq!(rules.transitions.iter().map(|transition| transition.3.vote).collect::<Vec<_>>(), vec![Some(TieBreak::Keep); 3]);
+        let text = fs::read_to_string("resources/tests/automaton_vote.txt").unwrap().replace("(red, vote, true)", "(red, vote random, true)");
+        assert_eq!(parse_str(&text).unwrap().transitions[2].3.vote, Some(TieBreak::Random));
+        let text = text.replace("(red, vote random, true)", "(red, vote often, true)");
+        assert!(parse_str(&text).is_err());
+    }
+
+    #[test]
+    fn parse_agents_succeeds() {
+        match parse(AGENTS_FILE) {
+            Ok(rules) => {
+                assert_eq!(rules.agents.len(), 1);
+                assert_eq!(rules.agents[0].position, (50, 50));
+                assert_eq!(rules.agent_rules.len(), 2);
+                assert_eq!((rules.agent_rules[1].cell_state, rules.agent_rules[1].new_cell_state), (1, 0));
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_agents_errors_fails() {
+        match parse(AGENTS_ERRORS_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 2);
+                assert_eq!(errors[0], "The agent at (10, 5) is outside of the world.");
+                assert_eq!(errors[1], "An agent rule refers to the state \"red\", but it's not defined.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_distance_succeeds() {
+        match parse(DISTANCE_FILE) {
+            Ok(rules) => {
+                assert!(matches!(rules.transitions[0].2[0][0], Condition::DistanceCondition(2, ComparisonOperator::LesserOrEqual, 2)));
+                assert_eq!(rules.distance_states(), vec![2]);
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_second_order_succeeds() {
+        match parse(SECOND_ORDER_FILE) {
+            Ok(rules) => assert!(rules.second_order),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_second_order_errors_fails() {
+        match parse(SECOND_ORDER_ERRORS_FILE) {
+            Err(errors) => {
+                assert_eq!(errors.len(), 2);
+                assert_eq!(errors[0], "A second-order automaton cannot have random conditions, because it must be reversible.");
+                assert_eq!(errors[1], "A second-order automaton cannot have delayed transitions, because it must be reversible.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_small_default_proportion_warns() {
+        match parse(SMALL_DEFAULT_PROPORTION_FILE) {
+            Ok(rules) => {
+                assert_eq!(rules.default_state(), 1);
+                assert!((rules.default_proportion() - 0.005).abs() < 1e-9);
+                assert_eq!(rules.warnings.len(), 1);
+                assert_eq!(rules.warnings[0], "The default state \"dead\" only gets the remaining proportion 0.0050, which is lesser than 0.01.");
+            },
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_small_default_proportion_with_lower_threshold_succeeds() {
+        let options = CompilerOptions { default_proportion_warning_threshold: 0.001, ..CompilerOptions::default() };
+        match parse_with_options(SMALL_DEFAULT_PROPORTION_FILE, &options) {
+            Ok(rules) => assert!(rules.warnings.is_empty()),
+            _ => assert!(false)
+        }
+    }
+
+    #[test]
+    fn parse_priorities_succeeds() {
+        match parse(PRIORITIES_FILE) {
+            Ok(rules) => {
+                let order = rules.transitions.iter().map(|(o, d, _, _)| (*o, *d)).collect::<Vec<_>>();
+                assert_eq!(order, vec![(0, 2), (1, 3), (0, 1), (0, 2), (3, 0), (1, 2), (1, 0)]);
+                assert_eq!(rules.warnings.len(), 1);
+                assert_eq!(rules.warnings[0], "The transitions 'dead -> zombie' and 'dead -> alive' have the same priority and can both be triggered, \
+                    so the first one declared is used. Consider using \"priority\" to make the order explicit.");
+            },
+            _ => assert!(false)
+        }
+    }
+
     #[test]
     fn parse_quantities_too_much_fails() {
         match parse(QUANTITIES_TOO_MUCH_FILE) {
@@ -432,4 +1923,15 @@ mod tests {
             _ => assert!(false)
         }
     }
+
+    #[test]
+    fn parse_random_draws() {
+        let draws = |condition: &str| parse_str(&format!("size (8, 8) states {{ (a, 0, 0, 0), }} transitions {{ (a, a, {}), }}", condition))
+            .map(|rules| rules.transitions[0].2[0].clone());
+        assert!(matches!(draws("rand 0.1").unwrap()[..], [Condition::RandomCondition(_, RandomDraw::Cell)]));
+        assert!(matches!(draws("rand 0.1 per tick").unwrap()[..], [Condition::RandomCondition(_, RandomDraw::Tick)]));
+        assert!(matches!(draws("rand 0.1 per block 4 && a > 1").unwrap()[..], [Condition::RandomCondition(_, RandomDraw::Block(4)), _]));
+        assert!(draws("rand 0.1 per block 0").is_err());
+        assert!(draws("rand 0.1 per cell").is_err());
+    }
 }
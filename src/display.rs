@@ -1,76 +1,216 @@
-use std::io::{stdout, Write};
+use std::time::{Duration, Instant};
+use crate::automaton::Automaton;
 use crate::camera::Image;
+use crate::inputs::UserAction;
 
-pub struct Display {
-    last_image: Vec<Vec<usize>>,
-    colors: Vec<(u8, u8, u8)>, // ansi color
-    redraw: bool
+pub mod recorder;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use terminal::TerminalDisplay;
+
+/// Renders the images captured by the camera.
+pub trait Display {
+    fn init(&mut self);
+    /// Renders the image, and the status of the simulation if it is given and the display can show it.
+    fn render(&mut self, image: &Image, status: Option<&Status>);
+    /// Returns the next action of the user on the display, for the displays with their own window.
+    /// The terminal is read by the executor instead.
+    fn read_inputs(&mut self) -> UserAction {
+        UserAction::Nop
+    }
+    /// Called once the run is over.
+    fn clean(&mut self);
 }
 
-impl Display {
-    pub fn new() -> Display {
-        Display {
-            last_image: Vec::new(),
-            colors: Vec::new(),
-            redraw: true,
-        }
+/// What is shown beside the cells : the iteration, the speed of the simulation and the population of each state.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+    pub iteration: usize,
+    pub speed: f64, // In iterations per second
+    pub populations: Vec<(String, usize)> // The name and the number of cells of each state declared by the rules
+}
+
+impl Status {
+    pub fn new(automaton: &Automaton, speed: f64) -> Status {
+        let populations = automaton.get_rules().user_states().iter()
+            .zip(automaton.state_counts())
+            .map(|(state, count)| (state.name.clone(), count))
+            .collect();
+        Status { iteration: automaton.get_iteration(), speed, populations }
     }
 
-    pub fn init(&self) {
-        print!("{}", termion::clear::All);
-        stdout().flush().unwrap();
+    /// Returns the status in a line, like "Iteration 42 | 120.5 it/s | dead 900 | alive 100".
+    pub fn line(&self) -> String {
+        let mut parts = vec![format!("Iteration {}", self.iteration), format!("{:.1} it/s", self.speed)];
+        parts.extend(self.populations.iter().map(|(name, count)| format!("{} {}", name, count)));
+        parts.join(" | ")
     }
+}
 
-    pub fn render(&mut self, image: & Image) {
-        if self.colors.is_empty() {
-            self.colors = image.colors.iter()
-                .map(|(r, g, b)| (to_ansi_value(*r), to_ansi_value(*g), to_ansi_value(*b)))
-                .collect::<Vec<_>>();
+/// Decides which iterations are rendered, so that the display refreshes at a target rate whatever the speed of the ticks.
+pub struct FramePacer {
+    frame_duration: Option<Duration>, // None means that every iteration is rendered
+    skip_when_behind: bool,
+    next_frame: Option<Instant>
+}
+
+impl FramePacer {
+    /// With a target rate, the iterations between two frames are not rendered.
+    /// If `skip_when_behind` is true, the frames missed because of slow iterations are dropped instead of being rendered in a row.
+    pub fn new(target_fps: Option<u32>, skip_when_behind: bool) -> FramePacer {
+        FramePacer {
+            frame_duration: target_fps.map(|fps| Duration::from_secs(1) / fps.max(1)),
+            skip_when_behind,
+            next_frame: None
         }
+    }
 
-        // Note : The case where the number of lines or columns of the image is 0 should be forbidden at configuration level.
-
-        if (image.grid.len() != self.last_image.len()) || (image.grid[0].len() != self.last_image[0].len()) {
-            // Clean-up old parts of display not used anymore if new image is smaller
-            for x in 0..self.last_image.len() {
-                for y in 0..self.last_image[0].len() {
-                    if x >= image.grid.len() || y >= image.grid[0].len() {
-                        println!("{}{}\u{2588}",
-                                 termion::cursor::Goto((x + 1) as u16, (y + 1) as u16),
-                                 termion::color::Fg(termion::color::AnsiValue::rgb(0, 0, 0)));
-                    }
-                }
+    /// Returns true if a frame must be rendered at this instant.
+    pub fn should_render(&mut self, now: Instant) -> bool {
+        let frame_duration = match self.frame_duration {
+            Some(duration) => duration,
+            None => return true
+        };
+        match self.next_frame {
+            Some(next_frame) if now < next_frame => false,
+            Some(next_frame) if !self.skip_when_behind || now < next_frame + frame_duration => {
+                self.next_frame = Some(next_frame + frame_duration);
+                true
+            },
+            _ => {
+                self.next_frame = Some(now + frame_duration);
+                true
             }
+        }
+    }
+}
+
+/// Measures the speed of the simulation over the last second, so that it follows the changes of the rules or the pauses.
+#[derive(Default)]
+pub struct SpeedMeter {
+    window_start: Option<(Instant, usize)>, // The instant and the iteration the current measure started at
+    speed: f64
+}
 
-            self.last_image = vec![vec![0; image.grid[0].len()]; image.grid.len()];
-            self.redraw = true;
+impl SpeedMeter {
+    pub fn new() -> SpeedMeter {
+        SpeedMeter::default()
+    }
+
+    /// Returns the iterations per second of the last complete second.
+    pub fn measure(&mut self, now: Instant, iteration: usize) -> f64 {
+        match self.window_start {
+            Some((start, first_iteration)) if now - start >= Duration::from_secs(1) => {
+                self.speed = iteration.saturating_sub(first_iteration) as f64 / (now - start).as_secs_f64();
+                self.window_start = Some((now, iteration));
+            },
+            Some(_) => {},
+            None => self.window_start = Some((now, iteration))
         }
+        self.speed
+    }
+}
 
-        for x in 0..image.grid.len() {
-            for y in 0..image.grid[0].len() {
-                if self.redraw || image.grid[x][y] != self.last_image[x][y] {
-                    let color_index = image.grid[x][y];
-                    let (r, g, b) = self.colors[color_index];
-                    print!("{}{}\u{2588}",
-                           termion::cursor::Goto((x + 1) as u16, (y + 1) as u16),
-                           termion::color::Fg(termion::color::AnsiValue::rgb(r, g, b)));
-                    self.last_image[x][y] = image.grid[x][y];
-                }
-            }
+/// Runs the simulation at a fixed number of ticks per second, whatever the time taken by the rendering :
+/// the ticks missed during a slow frame are run in a row before the next frame, so that the simulated time follows the wall-clock time.
+pub struct TickGovernor {
+    tick_duration: Duration,
+    max_catch_up: u32, // Beyond this number of missed ticks, the late ticks are dropped instead of freezing the display
+    next_tick: Option<Instant>
+}
+
+impl TickGovernor {
+    /// The ticks missed are caught up to a quarter of a second of simulated time.
+    pub fn new(target_tps: u32) -> TickGovernor {
+        TickGovernor {
+            tick_duration: Duration::from_secs(1) / target_tps.max(1),
+            max_catch_up: (target_tps / 4).max(1),
+            next_tick: None
+        }
+    }
+
+    /// Returns the number of ticks to run at this instant, 0 if the next tick is not due yet.
+    pub fn due_ticks(&mut self, now: Instant) -> u32 {
+        let next_tick = match self.next_tick {
+            Some(next_tick) if now < next_tick => return 0,
+            Some(next_tick) => next_tick,
+            None => now
+        };
+        let due = ((now - next_tick).as_nanos() / self.tick_duration.as_nanos()) as u32 + 1;
+        if due > self.max_catch_up {
+            self.next_tick = Some(now + self.tick_duration);
+            self.max_catch_up
+        } else {
+            self.next_tick = Some(next_tick + self.tick_duration * due);
+            due
         }
+    }
 
-        self.redraw = false;
-        stdout().flush().unwrap();
+    /// Returns the time left before the next tick.
+    pub fn time_to_next_tick(&self, now: Instant) -> Duration {
+        self.next_tick.map_or(Duration::new(0, 0), |next_tick| next_tick.saturating_duration_since(now))
     }
 
-    pub fn clean(&mut self) {
-        let cursor_vert_pos = if self.last_image.is_empty() { 1 } else { self.last_image[0].len() + 1 };
-        print!("{}{}", termion::cursor::Goto(1, cursor_vert_pos as u16), termion::color::Fg(termion::color::White));
-        stdout().flush().unwrap();
+    /// Starts again from the next call, without catching up the ticks missed during a pause.
+    pub fn resume(&mut self) {
+        self.next_tick = None;
     }
 }
 
-/// Map a [0; 255] value to a [0; 5] value
-fn to_ansi_value(x: u8) -> u8 {
-    (x as f64 * 5.0 / 255.0).round() as u8
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::display::{FramePacer, SpeedMeter, Status, TickGovernor};
+
+    #[test]
+    fn frame_pacer_renders_at_target_rate() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut uncapped = FramePacer::new(None, false);
+        assert!(uncapped.should_render(at(0)) && uncapped.should_render(at(0)));
+
+        let mut pacer = FramePacer::new(Some(10), false);
+        let rendered: Vec<bool> = [0, 50, 100, 150, 400, 410, 420].iter().map(|t| pacer.should_render(at(*t))).collect();
+        assert_eq!(rendered, vec![true, false, true, false, true, true, true]);
+
+        let mut pacer = FramePacer::new(Some(10), true);
+        let rendered: Vec<bool> = [0, 100, 400, 410, 420, 500].iter().map(|t| pacer.should_render(at(*t))).collect();
+        assert_eq!(rendered, vec![true, true, true, false, false, true]);
+    }
+
+    #[test]
+    fn tick_governor_catches_up_the_missed_ticks() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut governor = TickGovernor::new(20);
+        let ticks: Vec<u32> = [0, 20, 50, 100, 260, 1000, 1010, 1050].iter().map(|t| governor.due_ticks(at(*t))).collect();
+        assert_eq!(ticks, vec![1, 0, 1, 1, 3, 5, 0, 1]);
+        assert_eq!(governor.time_to_next_tick(at(1050)), Duration::from_millis(50));
+
+        governor.resume();
+        assert_eq!(governor.due_ticks(at(5000)), 1);
+        assert_eq!(governor.time_to_next_tick(at(5000)), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn speed_meter_measures_the_last_second() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut meter = SpeedMeter::new();
+        let speeds: Vec<f64> = [(0, 0), (500, 30), (1000, 50), (1500, 200), (2000, 250), (3000, 250)].iter()
+            .map(|(t, iteration)| meter.measure(at(*t), *iteration))
+            .collect();
+        assert_eq!(speeds, vec![0.0, 0.0, 50.0, 50.0, 200.0, 0.0]);
+    }
+
+    #[test]
+    fn status_line() {
+        let status = Status { iteration: 42, speed: 120.54, populations: vec![("dead".to_string(), 900), ("alive".to_string(), 100)] };
+        assert_eq!(status.line(), "Iteration 42 | 120.5 it/s | dead 900 | alive 100");
+    }
 }
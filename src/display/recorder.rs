@@ -0,0 +1,294 @@
+//! This module records the images captured by a camera, and writes them as an animation once the run is over.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use crate::camera::Image;
+use crate::display::{Display, Status};
+use crate::manifest::MANIFEST_KEYWORD;
+
+/// The palette of an animation, and the palette index of each cell of each frame.
+type Animation = (Vec<(u8, u8, u8)>, Vec<Vec<u8>>);
+
+/// Accumulates the images, and writes an animated GIF, or an animated PNG if the file name ends with ".png" or ".apng".
+/// The animation has a palette of at most 256 colors, so the images must not have more different colors. The palette holds
+/// the exact colors of the pixels, those of the delays and the gradients included, as they are displayed : nothing is quantized.
+pub struct Recorder {
+    file_name: String,
+    scale: usize, // The width and height in pixels of a cell
+    frame_delay: u16, // In milliseconds
+    crossfade: usize, // The number of frames blending the colors of each frame into the next one, inserted between them
+    palette: Vec<(u8, u8, u8)>,
+    color_indices: HashMap<(u8, u8, u8), u8>, // The index in the palette of each color
+    size: (usize, usize),
+    frames: Vec<Vec<u8>>, // The palette index of each cell, row after row
+    manifest: Option<String> // Embedded in the animation if set
+}
+
+impl Recorder {
+    pub fn new(file_name: &str, scale: usize, frame_delay: u16) -> Recorder {
+        Recorder {
+            file_name: file_name.to_string(),
+            scale: scale.max(1),
+            frame_delay,
+            crossfade: 0,
+            palette: Vec::new(),
+            color_indices: HashMap::new(),
+            size: (0, 0),
+            frames: Vec::new(),
+            manifest: None
+        }
+    }
+
+    /// Inserts the given number of frames between two recorded frames, which fade the colors of the cells that change
+    /// from the first frame into the second one. The animation keeps its speed, the frames being shorter.
+    pub fn set_crossfade(&mut self, frames: usize) {
+        self.crossfade = frames;
+    }
+
+    /// Embeds the manifest of the run in the animation : in a comment of a GIF, or in a text chunk of an animated PNG.
+    pub fn set_manifest(&mut self, manifest: Option<String>) {
+        self.manifest = manifest;
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Writes the animation of the recorded frames.
+    pub fn save(&self) -> Result<(), String> {
+        if self.palette.len() > 256 {
+            return Err(format!("The images have {} different colors, but an animation can only have 256 colors.", self.palette.len()));
+        }
+        let (palette, frames) = self.crossfaded_frames()?;
+        let frame_delay = self.frame_delay / (self.crossfade + 1) as u16;
+        let file = File::create(&self.file_name).map_err(|error| format!("{:?}", error))?;
+        let file_name = self.file_name.to_lowercase();
+        if file_name.ends_with(".png") || file_name.ends_with(".apng") {
+            self.write_apng(BufWriter::new(file), &palette, &frames, frame_delay).map_err(|error| error.to_string())
+        } else {
+            self.write_gif(BufWriter::new(file), &palette, &frames, frame_delay).map_err(|error| error.to_string())
+        }
+    }
+
+    /// Returns the palette and the frames of the animation, with the cross-fading frames and their blended colors.
+    fn crossfaded_frames(&self) -> Result<Animation, String> {
+        if self.crossfade == 0 {
+            return Ok((self.palette.clone(), self.frames.clone()));
+        }
+        let mut palette = self.palette.clone();
+        // The palette index of the color blended at a step from a color into another one.
+        let mut blends: HashMap<(u8, u8, usize), u8> = HashMap::new();
+        let mut frames = Vec::new();
+        for (from_frame, to_frame) in self.frames.iter().zip(self.frames.iter().skip(1)) {
+            frames.push(from_frame.clone());
+            for step in 1..=self.crossfade {
+                let t = step as f64 / (self.crossfade + 1) as f64;
+                let mut frame = Vec::with_capacity(from_frame.len());
+                for (from, to) in from_frame.iter().zip(to_frame) {
+                    if from == to {
+                        frame.push(*from);
+                        continue;
+                    }
+                    let index = match blends.get(&(*from, *to, step)) {
+                        Some(index) => *index,
+                        None => {
+                            let color = blend(palette[*from as usize], palette[*to as usize], t);
+                            let index = match palette.iter().position(|c| *c == color) {
+                                Some(index) => index,
+                                None => {
+                                    palette.push(color);
+                                    palette.len() - 1
+                                }
+                            };
+                            if index > 255 {
+                                return Err(format!("The cross-fade of {} frames needs more than 256 colors, which is the most an animation can have.", self.crossfade));
+                            }
+                            blends.insert((*from, *to, step), index as u8);
+                            index as u8
+                        }
+                    };
+                    frame.push(index);
+                }
+                frames.push(frame);
+            }
+        }
+        frames.extend(self.frames.last().cloned());
+        Ok((palette, frames))
+    }
+
+    fn scaled_size(&self) -> (usize, usize) {
+        (self.size.0 * self.scale, self.size.1 * self.scale)
+    }
+
+    /// Returns the palette indices of the pixels of the frame, row after row.
+    fn pixels(&self, frame: &[u8]) -> Vec<u8> {
+        let (width, height) = self.scaled_size();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| frame[(y / self.scale) * self.size.0 + x / self.scale])
+            .collect()
+    }
+
+    fn write_gif(&self, writer: BufWriter<File>, palette: &[(u8, u8, u8)], frames: &[Vec<u8>], frame_delay: u16) -> Result<(), gif::EncodingError> {
+        let (width, height) = self.scaled_size();
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &palette_bytes(palette))?;
+        if let Some(manifest) = &self.manifest {
+            encoder.write_raw_extension(gif::Extension::Comment.into(), &[manifest.as_bytes()])?;
+        }
+        encoder.set_repeat(gif::Repeat::Infinite)?;
+        for frame in frames {
+            let gif_frame = gif::Frame {
+                width: width as u16,
+                height: height as u16,
+                delay: frame_delay / 10, // In hundredths of a second
+                buffer: Cow::Owned(self.pixels(frame)),
+                ..gif::Frame::default()
+            };
+            encoder.write_frame(&gif_frame)?;
+        }
+        Ok(())
+    }
+
+    fn write_apng(&self, writer: BufWriter<File>, palette: &[(u8, u8, u8)], frames: &[Vec<u8>], frame_delay: u16) -> Result<(), png::EncodingError> {
+        let (width, height) = self.scaled_size();
+        let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(palette_bytes(palette));
+        encoder.set_animated(frames.len() as u32, 0)?;
+        encoder.set_frame_delay(frame_delay, 1000)?;
+        if let Some(manifest) = &self.manifest {
+            encoder.add_itxt_chunk(MANIFEST_KEYWORD.to_string(), manifest.clone())?;
+        }
+        let mut writer = encoder.write_header()?;
+        for frame in frames {
+            writer.write_image_data(&self.pixels(frame))?;
+        }
+        writer.finish()
+    }
+}
+
+fn palette_bytes(palette: &[(u8, u8, u8)]) -> Vec<u8> {
+    palette.iter().flat_map(|(r, g, b)| vec![*r, *g, *b]).collect()
+}
+
+/// Returns the color at the fraction `t` of the way from a color to another one.
+fn blend(from: (u8, u8, u8), to: (u8, u8, u8), t: f64) -> (u8, u8, u8) {
+    let component = |from: u8, to: u8| (from as f64 + (to as f64 - from as f64) * t).round() as u8;
+    (component(from.0, to.0), component(from.1, to.1), component(from.2, to.2))
+}
+
+impl Display for Recorder {
+    fn init(&mut self) {}
+
+    /// Records the image. All the images must have the size of the first one.
+    /// The colors of the pixels are added to the palette the first time they are seen, like the new colors of reloaded rules.
+    fn render(&mut self, image: &Image, _status: Option<&Status>) {
+        if self.frames.is_empty() {
+            self.size = (image.grid.len(), image.grid.first().map_or(0, Vec::len));
+        }
+        let (width, height) = self.size;
+        let mut frame = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let color = image.grid[x][y];
+                let palette = &mut self.palette;
+                let index = *self.color_indices.entry(color).or_insert_with(|| {
+                    palette.push(color);
+                    (palette.len() - 1) as u8
+                });
+                frame.push(index);
+            }
+        }
+        self.frames.push(frame);
+    }
+
+    fn clean(&mut self) {
+        match self.save() {
+            Ok(()) => info!("{} frames recorded in {}", self.frames.len(), self.file_name),
+            Err(error) => error!("Cannot record the run in {}. Cause : {}", self.file_name, error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use crate::automaton::Automaton;
+    use crate::camera::{Camera, Image};
+    use crate::compiler::semantic::{parse_with_options, CompilerOptions};
+    use crate::display::Display;
+    use crate::display::recorder::Recorder;
+
+    fn record(file_name: &str) -> Recorder {
+        let mut recorder = Recorder::new(file_name, 2, 100);
+        // Two columns of one cell each.
+        recorder.render(&Image { grid: vec![vec![(255, 0, 0)], vec![(0, 0, 255)]] }, None);
+        recorder.render(&Image { grid: vec![vec![(0, 0, 255)], vec![(255, 0, 0)]] }, None);
+        recorder
+    }
+
+    #[test]
+    fn record_gif() {
+        let file_name = std::env::temp_dir().join("mutations_record.gif");
+        let recorder = record(file_name.to_str().unwrap());
+        assert_eq!(recorder.palette.len(), 2);
+        assert_eq!(recorder.frames, vec![vec![0, 1], vec![1, 0]]);
+        recorder.save().unwrap();
+
+        let mut decoder = gif::DecodeOptions::new().read_info(File::open(&file_name).unwrap()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (4, 2));
+        let mut frames = 0;
+        while decoder.read_next_frame().unwrap().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 2);
+        fs::remove_file(&file_name).unwrap();
+    }
+
+    #[test]
+    fn palette_follows_the_colors_of_the_images() {
+        let mut recorder = record("mutations_palette.gif");
+        // The rules are reloaded with a new color for the first state.
+        recorder.render(&Image { grid: vec![vec![(0, 255, 0)], vec![(255, 0, 0)]] }, None);
+        assert_eq!(recorder.palette, vec![(255, 0, 0), (0, 0, 255), (0, 255, 0)]);
+        assert_eq!(recorder.frames, vec![vec![0, 1], vec![1, 0], vec![2, 0]]);
+    }
+
+    #[test]
+    fn palette_has_the_colors_of_the_delays() {
+        let options = CompilerOptions { delay_color_gradient: true, ..CompilerOptions::default() };
+        let mut automaton = Automaton::with_seed(parse_with_options("resources/tests/semantic_delay.txt", &options).unwrap(), 1);
+        let mut camera = Camera::with_size(0, 0, automaton.get_rules().world_size);
+        let mut recorder = Recorder::new("mutations_delays.gif", 1, 100);
+        for _ in 0..4 {
+            recorder.render(camera.capture(&automaton), None);
+            automaton.tick();
+        }
+        assert_eq!(recorder.palette, vec![(0, 0, 0), (255, 0, 0), (191, 0, 0), (128, 0, 0), (64, 0, 0)]);
+        // The burning cell goes through the colors of the gradient.
+        let burning = |frame: &Vec<u8>| frame.iter().copied().find(|index| *index != 0);
+        assert_eq!(recorder.frames.iter().map(burning).collect::<Vec<_>>(), vec![Some(1), Some(2), Some(3), Some(4)]);
+    }
+
+    #[test]
+    fn crossfade_blends_the_changed_cells() {
+        let mut recorder = record("mutations_crossfade.gif");
+        recorder.set_crossfade(1);
+        let (palette, frames) = recorder.crossfaded_frames().unwrap();
+        assert_eq!(palette, vec![(255, 0, 0), (0, 0, 255), (128, 0, 128)]);
+        assert_eq!(frames, vec![vec![0, 1], vec![2, 2], vec![1, 0]]);
+    }
+
+    #[test]
+    fn record_apng() {
+        let file_name = std::env::temp_dir().join("mutations_record.apng");
+        record(file_name.to_str().unwrap()).save().unwrap();
+
+        let reader = png::Decoder::new(File::open(&file_name).unwrap()).read_info().unwrap();
+        assert_eq!(reader.info().animation_control.unwrap().num_frames, 2);
+        fs::remove_file(&file_name).unwrap();
+    }
+}
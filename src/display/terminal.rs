@@ -0,0 +1,137 @@
+//! This module renders the images in the terminal, with escape sequences.
+
+use std::collections::HashMap;
+use std::io::{stdout, Write};
+use crate::camera::Image;
+use crate::display::{Display, Status};
+use crate::terminal::{ColorDepth, Charset};
+
+/// Renders the images in the terminal, redrawing only the cells that changed. The status is written in the line below the cells.
+pub struct TerminalDisplay {
+    last_image: Vec<Vec<(u8, u8, u8)>>,
+    last_status: Option<String>, // The line of the status written below the cells, if any
+    colors: HashMap<(u8, u8, u8), String>, // The escape sequence of each color already drawn
+    color_depth: ColorDepth,
+    glyph: char,
+    redraw: bool
+}
+
+impl Default for TerminalDisplay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TerminalDisplay {
+    /// Creates a display with 256 colors and block characters.
+    pub fn new() -> TerminalDisplay {
+        TerminalDisplay::with_rendering(ColorDepth::Ansi256, Charset::Block)
+    }
+
+    pub fn with_rendering(color_depth: ColorDepth, charset: Charset) -> TerminalDisplay {
+        TerminalDisplay {
+            last_image: Vec::new(),
+            last_status: None,
+            colors: HashMap::new(),
+            color_depth,
+            glyph: match charset {
+                Charset::Block => '\u{2588}',
+                Charset::Ascii => '#'
+            },
+            redraw: true,
+        }
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn init(&mut self) {
+        print!("{}", termion::clear::All);
+        stdout().flush().unwrap();
+    }
+
+    fn render(&mut self, image: &Image, status: Option<&Status>) {
+        // Note : The case where the number of lines or columns of the image is 0 should be forbidden at configuration level.
+
+        if (image.grid.len() != self.last_image.len()) || (image.grid[0].len() != self.last_image[0].len()) {
+            // The size changes with the terminal, whose content is reflowed : the whole screen is cleared and drawn again.
+            print!("{}", termion::clear::All);
+            self.last_image = vec![vec![(0, 0, 0); image.grid[0].len()]; image.grid.len()];
+            self.redraw = true;
+        }
+
+        for x in 0..image.grid.len() {
+            for y in 0..image.grid[0].len() {
+                let color = image.grid[x][y];
+                if self.redraw || color != self.last_image[x][y] {
+                    let color_depth = self.color_depth;
+                    print!("{}{}{}",
+                           termion::cursor::Goto((x + 1) as u16, (y + 1) as u16),
+                           self.colors.entry(color).or_insert_with(|| foreground(color, color_depth)),
+                           self.glyph);
+                    self.last_image[x][y] = color;
+                }
+            }
+        }
+
+        // The status is cut to the width of the image, so that it doesn't overflow the terminal.
+        let status = status.map(|status| status.line().chars().take(image.grid.len()).collect::<String>());
+        if status.is_some() && (self.redraw || status != self.last_status) {
+            print!("{}{}{}{}",
+                   termion::cursor::Goto(1, (image.grid[0].len() + 1) as u16),
+                   termion::clear::CurrentLine,
+                   foreground((255, 255, 255), self.color_depth),
+                   status.as_deref().unwrap_or_default());
+        }
+        self.last_status = status;
+
+        self.redraw = false;
+        stdout().flush().unwrap();
+    }
+
+    fn clean(&mut self) {
+        let status_rows = self.last_status.is_some() as usize;
+        let cursor_vert_pos = if self.last_image.is_empty() { 1 } else { self.last_image[0].len() + status_rows + 1 };
+        print!("{}{}", termion::cursor::Goto(1, cursor_vert_pos as u16), termion::color::Fg(termion::color::White));
+        stdout().flush().unwrap();
+    }
+}
+
+/// Returns the escape sequence that sets the foreground color, approximated with the colors of the depth.
+fn foreground((r, g, b): (u8, u8, u8), color_depth: ColorDepth) -> String {
+    match color_depth {
+        ColorDepth::TrueColor => termion::color::Fg(termion::color::Rgb(r, g, b)).to_string(),
+        ColorDepth::Ansi256 => termion::color::Fg(termion::color::AnsiValue::rgb(to_ansi_value(r), to_ansi_value(g), to_ansi_value(b))).to_string(),
+        ColorDepth::Ansi16 => termion::color::Fg(termion::color::AnsiValue(to_ansi_16((r, g, b)))).to_string()
+    }
+}
+
+/// Map a [0; 255] value to a [0; 5] value
+fn to_ansi_value(x: u8) -> u8 {
+    (x as f64 * 5.0 / 255.0).round() as u8
+}
+
+/// Returns the closest of the 16 standard colors : the 8 dark ones, then the 8 bright ones.
+fn to_ansi_16((r, g, b): (u8, u8, u8)) -> u8 {
+    const STANDARD_COLORS: [(u8, u8, u8); 16] = [
+        (0, 0, 0), (128, 0, 0), (0, 128, 0), (128, 128, 0), (0, 0, 128), (128, 0, 128), (0, 128, 128), (192, 192, 192),
+        (128, 128, 128), (255, 0, 0), (0, 255, 0), (255, 255, 0), (0, 0, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255)
+    ];
+    let distance = |(u, v, w): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, u) + d(g, v) + d(b, w)
+    };
+    (0..16).min_by_key(|i| distance(STANDARD_COLORS[*i])).unwrap() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::terminal::to_ansi_16;
+
+    #[test]
+    fn closest_standard_color() {
+        assert_eq!(to_ansi_16((0, 0, 0)), 0);
+        assert_eq!(to_ansi_16((250, 10, 10)), 9);
+        assert_eq!(to_ansi_16((200, 200, 200)), 7);
+        assert_eq!(to_ansi_16((255, 255, 255)), 15);
+    }
+}
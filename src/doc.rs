@@ -0,0 +1,227 @@
+//! This module writes the documentation of rules in Markdown, for the people who use a model without reading its rules :
+//! the title, the author and the description of the file, the parameters of the world, the states with a swatch of their
+//! color, and the transitions in plain sentences, like "A dead cell becomes alive when it has exactly 3 alive neighbors.".
+
+use crate::automaton::BoundaryMode;
+use crate::compiler::parser::{ComparisonOperator, CountedCells, Neighborhood, RandomDraw, TieBreak};
+use crate::compiler::semantic::{Condition, Effects, Rules, StateDistribution};
+
+/// Returns the documentation of the rules, in Markdown. The swatches are HTML, which most Markdown viewers show.
+pub fn document(rules: &Rules) -> String {
+    let metadata = |key: &str| rules.metadata.iter().find(|(other, _)| other == key).map(|(_, text)| text.as_str());
+    let mut lines = vec![format!("# {}", metadata("title").unwrap_or("Cellular automaton")), String::new()];
+    if let Some(author) = metadata("author") {
+        lines.extend(vec![format!("By {}", author), String::new()]);
+    }
+    if let Some(description) = metadata("description") {
+        lines.extend(vec![description.to_string(), String::new()]);
+    }
+
+    lines.extend(vec!["## World".to_string(), String::new()]);
+    lines.extend(parameters(rules).into_iter().map(|parameter| format!("* {}", parameter)));
+
+    lines.extend(vec![String::new(), "## States".to_string(), String::new()]);
+    for state in rules.user_states() {
+        let (r, g, b) = state.color;
+        let distribution = match &state.distribution {
+            StateDistribution::Default => "the default state, it fills the cells left by the other states".to_string(),
+            StateDistribution::Proportion(proportion) => format!("{} percent of the cells at the start", (proportion * 10000.0).round() / 100.0),
+            StateDistribution::Quantity(quantity) => format!("{} at the start", plural(*quantity, "cell")),
+            StateDistribution::Box(x, y, width, height) => format!("the rectangle of {} x {} cells at {}, {} at the start", width, height, x, y),
+            StateDistribution::Circle(x, y, radius) => format!("the disc of radius {} at {}, {} at the start", radius, x, y),
+            StateDistribution::Line(x1, y1, x2, y2) => format!("the line from {}, {} to {}, {} at the start", x1, y1, x2, y2),
+            StateDistribution::Image(file_name) => format!("the pixels of its color in the image {} at the start", file_name)
+        };
+        let gradient = state.gradient.map_or(String::new(), |gradient| {
+            let (r, g, b) = gradient.end;
+            format!(", fading to #{:02x}{:02x}{:02x} over {}", r, g, b, plural(gradient.ticks, "tick"))
+        });
+        lines.push(format!("* <span style=\"color:#{:02x}{:02x}{:02x}\">\u{2588}\u{2588}</span> **{}** (#{:02x}{:02x}{:02x}{}) : {}",
+                           r, g, b, state.name, r, g, b, gradient, distribution));
+    }
+
+    lines.extend(vec![String::new(), "## Transitions".to_string(), String::new()]);
+    lines.push("The transitions are checked in this order, and the first one whose conditions are met is applied.".to_string());
+    lines.push(String::new());
+    let sentences = transitions(rules);
+    if sentences.is_empty() {
+        lines.push("The cells never change.".to_string());
+    }
+    lines.extend(sentences.into_iter().map(|sentence| format!("1. {}", sentence)));
+    lines.join("\n") + "\n"
+}
+
+fn parameters(rules: &Rules) -> Vec<String> {
+    let (width, height) = rules.world_size;
+    let mut parameters = vec![if rules.depth > 1 {
+        format!("{} x {} cells, in {} layers", width, height, rules.depth)
+    } else {
+        format!("{} x {} cells", width, height)
+    }];
+    parameters.push(match rules.neighborhood {
+        Neighborhood::Moore(1) => "The neighbors of a cell are the cells around it.".to_string(),
+        Neighborhood::Moore(radius) => format!("The neighbors of a cell are the cells at most {} cells away.", radius),
+        Neighborhood::VonNeumann(1) => "The neighbors of a cell are the cells above, below, on the left and on the right of it.".to_string(),
+        Neighborhood::VonNeumann(radius) => format!("The neighbors of a cell are the cells within {} moves up, down, left or right.", radius)
+    });
+    parameters.push(match rules.boundary {
+        BoundaryMode::Wrap => "The world wraps around : the cells on a border are the neighbors of the cells on the other border.".to_string(),
+        BoundaryMode::Mirror => "The cells on the borders see a reflection of the world beyond them.".to_string(),
+        BoundaryMode::Wall(state) => format!("The cells on the borders see {} cells beyond them.", rules.states[state].name)
+    });
+    if rules.second_order {
+        parameters.push("The automaton is reversible : the new state of a cell also depends on its previous state.".to_string());
+    }
+    if rules.memory {
+        parameters.push("The conditions can refer to the previous tick.".to_string());
+    }
+    if rules.noise > 0.0 {
+        parameters.push(format!("After each tick, a cell takes a random state with a probability of {}.", rules.noise));
+    }
+    if let Some(data_channel) = &rules.data_channel {
+        parameters.push(format!("Each cell stores a number between 0 and 255, its {}.", data_channel));
+    }
+    if !rules.regions.is_empty() {
+        parameters.push(format!("{} regions of the world are frozen or reset at each tick.", rules.regions.len()));
+    }
+    if !rules.agents.is_empty() {
+        parameters.push(format!("{} agents move on the world, following {} rules.", rules.agents.len(), rules.agent_rules.len()));
+    }
+    if rules.initial_grid.is_some() {
+        parameters.push("The first tick is read from a file, instead of the distributions of the states.".to_string());
+    }
+    if !rules.initial_cells.is_empty() {
+        parameters.push(format!("The pattern places {} over the distributions at the first tick.", plural(rules.initial_cells.len(), "cell")));
+    }
+    parameters
+}
+
+/// Returns a sentence per transition declared in the rules. A delayed transition is a single sentence,
+/// although it is made of a transition per tick of the delay.
+fn transitions(rules: &Rules) -> Vec<String> {
+    let user_state_count = rules.user_states().len();
+    let name = |state: usize| rules.states[state].name.as_str();
+    rules.transitions.iter()
+        .filter(|(origin, _, _, _)| *origin < user_state_count)
+        .map(|(origin, destination, conditions, effects)| {
+            // The implicit states of a delay follow each other unconditionally, the last one leads to the destination.
+            let (mut destination, mut effects, mut delay) = (*destination, effects, 1);
+            while destination >= user_state_count {
+                let (_, next, _, next_effects) = rules.transitions.iter().find(|(other, _, _, _)| *other == destination).unwrap();
+                destination = *next;
+                effects = next_effects;
+                delay += 1;
+            }
+            let change = match (effects.swap, effects.vote) {
+                (Some(neighbor), _) => format!("swaps its state with its {} neighbor", neighbor.description()),
+                (_, Some(TieBreak::Keep)) => "takes the most common state of its neighbors, or keeps its state if it is tied".to_string(),
+                (_, Some(TieBreak::Lowest)) => "takes the most common state of its neighbors, the first declared one if several are tied".to_string(),
+                (_, Some(TieBreak::Random)) => "takes the most common state of its neighbors, one at random if several are tied".to_string(),
+                _ if destination == *origin => format!("stays {}", name(destination)),
+                _ => format!("becomes {}", name(destination))
+            };
+            let delay = if delay > 1 { format!(" after {} ticks", delay) } else { String::new() };
+            format!("{} {} cell {}{}{}{}.", article(name(*origin)), name(*origin), change, delay, when(rules, conditions), side_effects(rules, effects))
+        })
+        .collect()
+}
+
+/// Returns "An" before a name that starts with a vowel, "A" otherwise.
+fn article(name: &str) -> &'static str {
+    if name.starts_with(|c: char| "aeiouAEIOU".contains(c)) { "An" } else { "A" }
+}
+
+/// Returns the conditions as " when ..., or when ...", or nothing if they are always met.
+fn when(rules: &Rules, conditions: &[Vec<Condition>]) -> String {
+    let always = conditions.iter().any(|conjunction| conjunction.iter().all(|condition| matches!(condition, Condition::True)));
+    if always {
+        return String::new();
+    }
+    let conjunctions: Vec<String> = conditions.iter()
+        .map(|conjunction| conjunction.iter()
+            .filter(|condition| !matches!(condition, Condition::True))
+            .map(|condition| describe_condition(rules, condition))
+            .collect::<Vec<_>>()
+            .join(" and "))
+        .collect();
+    format!(" when {}", conjunctions.join(", or when "))
+}
+
+fn describe_condition(rules: &Rules, condition: &Condition) -> String {
+    let name = |state: usize| rules.states[state].name.as_str();
+    match condition {
+        Condition::QuantityCondition(state, operator, quantity, CountedCells::Neighbors) =>
+            format!("it has {} {}", quantifier(*operator), plural(*quantity as usize, &format!("{} neighbor", name(*state)))),
+        Condition::QuantityCondition(state, operator, quantity, CountedCells::NeighborsAndSelf) =>
+            format!("{} {} of the cell and its neighbors are {}", quantifier(*operator), quantity, name(*state)),
+        Condition::DataCondition(operator, value) =>
+            format!("its {} is {} {}", rules.data_channel.as_deref().unwrap_or_default(), quantifier(*operator), value),
+        Condition::DistanceCondition(state, operator, distance) =>
+            format!("the closest {} cell is {} {} away", name(*state), quantifier(*operator), plural(*distance as usize, "cell")),
+        Condition::NeighborCondition(neighbor, state) => format!("its {} neighbor is {}", neighbor.description(), name(*state)),
+        Condition::PreviousNeighborCondition(neighbor, state) => format!("its {} neighbor was {} at the previous tick", neighbor.description(), name(*state)),
+        Condition::PreviousStateCondition(state) => format!("it was {} at the previous tick", name(*state)),
+        Condition::RandomCondition(probability, RandomDraw::Cell) => format!("a draw with a probability of {} succeeds", probability),
+        Condition::RandomCondition(probability, RandomDraw::Tick) => format!("the draw of the iteration, with a probability of {}, succeeds", probability),
+        Condition::RandomCondition(probability, RandomDraw::Block(side)) =>
+            format!("the draw of its block of {} x {} cells, with a probability of {}, succeeds", side, side, probability),
+        Condition::True => "always".to_string()
+    }
+}
+
+/// Returns the count and the noun, like "1 cell" or "3 cells".
+fn plural(count: usize, noun: &str) -> String {
+    format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+}
+
+fn quantifier(operator: ComparisonOperator) -> &'static str {
+    match operator {
+        ComparisonOperator::Greater => "more than",
+        ComparisonOperator::Lesser => "fewer than",
+        ComparisonOperator::GreaterOrEqual => "at least",
+        ComparisonOperator::LesserOrEqual => "at most",
+        ComparisonOperator::Equal => "exactly",
+        ComparisonOperator::Different => "not exactly"
+    }
+}
+
+/// Returns the changes of the data channel and of the neighbors, as ", and sets ...".
+fn side_effects(rules: &Rules, effects: &Effects) -> String {
+    let mut changes = Vec::new();
+    if let Some(value) = effects.set_data {
+        changes.push(format!("sets its {} to {}", rules.data_channel.as_deref().unwrap_or_default(), value));
+    }
+    for (neighbor, state) in &effects.neighbors {
+        changes.push(format!("makes its {} neighbor {}", neighbor.description(), rules.states[*state].name));
+    }
+    changes.iter().map(|change| format!(", and {}", change)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::semantic::{parse, parse_str};
+    use crate::doc::document;
+
+    #[test]
+    fn document_the_game_of_life() {
+        let documentation = document(&parse("resources/game_of_life.txt").unwrap());
+        assert!(documentation.starts_with("# Cellular automaton\n\n## World\n\n* 200 x 50 cells\n"));
+        assert!(documentation.contains("* <span style=\"color:#ffffff\">\u{2588}\u{2588}</span> **alive** (#ffffff) : 20 percent of the cells at the start\n"));
+        assert!(documentation.ends_with("1. An alive cell becomes dead when it has fewer than 2 alive neighbors, or when it has more than 3 alive neighbors.\n\
+                                         1. A dead cell becomes alive when it has exactly 3 alive neighbors.\n"));
+    }
+
+    #[test]
+    fn document_the_metadata_and_the_delays() {
+        let text = "size (10, 10)\n\
+                    title \"Forest fire\"\n\
+                    author \"Ada\"\n\
+                    description \"Trees grow, and burn.\"\n\
+                    states { (tree, 0, 255, 0), (fire, 255, 0, 0, quantity 1), }\n\
+                    transitions { (tree, fire, B is fire && rand 0.5), (fire, tree, true, delay 3), }";
+        let documentation = document(&parse_str(text).unwrap());
+        assert!(documentation.starts_with("# Forest fire\n\nBy Ada\n\nTrees grow, and burn.\n\n## World\n"));
+        assert!(documentation.contains("1. A tree cell becomes fire when its up neighbor is fire and a draw with a probability of 0.5 succeeds.\n"));
+        assert!(documentation.ends_with("1. A fire cell becomes tree after 3 ticks.\n"));
+    }
+}
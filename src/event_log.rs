@@ -0,0 +1,155 @@
+//! This module records the changes of the states of the cells in an append-only binary file, so that the history of a cell
+//! can be queried after the run, like the iteration it first got infected at, without storing the whole grids.
+//!
+//! The file starts with the size of the grid. Then each iteration where cells changed is a block : the iteration, the number
+//! of changes, and the index, the old state and the new state of each changed cell. All the values are little-endian `u32`.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use crate::automaton::Automaton;
+
+/// The first bytes of an event log, with the version of the format.
+const MAGIC: &[u8; 8] = b"MUTEVLG1";
+
+/// The change of the state of a cell, at the iteration the cell got its new state.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CellEvent {
+    pub iteration: usize,
+    pub x: usize,
+    pub y: usize,
+    pub old_state: usize,
+    pub new_state: usize
+}
+
+/// Appends the changes of an automaton to an event log, after each tick.
+pub struct EventLogWriter {
+    writer: BufWriter<File>,
+    file_name: String,
+    grid: Vec<usize> // The grid at the last recorded iteration
+}
+
+impl EventLogWriter {
+    /// Creates the event log, whose changes are relative to the current grid of the automaton.
+    pub fn create(file_name: &str, automaton: &Automaton) -> Result<EventLogWriter, String> {
+        let file = File::create(file_name).map_err(|error| format!("Cannot create the event log {}. Cause : {:?}", file_name, error))?;
+        let mut writer = EventLogWriter { writer: BufWriter::new(file), file_name: file_name.to_string(), grid: automaton.get_grid().to_vec() };
+        let (width, height) = automaton.get_rules().grid_size();
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&(width as u32).to_le_bytes());
+        header.extend_from_slice(&(height as u32).to_le_bytes());
+        writer.write(&header)?;
+        Ok(writer)
+    }
+
+    /// Appends the cells that changed since the last recorded iteration.
+    pub fn record(&mut self, automaton: &Automaton) -> Result<(), String> {
+        let mut changes = Vec::new();
+        for (index, (old_state, new_state)) in self.grid.iter_mut().zip(automaton.get_grid()).enumerate() {
+            if old_state != new_state {
+                changes.extend_from_slice(&[index as u32, *old_state as u32, *new_state as u32]);
+                *old_state = *new_state;
+            }
+        }
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let mut bytes = Vec::with_capacity(4 * (changes.len() + 2));
+        for value in [automaton.get_iteration() as u32, (changes.len() / 3) as u32].iter().chain(&changes) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        self.write(&bytes)
+    }
+
+    /// Writes the buffered changes to the file.
+    pub fn finish(mut self) -> Result<(), String> {
+        let file_name = &self.file_name;
+        self.writer.flush().map_err(|error| format!("Cannot write the event log {}. Cause : {:?}", file_name, error))
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let file_name = &self.file_name;
+        self.writer.write_all(bytes).map_err(|error| format!("Cannot write the event log {}. Cause : {:?}", file_name, error))
+    }
+}
+
+/// The changes read from an event log, in the order of the iterations, and row after row for an iteration.
+pub struct EventLog {
+    pub world_size: (usize, usize), // The size of the grid, whose layers are stacked in a 3D world
+    pub events: Vec<CellEvent>
+}
+
+impl EventLog {
+    pub fn load(file_name: &str) -> Result<EventLog, String> {
+        let bytes = fs::read(file_name).map_err(|error| format!("Cannot read the event log {}. Cause : {:?}", file_name, error))?;
+        if !bytes.starts_with(MAGIC) {
+            return Err(format!("The file {} is not an event log.", file_name));
+        }
+        let mut values = bytes[MAGIC.len()..].chunks(4).map(|chunk| match chunk {
+            [a, b, c, d] => Ok(u32::from_le_bytes([*a, *b, *c, *d]) as usize),
+            _ => Err(format!("The event log {} is truncated.", file_name))
+        });
+        let mut read = || values.next().unwrap_or_else(|| Err(format!("The event log {} is truncated.", file_name)));
+        let world_size = (read()?, read()?);
+        let mut events = Vec::new();
+        while let Some(iteration) = values.next() {
+            let iteration = iteration?;
+            let mut read = || values.next().unwrap_or_else(|| Err(format!("The event log {} is truncated.", file_name)));
+            for _ in 0..read()? {
+                let index = read()?;
+                events.push(CellEvent { iteration, x: index % world_size.0, y: index / world_size.0, old_state: read()?, new_state: read()? });
+            }
+        }
+        Ok(EventLog { world_size, events })
+    }
+
+    /// Returns the changes of the cell, in the order of the iterations.
+    pub fn cell_events(&self, x: usize, y: usize) -> impl Iterator<Item = &CellEvent> {
+        self.events.iter().filter(move |event| event.x == x && event.y == y)
+    }
+
+    /// Returns the first iteration the cell got the state at, if it ever changed to it.
+    pub fn first_change_to(&self, x: usize, y: usize, state: usize) -> Option<usize> {
+        self.cell_events(x, y).find(|event| event.new_state == state).map(|event| event.iteration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+    use crate::event_log::{EventLog, EventLogWriter};
+
+    #[test]
+    fn event_log_replays_the_changes() {
+        let file_name = std::env::temp_dir().join("mutations_event_log.bin");
+        let file_name = file_name.to_str().unwrap();
+        let mut automaton = Automaton::with_seed(parse("resources/virus.txt").unwrap(), 7);
+        let mut grid = automaton.get_grid().to_vec();
+        let mut writer = EventLogWriter::create(file_name, &automaton).unwrap();
+        for _ in 0..10 {
+            automaton.tick();
+            writer.record(&automaton).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let log = EventLog::load(file_name).unwrap();
+        assert_eq!(log.world_size, automaton.get_rules().grid_size());
+        assert!(log.events.windows(2).all(|pair| pair[0].iteration <= pair[1].iteration));
+        for event in &log.events {
+            let cell = &mut grid[event.y * log.world_size.0 + event.x];
+            assert_eq!(*cell, event.old_state);
+            *cell = event.new_state;
+        }
+        assert_eq!(grid, automaton.get_grid());
+
+        let event = log.events.last().unwrap();
+        let first_change = log.first_change_to(event.x, event.y, event.new_state).unwrap();
+        assert!(first_change <= event.iteration);
+        assert_eq!(log.first_change_to(event.x, event.y, automaton.get_rules().states.len()), None);
+
+        fs::write(file_name, &fs::read(file_name).unwrap()[..18]).unwrap();
+        assert_eq!(EventLog::load(file_name).err(), Some(format!("The event log {} is truncated.", file_name)));
+        fs::remove_file(file_name).unwrap();
+    }
+}
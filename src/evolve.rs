@@ -0,0 +1,280 @@
+//! This module explores random mutations of a rule file : the numbers of the conditions are perturbed and the comparison
+//! operators are swapped, then the variants are scored by short runs and the best ones are kept for the next generation.
+//!
+//! The mutations are made on the tokens of the rule file, so that a variant is a rule file too, and the variants that
+//! don't compile are dropped.
+
+use std::fs;
+use rand::Rng;
+use rayon::prelude::*;
+use crate::automaton::Automaton;
+use crate::compiler::lexer::Lexer;
+use crate::compiler::semantic::{parse_str_with_options, parse_with_options, CompilerOptions, Rules};
+use crate::rng::CounterRng;
+
+const OPERATORS: [&str; 6] = ["<", ">", "<=", ">=", "==", "!="];
+
+/// How a variant is scored, the higher the better.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    Activity, // The mean proportion of cells that change state at each tick, over the second half of the runs
+    Diversity, // The entropy in bits of the populations of the states at the end of the runs
+    Population(usize) // The proportion of cells in the state at the end of the runs
+}
+
+impl Metric {
+    /// The metric is "activity", "diversity" or the name of a state for its population.
+    pub fn from_name(name: &str, rules: &Rules) -> Option<Metric> {
+        match name {
+            "activity" => Some(Metric::Activity),
+            "diversity" => Some(Metric::Diversity),
+            _ => rules.user_states().iter().position(|s| s.name == name).map(Metric::Population)
+        }
+    }
+}
+
+/// The settings of an exploration, see `evolve`.
+pub struct EvolveConf {
+    pub generations: usize,
+    pub variants: usize, // The number of variants created at each generation
+    pub kept: usize, // The number of best variants kept for the next generation, and returned at the end
+    pub ticks: usize, // The length of each run
+    pub runs: usize, // The number of runs that score a variant, all the variants are run with the same seeds
+    pub seed: u64
+}
+
+impl Default for EvolveConf {
+    fn default() -> EvolveConf {
+        EvolveConf {
+            generations: 10,
+            variants: 16,
+            kept: 3,
+            ticks: 100,
+            runs: 2,
+            seed: 0
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SiteKind {
+    Operator,
+    Integer, // The value a condition compares to
+    Probability // The probability of a random condition
+}
+
+/// A token of the transitions that can be mutated. The line starts at 0, and the bytes of the token are start..end.
+#[derive(Clone, Debug, PartialEq)]
+struct Site {
+    line: usize,
+    start: usize,
+    end: usize,
+    kind: SiteKind
+}
+
+/// A variant of the rules, with the value of each site.
+#[derive(Clone, Debug)]
+pub struct Variant {
+    values: Vec<String>,
+    pub text: String,
+    pub score: f64,
+    /// The changes from the original rules, like "line 12 : == becomes >=".
+    pub mutations: Vec<String>
+}
+
+/// Returns the best variants of the rules found, the best first. The original rules take part in the selection.
+pub fn evolve(file_name: &str, options: &CompilerOptions, metric: &str, conf: &EvolveConf) -> Result<Vec<Variant>, Vec<String>> {
+    let text = fs::read_to_string(file_name).map_err(|error| vec![format!("Cannot read file {}. Cause : {:?}", file_name, error)])?;
+    let sites = find_sites(&text).map_err(|error| vec![error])?;
+    if sites.is_empty() {
+        return Err(vec![format!("The transitions of {} have no comparison nor random condition to mutate.", file_name)]);
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let original: Vec<String> = sites.iter().map(|site| lines[site.line][site.start..site.end].to_string()).collect();
+    let rules = parse_with_options(file_name, options).map_err(|errors| errors.iter().map(ToString::to_string).collect::<Vec<_>>())?;
+    let metric = Metric::from_name(metric, &rules)
+        .ok_or_else(|| vec![format!("The metric is \"activity\", \"diversity\" or a state, found \"{}\".", metric)])?;
+
+    let mut population = vec![Variant {
+        text: render(&lines, &sites, &original),
+        score: score(rules, metric, conf),
+        mutations: Vec::new(),
+        values: original.clone()
+    }];
+    for generation in 0..conf.generations {
+        let children: Vec<Vec<String>> = (0..conf.variants)
+            .map(|index| {
+                let mut rng = CounterRng::new(conf.seed, generation as u64, index as u64);
+                mutate(&sites, &population[index % population.len()].values, &mut rng)
+            })
+            .collect();
+        let scored: Vec<Variant> = children.into_par_iter()
+            .filter_map(|values| {
+                let text = render(&lines, &sites, &values);
+                // The variants that don't compile are dropped.
+                let rules = parse_str_with_options(&text, options).ok()?;
+                let mutations = describe(&sites, &original, &values);
+                Some(Variant { text, score: score(rules, metric, conf), mutations, values })
+            })
+            .collect();
+        for variant in scored {
+            if population.iter().all(|other| other.values != variant.values) {
+                population.push(variant);
+            }
+        }
+        // The sort is stable, so the older variants win the ties.
+        population.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        population.truncate(conf.kept.max(1));
+    }
+    Ok(population)
+}
+
+/// Returns the comparison operators and the numbers of the conditions, after the "transitions" keyword.
+fn find_sites(text: &str) -> Result<Vec<Site>, String> {
+    let mut lexer = Lexer::from_text(text);
+    let mut sites = Vec::new();
+    let mut in_transitions = false;
+    let mut previous = String::new();
+    loop {
+        let token = lexer.get_next_token().map_err(|error| error.to_string())?;
+        if token.str.is_empty() {
+            return Ok(sites);
+        }
+        let kind = if OPERATORS.contains(&token.str.as_str()) {
+            Some(SiteKind::Operator)
+        } else if OPERATORS.contains(&previous.as_str()) && token.str.parse::<u8>().is_ok() {
+            Some(SiteKind::Integer)
+        } else if previous == "rand" {
+            Some(SiteKind::Probability)
+        } else {
+            None
+        };
+        match kind {
+            // The column is the one of the last character of the token.
+            Some(kind) if in_transitions => {
+                let end = token.column as usize;
+                sites.push(Site { line: token.line as usize - 1, start: end - token.str.len(), end, kind });
+            },
+            _ => in_transitions |= token.str == "transitions"
+        }
+        previous = token.str;
+    }
+}
+
+/// Changes one or two sites of the variant.
+fn mutate(sites: &[Site], values: &[String], rng: &mut CounterRng) -> Vec<String> {
+    let mut values = values.to_vec();
+    for _ in 0..rng.gen_range(1, 3) {
+        let index = rng.gen_range(0, sites.len());
+        values[index] = match sites[index].kind {
+            SiteKind::Operator => {
+                let operators: Vec<&str> = OPERATORS.iter().copied().filter(|operator| *operator != values[index]).collect();
+                operators[rng.gen_range(0, operators.len())].to_string()
+            },
+            SiteKind::Integer => {
+                let value: i32 = values[index].parse().unwrap();
+                let delta = if rng.gen() { rng.gen_range(1, 3) } else { -rng.gen_range(1, 3) };
+                (value + delta).clamp(0, 255).to_string()
+            },
+            SiteKind::Probability => {
+                let value: f64 = values[index].parse().unwrap();
+                format!("{:.2}", (value + rng.gen_range(-0.1, 0.1)).clamp(0.0, 1.0))
+            }
+        };
+    }
+    values
+}
+
+/// Returns the text of the rules with the values of the sites.
+fn render(lines: &[&str], sites: &[Site], values: &[String]) -> String {
+    let mut lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+    // The sites of a line are replaced from the last one, so that the positions of the others are still right.
+    for (site, value) in sites.iter().zip(values).rev() {
+        lines[site.line].replace_range(site.start..site.end, value);
+    }
+    lines.join("\n") + "\n"
+}
+
+fn describe(sites: &[Site], original: &[String], values: &[String]) -> Vec<String> {
+    sites.iter().zip(original.iter().zip(values))
+        .filter(|(_, (before, after))| before != after)
+        .map(|(site, (before, after))| format!("line {} : {} becomes {}", site.line + 1, before, after))
+        .collect()
+}
+
+fn score(rules: Rules, metric: Metric, conf: &EvolveConf) -> f64 {
+    let runs = conf.runs.max(1);
+    let mut automaton = Automaton::with_seed(rules, conf.seed);
+    let cell_count = automaton.get_grid().len() as f64;
+    let mut total = 0.0;
+    for run in 0..runs {
+        automaton.reset(conf.seed.wrapping_add(run as u64));
+        let mut changes = 0;
+        for tick in 0..conf.ticks {
+            let before = automaton.get_grid().to_vec();
+            automaton.tick();
+            if tick >= conf.ticks / 2 {
+                changes += before.iter().zip(automaton.get_grid()).filter(|(a, b)| a != b).count();
+            }
+        }
+        let counts = automaton.state_counts();
+        total += match metric {
+            Metric::Activity => changes as f64 / (cell_count * (conf.ticks - conf.ticks / 2).max(1) as f64),
+            Metric::Diversity => counts.iter()
+                .filter(|count| **count > 0)
+                .map(|count| *count as f64 / cell_count)
+                .map(|p| -p * p.log2())
+                .sum(),
+            Metric::Population(state) => counts[state] as f64 / cell_count
+        };
+    }
+    total / runs as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::compiler::semantic::{parse_str, CompilerOptions};
+    use crate::evolve::{evolve, find_sites, render, EvolveConf, Site, SiteKind};
+
+    static GAME_OF_LIFE_FILE: &str = "resources/game_of_life.txt";
+
+    #[test]
+    fn find_sites_of_the_conditions() {
+        let text = fs::read_to_string(GAME_OF_LIFE_FILE).unwrap();
+        let sites = find_sites(&text).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        let values: Vec<&str> = sites.iter().map(|site| &lines[site.line][site.start..site.end]).collect();
+        assert_eq!(values, vec!["<", "2", ">", "3", "==", "3"]);
+        assert!(sites.iter().step_by(2).all(|site| site.kind == SiteKind::Operator));
+        assert_eq!(render(&lines, &sites, &values.iter().map(|value| value.to_string()).collect::<Vec<_>>()), text);
+    }
+
+    #[test]
+    fn render_replaces_the_sites() {
+        let lines = ["(a, b, c < 2 || c == 12)"];
+        let sites = [
+            Site { line: 0, start: 11, end: 12, kind: SiteKind::Integer },
+            Site { line: 0, start: 18, end: 20, kind: SiteKind::Operator },
+            Site { line: 0, start: 21, end: 23, kind: SiteKind::Integer }
+        ];
+        assert_eq!(render(&lines, &sites, &["10".to_string(), "!=".to_string(), "1".to_string()]), "(a, b, c < 10 || c != 1)\n");
+    }
+
+    #[test]
+    fn evolve_keeps_the_best_variants() {
+        let conf = EvolveConf { generations: 3, variants: 6, ticks: 10, runs: 1, ..EvolveConf::default() };
+        let variants = evolve(GAME_OF_LIFE_FILE, &CompilerOptions::default(), "alive", &conf).unwrap();
+        assert_eq!(variants.len(), conf.kept);
+        assert!(variants.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        for variant in &variants {
+            assert!(parse_str(&variant.text).is_ok());
+            assert_eq!(variant.mutations.is_empty(), variant.text == fs::read_to_string(GAME_OF_LIFE_FILE).unwrap());
+        }
+        // The same seed gives the same variants.
+        let again = evolve(GAME_OF_LIFE_FILE, &CompilerOptions::default(), "alive", &conf).unwrap();
+        assert_eq!(again.iter().map(|v| &v.text).collect::<Vec<_>>(), variants.iter().map(|v| &v.text).collect::<Vec<_>>());
+
+        assert!(evolve(GAME_OF_LIFE_FILE, &CompilerOptions::default(), "unknown", &conf).is_err());
+    }
+}
@@ -3,102 +3,1349 @@ extern crate termion;
 extern crate rand;
 
 use std::{
-    time::{Instant, Duration},
+    fmt,
+    fs,
+    time::{Instant, Duration, SystemTime},
     thread::sleep,
     io,
 };
-use crate::compiler::semantic::{Rules, parse};
+use crate::compiler::diagnostics::render_diagnostic;
+use crate::compiler::error::CompileError;
+use crate::compiler::parser::ComparisonOperator;
+use crate::compiler::rename::rename_state;
+use crate::compiler::semantic::{Rules, StateDistribution, CompilerOptions, parse_with_options, parse_files_with_options};
 use crate::automaton::Automaton;
-use crate::camera::Camera;
-use crate::display::Display;
+use crate::generator::{generate_rules, GeneratorConf};
+use crate::palette::{load_palette, apply_palette, save_palette};
+use crate::analysis::{compute_metrics, analyze_rules, RunMonitor, PeriodDetector, PatternTracker, Stabilization, SteadyStateDetector};
+use crate::search::{search, population_in_range, save_snapshot};
+use crate::evolve::{evolve, EvolveConf};
+use crate::camera::{Camera, DEFAULT_VIEWPORT};
+use crate::display::{Display, TerminalDisplay, FramePacer, SpeedMeter, Status, TickGovernor};
+use crate::display::recorder::Recorder;
+use crate::image_display::PngDisplay;
+use crate::bench::{bench_file, to_json as bench_to_json};
+#[cfg(feature = "sdl2_rendering")]
+use crate::sdl_display::SdlDisplay;
+use crate::summary::Summarizer;
+use crate::checkpoint::Checkpoint;
+use crate::manifest::Manifest;
+use crate::event_log::EventLogWriter;
+use crate::population_log::PopulationLog;
+use crate::render::render;
+use crate::interop::RlePattern;
+use crate::pattern::{stamp, Orientation};
+use crate::doc::document;
+use crate::terminal::{TerminalCapabilities, ColorDepth, Charset};
 use crate::inputs::{Inputs, UserAction};
-use termion::raw::IntoRawMode;
+use termion::{input::MouseTerminal, raw::IntoRawMode};
 
+/// The time between two checks of the modification of the rules file.
+const RULES_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The memory in megabytes the cells of a run can use by default.
+pub const DEFAULT_MEMORY_LIMIT: usize = 4096;
+
+const MEGABYTE: f64 = 1024.0 * 1024.0;
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum MaxIterationCount {
     Infinite,
     Finite(usize)
 }
 
+/// When the run stops before its maximum number of iterations. The condition is checked after each tick.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StopCondition {
+    Never,
+    OnStabilization { max_period: usize }, // When the grid is frozen, or repeats a cycle of at most this period
+    Population { state: String, comparison: ComparisonOperator, value: PopulationThreshold } // When the population of the state compares to the value
+}
+
+/// A population, as a number of cells or as a percentage of the cells of the grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PopulationThreshold {
+    Cells(usize),
+    Percent(f64)
+}
+
+impl fmt::Display for PopulationThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopulationThreshold::Cells(cells) => write!(f, "{}", cells),
+            PopulationThreshold::Percent(percent) => write!(f, "{}%", percent)
+        }
+    }
+}
+
+/// Why a run stopped before its maximum number of iterations.
+#[derive(Debug, PartialEq)]
+pub enum StopReason {
+    Stabilization(Stabilization),
+    Population { state: String, population: usize }
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Stabilization(stabilization) => write!(f, "{}", stabilization),
+            StopReason::Population { state, population } => write!(f, "the population of the state {} is {}", state, population)
+        }
+    }
+}
+
+/// Checks the stop condition of a run after each tick.
+enum StopDetector {
+    SteadyState(SteadyStateDetector),
+    Population { state: usize, comparison: ComparisonOperator, value: PopulationThreshold }
+}
+
+impl StopDetector {
+    /// Returns the detector of the condition, which starts from the current state of the automaton, or none if the run never stops.
+    fn new(stop_condition: &StopCondition, automaton: &Automaton) -> Result<Option<StopDetector>, String> {
+        match stop_condition {
+            StopCondition::Never => Ok(None),
+            StopCondition::OnStabilization { max_period } => {
+                let mut detector = SteadyStateDetector::new(*max_period);
+                detector.record(automaton);
+                Ok(Some(StopDetector::SteadyState(detector)))
+            },
+            StopCondition::Population { state, comparison, value } => {
+                let state = automaton.get_rules().user_states().iter().position(|s| &s.name == state)
+                    .ok_or(format!("The state \"{}\" of the stop condition is not defined.", state))?;
+                Ok(Some(StopDetector::Population { state, comparison: *comparison, value: *value }))
+            }
+        }
+    }
+
+    /// Returns why the run stops after the last tick, if it does.
+    fn check(&mut self, automaton: &Automaton) -> Option<StopReason> {
+        match self {
+            StopDetector::SteadyState(detector) => detector.record(automaton).map(StopReason::Stabilization),
+            StopDetector::Population { state, comparison, value } => {
+                let population = automaton.state_counts()[*state];
+                let reached = match value {
+                    PopulationThreshold::Cells(cells) => comparison.evaluate(population, *cells),
+                    PopulationThreshold::Percent(percent) => comparison.evaluate(population as f64 * 100.0 / automaton.get_grid().len() as f64, *percent)
+                };
+                let name = &automaton.get_rules().states[*state].name;
+                Some(StopReason::Population { state: name.clone(), population }).filter(|_| reached)
+            }
+        }
+    }
+}
+
 pub struct Conf<'a> {
     pub file_name: &'a str,
+    pub overrides: Vec<&'a str>, // The rules files merged over the rules file, each one overriding the previous ones
     pub with_display: bool,
     pub iteration_delay: usize,
     pub max_iteration_count: MaxIterationCount,
+    pub stop_condition: StopCondition,
+    pub compiler_options: CompilerOptions,
+    pub raw_statistics: bool, // If true, the implicit states are not merged with their parent state in statistics
+    pub traced_cell: Option<(usize, usize)>, // The evaluation of this cell's transitions is logged at each tick
+    pub analyzed_state: Option<&'a str>, // If set, the metrics of the grid are logged at each tick, with the clusters of this state
+    pub monitor_window: Option<usize>, // If set, a verdict on the run is given, based on the population during this number of last ticks
+    pub max_period: Option<usize>, // If set, oscillators and spaceships of at most this period are detected
+    pub tagged_region: Option<(usize, usize, usize, usize)>, // If set, the bounding box and population of the cells descended from this region are logged at each tick
+    pub palette: Option<&'a str>, // If set, the colors of the states are overridden with the colors of this palette file
+    pub target_fps: Option<u32>, // If set, the display is refreshed at most this number of times per second, otherwise at each iteration
+    pub target_tps: Option<u32>, // If set, the simulation runs at this number of ticks per second, with several ticks per frame when the rendering is slow
+    pub skip_frames_when_behind: bool, // If true, the frames missed because of slow iterations are dropped instead of being rendered in a row
+    pub show_wrap: bool, // If true, the cells the camera sees beyond the world's borders are tinted
+    pub layer: usize, // The layer of a 3D world shown by the camera and written in the PNG files and the recording
+    pub downscale: Option<usize>, // The side of the block of cells shown by a character of the terminal, 1 to show each cell, or None to fit the world
+    pub seed: Option<u64>, // The seed of the initial grid and the random conditions, random if not set
+    pub summary_interval: Option<usize>, // If set, a textual summary is printed every this number of iterations, instead of the display of the grid
+    pub color_depth: Option<ColorDepth>, // Overrides the color depth detected from the terminal
+    pub charset: Option<Charset>, // Overrides the charset detected from the terminal
+    pub png_frames: Option<&'a str>, // If set, the whole grid is written in a PNG file of this directory at each iteration, instead of the terminal display
+    pub png_scale: usize, // The width and height in pixels of a cell in the PNG files, the recording and the SDL window
+    pub sdl_window: bool, // If true, the grid is shown in a SDL window instead of the terminal, with the sdl2_rendering feature
+    pub record: Option<&'a str>, // If set, the whole grid is recorded at each iteration, and written in this animated GIF or PNG file at the end
+    pub crossfade: usize, // The number of frames of the recording that fade each iteration into the next one
+    pub resume: Option<&'a str>, // If set, the run starts from this checkpoint file, and stops where the original run was to stop unless a maximum is given
+    pub checkpoint: Option<&'a str>, // If set, the state of the automaton is saved in this checkpoint file at the end of the run
+    pub event_log: Option<&'a str>, // If set, the changes of the cells are appended to this event log file after each tick
+    pub population_csv: Option<&'a str>, // If set, the population of each state is appended to this CSV file every `population_interval` iterations
+    pub population_interval: usize,
+    pub pattern: Option<&'a str>, // If set, the pattern of this RLE file is placed on the first iteration
+    pub pattern_position: Option<(usize, usize)>, // The upper-left corner of the pattern, which is centered if not set
+    pub pattern_states: Vec<&'a str>, // The states of the rules of the states of the pattern, the states in their order of declaration if empty
+    pub memory_limit: usize, // The memory in megabytes the cells can use, the run is refused if the estimate exceeds it
+    pub transition_cache: bool, // If true, the outcomes of deterministic transitions are memoized by the states of the neighborhood
+    pub manifest: Option<&'a str>, // If set, the manifest of the run is written in this file
+    pub embed_manifest: bool, // If true, the manifest of the run is embedded in the checkpoint and the recording
+    pub hud: bool, // If true, the iteration, the speed and the population of each state are shown below the grid
+}
+
+/// The options of a run with the grid shown in the terminal, without limit of iterations.
+impl Default for Conf<'_> {
+    fn default() -> Self {
+        Conf {
+            file_name: "",
+            overrides: Vec::new(),
+            with_display: true,
+            iteration_delay: 10,
+            max_iteration_count: MaxIterationCount::Infinite,
+            stop_condition: StopCondition::Never,
+            compiler_options: CompilerOptions::default(),
+            raw_statistics: false,
+            traced_cell: None,
+            analyzed_state: None,
+            monitor_window: None,
+            max_period: None,
+            tagged_region: None,
+            palette: None,
+            target_fps: None,
+            target_tps: None,
+            skip_frames_when_behind: false,
+            show_wrap: false,
+            layer: 0,
+            downscale: None,
+            seed: None,
+            summary_interval: None,
+            color_depth: None,
+            charset: None,
+            png_frames: None,
+            png_scale: 1,
+            sdl_window: false,
+            record: None,
+            crossfade: 0,
+            resume: None,
+            checkpoint: None,
+            event_log: None,
+            population_csv: None,
+            population_interval: 1,
+            pattern: None,
+            pattern_position: None,
+            pattern_states: Vec::new(),
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            transition_cache: false,
+            manifest: None,
+            embed_manifest: false,
+            hud: false,
+        }
+    }
 }
 
 pub fn execute(conf: &Conf) {
-    match parse(conf.file_name) {
-        Ok(rules) => {
-            info!("Cellular automaton rules where parsed successfully from file {}.", conf.file_name);
+    match parse_files_with_options(&rules_files(conf), &conf.compiler_options) {
+        Ok(mut rules) => {
+            info!("Cellular automaton rules where parsed successfully from file {}.", rules_files(conf).join(", "));
+            for warning in &rules.warnings {
+                warn!("{}", warning);
+            }
+            if let Some(Err(error)) = conf.palette.map(|palette_file| apply_palette_file(palette_file, &mut rules)) {
+                error!("{}", error);
+                return;
+            }
+            log_distributions(&rules);
             execute_rules(conf, rules);
         },
+        Err((file_name, errors)) => {
+            log_compile_errors(file_name.unwrap_or(&rules_files(conf).join(" + ")), &errors);
+        }
+    }
+}
+
+/// Returns the rules file followed by the files merged over it.
+fn rules_files<'a>(conf: &Conf<'a>) -> Vec<&'a str> {
+    let mut file_names = vec![conf.file_name];
+    file_names.extend(&conf.overrides);
+    file_names
+}
+
+/// Returns the field of view filling a terminal of the given number of columns and rows. The last row is left for the cursor,
+/// and the row above it for the status if it is shown.
+fn terminal_viewport((columns, rows): (u16, u16), hud: bool) -> (usize, usize) {
+    (columns.max(1) as usize, rows.saturating_sub(1 + hud as u16).max(1) as usize)
+}
+
+/// Opens the window of the SDL display, showing the images of the given size with cells of `scale` x `scale` pixels.
+#[cfg(feature = "sdl2_rendering")]
+fn sdl_display(size: (usize, usize), scale: usize) -> Result<Box<dyn Display>, String> {
+    Ok(Box::new(SdlDisplay::new(size, scale)?))
+}
+
+#[cfg(not(feature = "sdl2_rendering"))]
+fn sdl_display(_size: (usize, usize), _scale: usize) -> Result<Box<dyn Display>, String> {
+    Err("the program is built without the sdl2_rendering feature, add --features sdl2_rendering to the cargo command.".to_string())
+}
+
+/// Returns the status shown by the display, if the `--hud` option is given.
+fn status(conf: &Conf, speed_meter: &mut SpeedMeter, automaton: &Automaton) -> Option<Status> {
+    if conf.hud {
+        Some(Status::new(automaton, speed_meter.measure(Instant::now(), automaton.get_iteration())))
+    } else {
+        None
+    }
+}
+
+/// Places the pattern of the RLE file on the layer shown by the camera.
+fn place_pattern(conf: &Conf, file_name: &str, automaton: &mut Automaton) -> Result<(), String> {
+    let rle = RlePattern::load(file_name)?;
+    let pattern = rle.to_pattern(automaton.get_rules(), &conf.pattern_states)?;
+    let (width, height) = automaton.get_rules().world_size;
+    let (x, y) = conf.pattern_position.unwrap_or(((width.saturating_sub(rle.size.0)) / 2, (height.saturating_sub(rle.size.1)) / 2));
+    stamp(automaton, &pattern, (x as isize, y as isize), conf.layer, Orientation::default())?;
+    info!("The pattern {} of {} x {} cells is placed at ({}, {}){}", file_name, rle.size.0, rle.size.1, x, y,
+          rle.rule.map_or(String::new(), |rule| format!(", it was made for the rule {}", rule)));
+    Ok(())
+}
+
+/// Returns the options of the run that change its evolution, written in its manifest to replay it.
+fn replay_options(conf: &Conf) -> Vec<String> {
+    let mut options = Vec::new();
+    if let MaxIterationCount::Finite(count) = conf.max_iteration_count {
+        options.extend(vec!["--max-iterations".to_string(), count.to_string()]);
+    }
+    match &conf.stop_condition {
+        StopCondition::Never => {},
+        StopCondition::OnStabilization { max_period } => options.extend(vec!["--stop-when-stable".to_string(), max_period.to_string()]),
+        StopCondition::Population { state, comparison, value } => options.extend(vec!["--stop-when".to_string(), format!("{}{}{}", state, comparison, value)])
+    }
+    if let Some((width, height)) = conf.compiler_options.world_size {
+        options.extend(vec!["--size".to_string(), format!("{},{}", width, height)]);
+    }
+    if let Some(file_name) = conf.pattern {
+        options.extend(vec!["--pattern".to_string(), file_name.to_string()]);
+    }
+    if let Some((x, y)) = conf.pattern_position {
+        options.extend(vec!["--pattern-at".to_string(), format!("{},{}", x, y)]);
+    }
+    if !conf.pattern_states.is_empty() {
+        options.extend(vec!["--pattern-states".to_string(), conf.pattern_states.join(",")]);
+    }
+    if let Some(file_name) = conf.resume {
+        options.extend(vec!["--resume".to_string(), file_name.to_string()]);
+    }
+    if conf.transition_cache {
+        options.push("--cache".to_string());
+    }
+    options
+}
+
+/// Gives the colors of the palette file to the states of the rules.
+fn apply_palette_file(palette_file: &str, rules: &mut Rules) -> Result<(), String> {
+    let palette = load_palette(palette_file)?;
+    for warning in apply_palette(rules, &palette) {
+        warn!("{}", warning);
+    }
+    Ok(())
+}
+
+/// Compiles the rules file again and gives the new rules to the running automaton, which keeps its cells.
+/// The running rules are kept if the file has errors. Returns true if the rules were replaced.
+fn reload_rules(conf: &Conf, automaton: &mut Automaton) -> bool {
+    let mut rules = match parse_files_with_options(&rules_files(conf), &conf.compiler_options) {
+        Ok(rules) => rules,
+        Err((file_name, errors)) => {
+            log_compile_errors(file_name.unwrap_or(&rules_files(conf).join(" + ")), &errors);
+            warn!("The running rules are kept.");
+            return false;
+        }
+    };
+    for warning in &rules.warnings {
+        warn!("{}", warning);
+    }
+    if let Some(Err(error)) = conf.palette.map(|palette_file| apply_palette_file(palette_file, &mut rules)) {
+        warn!("{}", error);
+    }
+    match automaton.replace_rules(rules) {
+        Ok(warnings) => {
+            for warning in warnings {
+                warn!("{}", warning);
+            }
+            info!("Rules reloaded from {} at iteration {}", rules_files(conf).join(", "), automaton.get_iteration());
+            true
+        },
+        Err(error) => {
+            error!("Cannot reload the rules from {}. Cause : {}", rules_files(conf).join(", "), error);
+            false
+        }
+    }
+}
+
+/// Returns the time the file was last modified, if it can be read.
+fn modification_time(file_name: &str) -> Option<SystemTime> {
+    fs::metadata(file_name).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Logs the errors of the rules file, with the lines of the file they refer to.
+fn log_compile_errors(file_name: &str, errors: &[CompileError]) {
+    error!("Cellular automaton rules could not be parsed from file {}.", file_name);
+    let source = fs::read_to_string(file_name).unwrap_or_default();
+    for error in errors {
+        error!("{}", render_diagnostic(error, file_name, &source));
+    }
+}
+
+/// Prints the metadata of the rules and the static measures of their rule table, without running the automaton.
+pub fn analyze(file_name: &str, compiler_options: &CompilerOptions) {
+    match parse_with_options(file_name, compiler_options) {
+        Ok(rules) => {
+            println!("World size : {} x {}", rules.world_size.0, rules.world_size.1);
+            println!("States : {} declared, {} implicit", rules.user_states().len(), rules.states.len() - rules.user_states().len());
+            for state in rules.user_states() {
+                println!("    {}", state.name);
+            }
+            println!("Transitions : {}", rules.transitions.len());
+            println!("Quiescent state : {}", rules.states[rules.default_state()].name);
+            let report = analyze_rules(&rules, 100_000, 0);
+            println!("Lambda : {:.4}", report.lambda);
+            println!("Activity : {:.4}", report.activity);
+            println!("(estimated on {} random neighborhoods)", report.samples);
+        },
+        Err(errors) => {
+            log_compile_errors(file_name, &errors);
+        }
+    }
+}
+
+/// Compiles the rules files without running them, and prints their errors and warnings, then a summary of each file.
+/// Returns false if a file has errors, the denied warnings included.
+pub fn check(file_names: &[&str], compiler_options: &CompilerOptions) -> bool {
+    let mut invalid_files = 0;
+    for file_name in file_names {
+        match parse_with_options(file_name, compiler_options) {
+            Ok(rules) => {
+                for warning in &rules.warnings {
+                    eprintln!("warning: {}", warning);
+                }
+                println!("{} : ok, {} states ({} implicit), {} transitions, {} warnings", file_name, rules.user_states().len(),
+                         rules.states.len() - rules.user_states().len(), rules.transitions.len(), rules.warnings.len());
+            },
+            Err(errors) => {
+                let source = fs::read_to_string(file_name).unwrap_or_default();
+                for error in &errors {
+                    eprintln!("error: {}", render_diagnostic(error, file_name, &source));
+                }
+                println!("{} : {} errors", file_name, errors.len());
+                invalid_files += 1;
+            }
+        }
+    }
+    println!("{} files checked, {} with errors", file_names.len(), invalid_files);
+    invalid_files == 0
+}
+
+/// Checks that the rules files and the engine give the run of a manifest, read from a manifest file, or embedded in a
+/// checkpoint or a recording. Prints the differences, or the command line that replays the run.
+pub fn verify(manifest_file: &str, file_names: &[&str]) -> bool {
+    let expected = match Manifest::load(manifest_file) {
+        Ok(expected) => expected,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return false;
+        }
+    };
+    let manifest = match Manifest::new(file_names, expected.seed, expected.options.clone()) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return false;
+        }
+    };
+    let differences = expected.differences(&manifest);
+    for difference in &differences {
+        eprintln!("error: {}", difference);
+    }
+    if differences.is_empty() {
+        println!("{} : ok, the rules and the engine match, the run is replayed with : run {}", manifest_file, manifest.replay_arguments().join(" "));
+    } else {
+        println!("{} : {} differences, the run cannot be replayed", manifest_file, differences.len());
+    }
+    differences.is_empty()
+}
+
+/// Writes the documentation of the rules in Markdown, in the output file or on the standard output.
+pub fn document_rules(file_name: &str, output: Option<&str>) {
+    let rules = match parse_with_options(file_name, &CompilerOptions::default()) {
+        Ok(rules) => rules,
+        Err(errors) => {
+            log_compile_errors(file_name, &errors);
+            return;
+        }
+    };
+    match output {
+        Some(output) => match fs::write(output, document(&rules)) {
+            Ok(()) => println!("Documentation written in {}", output),
+            Err(error) => error!("Cannot write the documentation in {}. Cause : {:?}", output, error)
+        },
+        None => print!("{}", document(&rules))
+    }
+}
+
+/// Renames a state in the rules file, and checks that the renamed rules still compile, otherwise the file is restored.
+/// Returns false if the state could not be renamed.
+pub fn rename_state_in_file(file_name: &str, old_name: &str, new_name: &str) -> bool {
+    let rules = match parse_with_options(file_name, &CompilerOptions::default()) {
+        Ok(rules) => rules,
+        Err(errors) => {
+            log_compile_errors(file_name, &errors);
+            return false;
+        }
+    };
+    let is_state = |name: &str| rules.user_states().iter().any(|state| state.name == name);
+    let error = if !is_state(old_name) {
+        Some(format!("The state \"{}\" is not defined in {}.", old_name, file_name))
+    } else if is_state(new_name) {
+        Some(format!("The state \"{}\" is already defined in {}.", new_name, file_name))
+    } else if new_name.is_empty() || new_name.chars().all(|c| c.is_ascii_digit()) || !new_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Some(format!("The name \"{}\" is not a valid state name, it must be made of letters, digits and underscores.", new_name))
+    } else if rules.data_channel.as_deref().is_some_and(|channel| channel == old_name || channel == new_name) {
+        Some("A state cannot be renamed from or to the name of the data channel.".to_string())
+    } else {
+        None
+    };
+    if let Some(error) = error {
+        error!("{}", error);
+        return false;
+    }
+    let text = match fs::read_to_string(file_name) {
+        Ok(text) => text,
+        Err(error) => {
+            error!("Cannot read the rules in {}. Cause : {:?}", file_name, error);
+            return false;
+        }
+    };
+    let renamed = match rename_state(&text, old_name, new_name) {
+        Ok(renamed) => renamed,
+        Err(error) => {
+            error!("{}", error);
+            return false;
+        }
+    };
+    if let Err(error) = fs::write(file_name, &renamed) {
+        error!("Cannot write the rules in {}. Cause : {:?}", file_name, error);
+        return false;
+    }
+    // The file is compiled in place, so that the paths of the directives are still relative to its directory.
+    if let Err(errors) = parse_with_options(file_name, &CompilerOptions::default()) {
+        log_compile_errors(file_name, &errors);
+        error!("The renamed rules don't compile, the file is restored.");
+        if let Err(error) = fs::write(file_name, &text) {
+            error!("Cannot restore the rules in {}. Cause : {:?}", file_name, error);
+        }
+        return false;
+    }
+    println!("The state \"{}\" is renamed \"{}\" in {}.", old_name, new_name, file_name);
+    true
+}
+
+/// Saves the colors of the states in a palette file, in the GIMP palette format.
+pub fn export_palette(file_name: &str, palette_file: &str) {
+    match parse_with_options(file_name, &CompilerOptions::default()) {
+        Ok(rules) => match save_palette(&rules, palette_file) {
+            Ok(()) => println!("Palette saved in {}", palette_file),
+            Err(error) => error!("Cannot save the palette in {}. Cause : {:?}", palette_file, error)
+        },
+        Err(errors) => {
+            log_compile_errors(file_name, &errors);
+        }
+    }
+}
+
+/// Writes a random rule file, to stress-test the compiler and the automaton.
+pub fn generate_benchmark(file_name: &str, conf: &GeneratorConf, seed: u64) {
+    match fs::write(file_name, generate_rules(conf, seed)) {
+        Ok(()) => println!("Rules generated with seed {} in {}", seed, file_name),
+        Err(error) => error!("Cannot write the rules in {}. Cause : {:?}", file_name, error)
+    }
+}
+
+/// The settings of an exploration of mutated rules, see `evolve_rules`.
+pub struct EvolveRulesConf<'a> {
+    pub file_name: &'a str,
+    pub output: &'a str, // The directory where the best variants are written
+    pub metric: &'a str, // "activity", "diversity" or the name of a state to maximize its population
+    pub generations: usize,
+    pub ticks: usize,
+    pub seed: u64
+}
+
+/// Mutates the numbers and the comparison operators of the conditions, and writes the best variants found
+/// for the metric in the files "variant_<rank>.txt" of the output directory.
+pub fn evolve_rules(conf: &EvolveRulesConf) {
+    let evolve_conf = EvolveConf { generations: conf.generations, ticks: conf.ticks, seed: conf.seed, ..EvolveConf::default() };
+    let variants = match evolve(conf.file_name, &CompilerOptions::default(), conf.metric, &evolve_conf) {
+        Ok(variants) => variants,
         Err(errors) => {
-            error!("Cellular automaton rules could not be parsed from file {}.", conf.file_name);
-            for error in &errors {
+            error!("Cannot evolve the rules of file {}.", conf.file_name);
+            for error in errors {
                 error!("{}", error);
             }
+            return;
+        }
+    };
+    if let Err(error) = fs::create_dir_all(conf.output) {
+        error!("Cannot create the directory {}. Cause : {:?}", conf.output, error);
+        return;
+    }
+    for (rank, variant) in variants.iter().enumerate() {
+        let file_name = format!("{}/variant_{}.txt", conf.output, rank + 1);
+        println!("#{} : {} {:.4}, saved in {}", rank + 1, conf.metric, variant.score, file_name);
+        if variant.mutations.is_empty() {
+            println!("    the original rules");
+        }
+        for mutation in &variant.mutations {
+            println!("    {}", mutation);
+        }
+        if let Err(error) = fs::write(&file_name, &variant.text) {
+            error!("Cannot write the variant in {}. Cause : {:?}", file_name, error);
         }
     }
 }
 
-fn execute_rules(conf: &Conf, rules: Rules) {
+/// The settings of an offline rendering, see `render_run`.
+pub struct RenderConf<'a> {
+    pub file_name: &'a str,
+    pub output: &'a str, // An animated GIF or PNG file, or a directory for a PNG file per frame
+    pub ticks: usize,
+    pub scale: usize, // The width and height in pixels of a cell
+    pub seed: u64
+}
+
+/// Runs the automaton without display, and renders each iteration in an animation or in PNG files.
+/// The frames are encoded on another thread while the next ones are computed.
+pub fn render_run(conf: &RenderConf) {
+    let rules = match parse_with_options(conf.file_name, &CompilerOptions::default()) {
+        Ok(rules) => rules,
+        Err(errors) => {
+            log_compile_errors(conf.file_name, &errors);
+            return;
+        }
+    };
+    let output = conf.output.to_lowercase();
+    let display: Box<dyn Display + Send> = if output.ends_with(".gif") || output.ends_with(".png") || output.ends_with(".apng") {
+        Box::new(Recorder::new(conf.output, conf.scale, 100))
+    } else {
+        Box::new(PngDisplay::new(conf.output, conf.scale))
+    };
+    let mut automaton = Automaton::with_seed(rules, conf.seed);
+    let start = Instant::now();
+    match render(&mut automaton, conf.ticks, display) {
+        Ok(frames) => println!("{} frames rendered in {} with seed {}, in {:.2} s", frames, conf.output, conf.seed, start.elapsed().as_secs_f64()),
+        Err(error) => error!("{}", error)
+    }
+}
+
+/// The settings of a benchmark, see `bench`.
+pub struct BenchConf<'a> {
+    pub file_names: Vec<&'a str>,
+    pub ticks: usize,
+    pub seed: u64, // The same seed gives the same initial grids, so that the runs of the benchmark can be compared
+    pub json: Option<&'a str> // If set, the results are also written in this JSON file
+}
+
+/// Runs each rules file without display for the number of ticks, and prints the speed of the ticks, their latency
+/// and the memory of the cells. Returns false if a file cannot be compiled, or the JSON file cannot be written.
+pub fn bench(conf: &BenchConf) -> bool {
+    let mut results = Vec::new();
+    for file_name in &conf.file_names {
+        match bench_file(file_name, conf.ticks, conf.seed, &CompilerOptions::default()) {
+            Ok(result) => {
+                println!("{}", result.line());
+                results.push(result);
+            },
+            Err(errors) => {
+                log_compile_errors(file_name, &errors);
+                return false;
+            }
+        }
+    }
+    match conf.json.map(|file_name| (file_name, fs::write(file_name, bench_to_json(&results, conf.seed)))) {
+        Some((file_name, Err(error))) => {
+            error!("Cannot write the results in {}. Cause : {:?}", file_name, error);
+            false
+        },
+        Some((file_name, Ok(()))) => {
+            println!("The results are written in {}", file_name);
+            true
+        },
+        None => true
+    }
+}
+
+/// The settings of a run by a program, see `run_file`.
+pub struct RunOptions {
+    pub compiler_options: CompilerOptions,
+    pub seed: Option<u64>, // The seed of the initial grid and the random conditions, random if not set
+    pub max_iteration_count: MaxIterationCount,
+    pub stop_condition: StopCondition
+}
+
+impl Default for RunOptions {
+    fn default() -> RunOptions {
+        RunOptions {
+            compiler_options: CompilerOptions::default(),
+            seed: None,
+            max_iteration_count: MaxIterationCount::Infinite,
+            stop_condition: StopCondition::Never
+        }
+    }
+}
+
+/// Notified of the steps of a run started by `run_file`. The callbacks do nothing by default.
+pub trait RunObserver {
+    /// Called once the rules are compiled, before the first tick. The warnings of the compiler are in the rules.
+    fn on_compile(&mut self, _rules: &Rules) {}
+
+    /// Called after each tick, with the automaton in its new state.
+    fn on_tick(&mut self, _automaton: &Automaton) {}
+
+    /// Called once the run is over, with the automaton in its last state.
+    fn on_finish(&mut self, _automaton: &Automaton, _outcome: &RunOutcome) {}
+}
+
+impl<O: RunObserver> RunObserver for &mut O {
+    fn on_compile(&mut self, rules: &Rules) {
+        (**self).on_compile(rules)
+    }
+
+    fn on_tick(&mut self, automaton: &Automaton) {
+        (**self).on_tick(automaton)
+    }
+
+    fn on_finish(&mut self, automaton: &Automaton, outcome: &RunOutcome) {
+        (**self).on_finish(automaton, outcome)
+    }
+}
+
+/// How a run started by `run_file` ended.
+#[derive(Debug, PartialEq)]
+pub struct RunOutcome {
+    pub seed: u64,
+    pub iterations: usize,
+    pub stop_reason: Option<StopReason> // Set if the run was stopped by its stop condition
+}
+
+/// Compiles the rules file and runs the automaton without display until it reaches the maximum number of iterations or its
+/// stop condition, notifying the observer along the way. Nothing is logged nor printed. An infinite run without a stop
+/// condition never returns.
+///
+/// ```
+/// use mutations::executor::{run_file, MaxIterationCount, RunObserver, RunOptions, RunOutcome};
+/// use mutations::automaton::Automaton;
+///
+/// struct Populations(Vec<usize>);
+///
+/// impl RunObserver for Populations {
+///     fn on_tick(&mut self, automaton: &Automaton) {
+///         self.0.push(automaton.state_counts()[1]);
+///     }
+/// }
+///
+/// let options = RunOptions { seed: Some(42), max_iteration_count: MaxIterationCount::Finite(10), ..RunOptions::default() };
+/// let mut populations = Populations(Vec::new());
+/// let outcome = run_file("resources/game_of_life.txt", options, &mut populations).unwrap();
+/// assert_eq!(outcome, RunOutcome { seed: 42, iterations: 10, stop_reason: None });
+/// assert_eq!(populations.0.len(), 10);
+/// ```
+/// The state of a population stop condition must be defined by the rules, or it is reported as a semantic error.
+pub fn run_file(file_name: &str, options: RunOptions, mut observer: impl RunObserver) -> Result<RunOutcome, Vec<CompileError>> {
+    let rules = parse_with_options(file_name, &options.compiler_options)?;
+    observer.on_compile(&rules);
+    let mut automaton = match options.seed {
+        Some(seed) => Automaton::with_seed(rules, seed),
+        None => Automaton::new(rules)
+    };
+    let mut detector = StopDetector::new(&options.stop_condition, &automaton).map_err(|error| vec![CompileError::semantic(error)])?;
+    let mut outcome = RunOutcome { seed: automaton.get_seed(), iterations: 0, stop_reason: None };
+    while options.max_iteration_count != MaxIterationCount::Finite(outcome.iterations) {
+        automaton.tick();
+        outcome.iterations += 1;
+        observer.on_tick(&automaton);
+        outcome.stop_reason = detector.as_mut().and_then(|detector| detector.check(&automaton));
+        if outcome.stop_reason.is_some() {
+            break;
+        }
+    }
+    observer.on_finish(&automaton, &outcome);
+    Ok(outcome)
+}
+
+/// The settings of a search of initial conditions, see `search_patterns`.
+pub struct SearchConf<'a> {
+    pub file_name: &'a str,
+    pub runs: usize,
+    pub ticks: usize,
+    pub state: &'a str,
+    pub min_population: usize,
+    pub max_population: usize
+}
+
+/// Runs the automaton with random seeds, and saves the seed and a snapshot of the runs for which
+/// the population of the state is within the range at the end. Each snapshot is saved in the file "search_<seed>.txt".
+pub fn search_patterns(conf: &SearchConf) {
+    let rules = match parse_with_options(conf.file_name, &CompilerOptions::default()) {
+        Ok(rules) => rules,
+        Err(errors) => {
+            log_compile_errors(conf.file_name, &errors);
+            return;
+        }
+    };
+    let state = match rules.user_states().iter().position(|s| s.name == conf.state) {
+        Some(state) => state,
+        None => {
+            error!("The searched state \"{}\" is not defined.", conf.state);
+            return;
+        }
+    };
     let mut automaton = Automaton::new(rules);
-    let mut camera = Camera::new(0, 0, &automaton);
-    let mut display = Display::new();
-    let mut inputs = Inputs::new();
+    let seeds = (0..conf.runs).map(|_| rand::random());
+    let hits = search(&mut automaton, seeds, conf.ticks, population_in_range(state, conf.min_population, conf.max_population));
+    for hit in &hits {
+        let file_name = format!("search_{}.txt", hit.seed);
+        match save_snapshot(&automaton, hit, &file_name) {
+            Ok(()) => println!("Seed {} : snapshot saved in {}", hit.seed, file_name),
+            Err(error) => error!("Seed {} : cannot save the snapshot in {}. Cause : {:?}", hit.seed, file_name, error)
+        }
+    }
+    println!("{} runs out of {} matched.", hits.len(), conf.runs);
+}
+
+fn log_distributions(rules: &Rules) {
+    // The implicit states come after the states declared by the user.
+    for state in rules.states.iter().take(rules.implicit_state_ranges.len()) {
+        match &state.distribution {
+            StateDistribution::Proportion(p) => info!("State {} : proportion {}", state.name, p),
+            StateDistribution::Quantity(q) => info!("State {} : quantity {}", state.name, q),
+            StateDistribution::Box(x, y, w, h) => info!("State {} : box {} {} {} {}", state.name, x, y, w, h),
+            StateDistribution::Circle(x, y, r) => info!("State {} : circle {} {} {}", state.name, x, y, r),
+            StateDistribution::Line(x1, y1, x2, y2) => info!("State {} : line {} {} {} {}", state.name, x1, y1, x2, y2),
+            StateDistribution::Image(file_name) => info!("State {} : from image {}", state.name, file_name),
+            StateDistribution::Default => info!("State {} : default, remaining proportion {}", state.name, rules.default_proportion())
+        }
+    }
+}
 
-    let _stdout = io::stdout().into_raw_mode().unwrap();
-    if conf.with_display {
+fn execute_rules(conf: &Conf, rules: Rules) {
+    let mut analyzed_state = match conf.analyzed_state {
+        Some(name) => match rules.user_states().iter().position(|s| s.name == name) {
+            Some(state) => Some(state),
+            None => {
+                error!("The analyzed state \"{}\" is not defined.", name);
+                return;
+            }
+        },
+        None => None
+    };
+    let mut monitor = conf.monitor_window.map(|window| RunMonitor::new(&rules, window));
+    let mut detector = conf.max_period.map(|max_period| PeriodDetector::new(&rules, max_period));
+    let mut periodicity = None;
+    if let Err(error) = check_memory(&rules, conf.memory_limit) {
+        error!("{}", error);
+        return;
+    }
+    let mut automaton = match conf.seed {
+        Some(seed) => Automaton::with_seed(rules, seed),
+        None => Automaton::new(rules)
+    };
+    info!("Seed : {}", automaton.get_seed());
+    if let Some(Err(error)) = conf.pattern.map(|file_name| place_pattern(conf, file_name, &mut automaton)) {
+        error!("{}", error);
+        return;
+    }
+    let mut max_iteration_count = conf.max_iteration_count;
+    let mut resumed_manifest = None;
+    if let Some(file_name) = conf.resume {
+        match Checkpoint::load(file_name).and_then(|checkpoint| automaton.restore(&checkpoint).map(|_| checkpoint)) {
+            Ok(checkpoint) => {
+                info!("Resumed from {} at iteration {}, seed {}", file_name, automaton.get_iteration(), automaton.get_seed());
+                if let (MaxIterationCount::Infinite, Some(stop_iteration)) = (max_iteration_count, checkpoint.stop_iteration) {
+                    max_iteration_count = MaxIterationCount::Finite(stop_iteration.saturating_sub(automaton.get_iteration()));
+                }
+                resumed_manifest = checkpoint.manifest;
+            },
+            Err(error) => {
+                error!("Cannot resume the run from {}. Cause : {}", file_name, error);
+                return;
+            }
+        }
+    }
+    let manifest = match Manifest::new(&rules_files(conf), automaton.get_seed(), replay_options(conf)) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            error!("{}", error);
+            return;
+        }
+    };
+    // The run resumed from a checkpoint evolves like the original run only with the same rules and engine.
+    match resumed_manifest.as_deref().map(Manifest::parse) {
+        Some(Ok(original)) => original.differences(&manifest).iter().for_each(|difference| warn!("The checkpoint was made by another run. {}", difference)),
+        Some(Err(error)) => warn!("The manifest of the checkpoint cannot be read. {}", error),
+        None => {}
+    }
+    if let Some(file_name) = conf.manifest {
+        match manifest.save(file_name) {
+            Ok(()) => info!("The manifest of the run is written in {}", file_name),
+            Err(error) => error!("{}", error)
+        }
+    }
+    let embedded_manifest = Some(manifest.to_toml()).filter(|_| conf.embed_manifest);
+    let first_iteration = automaton.get_iteration();
+    let mut event_log = match conf.event_log.map(|file_name| EventLogWriter::create(file_name, &automaton)).transpose() {
+        Ok(event_log) => event_log,
+        Err(error) => {
+            error!("{}", error);
+            return;
+        }
+    };
+    let mut population_log = match conf.population_csv.map(|file_name| PopulationLog::create(file_name, conf.population_interval, &automaton)).transpose() {
+        Ok(population_log) => population_log,
+        Err(error) => {
+            error!("{}", error);
+            return;
+        }
+    };
+    automaton.set_traced_cell(conf.traced_cell);
+    if conf.transition_cache {
+        match automaton.enable_transition_cache() {
+            Ok(()) => info!("The outcomes of the transitions are cached"),
+            Err(error) => warn!("{}", error)
+        }
+    }
+    let mut tracker = conf.tagged_region.map(|region| PatternTracker::new(&automaton, region));
+    let mut stop_detector = match StopDetector::new(&conf.stop_condition, &automaton) {
+        Ok(stop_detector) => stop_detector,
+        Err(error) => {
+            error!("{}", error);
+            return;
+        }
+    };
+    let capabilities = TerminalCapabilities::detect();
+    let color_depth = conf.color_depth.unwrap_or(capabilities.color_depth);
+    let charset = conf.charset.unwrap_or(capabilities.charset);
+    info!("Rendering with {:?} colors and {:?} characters", color_depth, charset);
+    let viewport = capabilities.size.map_or(DEFAULT_VIEWPORT, |size| terminal_viewport(size, conf.hud));
+    let with_display = (conf.with_display || conf.png_frames.is_some()) && conf.summary_interval.is_none();
+    let sdl_window = with_display && conf.sdl_window && conf.png_frames.is_none();
+    let mut camera = match (conf.png_frames, conf.downscale) {
+        (Some(_), _) => Camera::with_size(0, 0, automaton.get_rules().world_size),
+        // The window shows the whole world, with a cell per pixel unless a scale is given.
+        (None, scale) if sdl_window => {
+            let scale = scale.unwrap_or(1).max(1);
+            let (width, height) = automaton.get_rules().world_size;
+            let mut camera = Camera::with_size(0, 0, (width.div_ceil(scale), height.div_ceil(scale)));
+            camera.set_scale(scale);
+            camera
+        },
+        // A given scale shows the world from its upper-left corner.
+        (None, Some(scale)) => {
+            let mut camera = Camera::with_size(0, 0, viewport);
+            camera.set_scale(scale);
+            camera
+        },
+        (None, None) => {
+            let camera = Camera::fit(&automaton, viewport);
+            info!("Showing {}, to fit the world in the terminal", camera.zoom_description());
+            camera
+        }
+    };
+    if conf.show_wrap {
+        camera.show_wrap();
+    }
+    if conf.png_frames.is_none() && !sdl_window && camera.sees_beyond_the_world(&automaton) {
+        info!("The field of view is larger than the world, which is repeated around itself as it wraps around.{}",
+              if conf.show_wrap { "" } else { " The --show-wrap option tints the copies." });
+    }
+    camera.set_layer(conf.layer as isize, &automaton);
+    let mut display: Box<dyn Display> = match conf.png_frames {
+        Some(directory) => Box::new(PngDisplay::new(directory, conf.png_scale)),
+        None if sdl_window => match sdl_display(camera.size(), conf.png_scale) {
+            Ok(display) => display,
+            Err(error) => {
+                error!("Cannot open the SDL window. Cause : {}", error);
+                return;
+            }
+        },
+        None => Box::new(TerminalDisplay::with_rendering(color_depth, charset))
+    };
+    let mut rendered_iteration = None;
+    // The recording runs at the target rate, or 10 frames per second.
+    let mut recorder = conf.record.map(|file_name| {
+        let mut recorder = Recorder::new(file_name, conf.png_scale, conf.target_fps.map_or(100, |fps| (1000 / fps.max(1)) as u16));
+        recorder.set_crossfade(conf.crossfade);
+        recorder.set_manifest(embedded_manifest.clone());
+        recorder
+    });
+    let mut recorder_camera = Camera::with_size(0, 0, automaton.get_rules().world_size);
+    recorder_camera.set_layer(conf.layer as isize, &automaton);
+    if let Some(recorder) = &mut recorder {
+        recorder.render(recorder_camera.capture(&automaton), None);
+    }
+    let mut frame_pacer = FramePacer::new(conf.target_fps, conf.skip_frames_when_behind);
+    let mut speed_meter = SpeedMeter::new();
+    let mut governor = conf.target_tps.map(TickGovernor::new);
+    let mut summarizer = Summarizer::new();
+    // The rules files are watched while the grid is shown in the terminal or the window, and reloaded when one of them changes.
+    let watch_rules = with_display && conf.png_frames.is_none();
+    // The terminal is only read and set in raw mode while it shows the grid, so the other runs work without a terminal.
+    let in_terminal = watch_rules && !sdl_window;
+    let mut inputs = if in_terminal { Some(Inputs::new()) } else { None };
+    let mut rules_modified: Vec<_> = rules_files(conf).into_iter().map(modification_time).collect();
+    let mut next_rules_check = Instant::now() + RULES_CHECK_INTERVAL;
+
+    let raw_stdout = match in_terminal.then(|| io::stdout().into_raw_mode()) {
+        Some(Ok(raw_stdout)) => Some(raw_stdout),
+        Some(Err(error)) => {
+            error!("Cannot show the grid in the terminal, see the --headless option. Cause : {}", error);
+            return;
+        },
+        None => None
+    };
+    // The terminal display reports the clicks and the drags of the mouse, which paint the cells with the brush.
+    let mouse_terminal = if in_terminal { Some(MouseTerminal::from(io::stdout())) } else { None };
+    let mut brush = automaton.get_rules().user_states().len().min(2) - 1;
+    if with_display {
         display.init();
     }
+    if conf.summary_interval.is_some() {
+        print_summary(&mut summarizer, &automaton);
+    }
 
     let mut start = Instant::now();
     let mut runtime_duration = Duration::new(0, 0);
     let mut i = 0;
     let mut pause = false;
+    let mut stabilized = false;
 
-    let mut continue_simulation = true;
+    let mut continue_simulation = max_iteration_count != MaxIterationCount::Finite(0);
     while continue_simulation {
-        match inputs.read_keyboard() {
+        let mut reload = false;
+        let mut step = false;
+        // The window of the display has its own keyboard and mouse, the terminal is read when it has no action.
+        let action = match display.read_inputs() {
+            UserAction::Nop => inputs.as_mut().map_or(UserAction::Nop, Inputs::read_inputs),
+            action => action
+        };
+        match action {
             UserAction::TranslateCamera(direction) => { camera.translate(&direction); },
-            UserAction::ZoomCamera(zoom) => { camera.zoom(&zoom); },
+            UserAction::ZoomCamera(zoom) => {
+                camera.zoom(&zoom);
+                info!("Showing {}", camera.zoom_description());
+            },
+            UserAction::ChangeLayer(delta) => {
+                camera.change_layer(delta, &automaton);
+                info!("Showing the layer {}", camera.get_layer());
+            },
             UserAction::TogglePause => {
                 pause = !pause;
                 if pause {
                     runtime_duration += start.elapsed();
                 } else {
                     start = Instant::now();
+                    if let Some(governor) = &mut governor {
+                        governor.resume();
+                    }
+                }
+            },
+            UserAction::Step => { step = pause; },
+            UserAction::ReloadRules => { reload = true; },
+            UserAction::CycleBrush => {
+                brush = (brush + 1) % automaton.get_rules().user_states().len();
+                info!("Painting with the state {}", automaton.get_rules().states[brush].name);
+            },
+            UserAction::ExportWorld => {
+                let file_name = format!("world_{}_{}.rle", automaton.get_seed(), automaton.get_iteration());
+                match automaton.export_rle(&file_name) {
+                    Ok(()) => info!("Iteration {} : the world is exported to {}", automaton.get_iteration(), file_name),
+                    Err(error) => error!("{}", error)
+                }
+            },
+            UserAction::PaintCell { x, y } if with_display && conf.png_frames.is_none() => {
+                for (x, y) in camera.cells_at((x as usize, y as usize), &automaton) {
+                    automaton.set_cell(x, y, brush).unwrap();
                 }
             },
             UserAction::Quit => {
                 break;
             },
-            UserAction::Nop => {}
+            UserAction::PaintCell { .. } | UserAction::Nop => {}
         }
 
-        if conf.with_display {
-            let image = camera.capture(&automaton);
-            display.render(&image);
-            sleep(Duration::from_millis(10));
+        if watch_rules && Instant::now() >= next_rules_check {
+            next_rules_check = Instant::now() + RULES_CHECK_INTERVAL;
+            let modified: Vec<_> = rules_files(conf).into_iter().map(modification_time).collect();
+            reload |= modified != rules_modified;
+            rules_modified = modified;
+        }
+        if reload && recorder.is_some() {
+            warn!("The rules cannot be reloaded while the run is recorded.");
+        } else if reload && reload_rules(conf, &mut automaton) {
+            brush = brush.min(automaton.get_rules().user_states().len() - 1);
+            // The statistics made with the previous rules are started again.
+            monitor = conf.monitor_window.map(|window| RunMonitor::new(automaton.get_rules(), window));
+            detector = conf.max_period.map(|max_period| PeriodDetector::new(automaton.get_rules(), max_period));
+            periodicity = None;
+            stop_detector = StopDetector::new(&conf.stop_condition, &automaton).unwrap_or_else(|error| {
+                warn!("{} The run does not stop on it anymore.", error);
+                None
+            });
+            summarizer = Summarizer::new();
+            if let Some(name) = conf.analyzed_state {
+                analyzed_state = automaton.get_rules().user_states().iter().position(|s| s.name == name);
+                if analyzed_state.is_none() {
+                    warn!("The analyzed state \"{}\" is not in the new rules, the metrics are not logged anymore.", name);
+                }
+            }
         }
 
-        if !pause {
+        if with_display && conf.png_frames.is_some() {
+            // Each iteration is written once, even while the simulation is paused.
+            if rendered_iteration != Some(automaton.get_iteration()) {
+                let status = status(conf, &mut speed_meter, &automaton);
+                display.render(camera.capture(&automaton), status.as_ref());
+                rendered_iteration = Some(automaton.get_iteration());
+            }
+        } else if with_display {
+            if frame_pacer.should_render(Instant::now()) {
+                // The field of view follows the size of the terminal, while the window scales the image to its size.
+                if let Some(size) = termion::terminal_size().ok().filter(|_| !sdl_window).map(|size| terminal_viewport(size, conf.hud)).filter(|size| *size != camera.size()) {
+                    camera.resize(size);
+                    info!("The terminal is resized to {} x {} characters", size.0, size.1);
+                }
+                let status = status(conf, &mut speed_meter, &automaton);
+                display.render(camera.capture(&automaton), status.as_ref());
+            }
+            // The governor sets the pace of the ticks instead of the delay.
+            if governor.is_none() {
+                sleep(Duration::from_millis(conf.iteration_delay as u64));
+            }
+        }
+
+        let due_ticks = match &mut governor {
+            _ if pause => step as u32,
+            Some(governor) => {
+                let due_ticks = governor.due_ticks(Instant::now());
+                if due_ticks == 0 {
+                    // The keyboard is still read while waiting for the next tick.
+                    sleep(governor.time_to_next_tick(Instant::now()).min(Duration::from_millis(10)));
+                }
+                due_ticks
+            },
+            None => 1
+        };
+        for _ in 0..due_ticks {
+            if max_iteration_count == MaxIterationCount::Finite(i) {
+                break;
+            }
             automaton.tick();
+            for line in automaton.get_trace() {
+                info!("{}", line);
+            }
+            if let Some(monitor) = &mut monitor {
+                monitor.record(automaton.state_counts());
+            }
+            if let Some(detector) = &mut detector {
+                let detected = detector.record(&automaton);
+                if let (Some(detected), None) = (&detected, &periodicity) {
+                    info!("Iteration {} : {} detected", automaton.get_iteration(), detected);
+                }
+                periodicity = detected;
+            }
+            if let Some(writer) = &mut event_log {
+                if let Err(error) = writer.record(&automaton) {
+                    error!("{}", error);
+                    event_log = None;
+                }
+            }
+            if let Some(log) = &mut population_log {
+                if let Err(error) = log.record(&automaton) {
+                    error!("{}", error);
+                    population_log = None;
+                }
+            }
+            if let Some(tracker) = &mut tracker {
+                let pattern = tracker.record(&automaton);
+                match pattern.bounding_box {
+                    Some((x, y, width, height)) => info!("Iteration {} : tagged pattern of {} cells in box {} {} {} {}, growth rate {:.4}",
+                                                         automaton.get_iteration(), pattern.population, x, y, width, height, pattern.growth_rate),
+                    None => info!("Iteration {} : tagged pattern died out", automaton.get_iteration())
+                }
+            }
+            if let Some(state) = analyzed_state {
+                let metrics = compute_metrics(&automaton, state);
+                info!("Iteration {} : spatial entropy {:.4}, {} clusters, mean cluster size {:.2}",
+                      automaton.get_iteration(), metrics.spatial_entropy, metrics.cluster_count, metrics.mean_cluster_size);
+            }
+            if let Some(recorder) = &mut recorder {
+                recorder.render(recorder_camera.capture(&automaton), None);
+            }
+            if conf.summary_interval.is_some_and(|interval| automaton.get_iteration() % interval == 0) {
+                print_summary(&mut summarizer, &automaton);
+            }
             i += 1;
+            if let Some(reason) = stop_detector.as_mut().and_then(|detector| detector.check(&automaton)) {
+                info!("Iteration {} : {}, the run is stopped", automaton.get_iteration(), reason);
+                stabilized = true;
+                break;
+            }
         }
 
-        continue_simulation = match conf.max_iteration_count {
+        continue_simulation = !stabilized && match max_iteration_count {
             MaxIterationCount::Infinite => true,
             MaxIterationCount::Finite(max) => i < max
         };
     }
 
-    if conf.with_display {
+    if with_display {
+        if conf.png_frames.is_some() && rendered_iteration != Some(automaton.get_iteration()) {
+            let status = status(conf, &mut speed_meter, &automaton);
+            display.render(camera.capture(&automaton), status.as_ref());
+        }
         display.clean();
     }
+    if let Some(recorder) = &mut recorder {
+        recorder.clean();
+    }
+    if let Some(Err(error)) = event_log.map(EventLogWriter::finish) {
+        error!("{}", error);
+    }
+    if let Some(Err(error)) = population_log.map(PopulationLog::finish) {
+        error!("{}", error);
+    }
+    if let Some(file_name) = conf.checkpoint {
+        let stop_iteration = match max_iteration_count {
+            MaxIterationCount::Infinite => None,
+            MaxIterationCount::Finite(max) => Some(first_iteration + max)
+        };
+        let mut checkpoint = automaton.checkpoint(stop_iteration);
+        checkpoint.manifest = embedded_manifest;
+        match checkpoint.save(file_name) {
+            Ok(()) => info!("Iteration {} saved in {}", automaton.get_iteration(), file_name),
+            Err(error) => error!("{}", error)
+        }
+    }
 
     if !pause {
         runtime_duration += start.elapsed();
     }
-    println!("Over. {} iterations / s", (i as f32 / runtime_duration.as_millis() as f32)*1000.0);
+    drop(mouse_terminal);
+    drop(raw_stdout);
+    // A resumed run may have nothing left to do.
+    let speed = if i == 0 { 0.0 } else { i as f64 / runtime_duration.as_secs_f64() };
+    println!("Over. {} iterations / s", speed);
+    println!("Memory : {:.1} MB for the cells", automaton.memory_usage() as f64 / MEGABYTE);
+    print_population(conf, &automaton);
+    if let Some(monitor) = monitor {
+        println!("Verdict : {}", monitor.verdict());
+    }
+    if detector.is_some() {
+        match periodicity {
+            Some(periodicity) => println!("Periodicity : {}", periodicity),
+            None => println!("Periodicity : none detected")
+        }
+    }
+}
+
+/// Returns an error if the cells of an automaton with the rules would need more memory than the limit in megabytes,
+/// and warns if they need more than half of it.
+fn check_memory(rules: &Rules, limit: usize) -> Result<(), String> {
+    let estimate = Automaton::estimate_memory(rules) as f64 / MEGABYTE;
+    let (width, height) = rules.grid_size();
+    if estimate > limit as f64 {
+        return Err(format!("The world of {} x {} cells needs about {:.1} MB, more than the limit of {} MB. \
+                            Reduce the size of the world, or raise the limit with --memory-limit.", width, height, estimate, limit));
+    }
+    if estimate > limit as f64 / 2.0 {
+        warn!("The world of {} x {} cells needs about {:.1} MB, more than half of the limit of {} MB.", width, height, estimate, limit);
+    } else {
+        info!("The world of {} x {} cells needs about {:.1} MB.", width, height, estimate);
+    }
+    Ok(())
+}
+
+fn print_summary(summarizer: &mut Summarizer, automaton: &Automaton) {
+    println!("{}", summarizer.summarize(automaton).join(" "));
+}
+
+fn print_population(conf: &Conf, automaton: &Automaton) {
+    let rules = automaton.get_rules();
+    if conf.raw_statistics {
+        for (state, count) in automaton.raw_state_counts().iter().enumerate() {
+            let parent = rules.parent_state(state);
+            if parent == state {
+                println!("{} : {}", rules.states[state].name, count);
+            } else {
+                println!("{} (implicit state #{}) : {}", rules.states[parent].name, state, count);
+            }
+        }
+    } else {
+        for (state, count) in rules.user_states().iter().zip(automaton.state_counts()) {
+            println!("{} : {}", state.name, count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::analysis::Stabilization;
+    use crate::checkpoint::Checkpoint;
+    use crate::automaton::Automaton;
+    use crate::compiler::parser::ComparisonOperator;
+    use crate::executor::{execute, run_file, Conf, MaxIterationCount, PopulationThreshold, RunObserver, RunOptions, StopCondition, StopReason};
+
+    struct Ticks(usize);
+
+    impl RunObserver for Ticks {
+        fn on_tick(&mut self, _automaton: &Automaton) {
+            self.0 += 1;
+        }
+    }
+
+    fn options(stop_condition: StopCondition) -> RunOptions {
+        RunOptions { seed: Some(1), max_iteration_count: MaxIterationCount::Finite(20), stop_condition, ..RunOptions::default() }
+    }
+
+    #[test]
+    fn headless_run_needs_no_terminal() {
+        // The standard output of the tests is not a terminal, the runs go to their end all the same.
+        let checkpoint = std::env::temp_dir().join("mutations_headless_run.bin");
+        let checkpoint = checkpoint.to_str().unwrap();
+        let conf = |with_display, summary_interval| Conf {
+            file_name: "resources/game_of_life.txt",
+            with_display,
+            summary_interval,
+            iteration_delay: 0,
+            max_iteration_count: MaxIterationCount::Finite(20),
+            checkpoint: Some(checkpoint),
+            ..Conf::default()
+        };
+        // A headless run, then a run with a summary instead of the grid.
+        for conf in [conf(false, None), conf(true, Some(10))].iter() {
+            let _ = fs::remove_file(checkpoint);
+            execute(conf);
+            assert_eq!(Checkpoint::load(checkpoint).unwrap().iteration, 20);
+        }
+        fs::remove_file(checkpoint).unwrap();
+    }
+
+    #[test]
+    fn run_stops_on_its_condition() {
+        // The burning cell counts down its delay before it turns to ash, and the grid is frozen.
+        let burnt_out = StopCondition::Population { state: "burning".to_string(), comparison: ComparisonOperator::Equal, value: PopulationThreshold::Cells(0) };
+        let mut ticks = Ticks(0);
+        let outcome = run_file("resources/tests/semantic_delay.txt", options(burnt_out), &mut ticks).unwrap();
+        assert_eq!((outcome.iterations, ticks.0), (4, 4));
+        assert_eq!(outcome.stop_reason, Some(StopReason::Population { state: "burning".to_string(), population: 0 }));
+
+        let outcome = run_file("resources/tests/semantic_delay.txt", options(StopCondition::OnStabilization { max_period: 1 }), Ticks(0)).unwrap();
+        assert_eq!(outcome.stop_reason, Some(StopReason::Stabilization(Stabilization { iteration: 4, period: 1 })));
+
+        // The 200 cells of the grid are all ash once the fire is out, and never less than 99% ash.
+        let all_ash = StopCondition::Population { state: "ash".to_string(), comparison: ComparisonOperator::GreaterOrEqual, value: PopulationThreshold::Percent(100.0) };
+        assert_eq!(run_file("resources/tests/semantic_delay.txt", options(all_ash), Ticks(0)).unwrap().iterations, 4);
+        let little_ash = StopCondition::Population { state: "ash".to_string(), comparison: ComparisonOperator::Lesser, value: PopulationThreshold::Percent(99.0) };
+        let outcome = run_file("resources/tests/semantic_delay.txt", options(little_ash), Ticks(0)).unwrap();
+        assert_eq!((outcome.iterations, outcome.stop_reason), (20, None));
+
+        let unknown = StopCondition::Population { state: "smoke".to_string(), comparison: ComparisonOperator::Equal, value: PopulationThreshold::Cells(0) };
+        let errors = run_file("resources/tests/semantic_delay.txt", options(unknown), Ticks(0)).err().unwrap();
+        assert_eq!(errors[0].to_string(), "The state \"smoke\" of the stop condition is not defined.");
+    }
 }
@@ -0,0 +1,109 @@
+//! This module synthesizes random valid rule files, to stress-test the compiler and the automaton.
+
+use rand::Rng;
+use crate::compiler::parser::NeighborCell;
+use crate::rng::CounterRng;
+
+const OPERATORS: [&str; 6] = ["<", ">", "<=", ">=", "==", "!="];
+
+/// The size of the generated rules.
+pub struct GeneratorConf {
+    pub world_size: (usize, usize),
+    pub states: usize,
+    pub transitions: usize,
+    /// The maximum number of conjunctions in a condition, and of conditions in a conjunction.
+    pub condition_complexity: usize
+}
+
+impl Default for GeneratorConf {
+    fn default() -> GeneratorConf {
+        GeneratorConf {
+            world_size: (200, 50),
+            states: 4,
+            transitions: 8,
+            condition_complexity: 3
+        }
+    }
+}
+
+/// Returns the text of a random rule file. The same seed always gives the same file.
+///
+/// The first state is the default state, the others share half of the grid. The conditions mix quantity,
+/// neighbor and random conditions, or are always true, and some transitions have a delay or a priority.
+pub fn generate_rules(conf: &GeneratorConf, seed: u64) -> String {
+    let mut rng = CounterRng::new(seed, 0, 0);
+    let states = conf.states.max(2);
+    let name = |state: usize| format!("s{}", state);
+
+    let mut text = format!("size ({}, {})\n\nstates {{\n", conf.world_size.0, conf.world_size.1);
+    for state in 0..states {
+        let (red, green, blue): (u8, u8, u8) = (rng.gen(), rng.gen(), rng.gen());
+        let distribution = if state == 0 { String::new() } else { format!(", proportion {:.4}", 0.5 / (states - 1) as f64) };
+        text.push_str(&format!("    ({}, {}, {}, {}{}),\n", name(state), red, green, blue, distribution));
+    }
+
+    text.push_str("}\n\ntransitions {\n");
+    let complexity = conf.condition_complexity.max(1);
+    for _ in 0..conf.transitions {
+        let origin = rng.gen_range(0, states);
+        let destination = (origin + rng.gen_range(1, states)) % states;
+        let conditions = generate_conditions(&mut rng, states, complexity);
+        let mut annotations = String::new();
+        if rng.gen_range(0, 5) == 0 {
+            annotations.push_str(&format!(", delay {}", rng.gen_range(2, 6)));
+        }
+        if rng.gen_range(0, 5) == 0 {
+            annotations.push_str(&format!(", priority {}", rng.gen_range(0, 4)));
+        }
+        text.push_str(&format!("    ({}, {}, {}{}),\n", name(origin), name(destination), conditions, annotations));
+    }
+    text.push_str("}\n");
+    text
+}
+
+/// Returns a disjunction of conjunctions. The "true" condition is always alone.
+fn generate_conditions(rng: &mut CounterRng, states: usize, complexity: usize) -> String {
+    if rng.gen_range(0, 10) == 0 {
+        return String::from("true");
+    }
+    let conjunctions: Vec<String> = (0..rng.gen_range(1, complexity + 1))
+        .map(|_| (0..rng.gen_range(1, complexity + 1))
+            .map(|_| generate_condition(rng, states))
+            .collect::<Vec<_>>()
+            .join(" && "))
+        .collect();
+    conjunctions.join(" || ")
+}
+
+fn generate_condition(rng: &mut CounterRng, states: usize) -> String {
+    match rng.gen_range(0, 9) {
+        0..=1 => format!("rand {:.2}", rng.gen_range(1, 101) as f64 / 100.0),
+        2..=4 => format!("{} is s{}", NeighborCell::ALL[rng.gen_range(0, 8)], rng.gen_range(0, states)),
+        _ => format!("s{} {} {}", rng.gen_range(0, states), OPERATORS[rng.gen_range(0, 6)], rng.gen_range(0, 9))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+    use crate::generator::{generate_rules, GeneratorConf};
+
+    #[test]
+    fn generated_rules_are_valid_and_run() {
+        let conf = GeneratorConf { world_size: (20, 20), states: 5, transitions: 12, condition_complexity: 3 };
+        let file_name = std::env::temp_dir().join("mutations_generated_rules.txt");
+        for seed in 0..50 {
+            let text = generate_rules(&conf, seed);
+            assert_eq!(generate_rules(&conf, seed), text);
+            fs::write(&file_name, &text).unwrap();
+            let rules = parse(file_name.to_str().unwrap()).unwrap_or_else(|errors| panic!("{:?}\n{}", errors, text));
+            let mut automaton = Automaton::with_seed(rules, seed);
+            for _ in 0..5 {
+                automaton.tick();
+            }
+        }
+        fs::remove_file(&file_name).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! This module renders the images captured by the camera as PNG files, with the exact colors of their pixels.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use crate::camera::Image;
+use crate::display::{Display, Status};
+
+/// Writes each image in a numbered PNG file of the directory : frame_00001.png, frame_00002.png...
+pub struct PngDisplay {
+    directory: PathBuf,
+    scale: usize, // The width and height in pixels of a cell
+    frame: usize
+}
+
+impl PngDisplay {
+    pub fn new(directory: &str, scale: usize) -> PngDisplay {
+        PngDisplay { directory: PathBuf::from(directory), scale: scale.max(1), frame: 0 }
+    }
+}
+
+impl Display for PngDisplay {
+    fn init(&mut self) {
+        if let Err(error) = std::fs::create_dir_all(&self.directory) {
+            error!("Cannot create the directory {}. Cause : {:?}", self.directory.display(), error);
+        }
+    }
+
+    /// The status is written in a comment of the PNG file.
+    fn render(&mut self, image: &Image, status: Option<&Status>) {
+        self.frame += 1;
+        let file_name = self.directory.join(format!("frame_{:05}.png", self.frame));
+        if let Err(error) = write_png(&file_name, image, self.scale, status) {
+            error!("Cannot write the frame {}. Cause : {}", file_name.display(), error);
+        }
+    }
+
+    fn clean(&mut self) {
+        info!("{} frames written in {}", self.frame, self.directory.display());
+    }
+}
+
+/// Returns the RGB pixels of the image, row after row. Each cell is a square of scale x scale pixels.
+pub fn to_pixels(image: &Image, scale: usize) -> (usize, usize, Vec<u8>) {
+    let (width, height) = (image.grid.len(), image.grid.first().map_or(0, Vec::len));
+    let mut pixels = Vec::with_capacity(width * height * scale * scale * 3);
+    for y in 0..height * scale {
+        for x in 0..width * scale {
+            let (r, g, b) = image.grid[x / scale][y / scale];
+            pixels.extend_from_slice(&[r, g, b]);
+        }
+    }
+    (width * scale, height * scale, pixels)
+}
+
+fn write_png(file_name: &Path, image: &Image, scale: usize, status: Option<&Status>) -> Result<(), String> {
+    let (width, height, pixels) = to_pixels(image, scale);
+    let file = File::create(file_name).map_err(|error| format!("{:?}", error))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(status) = status {
+        encoder.add_text_chunk("Comment".to_string(), status.line()).map_err(|error| error.to_string())?;
+    }
+    let mut writer = encoder.write_header().map_err(|error| error.to_string())?;
+    writer.write_image_data(&pixels).map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use crate::camera::Image;
+    use crate::display::{Display, Status};
+    use crate::image_display::{PngDisplay, to_pixels};
+
+    fn image() -> Image {
+        // Two columns of one cell each : a red cell, then a white cell.
+        Image { grid: vec![vec![(255, 0, 0)], vec![(255, 255, 255)]] }
+    }
+
+    #[test]
+    fn scale_pixels() {
+        let (width, height, pixels) = to_pixels(&image(), 2);
+        assert_eq!((width, height), (4, 2));
+        assert_eq!(&pixels[..12], &[255, 0, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255]);
+        assert_eq!(&pixels[..12], &pixels[12..]);
+    }
+
+    #[test]
+    fn write_numbered_frames() {
+        let directory = std::env::temp_dir().join("mutations_png_frames");
+        let mut display = PngDisplay::new(directory.to_str().unwrap(), 3);
+        display.init();
+        display.render(&image(), None);
+        let status = Status { iteration: 1, speed: 0.0, populations: vec![("red".to_string(), 1), ("white".to_string(), 1)] };
+        display.render(&image(), Some(&status));
+
+        let decoder = png::Decoder::new(File::open(directory.join("frame_00002.png")).unwrap());
+        let reader = decoder.read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (6, 3));
+        assert_eq!(reader.info().uncompressed_latin1_text[0].text, "Iteration 1 | 0.0 it/s | red 1 | white 1");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -1,6 +1,7 @@
+#[cfg(not(target_arch = "wasm32"))]
 use termion::{
     AsyncReader,
-    event::Key,
+    event::{Event, Key, MouseButton, MouseEvent},
     input::TermRead
 };
 
@@ -19,37 +20,67 @@ pub enum Zoom {
 pub enum UserAction {
     TranslateCamera(Direction),
     ZoomCamera(Zoom),
+    ChangeLayer(isize),
     TogglePause,
+    Step, // Runs a single tick while the simulation is paused
+    ReloadRules,
+    CycleBrush, // Selects the next state painted with the mouse
+    ExportWorld, // Writes the current world in a RLE file
+    PaintCell { x: u16, y: u16 }, // The position in the image, the character of the terminal or the pixel of the window, starting at 0
     Quit,
     Nop
 }
 
+/// Reads the keyboard and the mouse in the terminal.
+#[cfg(not(target_arch = "wasm32"))]
 pub struct Inputs {
-    keys: termion::input::Keys<AsyncReader>
+    events: termion::input::Events<AsyncReader>
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for Inputs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
 impl Inputs {
     pub fn new() -> Inputs {
         Inputs {
-            keys: termion::async_stdin().keys()
+            events: termion::async_stdin().events()
         }
     }
 
-    pub fn read_keyboard(&mut self) -> UserAction {
-        if let Some(Ok(key)) = self.keys.next() {
-            match key {
-                Key::Esc => UserAction::Quit,
-                Key::Left => UserAction::TranslateCamera(Direction::Left),
-                Key::Right => UserAction::TranslateCamera(Direction::Right),
-                Key::Up => UserAction::TranslateCamera(Direction::Up),
-                Key::Down => UserAction::TranslateCamera(Direction::Down),
-                Key::Char('z') => UserAction::ZoomCamera(Zoom::In),
-                Key::Char('s') => UserAction::ZoomCamera(Zoom::Out),
-                Key::Char('p') => UserAction::TogglePause,
-                _ => UserAction::Nop
-            }
-        } else {
-            UserAction::Nop
+    /// Reads the next key or mouse event. A click or a drag with the left button paints the cell under the mouse.
+    pub fn read_inputs(&mut self) -> UserAction {
+        match self.events.next() {
+            Some(Ok(Event::Key(key))) => read_key(key),
+            Some(Ok(Event::Mouse(MouseEvent::Press(MouseButton::Left, x, y)))) | Some(Ok(Event::Mouse(MouseEvent::Hold(x, y)))) =>
+                // The terminal reports the positions from 1.
+                UserAction::PaintCell { x: x.saturating_sub(1), y: y.saturating_sub(1) },
+            _ => UserAction::Nop
         }
     }
 }
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_key(key: Key) -> UserAction {
+    match key {
+        Key::Esc => UserAction::Quit,
+        Key::Left => UserAction::TranslateCamera(Direction::Left),
+        Key::Right => UserAction::TranslateCamera(Direction::Right),
+        Key::Up => UserAction::TranslateCamera(Direction::Up),
+        Key::Down => UserAction::TranslateCamera(Direction::Down),
+        Key::Char('z') => UserAction::ZoomCamera(Zoom::In),
+        Key::Char('s') => UserAction::ZoomCamera(Zoom::Out),
+        Key::PageUp => UserAction::ChangeLayer(-1),
+        Key::PageDown => UserAction::ChangeLayer(1),
+        Key::Char('p') => UserAction::TogglePause,
+        Key::Char('n') => UserAction::Step,
+        Key::Char('r') => UserAction::ReloadRules,
+        Key::Char('c') => UserAction::CycleBrush,
+        Key::Char('e') => UserAction::ExportWorld,
+        _ => UserAction::Nop
+    }
+}
@@ -0,0 +1,314 @@
+//! This module reads the patterns of other cellular automaton tools, like the Run Length Encoded (.rle) files of Golly,
+//! so that well-known patterns like gliders and guns can be placed on the world :
+//!
+//! ```text
+//! #N Glider
+//! x = 3, y = 3, rule = B3/S23
+//! bob$2bo$3o!
+//! ```
+//!
+//! A run is a number of cells, 1 if omitted, followed by their state : `b` or `.` for the dead state, `o` for the alive state,
+//! and `A` to `X`, preceded by `p` to `y` above 24, for the states of the multi-state rules. `$` ends a row and `!` the pattern.
+//! The states of the pattern are numbered from 0, and are mapped to the states of the rules by name.
+//!
+//! The world can also be written back as a RLE file, or as a Life 1.06 file listing the coordinates of the alive cells,
+//! to share the structures found during a run. The PNG images drawn in a paint program can give the initial cells.
+
+use std::fs;
+use std::fs::File;
+use crate::automaton::Automaton;
+use crate::compiler::semantic::Rules;
+use crate::pattern::Pattern;
+
+const RLE_LINE_LENGTH: usize = 70; // The lines of the RLE files should not be longer
+
+/// The size of an image and the colors of its pixels, row after row.
+pub type Pixels = ((usize, usize), Vec<(u8, u8, u8)>);
+
+/// A pattern read from a RLE file, whose states are the numbers of the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RlePattern {
+    pub size: (usize, usize),
+    pub rule: Option<String>, // The rule the pattern was made for, like "B3/S23"
+    pub cells: Vec<((usize, usize), usize)> // The position and the state of the cells that are not in the state 0
+}
+
+impl RlePattern {
+    pub fn parse(text: &str) -> Result<RlePattern, String> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let header = lines.next().ok_or("The pattern has no header line, like \"x = 3, y = 3\".")?;
+        let mut size = (None, None);
+        let mut rule = None;
+        for field in header.split(',') {
+            let (key, value) = field.split_once('=').ok_or(format!("Invalid header \"{}\", expected a line like \"x = 3, y = 3\".", header))?;
+            let number = || value.trim().parse::<usize>().map_err(|_| format!("The {} of the header is not a positive integer.", key.trim()));
+            match key.trim() {
+                "x" => size.0 = Some(number()?),
+                "y" => size.1 = Some(number()?),
+                "rule" => rule = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+        let size = match size {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Err(format!("The header \"{}\" doesn't give the size of the pattern.", header))
+        };
+
+        let mut cells = Vec::new();
+        let (mut x, mut y) = (0, 0);
+        let mut count = None;
+        let mut prefix = None;
+        for c in lines.flat_map(str::chars).filter(|c| !c.is_whitespace()) {
+            if let Some(digit) = c.to_digit(10) {
+                count = Some(count.unwrap_or(0) * 10 + digit as usize);
+                continue;
+            }
+            let run = count.take().unwrap_or(1);
+            let state = match (prefix.take(), c) {
+                (None, '!') => break,
+                (None, '$') => {
+                    x = 0;
+                    y += run;
+                    continue;
+                },
+                (None, 'b') | (None, '.') => 0,
+                (None, 'o') => 1,
+                (None, 'p'..='y') => {
+                    prefix = Some(c);
+                    count = Some(run);
+                    continue;
+                },
+                (prefix, 'A'..='X') => prefix.map_or(0, |p| (p as usize - 'p' as usize + 1) * 24) + (c as usize - 'A' as usize + 1),
+                _ => return Err(format!("Unexpected character '{}' in the cells of the pattern.", c))
+            };
+            if state != 0 {
+                if x + run > size.0 || y >= size.1 {
+                    return Err(format!("The cells of the row {} are outside of the pattern of {} x {} cells.", y + 1, size.0, size.1));
+                }
+                cells.extend((x..x + run).map(|cell_x| ((cell_x, y), state)));
+            }
+            x += run;
+        }
+        Ok(RlePattern { size, rule, cells })
+    }
+
+    pub fn load(file_name: &str) -> Result<RlePattern, String> {
+        let text = fs::read_to_string(file_name).map_err(|error| format!("Cannot read the pattern file {}. Cause : {:?}", file_name, error))?;
+        RlePattern::parse(&text).map_err(|error| format!("In {} : {}", file_name, error))
+    }
+
+    /// Returns the whole world, layers stacked, with the states declared by the user : the cells counting down a delay
+    /// are in the state of the delay.
+    pub fn from_automaton(automaton: &Automaton) -> RlePattern {
+        let rules = automaton.get_rules();
+        let cells = automaton.cells()
+            .map(|(position, state)| (position, rules.parent_state(state)))
+            .filter(|(_, state)| *state != 0)
+            .collect();
+        RlePattern { size: rules.grid_size(), rule: None, cells }
+    }
+
+    /// Returns the RLE text of the pattern. The two states patterns use `b` and `o`, like the patterns of the Game of Life.
+    pub fn to_rle(&self) -> String {
+        let two_states = self.cells.iter().all(|(_, state)| *state <= 1);
+        let symbol = |state: usize| match state {
+            0 if two_states => "b".to_string(),
+            1 if two_states => "o".to_string(),
+            0 => ".".to_string(),
+            _ => {
+                let prefix = if state > 24 { Some((b'p' + ((state - 1) / 24 - 1) as u8) as char) } else { None };
+                prefix.into_iter().chain(Some((b'A' + ((state - 1) % 24) as u8) as char)).collect()
+            }
+        };
+        let mut rows = vec![Vec::new(); self.size.1];
+        let mut cells = self.cells.clone();
+        cells.sort_by_key(|((x, y), _)| (*y, *x));
+        for ((x, y), state) in cells {
+            rows[y].push((x, state));
+        }
+
+        // The runs of the row are written without the dead cells at the end, and the empty rows are merged in the '$' run.
+        let mut items = Vec::new();
+        let mut row_ends = 0;
+        for row in rows {
+            if row.is_empty() {
+                row_ends += 1;
+                continue;
+            }
+            if row_ends > 0 {
+                items.push(run(row_ends, "$"));
+            }
+            row_ends = 1;
+            let mut next_x = 0;
+            let mut runs: Vec<(usize, usize)> = Vec::new();
+            for (x, state) in row {
+                if x > next_x {
+                    runs.push((x - next_x, 0));
+                }
+                match runs.last_mut() {
+                    Some((count, last_state)) if *last_state == state => *count += 1,
+                    _ => runs.push((1, state))
+                }
+                next_x = x + 1;
+            }
+            items.extend(runs.into_iter().map(|(count, state)| run(count, &symbol(state))));
+        }
+        items.push("!".to_string());
+
+        let mut text = format!("x = {}, y = {}", self.size.0, self.size.1);
+        if let Some(rule) = &self.rule {
+            text += &format!(", rule = {}", rule);
+        }
+        let mut line = String::new();
+        for item in items {
+            if line.len() + item.len() > RLE_LINE_LENGTH {
+                text += &format!("\n{}", line);
+                line.clear();
+            }
+            line += &item;
+        }
+        text + "\n" + &line + "\n"
+    }
+
+    /// Returns the Life 1.06 text of the pattern, a line with the coordinates of each alive cell.
+    /// The format has no other states, so it only writes the patterns of two states.
+    pub fn to_life_106(&self) -> Result<String, String> {
+        if self.cells.iter().any(|(_, state)| *state > 1) {
+            return Err("The Life 1.06 format only has the dead and alive states, use the RLE format for more states.".to_string());
+        }
+        let mut cells: Vec<_> = self.cells.iter().map(|(position, _)| *position).collect();
+        cells.sort_by_key(|(x, y)| (*y, *x));
+        Ok(cells.into_iter().fold("#Life 1.06\n".to_string(), |text, (x, y)| text + &format!("{} {}\n", x, y)))
+    }
+
+    /// Writes the pattern in a Life 1.06 file if its extension is `.lif` or `.life`, and in a RLE file otherwise.
+    pub fn save(&self, file_name: &str) -> Result<(), String> {
+        let text = if file_name.ends_with(".lif") || file_name.ends_with(".life") {
+            self.to_life_106()?
+        } else {
+            self.to_rle()
+        };
+        fs::write(file_name, text).map_err(|error| format!("Cannot write the pattern file {}. Cause : {:?}", file_name, error))
+    }
+
+    /// Returns the pattern with the states of the rules : the state `i` of the file becomes the state named `state_names[i]`.
+    /// Without names, the states of the file are the states of the rules in their order of declaration, like dead and alive.
+    /// The cells in the state 0 are part of the pattern, so the pattern replaces the whole box it is stamped on.
+    pub fn to_pattern(&self, rules: &Rules, state_names: &[&str]) -> Result<Pattern, String> {
+        let states = if state_names.is_empty() {
+            (0..rules.user_states().len()).collect()
+        } else {
+            state_names.iter()
+                .map(|name| rules.user_states().iter().position(|state| state.name == *name).ok_or(format!("The state \"{}\" is not defined.", name)))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let state = |rle_state: usize| states.get(rle_state).copied()
+            .ok_or(format!("The pattern has the state {}, but only {} states are mapped to the states of the rules.", rle_state, states.len()));
+        let mut grid = vec![state(0)?; self.size.0 * self.size.1];
+        for ((x, y), rle_state) in &self.cells {
+            grid[y * self.size.0 + x] = state(*rle_state)?;
+        }
+        let cells = grid.into_iter().enumerate().map(|(index, state)| ((index % self.size.0, index / self.size.0), state)).collect();
+        Pattern::new(self.size, cells)
+    }
+}
+
+/// Reads a PNG image. The transparency is ignored.
+pub fn load_image(file_name: &str) -> Result<Pixels, String> {
+    let cause = |error: &dyn std::fmt::Display| format!("Cannot read the image {}. Cause : {}", file_name, error);
+    let file = File::open(file_name).map_err(|error| cause(&error))?;
+    let mut decoder = png::Decoder::new(file);
+    // The palettes, the grayscale images and the 16 bits channels are converted to 8 bits channels.
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|error| cause(&error))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|error| cause(&error))?;
+    let channels = info.color_type.samples();
+    let pixels = buffer[..info.buffer_size()].chunks(channels)
+        .map(|pixel| if channels < 3 { (pixel[0], pixel[0], pixel[0]) } else { (pixel[0], pixel[1], pixel[2]) })
+        .collect();
+    Ok(((info.width as usize, info.height as usize), pixels))
+}
+
+fn run(count: usize, symbol: &str) -> String {
+    if count == 1 {
+        symbol.to_string()
+    } else {
+        format!("{}{}", count, symbol)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+    use crate::interop::RlePattern;
+    use crate::pattern::{stamp, Orientation};
+
+    static LIFE_FILE: &str = "resources/tests/interop_life.txt";
+
+    #[test]
+    fn parse_rle_runs_rows_and_states() {
+        let glider = RlePattern::parse("#N Glider\nx = 3, y = 3, rule = B3/S23\nbob$2bo$\n3o!").unwrap();
+        assert_eq!(glider.size, (3, 3));
+        assert_eq!(glider.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(glider.cells, vec![((1, 0), 1), ((2, 1), 1), ((0, 2), 1), ((1, 2), 1), ((2, 2), 1)]);
+
+        // The extended states, and several empty rows at once.
+        let states = RlePattern::parse("x = 4, y = 4\n.A2B3$pAqX!").unwrap();
+        assert_eq!(states.cells, vec![((1, 0), 1), ((2, 0), 2), ((3, 0), 2), ((0, 3), 25), ((1, 3), 72)]);
+
+        assert!(RlePattern::parse("bob!").is_err());
+        assert!(RlePattern::parse("x = 2, y = 1\n3o!").is_err());
+        assert!(RlePattern::parse("x = 2, y = 1\n2z!").is_err());
+    }
+
+    #[test]
+    fn glider_gun_emits_a_glider_every_30_ticks() {
+        let rules = parse(LIFE_FILE).unwrap();
+        let gun = RlePattern::load("resources/patterns/gosper_glider_gun.rle").unwrap();
+        assert_eq!(gun.cells.len(), 36);
+        let pattern = gun.to_pattern(&rules, &["dead", "alive"]).unwrap();
+        let mut automaton = Automaton::with_seed(rules, 0);
+        stamp(&mut automaton, &pattern, (2, 2), 0, Orientation::default()).unwrap();
+        assert_eq!(automaton.state_counts(), vec![80 * 40 - 36, 36]);
+        for _ in 0..30 {
+            automaton.tick();
+        }
+        assert_eq!(automaton.state_counts()[1], 36 + 5);
+
+        assert!(gun.to_pattern(automaton.get_rules(), &["dead", "zombie"]).is_err());
+        assert!(gun.to_pattern(automaton.get_rules(), &["alive"]).is_err());
+    }
+
+    #[test]
+    fn export_rle_round_trips() {
+        let rules = parse(LIFE_FILE).unwrap();
+        let gun = RlePattern::load("resources/patterns/gosper_glider_gun.rle").unwrap();
+        let pattern = gun.to_pattern(&rules, &[]).unwrap();
+        let mut automaton = Automaton::with_seed(rules, 0);
+        stamp(&mut automaton, &pattern, (3, 5), 0, Orientation::default()).unwrap();
+        let world = RlePattern::from_automaton(&automaton);
+        assert_eq!(world.size, (80, 40));
+        let text = world.to_rle();
+        assert!(text.starts_with("x = 80, y = 40\n5$"));
+        assert!(text.lines().all(|line| line.len() <= 70));
+        let mut cells = RlePattern::parse(&text).unwrap().cells;
+        cells.sort();
+        let mut gun_cells: Vec<_> = gun.cells.iter().map(|((x, y), state)| ((x + 3, y + 5), *state)).collect();
+        gun_cells.sort();
+        assert_eq!(cells, gun_cells);
+
+        let file_name = std::env::temp_dir().join("interop_export.lif");
+        let file_name = file_name.to_str().unwrap();
+        automaton.export_rle(file_name).unwrap();
+        let life = std::fs::read_to_string(file_name).unwrap();
+        assert_eq!(life.lines().next(), Some("#Life 1.06"));
+        assert_eq!(life.lines().count(), 37);
+
+        // The states above the alive state use the letters, and can't be written in the Life 1.06 format.
+        let states = RlePattern { size: (4, 4), rule: None, cells: vec![((1, 0), 1), ((2, 0), 2), ((3, 0), 2), ((0, 3), 25), ((1, 3), 72)] };
+        assert_eq!(states.to_rle(), "x = 4, y = 4\n.A2B3$pAqX!\n");
+        assert!(states.to_life_106().is_err());
+    }
+}
@@ -1,9 +1,40 @@
 #[macro_use]
 extern crate log;
 
+pub mod prelude;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod executor;
+pub mod generator;
 pub mod compiler;
 pub mod automaton;
-pub mod camera;
+pub mod checkpoint;
+pub mod manifest;
+pub mod event_log;
+pub mod population_log;
+pub(crate) mod camera;
 pub mod display;
-pub mod inputs;
+pub mod image_display;
+#[cfg(feature = "sdl2_rendering")]
+pub mod sdl_display;
+#[cfg(feature = "wasm")]
+pub mod canvas_display;
+#[cfg(feature = "wasm")]
+pub mod web;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod terminal;
+pub(crate) mod inputs;
+pub(crate) mod rng;
+pub mod simulation;
+pub mod simulation_set;
+pub(crate) mod summary;
+pub mod topology;
+pub mod analysis;
+pub(crate) mod search;
+pub(crate) mod evolve;
+pub mod render;
+pub mod bench;
+pub mod palette;
+pub mod profile;
+pub mod doc;
+pub mod pattern;
+pub mod interop;
@@ -0,0 +1,225 @@
+//! This module describes a run with what is needed to reproduce it : the hash of its rules, its seed, the version and the
+//! variant of the engine, and the options of the command line that change the evolution. The manifest is written in a TOML
+//! file, and can be embedded in the checkpoints and the recordings, so that a replay is checked before it is played :
+//!
+//! ```toml
+//! version = "0.1.0"
+//! variant = "default"
+//! rules = ["resources/virus.txt"]
+//! rules-hash = "5f1bd4a7c3e09a2b"
+//! seed = "42"
+//! options = ["--max-iterations", "500"]
+//! ```
+
+use std::fs::{self, File};
+use toml::Value;
+use crate::checkpoint::Checkpoint;
+
+/// The keyword of the text chunk of an animated PNG holding the manifest.
+pub const MANIFEST_KEYWORD: &str = "mutations-manifest";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Manifest {
+    pub version: String, // The version of the crate that made the run
+    pub variant: String, // The variant of the engine, given by the features of the build
+    pub rules: Vec<String>, // The rules file followed by the files merged over it
+    pub rules_hash: String, // The hash of the content of the rules files, in hexadecimal
+    pub seed: u64,
+    pub options: Vec<String> // The options of the command line that change the evolution, like `--max-iterations 500`
+}
+
+impl Manifest {
+    /// Describes a run of the current engine with the rules files.
+    pub fn new(rules: &[&str], seed: u64, options: Vec<String>) -> Result<Manifest, String> {
+        let sources = rules.iter()
+            .map(|file_name| fs::read(file_name).map_err(|error| format!("Cannot read the rules file {}. Cause : {:?}", file_name, error)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Manifest {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            variant: engine_variant().to_string(),
+            rules: rules.iter().map(|file_name| file_name.to_string()).collect(),
+            rules_hash: rules_hash(&sources),
+            seed,
+            options
+        })
+    }
+
+    pub fn to_toml(&self) -> String {
+        let strings = |values: &[String]| Value::Array(values.iter().cloned().map(Value::String).collect());
+        let mut table = toml::map::Map::new();
+        table.insert("version".to_string(), Value::String(self.version.clone()));
+        table.insert("variant".to_string(), Value::String(self.variant.clone()));
+        table.insert("rules".to_string(), strings(&self.rules));
+        table.insert("rules-hash".to_string(), Value::String(self.rules_hash.clone()));
+        // The seed is written as a string, since the integers of TOML are signed.
+        table.insert("seed".to_string(), Value::String(self.seed.to_string()));
+        table.insert("options".to_string(), strings(&self.options));
+        Value::Table(table).to_string()
+    }
+
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let manifest = text.parse::<Value>().map_err(|error| format!("Invalid manifest : {}", error))?;
+        let string = |key: &str| manifest.get(key).and_then(Value::as_str).map(str::to_string)
+            .ok_or(format!("The manifest has no \"{}\" string.", key));
+        let strings = |key: &str| manifest.get(key).and_then(Value::as_array)
+            .and_then(|values| values.iter().map(|value| value.as_str().map(str::to_string)).collect::<Option<Vec<_>>>())
+            .ok_or(format!("The manifest has no \"{}\" array of strings.", key));
+        Ok(Manifest {
+            version: string("version")?,
+            variant: string("variant")?,
+            rules: strings("rules")?,
+            rules_hash: string("rules-hash")?,
+            seed: string("seed")?.parse().map_err(|_| "The seed of the manifest is not a positive integer.".to_string())?,
+            options: strings("options")?
+        })
+    }
+
+    pub fn save(&self, file_name: &str) -> Result<(), String> {
+        fs::write(file_name, self.to_toml()).map_err(|error| format!("Cannot write the manifest {}. Cause : {:?}", file_name, error))
+    }
+
+    /// Reads a manifest file, or the manifest embedded in a checkpoint, an animated PNG or a GIF.
+    pub fn load(file_name: &str) -> Result<Manifest, String> {
+        let bytes = fs::read(file_name).map_err(|error| format!("Cannot read the manifest {}. Cause : {:?}", file_name, error))?;
+        let embedded = if bytes.starts_with(b"MUTCKPT") {
+            Checkpoint::load(file_name)?.manifest
+        } else if bytes.starts_with(b"\x89PNG") {
+            png_manifest(file_name)?
+        } else if bytes.starts_with(b"GIF8") {
+            gif_comment(&bytes)
+        } else {
+            let text = String::from_utf8(bytes).map_err(|_| format!("The file {} is not a manifest.", file_name))?;
+            return Manifest::parse(&text).map_err(|error| format!("In {} : {}", file_name, error));
+        };
+        let text = embedded.ok_or(format!("The file {} has no embedded manifest.", file_name))?;
+        Manifest::parse(&text).map_err(|error| format!("In {} : {}", file_name, error))
+    }
+
+    /// Returns what differs between the engine and the rules of the runs, which would make the evolution of the other run
+    /// differ from this one with the same seed and options.
+    pub fn differences(&self, other: &Manifest) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.rules_hash != other.rules_hash {
+            differences.push(format!("The hash of the rules is {}, but {} is expected.", other.rules_hash, self.rules_hash));
+        }
+        if self.version != other.version {
+            differences.push(format!("The version of the engine is {}, but {} is expected.", other.version, self.version));
+        }
+        if self.variant != other.variant {
+            differences.push(format!("The variant of the engine is {}, but {} is expected.", other.variant, self.variant));
+        }
+        differences
+    }
+
+    /// Returns the arguments of the command line that replays the run.
+    pub fn replay_arguments(&self) -> Vec<String> {
+        let mut arguments = self.rules.clone();
+        arguments.extend(vec!["--seed".to_string(), self.seed.to_string()]);
+        arguments.extend(self.options.iter().cloned());
+        arguments
+    }
+}
+
+/// Returns the variant of the engine : the features of the build that change how the cells are computed.
+pub fn engine_variant() -> &'static str {
+    if cfg!(feature = "wrap_table") { "wrap_table" } else { "default" }
+}
+
+/// Returns the 64 bits FNV-1a hash of the contents of the files, in hexadecimal. Unlike the hasher of the standard library,
+/// it is the same for every build.
+fn rules_hash(sources: &[Vec<u8>]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    // Each file is followed by a zero, so that the same text split in other files gives another hash.
+    for byte in sources.iter().flat_map(|source| source.iter().chain(&[0])) {
+        hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+fn png_manifest(file_name: &str) -> Result<Option<String>, String> {
+    let file = File::open(file_name).map_err(|error| format!("Cannot read {}. Cause : {:?}", file_name, error))?;
+    let reader = png::Decoder::new(file).read_info().map_err(|error| format!("Cannot read {}. Cause : {}", file_name, error))?;
+    let chunk = reader.info().utf8_text.iter().find(|chunk| chunk.keyword == MANIFEST_KEYWORD);
+    chunk.map(|chunk| chunk.get_text().map_err(|error| format!("Cannot read the manifest of {}. Cause : {}", file_name, error))).transpose()
+}
+
+/// Returns the text of the first comment extension of a GIF file, among the extensions before the first image.
+fn gif_comment(bytes: &[u8]) -> Option<String> {
+    // The global color table follows the header and the screen descriptor, if the flags of the descriptor tell it.
+    let flags = *bytes.get(10)?;
+    let mut position = 13 + if flags & 0x80 != 0 { 3 << ((flags & 0x07) + 1) } else { 0 };
+    // An extension starts with 0x21 and its label, followed by blocks of data prefixed by their length, and an empty block.
+    while bytes.get(position) == Some(&0x21) {
+        let label = *bytes.get(position + 1)?;
+        position += 2;
+        let mut data = Vec::new();
+        loop {
+            let length = *bytes.get(position)? as usize;
+            data.extend_from_slice(bytes.get(position + 1..position + 1 + length)?);
+            position += 1 + length;
+            if length == 0 {
+                break;
+            }
+        }
+        if label == 0xFE {
+            return String::from_utf8(data).ok();
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::automaton::Automaton;
+    use crate::camera::Image;
+    use crate::compiler::semantic::parse;
+    use crate::display::Display;
+    use crate::display::recorder::Recorder;
+    use crate::manifest::Manifest;
+
+    #[test]
+    fn manifest_is_read_from_files_checkpoints_and_recordings() {
+        let manifest = Manifest::new(&["resources/virus.txt"], u64::MAX, vec!["--max-iterations".to_string(), "10".to_string()]).unwrap();
+        assert_eq!(Manifest::parse(&manifest.to_toml()).unwrap(), manifest);
+        assert_eq!(manifest.replay_arguments(), vec!["resources/virus.txt", "--seed", "18446744073709551615", "--max-iterations", "10"]);
+
+        let directory = std::env::temp_dir();
+        let file_name = directory.join("mutations_manifest.toml");
+        manifest.save(file_name.to_str().unwrap()).unwrap();
+        assert_eq!(Manifest::load(file_name.to_str().unwrap()).unwrap(), manifest);
+        fs::remove_file(&file_name).unwrap();
+
+        let file_name = directory.join("mutations_manifest_checkpoint.bin");
+        let mut checkpoint = Automaton::with_seed(parse("resources/virus.txt").unwrap(), 7).checkpoint(None);
+        checkpoint.save(file_name.to_str().unwrap()).unwrap();
+        assert!(Manifest::load(file_name.to_str().unwrap()).is_err());
+        checkpoint.manifest = Some(manifest.to_toml());
+        checkpoint.save(file_name.to_str().unwrap()).unwrap();
+        assert_eq!(Manifest::load(file_name.to_str().unwrap()).unwrap(), manifest);
+        fs::remove_file(&file_name).unwrap();
+
+        for extension in &["gif", "apng"] {
+            let file_name = directory.join(format!("mutations_manifest.{}", extension));
+            let mut recorder = Recorder::new(file_name.to_str().unwrap(), 1, 100);
+            recorder.set_manifest(Some(manifest.to_toml()));
+            recorder.render(&Image { grid: vec![vec![(0, 0, 0)], vec![(255, 255, 255)]] }, None);
+            recorder.save().unwrap();
+            assert_eq!(Manifest::load(file_name.to_str().unwrap()).unwrap(), manifest);
+            fs::remove_file(&file_name).unwrap();
+        }
+    }
+
+    #[test]
+    fn differences_tell_the_changed_rules() {
+        let manifest = Manifest::new(&["resources/virus.txt"], 7, Vec::new()).unwrap();
+        assert!(manifest.differences(&Manifest::new(&["resources/virus.txt"], 8, Vec::new()).unwrap()).is_empty());
+        let other = Manifest::new(&["resources/game_of_life.txt"], 7, Vec::new()).unwrap();
+        assert_eq!(manifest.differences(&other), vec![format!("The hash of the rules is {}, but {} is expected.", other.rules_hash, manifest.rules_hash)]);
+        let merged = Manifest::new(&["resources/virus.txt", "resources/game_of_life.txt"], 7, Vec::new()).unwrap();
+        assert_ne!(merged.rules_hash, manifest.rules_hash);
+        let mut other_version = manifest.clone();
+        other_version.version = "0.0.1".to_string();
+        assert_eq!(manifest.differences(&other_version).len(), 1);
+    }
+}
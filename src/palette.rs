@@ -0,0 +1,107 @@
+//! This module provides the import and export of the colors of the states, in the GIMP palette format (.gpl),
+//! so that color schemes can be shared between rule files. A color is given to the state that has the same name.
+
+use std::fs;
+use std::io;
+use crate::compiler::semantic::Rules;
+
+/// The colors of the states, by state name.
+pub type Palette = Vec<(String, (u8, u8, u8))>;
+
+/// Returns the colors of the states declared by the user.
+pub fn palette_of(rules: &Rules) -> Palette {
+    rules.user_states().iter()
+        .map(|state| (state.name.clone(), state.color))
+        .collect()
+}
+
+/// Writes the palette in the GIMP format : a header, then a line per color with its components and its name.
+pub fn to_gpl(palette: &[(String, (u8, u8, u8))], name: &str) -> String {
+    let mut text = format!("GIMP Palette\nName: {}\n#\n", name);
+    for (state, (red, green, blue)) in palette {
+        text.push_str(&format!("{:3} {:3} {:3}\t{}\n", red, green, blue, state));
+    }
+    text
+}
+
+/// Reads a palette in the GIMP format. The header lines and the comments are ignored.
+pub fn from_gpl(text: &str) -> Result<Palette, String> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, "GIMP Palette")) => {},
+        _ => return Err(String::from("Expected \"GIMP Palette\" on the first line."))
+    }
+    let mut palette = Vec::new();
+    for (index, line) in lines {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("Name:") || line.starts_with("Columns:") {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let mut component = || words.next().and_then(|word| word.parse::<u8>().ok());
+        match (component(), component(), component(), words.next()) {
+            (Some(red), Some(green), Some(blue), Some(name)) => palette.push((name.to_string(), (red, green, blue))),
+            _ => return Err(format!("Expected three integers between 0 and 255 and a state name, found \"{}\" - line {}.", line, index + 1))
+        }
+    }
+    Ok(palette)
+}
+
+pub fn save_palette(rules: &Rules, file_name: &str) -> io::Result<()> {
+    fs::write(file_name, to_gpl(&palette_of(rules), file_name))
+}
+
+pub fn load_palette(file_name: &str) -> Result<Palette, String> {
+    match fs::read_to_string(file_name) {
+        Ok(text) => from_gpl(&text),
+        Err(error) => Err(format!("Cannot read the palette file {}. Cause : {:?}", file_name, error))
+    }
+}
+
+/// Overrides the colors of the states with the palette, and returns a warning for each color whose state is not defined.
+/// The implicit states of a state take its color.
+pub fn apply_palette(rules: &mut Rules, palette: &[(String, (u8, u8, u8))]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (name, color) in palette {
+        let mut found = false;
+        for state in rules.states.iter_mut().filter(|state| &state.name == name) {
+            state.color = *color;
+            found = true;
+        }
+        if !found {
+            warnings.push(format!("The palette gives a color to the state \"{}\", but it's not defined.", name));
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::semantic::parse;
+    use crate::palette::{palette_of, to_gpl, from_gpl, apply_palette};
+
+    static GAME_OF_LIFE_FILE: &str = "resources/game_of_life.txt";
+
+    #[test]
+    fn palette_round_trip() {
+        let rules = parse(GAME_OF_LIFE_FILE).unwrap();
+        let palette = palette_of(&rules);
+        assert_eq!(from_gpl(&to_gpl(&palette, "life")).unwrap(), palette);
+    }
+
+    #[test]
+    fn apply_palette_overrides_colors() {
+        let mut rules = parse(GAME_OF_LIFE_FILE).unwrap();
+        let palette = from_gpl("GIMP Palette\nName: test\n# comment\n255 0 0\talive\n0 0 255 zombie\n").unwrap();
+        let warnings = apply_palette(&mut rules, &palette);
+        assert_eq!(rules.states[1].color, (255, 0, 0));
+        assert_eq!(warnings, vec!["The palette gives a color to the state \"zombie\", but it's not defined."]);
+    }
+
+    #[test]
+    fn from_gpl_errors() {
+        assert_eq!(from_gpl("255 0 0 alive").unwrap_err(), "Expected \"GIMP Palette\" on the first line.");
+        assert_eq!(from_gpl("GIMP Palette\n256 0 0 alive").unwrap_err(),
+                   "Expected three integers between 0 and 255 and a state name, found \"256 0 0 alive\" - line 2.");
+    }
+}
@@ -0,0 +1,111 @@
+//! This module places patterns of cells on the world, like a glider on the game of life. The placement is shared by all
+//! the ways to stamp a pattern : a pattern can be turned and mirrored, and it wraps around the borders of the world,
+//! which is a tore, so that a pattern stamped near a border continues on the other side.
+
+use crate::automaton::Automaton;
+
+/// The cells of a pattern, in a box of the given size. The cells of the box that are not in the pattern are left as they are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    size: (usize, usize),
+    cells: Vec<((usize, usize), usize)> // The position of each cell in the box, and its state
+}
+
+/// How a pattern is turned before it is stamped : mirrored from left to right first, then turned clockwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    pub quarter_turns: usize,
+    pub mirrored: bool
+}
+
+impl Pattern {
+    pub fn new(size: (usize, usize), cells: Vec<((usize, usize), usize)>) -> Result<Pattern, String> {
+        match cells.iter().find(|((x, y), _)| *x >= size.0 || *y >= size.1) {
+            Some(((x, y), _)) => Err(format!("The cell ({}, {}) is outside of the pattern of {} x {} cells.", x, y, size.0, size.1)),
+            None => Ok(Pattern { size, cells })
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    pub fn cells(&self) -> &[((usize, usize), usize)] {
+        &self.cells
+    }
+
+    /// Returns the pattern mirrored and turned. A quarter turn swaps the width and the height of the pattern.
+    pub fn oriented(&self, orientation: Orientation) -> Pattern {
+        let mut size = self.size;
+        let mut cells = self.cells.clone();
+        if orientation.mirrored {
+            cells.iter_mut().for_each(|((x, _), _)| *x = size.0 - 1 - *x);
+        }
+        for _ in 0..orientation.quarter_turns % 4 {
+            cells.iter_mut().for_each(|((x, y), _)| (*x, *y) = (size.1 - 1 - *y, *x));
+            size = (size.1, size.0);
+        }
+        Pattern { size, cells }
+    }
+}
+
+/// Stamps the oriented pattern on a layer of the world, with its upper-left corner at the position, which can be outside
+/// of the world. The pattern wraps around the borders, and must fit in the world so that its cells don't overlap.
+pub fn stamp(automaton: &mut Automaton, pattern: &Pattern, (x, y): (isize, isize), layer: usize, orientation: Orientation) -> Result<(), String> {
+    let pattern = pattern.oriented(orientation);
+    let (width, height) = automaton.get_rules().world_size;
+    if pattern.size.0 > width || pattern.size.1 > height {
+        return Err(format!("The pattern of {} x {} cells doesn't fit in the world of {} x {} cells.", pattern.size.0, pattern.size.1, width, height));
+    }
+    if layer >= automaton.get_rules().depth {
+        return Err(format!("The layer {} is outside of the world.", layer));
+    }
+    for ((cell_x, cell_y), state) in pattern.cells {
+        let world_x = (x + cell_x as isize).rem_euclid(width as isize) as usize;
+        let world_y = (y + cell_y as isize).rem_euclid(height as isize) as usize;
+        automaton.set_cell(world_x, layer * height + world_y, state)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+    use crate::pattern::{stamp, Orientation, Pattern};
+
+    // The glider of the game of life, heading down and to the right.
+    fn glider() -> Pattern {
+        Pattern::new((3, 3), vec![((1, 0), 1), ((2, 1), 1), ((0, 2), 1), ((1, 2), 1), ((2, 2), 1)]).unwrap()
+    }
+
+    #[test]
+    fn oriented_turns_and_mirrors_the_pattern() {
+        let pattern = Pattern::new((3, 2), vec![((0, 0), 1), ((2, 1), 2)]).unwrap();
+        let turned = pattern.oriented(Orientation { quarter_turns: 1, mirrored: false });
+        assert_eq!(turned, Pattern::new((2, 3), vec![((1, 0), 1), ((0, 2), 2)]).unwrap());
+        let mirrored = pattern.oriented(Orientation { quarter_turns: 2, mirrored: true });
+        assert_eq!(mirrored, Pattern::new((3, 2), vec![((0, 1), 1), ((2, 0), 2)]).unwrap());
+        assert_eq!(pattern.oriented(Orientation { quarter_turns: 4, mirrored: false }), pattern);
+        assert!(Pattern::new((2, 2), vec![((2, 0), 1)]).is_err());
+    }
+
+    #[test]
+    fn stamp_wraps_around_the_borders() {
+        let mut automaton = Automaton::with_seed(parse("resources/deterministic_game_of_life.txt").unwrap(), 0);
+        let (width, height) = automaton.get_rules().world_size;
+        for ((x, y), _) in automaton.cells().collect::<Vec<_>>() {
+            automaton.set_cell(x, y, 0).unwrap();
+        }
+        // The glider turned to head up and to the left, across the corners of the world.
+        stamp(&mut automaton, &glider(), (width as isize - 2, -1), 0, Orientation { quarter_turns: 2, mirrored: false }).unwrap();
+        let mut alive: Vec<(usize, usize)> = automaton.cells().filter(|(_, state)| *state == 1).map(|(position, _)| position).collect();
+        alive.sort();
+        let mut expected = vec![(width - 2, 0), (width - 1, 1), (0, height - 1), (width - 2, height - 1), (width - 1, height - 1)];
+        expected.sort();
+        assert_eq!(alive, expected);
+
+        let too_large = Pattern::new((width + 1, 1), Vec::new()).unwrap();
+        assert!(stamp(&mut automaton, &too_large, (0, 0), 0, Orientation::default()).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+//! This module writes the population of each state every N iterations in a CSV file, as a time series for the studies of
+//! population dynamics. Each line is an iteration, the name of a state and its number of cells :
+//!
+//! ```text
+//! iteration,state_name,count
+//! 0,dead,7960
+//! 0,alive,2040
+//! 10,dead,8771
+//! 10,alive,1229
+//! ```
+//!
+//! The lines are written as the run goes, so that the series can be followed while it runs. The implicit states of the
+//! delays are counted with their parent state.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use crate::automaton::Automaton;
+
+/// Appends the populations of an automaton to a CSV file, at the iterations that are multiples of the interval.
+pub struct PopulationLog {
+    writer: BufWriter<File>,
+    file_name: String,
+    interval: usize
+}
+
+impl PopulationLog {
+    /// Creates the CSV file with its header, and the populations of the current iteration.
+    pub fn create(file_name: &str, interval: usize, automaton: &Automaton) -> Result<PopulationLog, String> {
+        let file = File::create(file_name).map_err(|error| format!("Cannot create the population file {}. Cause : {:?}", file_name, error))?;
+        let mut log = PopulationLog { writer: BufWriter::new(file), file_name: file_name.to_string(), interval: interval.max(1) };
+        log.write("iteration,state_name,count\n")?;
+        log.write_populations(automaton)?;
+        Ok(log)
+    }
+
+    /// Appends the populations of the current iteration, if it is a multiple of the interval.
+    pub fn record(&mut self, automaton: &Automaton) -> Result<(), String> {
+        if automaton.get_iteration().is_multiple_of(self.interval) {
+            self.write_populations(automaton)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered lines to the file.
+    pub fn finish(mut self) -> Result<(), String> {
+        let file_name = &self.file_name;
+        self.writer.flush().map_err(|error| format!("Cannot write the population file {}. Cause : {:?}", file_name, error))
+    }
+
+    fn write_populations(&mut self, automaton: &Automaton) -> Result<(), String> {
+        let iteration = automaton.get_iteration();
+        let lines: String = automaton.get_rules().user_states().iter()
+            .zip(automaton.state_counts())
+            .map(|(state, count)| format!("{},{},{}\n", iteration, state.name, count))
+            .collect();
+        self.write(&lines)?;
+        // The lines of an iteration are flushed together, so that a reader never sees a partial iteration.
+        let file_name = &self.file_name;
+        self.writer.flush().map_err(|error| format!("Cannot write the population file {}. Cause : {:?}", file_name, error))
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), String> {
+        let file_name = &self.file_name;
+        self.writer.write_all(text.as_bytes()).map_err(|error| format!("Cannot write the population file {}. Cause : {:?}", file_name, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+    use crate::population_log::PopulationLog;
+
+    #[test]
+    fn population_log_writes_the_series() {
+        let file_name = std::env::temp_dir().join("mutations_populations.csv");
+        let file_name = file_name.to_str().unwrap();
+        let mut automaton = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 3);
+        let mut expected = vec!["iteration,state_name,count".to_string()];
+        let mut log = PopulationLog::create(file_name, 2, &automaton).unwrap();
+        for _ in 0..5 {
+            if automaton.get_iteration().is_multiple_of(2) {
+                let counts = automaton.state_counts();
+                expected.push(format!("{},dead,{}", automaton.get_iteration(), counts[0]));
+                expected.push(format!("{},alive,{}", automaton.get_iteration(), counts[1]));
+            }
+            automaton.tick();
+            log.record(&automaton).unwrap();
+        }
+        log.finish().unwrap();
+
+        let text = fs::read_to_string(file_name).unwrap();
+        assert_eq!(text.lines().collect::<Vec<_>>(), expected);
+        assert_eq!(text.lines().last().unwrap().split(',').next(), Some("4"));
+        fs::remove_file(file_name).unwrap();
+    }
+}
@@ -0,0 +1,26 @@
+//! This module re-exports what a program embedding the automatons usually needs, so that `use mutations::prelude::*;` is enough.
+//!
+//! The compiler returns its errors as a list (`Vec<CompileError>`), each displayed as its message and with the `Span` of the rules
+//! it refers to when it is known, and the other fallible functions a single message (`String`).
+//! The options are plain structures with a default value, like `CompilerOptions { deny_warnings: true, ..CompilerOptions::default() }`.
+
+pub use crate::automaton::Automaton;
+pub use crate::camera::Image;
+pub use crate::checkpoint::Checkpoint;
+pub use crate::event_log::{CellEvent, EventLog, EventLogWriter};
+pub use crate::compiler::builder::{Condition, RulesBuilder};
+pub use crate::compiler::error::{CompileError, Span};
+pub use crate::compiler::messages::Catalog;
+pub use crate::compiler::semantic::{parse, parse_with_options, parse_str, parse_str_with_options, parse_reader, Rules, State, CompilerOptions, Lint, LintLevel};
+pub use crate::display::Display;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::display::TerminalDisplay;
+pub use crate::display::recorder::Recorder;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::executor::{execute, run_file, Conf, MaxIterationCount, PopulationThreshold, RunObserver, RunOptions, RunOutcome, SearchConf, StopCondition, StopReason};
+pub use crate::generator::{generate_rules, GeneratorConf};
+pub use crate::image_display::PngDisplay;
+pub use crate::simulation::{Simulation, Observer, Snapshot, SharedGrid, Stats};
+pub use crate::simulation_set::SimulationSet;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::terminal::{Charset, ColorDepth, TerminalCapabilities};
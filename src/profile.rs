@@ -0,0 +1,104 @@
+//! This module reads the named profiles of a configuration file, so that a run can be reproduced without a long command line.
+//!
+//! A profile is a table of the `profiles` table. Its `file` key is the automaton file, and its other keys are the options
+//! of a run without their leading dashes :
+//!
+//! ```toml
+//! [profiles.demo]
+//! file = "resources/virus.txt"
+//! seed = 42
+//! size = [100, 40]
+//! fps = 20
+//! max-iterations = 500
+//! ```
+
+use std::fs;
+use toml::Value;
+
+/// The configuration file read by the `--profile` option, in the current directory.
+pub const CONFIG_FILE: &str = "automaton.toml";
+
+/// The automaton file and the command line options of a profile.
+#[derive(Debug, PartialEq)]
+pub struct Profile {
+    pub file: Option<String>,
+    pub options: Vec<String>
+}
+
+pub fn load_profile(file_name: &str, name: &str) -> Result<Profile, String> {
+    match fs::read_to_string(file_name) {
+        Ok(text) => parse_profile(&text, name).map_err(|error| format!("In {} : {}", file_name, error)),
+        Err(error) => Err(format!("Cannot read the configuration file {}. Cause : {:?}", file_name, error))
+    }
+}
+
+/// Reads the profile of the configuration. A `true` value gives an option without value, and a `false` value no option.
+/// An array of numbers is written with commas, like `10,20`, and an array of strings repeats the option.
+pub fn parse_profile(text: &str, name: &str) -> Result<Profile, String> {
+    let config = text.parse::<Value>().map_err(|error| format!("Invalid configuration : {}", error))?;
+    let profiles = config.get("profiles").and_then(Value::as_table).ok_or("The configuration has no \"profiles\" table.")?;
+    let profile = match profiles.get(name) {
+        Some(Value::Table(profile)) => profile,
+        Some(_) => return Err(format!("The profile \"{}\" is not a table.", name)),
+        None => return Err(format!("The profile \"{}\" is not defined. The profiles are : {}.",
+                                   name, profiles.keys().cloned().collect::<Vec<_>>().join(", ")))
+    };
+    let mut file = None;
+    let mut options = Vec::new();
+    for (key, value) in profile {
+        let option = format!("--{}", key);
+        match (key.as_str(), value) {
+            ("file", Value::String(file_name)) => file = Some(file_name.clone()),
+            (_, Value::Boolean(true)) => options.push(option),
+            (_, Value::Boolean(false)) => {},
+            (_, Value::Array(values)) if values.iter().all(Value::is_str) => {
+                for value in values.iter().filter_map(Value::as_str) {
+                    options.push(option.clone());
+                    options.push(value.to_string());
+                }
+            },
+            (_, Value::Array(values)) if values.iter().all(Value::is_integer) => {
+                options.push(option);
+                options.push(values.iter().map(Value::to_string).collect::<Vec<_>>().join(","));
+            },
+            (_, Value::String(value)) => {
+                options.push(option);
+                options.push(value.clone());
+            },
+            (_, Value::Integer(_)) | (_, Value::Float(_)) => {
+                options.push(option);
+                options.push(value.to_string());
+            },
+            _ => return Err(format!("The key \"{}\" of the profile \"{}\" has an unexpected value {}.", key, name, value))
+        }
+    }
+    Ok(Profile { file, options })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profile::{load_profile, parse_profile, Profile};
+
+    static PROFILES_FILE: &str = "resources/tests/profiles.toml";
+
+    #[test]
+    fn load_profile_options() {
+        let profile = load_profile(PROFILES_FILE, "demo").unwrap();
+        assert_eq!(profile, Profile {
+            file: Some("resources/virus.txt".to_string()),
+            options: ["--allow", "shadowed-transition", "--allow", "impossible-condition", "--fps", "20",
+                      "--headless", "--max-iterations", "500", "--seed", "42", "--size", "100,40"]
+                .iter().map(|option| option.to_string()).collect()
+        });
+    }
+
+    #[test]
+    fn load_profile_errors_fails() {
+        assert_eq!(load_profile(PROFILES_FILE, "unknown"),
+                   Err(format!("In {} : The profile \"unknown\" is not defined. The profiles are : demo, invalid.", PROFILES_FILE)));
+        assert_eq!(load_profile(PROFILES_FILE, "invalid"),
+                   Err(format!("In {} : The key \"tag\" of the profile \"invalid\" has an unexpected value [1, \"a\"].", PROFILES_FILE)));
+        assert!(parse_profile("seed = ", "demo").is_err());
+        assert_eq!(parse_profile("seed = 1", "demo"), Err("The configuration has no \"profiles\" table.".to_string()));
+    }
+}
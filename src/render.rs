@@ -0,0 +1,76 @@
+//! This module renders a run offline : the automaton is ticked on the calling thread while the frames are encoded on another one.
+
+use std::sync::mpsc;
+use std::thread;
+use crate::automaton::Automaton;
+use crate::camera::Camera;
+use crate::display::Display;
+
+/// The number of frames that can wait for the encoder. When the queue is full, the simulation waits, so the memory stays bounded.
+const QUEUE_SIZE: usize = 16;
+
+/// Renders the whole grid at the current iteration and after each tick. Returns the number of frames rendered.
+pub fn render(automaton: &mut Automaton, ticks: usize, mut display: Box<dyn Display + Send>) -> Result<usize, String> {
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+    let encoder = thread::spawn(move || {
+        display.init();
+        let mut frames = 0;
+        for image in receiver {
+            display.render(&image, None);
+            frames += 1;
+        }
+        display.clean();
+        frames
+    });
+
+    let mut camera = Camera::with_size(0, 0, automaton.get_rules().world_size);
+    for tick in 0..=ticks {
+        if tick > 0 {
+            automaton.tick();
+        }
+        // The encoder only stops receiving if it panicked, which is reported when it's joined.
+        if sender.send(camera.capture(automaton).clone()).is_err() {
+            break;
+        }
+    }
+    drop(sender);
+    encoder.join().map_err(|_| "The encoder thread stopped unexpectedly.".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use crate::automaton::Automaton;
+    use crate::camera::Image;
+    use crate::compiler::semantic::parse;
+    use crate::display::{Display, Status};
+    use crate::render::render;
+
+    /// Records the population of the alive state, drawn in white, in each frame.
+    struct PopulationDisplay(Arc<Mutex<Vec<usize>>>);
+
+    impl Display for PopulationDisplay {
+        fn init(&mut self) {}
+
+        fn render(&mut self, image: &Image, _status: Option<&Status>) {
+            self.0.lock().unwrap().push(image.grid.iter().flatten().filter(|color| **color == (255, 255, 255)).count());
+        }
+
+        fn clean(&mut self) {}
+    }
+
+    #[test]
+    fn render_frames_in_order() {
+        let populations = Arc::new(Mutex::new(Vec::new()));
+        let mut automaton = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 5);
+        let frames = render(&mut automaton, 40, Box::new(PopulationDisplay(populations.clone()))).unwrap();
+        assert_eq!(frames, 41);
+        assert_eq!(automaton.get_iteration(), 40);
+
+        let mut expected = Automaton::with_seed(parse("resources/game_of_life.txt").unwrap(), 5);
+        for population in populations.lock().unwrap().iter() {
+            assert_eq!(*population, expected.state_counts()[1]);
+            expected.tick();
+        }
+    }
+}
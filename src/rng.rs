@@ -0,0 +1,118 @@
+//! This module provides a counter-based random number generator.
+//!
+//! The random numbers of a cell are derived from a hash of (seed, iteration, cell index), so the
+//! simulation is reproducible whatever the number of threads and the order in which cells are evaluated.
+//! The numbers shared by several cells, like the draw of an iteration, are derived from a key instead of the cell index.
+
+use rand::{RngCore, Error};
+
+/// Salt of the seed from which the shared draws are derived, so they are independent of the draws of the cells.
+const SHARED_SEED_SALT: u64 = 0x7368_6172_6564_0000;
+
+/// The state is derived from the counter at the first draw only : a generator is built for each cell at each tick, and
+/// the cells of deterministic rules never draw, so they do not pay for the hash.
+pub struct CounterRng {
+    state: Option<u64>,
+    seed: u64,
+    iteration: u64,
+    index: u64
+}
+
+impl CounterRng {
+    pub fn new(seed: u64, iteration: u64, index: u64) -> CounterRng {
+        CounterRng { state: None, seed, iteration, index }
+    }
+
+    /// Returns the generator of the numbers shared by the cells with the same key at this iteration, for the draw : the
+    /// numbers of different draws are independent, even with the same key.
+    pub fn shared(&self, draw: u64, key: u64) -> CounterRng {
+        CounterRng::new(mix(self.seed ^ SHARED_SEED_SALT ^ mix(draw)), self.iteration, key)
+    }
+}
+
+impl RngCore for CounterRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    // SplitMix64 step
+    fn next_u64(&mut self) -> u64 {
+        let state = match self.state {
+            Some(state) => state,
+            None => mix(self.seed ^ mix(self.iteration ^ mix(self.index)))
+        }.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state = Some(state);
+        mix(state)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// SplitMix64 finalizer, a bijective function that scatters the bits of its input.
+fn mix(value: u64) -> u64 {
+    let mut z = value;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rng::CounterRng;
+    use rand::Rng;
+
+    #[test]
+    fn same_counter_gives_same_numbers() {
+        let mut rng1 = CounterRng::new(42, 7, 1234);
+        let mut rng2 = CounterRng::new(42, 7, 1234);
+        for _ in 0..10 {
+            assert_eq!(rng1.gen::<u64>(), rng2.gen::<u64>());
+        }
+    }
+
+    #[test]
+    fn different_counters_give_different_numbers() {
+        let first = CounterRng::new(42, 7, 1234).gen::<u64>();
+        assert_ne!(first, CounterRng::new(43, 7, 1234).gen::<u64>());
+        assert_ne!(first, CounterRng::new(42, 8, 1234).gen::<u64>());
+        assert_ne!(first, CounterRng::new(42, 7, 1235).gen::<u64>());
+    }
+
+    #[test]
+    fn shared_numbers_depend_on_the_draw_and_the_key_but_not_on_the_cell() {
+        let shared = CounterRng::new(42, 7, 1234).shared(3, 5).gen::<u64>();
+        assert_eq!(shared, CounterRng::new(42, 7, 99).shared(3, 5).gen::<u64>());
+        assert_ne!(shared, CounterRng::new(42, 7, 1234).shared(3, 6).gen::<u64>());
+        assert_ne!(shared, CounterRng::new(42, 7, 1234).shared(4, 5).gen::<u64>());
+        assert_ne!(shared, CounterRng::new(42, 8, 1234).shared(3, 5).gen::<u64>());
+        assert_ne!(shared, CounterRng::new(42, 7, 5).gen::<u64>());
+    }
+
+    #[test]
+    fn numbers_do_not_change_with_the_lazy_state() {
+        // The first numbers of the generator when its state was derived at its creation.
+        let mut rng = CounterRng::new(42, 7, 1234);
+        for expected in &[0x50cc_e413_bb58_dd79_u64, 0x6b9f_c242_c8aa_0914, 0x93b9_aa9c_85f4_cbc2] {
+            assert_eq!(rng.gen::<u64>(), *expected);
+        }
+    }
+
+    #[test]
+    fn floats_are_in_unit_interval() {
+        let mut rng = CounterRng::new(0, 0, 0);
+        for _ in 0..1000 {
+            let r: f64 = rng.gen();
+            assert!((0.0..1.0).contains(&r));
+        }
+    }
+}
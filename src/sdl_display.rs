@@ -0,0 +1,138 @@
+//! This module shows the images captured by the camera in a window with SDL2, for the machines without a terminal.
+//! It needs the `sdl2_rendering` feature and the SDL2 library.
+
+use sdl2::EventPump;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::WindowCanvas;
+use crate::camera::Image;
+use crate::display::{Display, Status};
+use crate::image_display::to_pixels;
+use crate::inputs::{Direction, UserAction, Zoom};
+
+const WINDOW_TITLE: &str = "Mutations";
+
+/// Shows each image in a resizable window, scaled to fill it with square cells. The window is presented at the refresh rate
+/// of the screen, and the status is written in its title. The keys are those of the terminal display, and a click or a drag
+/// with the left button of the mouse paints the cells.
+pub struct SdlDisplay {
+    canvas: WindowCanvas,
+    events: EventPump,
+    image_size: (usize, usize), // The number of columns and rows of the last image
+    target: Rect, // The area of the window the last image was drawn in
+    last_status: Option<String>
+}
+
+impl SdlDisplay {
+    /// Opens a window showing each pixel of the images of the given size as a square of `scale` x `scale` pixels.
+    pub fn new(size: (usize, usize), scale: usize) -> Result<SdlDisplay, String> {
+        let sdl = sdl2::init()?;
+        let video = sdl.video()?;
+        let (width, height) = ((size.0 * scale.max(1)) as u32, (size.1 * scale.max(1)) as u32);
+        let window = video.window(WINDOW_TITLE, width, height)
+            .position_centered()
+            .resizable()
+            .build()
+            .map_err(|error| error.to_string())?;
+        // The presentation waits for the vertical sync, which caps the rendering at the refresh rate of the screen.
+        let canvas = window.into_canvas().present_vsync().build().map_err(|error| error.to_string())?;
+        let events = sdl.event_pump()?;
+        Ok(SdlDisplay { canvas, events, image_size: size, target: Rect::new(0, 0, width, height), last_status: None })
+    }
+
+    /// Returns the largest area of the window that shows the image with square pixels, in its center.
+    fn fit_target(&self) -> Result<Rect, String> {
+        let (window_width, window_height) = self.canvas.output_size()?;
+        let (width, height) = (self.image_size.0.max(1) as f64, self.image_size.1.max(1) as f64);
+        let scale = (window_width as f64 / width).min(window_height as f64 / height);
+        let (target_width, target_height) = ((width * scale) as u32, (height * scale) as u32);
+        Ok(Rect::new(((window_width - target_width) / 2) as i32, ((window_height - target_height) / 2) as i32, target_width.max(1), target_height.max(1)))
+    }
+
+    fn draw(&mut self, image: &Image) -> Result<(), String> {
+        let (width, height, pixels) = to_pixels(image, 1);
+        self.image_size = (width, height);
+        // The window may have been resized since the last image.
+        self.target = self.fit_target()?;
+        let texture_creator = self.canvas.texture_creator();
+        let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+            .map_err(|error| error.to_string())?;
+        texture.update(None, &pixels, width * 3).map_err(|error| error.to_string())?;
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.canvas.copy(&texture, None, self.target)?;
+        self.canvas.present();
+        Ok(())
+    }
+
+    /// Returns the action painting the pixel of the image under the mouse, if the mouse is on the image.
+    fn paint(&self, x: i32, y: i32) -> UserAction {
+        if !self.target.contains_point((x, y)) {
+            return UserAction::Nop;
+        }
+        let column = (x - self.target.x()) as usize * self.image_size.0 / self.target.width() as usize;
+        let row = (y - self.target.y()) as usize * self.image_size.1 / self.target.height() as usize;
+        UserAction::PaintCell { x: column as u16, y: row as u16 }
+    }
+}
+
+impl Display for SdlDisplay {
+    fn init(&mut self) {}
+
+    fn render(&mut self, image: &Image, status: Option<&Status>) {
+        if let Err(error) = self.draw(image) {
+            error!("Cannot draw the image in the window. Cause : {}", error);
+        }
+        let status = status.map(Status::line);
+        if status != self.last_status {
+            let title = status.as_ref().map_or(WINDOW_TITLE.to_string(), |status| format!("{} | {}", WINDOW_TITLE, status));
+            if let Err(error) = self.canvas.window_mut().set_title(&title) {
+                error!("Cannot write the status in the title of the window. Cause : {}", error);
+            }
+            self.last_status = status;
+        }
+    }
+
+    /// Reads the events of the window until one of them is an action : a key, a click or a drag, or the closing of the window.
+    fn read_inputs(&mut self) -> UserAction {
+        while let Some(event) = self.events.poll_event() {
+            let action = match event {
+                Event::Quit { .. } => UserAction::Quit,
+                Event::KeyDown { keycode: Some(keycode), .. } => read_key(keycode),
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => self.paint(x, y),
+                Event::MouseMotion { mousestate, x, y, .. } if mousestate.left() => self.paint(x, y),
+                _ => UserAction::Nop
+            };
+            if !matches!(action, UserAction::Nop) {
+                return action;
+            }
+        }
+        UserAction::Nop
+    }
+
+    fn clean(&mut self) {}
+}
+
+/// The keys of the terminal display.
+fn read_key(keycode: Keycode) -> UserAction {
+    match keycode {
+        Keycode::ESCAPE => UserAction::Quit,
+        Keycode::LEFT => UserAction::TranslateCamera(Direction::Left),
+        Keycode::RIGHT => UserAction::TranslateCamera(Direction::Right),
+        Keycode::UP => UserAction::TranslateCamera(Direction::Up),
+        Keycode::DOWN => UserAction::TranslateCamera(Direction::Down),
+        Keycode::Z => UserAction::ZoomCamera(Zoom::In),
+        Keycode::S => UserAction::ZoomCamera(Zoom::Out),
+        Keycode::PAGEUP => UserAction::ChangeLayer(-1),
+        Keycode::PAGEDOWN => UserAction::ChangeLayer(1),
+        Keycode::P => UserAction::TogglePause,
+        Keycode::N => UserAction::Step,
+        Keycode::R => UserAction::ReloadRules,
+        Keycode::C => UserAction::CycleBrush,
+        Keycode::E => UserAction::ExportWorld,
+        _ => UserAction::Nop
+    }
+}
@@ -0,0 +1,76 @@
+//! This module provides a search of interesting initial conditions : the automaton is reinitialized with random seeds and run,
+//! and the runs for which a predicate holds are kept.
+
+use std::fs;
+use std::io;
+use crate::automaton::Automaton;
+
+/// A run for which the predicate held.
+pub struct SearchHit {
+    pub seed: u64,
+    /// The states of the cells, row after row, at the end of the run.
+    pub snapshot: Vec<usize>
+}
+
+/// Runs the automaton for the given number of ticks once per seed, and returns the runs for which the predicate holds at the end.
+/// The same seed always gives the same run, so a hit can be replayed with `Automaton::with_seed`.
+pub fn search<I, P>(automaton: &mut Automaton, seeds: I, ticks: usize, predicate: P) -> Vec<SearchHit>
+    where I: IntoIterator<Item = u64>,
+          P: Fn(&Automaton) -> bool {
+    let mut hits = Vec::new();
+    for seed in seeds {
+        automaton.reset(seed);
+        for _ in 0..ticks {
+            automaton.tick();
+        }
+        if predicate(automaton) {
+            hits.push(SearchHit { seed, snapshot: automaton.get_grid().to_vec() });
+        }
+    }
+    hits
+}
+
+/// A predicate that holds if the population of the state (implicit states included) is within the range, bounds included.
+pub fn population_in_range(state: usize, min: usize, max: usize) -> impl Fn(&Automaton) -> bool {
+    move |automaton| {
+        let population = automaton.state_counts()[state];
+        population >= min && population <= max
+    }
+}
+
+/// Writes the snapshot in a text file, with one line per row and the name of the state of each cell.
+pub fn save_snapshot(automaton: &Automaton, hit: &SearchHit, file_name: &str) -> io::Result<()> {
+    let rules = automaton.get_rules();
+    let lines: Vec<String> = hit.snapshot.chunks(rules.world_size.0)
+        .map(|row| row.iter()
+            .map(|state| rules.states[rules.parent_state(*state)].name.as_str())
+            .collect::<Vec<_>>()
+            .join(" "))
+        .collect();
+    fs::write(file_name, format!("seed {}\n{}\n", hit.seed, lines.join("\n")))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+    use crate::search::{search, population_in_range};
+
+    static GAME_OF_LIFE_FILE: &str = "resources/game_of_life.txt";
+
+    #[test]
+    fn search_finds_reproducible_hits() {
+        let mut automaton = Automaton::new(parse(GAME_OF_LIFE_FILE).unwrap());
+        let hits = search(&mut automaton, 0..5, 10, population_in_range(1, 0, 10000));
+        assert_eq!(hits.len(), 5);
+        let hits = search(&mut automaton, 0..5, 10, population_in_range(1, 10000, 10000));
+        assert!(hits.is_empty());
+
+        let hits = search(&mut automaton, vec![7], 10, |_| true);
+        let mut replay = Automaton::with_seed(parse(GAME_OF_LIFE_FILE).unwrap(), 7);
+        for _ in 0..10 {
+            replay.tick();
+        }
+        assert_eq!(replay.get_grid(), &hits[0].snapshot[..]);
+    }
+}
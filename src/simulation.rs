@@ -0,0 +1,191 @@
+//! This module provides a single entry point to embed the automatons in another program : a simulation owns
+//! an automaton and notifies observers after each tick.
+
+use std::sync::Arc;
+use arc_swap::ArcSwap;
+use crate::automaton::Automaton;
+use crate::compiler::error::CompileError;
+use crate::compiler::semantic::{Rules, CompilerOptions, parse_with_options};
+
+/// Notified after each tick of a simulation, with the automaton in its new state.
+pub trait Observer {
+    fn on_tick(&mut self, automaton: &Automaton);
+}
+
+impl<F: FnMut(&Automaton)> Observer for F {
+    fn on_tick(&mut self, automaton: &Automaton) {
+        self(automaton)
+    }
+}
+
+/// The grid of a simulation at some iteration. The run can be replayed from the seed.
+pub struct Snapshot {
+    pub seed: u64,
+    pub iteration: usize,
+    pub world_size: (usize, usize), // The size of the grid, whose layers are stacked in a 3D world
+    /// The states of the cells, row after row.
+    pub grid: Vec<usize>
+}
+
+/// A handle on the last completed generation of an automaton, which other threads read while the next tick computes.
+/// The automaton publishes an immutable snapshot after each tick, so the readers never wait, and keep the snapshot they loaded as long as they need.
+#[derive(Clone)]
+pub struct SharedGrid {
+    current: Arc<ArcSwap<Snapshot>>
+}
+
+impl SharedGrid {
+    pub(crate) fn new(snapshot: Snapshot) -> SharedGrid {
+        SharedGrid { current: Arc::new(ArcSwap::from_pointee(snapshot)) }
+    }
+
+    pub(crate) fn publish(&self, snapshot: Snapshot) {
+        self.current.store(Arc::new(snapshot));
+    }
+
+    /// Returns the last generation published by the automaton.
+    pub fn load(&self) -> Arc<Snapshot> {
+        self.current.load_full()
+    }
+}
+
+pub struct Stats {
+    pub iteration: usize,
+    /// The name and the population of each state declared by the user, implicit states included.
+    pub populations: Vec<(String, usize)>
+}
+
+pub struct Simulation {
+    automaton: Automaton,
+    observers: Vec<Box<dyn Observer>>
+}
+
+impl Simulation {
+    pub fn new(rules: Rules) -> Simulation {
+        Simulation::with_seed(rules, rand::random())
+    }
+
+    pub fn with_seed(rules: Rules, seed: u64) -> Simulation {
+        Simulation { automaton: Automaton::with_seed(rules, seed), observers: Vec::new() }
+    }
+
+    /// Parses the rules file, and returns the compiler errors if it's invalid.
+    pub fn from_file(file_name: &str, options: &CompilerOptions, seed: u64) -> Result<Simulation, Vec<CompileError>> {
+        parse_with_options(file_name, options).map(|rules| Simulation::with_seed(rules, seed))
+    }
+
+    /// Adds an observer, notified after each tick in the order they were added.
+    pub fn add_observer<O: Observer + 'static>(&mut self, observer: O) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn step(&mut self) {
+        self.automaton.tick();
+        for observer in self.observers.iter_mut() {
+            observer.on_tick(&self.automaton);
+        }
+    }
+
+    pub fn run(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.step();
+        }
+    }
+
+    /// Restarts the simulation from its initial grid, drawn with the given seed. The observers are kept.
+    pub fn reset(&mut self, seed: u64) {
+        self.automaton.reset(seed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.automaton.snapshot()
+    }
+
+    /// Returns a handle to read the last completed generation from other threads.
+    pub fn share_grid(&mut self) -> SharedGrid {
+        self.automaton.share_grid()
+    }
+
+    pub fn stats(&self) -> Stats {
+        let counts = self.automaton.state_counts();
+        Stats {
+            iteration: self.automaton.get_iteration(),
+            populations: self.automaton.get_rules().user_states().iter()
+                .map(|state| (state.name.clone(), counts[state.id]))
+                .collect()
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.automaton.get_seed()
+    }
+
+    pub fn iteration(&self) -> usize {
+        self.automaton.get_iteration()
+    }
+
+    pub fn automaton(&self) -> &Automaton {
+        &self.automaton
+    }
+
+    pub fn automaton_mut(&mut self) -> &mut Automaton {
+        &mut self.automaton
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use crate::compiler::semantic::CompilerOptions;
+    use crate::simulation::Simulation;
+    use std::thread;
+
+    static GAME_OF_LIFE_FILE: &str = "resources/game_of_life.txt";
+
+    #[test]
+    fn run_notifies_observers_and_replays() {
+        let mut simulation = Simulation::from_file(GAME_OF_LIFE_FILE, &CompilerOptions::default(), 42).unwrap();
+        let ticks = Rc::new(Cell::new(0));
+        let observed = ticks.clone();
+        simulation.add_observer(move |_: &_| observed.set(observed.get() + 1));
+        simulation.step();
+        simulation.run(9);
+        assert_eq!(ticks.get(), 10);
+        assert_eq!(simulation.iteration(), 10);
+
+        let snapshot = simulation.snapshot();
+        let stats = simulation.stats();
+        assert_eq!(snapshot.seed, 42);
+        assert_eq!(stats.populations.iter().map(|(_, count)| count).sum::<usize>(), snapshot.grid.len());
+        assert_eq!(stats.populations[1], (String::from("alive"), snapshot.grid.iter().filter(|state| **state == 1).count()));
+
+        simulation.reset(42);
+        simulation.run(10);
+        assert_eq!(simulation.snapshot().grid, snapshot.grid);
+        assert_eq!(ticks.get(), 20);
+    }
+
+    #[test]
+    fn shared_grid_is_read_while_ticking() {
+        let mut simulation = Simulation::from_file(GAME_OF_LIFE_FILE, &CompilerOptions::default(), 42).unwrap();
+        let shared_grid = simulation.share_grid();
+        assert_eq!(shared_grid.load().iteration, 0);
+        let reader = {
+            let shared_grid = shared_grid.clone();
+            thread::spawn(move || {
+                let mut iterations = Vec::new();
+                while iterations.last() != Some(&20) {
+                    let snapshot = shared_grid.load();
+                    assert_eq!(snapshot.grid.len(), snapshot.world_size.0 * snapshot.world_size.1);
+                    iterations.push(snapshot.iteration);
+                }
+                iterations
+            })
+        };
+        simulation.run(20);
+        let iterations = reader.join().unwrap();
+        assert!(iterations.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(shared_grid.load().grid, simulation.snapshot().grid);
+    }
+}
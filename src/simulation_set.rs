@@ -0,0 +1,91 @@
+//! This module runs several independent automatons at the same time.
+
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder, ThreadPoolBuildError};
+use crate::automaton::Automaton;
+
+/// A set of independent automatons, with the same or different rules, ticked together on a shared thread pool.
+pub struct SimulationSet {
+    automatons: Vec<Automaton>,
+    pool: ThreadPool
+}
+
+impl SimulationSet {
+    /// Creates an empty set whose thread pool has as many threads as there are CPUs.
+    pub fn new() -> Result<SimulationSet, ThreadPoolBuildError> {
+        Self::with_threads(0)
+    }
+
+    /// Creates an empty set whose thread pool has the given number of threads (0 means one per CPU).
+    pub fn with_threads(threads: usize) -> Result<SimulationSet, ThreadPoolBuildError> {
+        Ok(SimulationSet {
+            automatons: Vec::new(),
+            pool: ThreadPoolBuilder::new().num_threads(threads).build()?
+        })
+    }
+
+    /// Adds an automaton to the set, and returns its identifier in the set.
+    pub fn add(&mut self, automaton: Automaton) -> usize {
+        self.automatons.push(automaton);
+        self.automatons.len() - 1
+    }
+
+    /// Ticks every automaton of the set once.
+    pub fn tick(&mut self) {
+        let automatons = &mut self.automatons;
+        self.pool.install(|| automatons.par_iter_mut().for_each(|automaton| automaton.tick()));
+    }
+
+    /// Ticks every automaton of the set the given number of times.
+    pub fn tick_n(&mut self, count: usize) {
+        let automatons = &mut self.automatons;
+        self.pool.install(|| automatons.par_iter_mut().for_each(|automaton| {
+            for _ in 0..count {
+                automaton.tick();
+            }
+        }));
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Automaton> {
+        self.automatons.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Automaton> {
+        self.automatons.get_mut(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Automaton> {
+        self.automatons.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.automatons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.automatons.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+    use crate::simulation_set::SimulationSet;
+
+    static GAME_OF_LIFE_FILE: &str = "resources/game_of_life.txt";
+    static VIRUS_FILE: &str = "resources/virus.txt";
+
+    #[test]
+    fn tick_all_automatons() {
+        let mut set = SimulationSet::with_threads(2).unwrap();
+        let game_of_life = set.add(Automaton::new(parse(GAME_OF_LIFE_FILE).unwrap()));
+        let virus = set.add(Automaton::new(parse(VIRUS_FILE).unwrap()));
+        set.tick();
+        set.tick_n(3);
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.get(game_of_life).unwrap().get_iteration(), 4);
+        assert_eq!(set.get(virus).unwrap().get_iteration(), 4);
+        assert!(set.get(2).is_none());
+    }
+}
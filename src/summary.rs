@@ -0,0 +1,74 @@
+//! This module provides a textual summary of the automaton, as an alternative to the display of the grid
+//! that can be read by a screen reader. The summary is written in plain sentences, without symbols.
+
+use crate::automaton::Automaton;
+
+/// Summarizes the automaton, and reports the notable changes since the previous summary.
+#[derive(Default)]
+pub struct Summarizer {
+    previous_counts: Option<Vec<usize>>
+}
+
+impl Summarizer {
+    pub fn new() -> Summarizer {
+        Summarizer::default()
+    }
+
+    /// Returns the sentences of the summary : the iteration, the population of each state, and the notable changes.
+    /// A change is notable if a state appears, dies out, or if its population doubles or halves.
+    pub fn summarize(&mut self, automaton: &Automaton) -> Vec<String> {
+        let states = automaton.get_rules().user_states();
+        let counts = automaton.state_counts();
+        let total: usize = counts.iter().sum();
+        let populations: Vec<String> = states.iter()
+            .map(|state| format!("{} {}, {} percent", state.name, cells(counts[state.id]), (counts[state.id] * 100 + total / 2) / total))
+            .collect();
+        let mut sentences = vec![
+            format!("Iteration {}.", automaton.get_iteration()),
+            format!("{}.", populations.join(", "))
+        ];
+        if let Some(previous_counts) = &self.previous_counts {
+            for state in states {
+                let (previous, current) = (previous_counts[state.id], counts[state.id]);
+                if previous > 0 && current == 0 {
+                    sentences.push(format!("{} died out.", state.name));
+                } else if previous == 0 && current > 0 {
+                    sentences.push(format!("{} appeared.", state.name));
+                } else if current >= 2 * previous && previous > 0 {
+                    sentences.push(format!("{} grew from {} to {}.", state.name, cells(previous), cells(current)));
+                } else if 2 * current <= previous && current > 0 {
+                    sentences.push(format!("{} shrank from {} to {}.", state.name, cells(previous), cells(current)));
+                }
+            }
+        }
+        self.previous_counts = Some(counts);
+        sentences
+    }
+}
+
+fn cells(count: usize) -> String {
+    if count == 1 { String::from("1 cell") } else { format!("{} cells", count) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::automaton::Automaton;
+    use crate::compiler::semantic::parse;
+    use crate::summary::Summarizer;
+
+    static BLINKER_FILE: &str = "resources/tests/analysis_blinker.txt";
+
+    #[test]
+    fn summarize_populations_and_changes() {
+        let mut automaton = Automaton::new(parse(BLINKER_FILE).unwrap());
+        let mut summarizer = Summarizer::new();
+        assert_eq!(summarizer.summarize(&automaton), vec!["Iteration 0.", "dead 397 cells, 99 percent, alive 3 cells, 1 percent."]);
+
+        automaton.tick();
+        assert_eq!(summarizer.summarize(&automaton).len(), 2);
+
+        automaton.add_source_region(0, 0, 20, 20, 0).unwrap();
+        automaton.tick();
+        assert_eq!(summarizer.summarize(&automaton), vec!["Iteration 2.", "dead 400 cells, 100 percent, alive 0 cells, 0 percent.", "alive died out."]);
+    }
+}
@@ -0,0 +1,67 @@
+//! This module detects what the terminal can display, so that the display uses the best rendering it supports.
+
+use std::env;
+
+/// The number of colors the terminal can display, from the best to the worst.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorDepth {
+    TrueColor,
+    Ansi256,
+    Ansi16
+}
+
+/// The character used to draw a cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Charset {
+    Block, // A full block, which needs Unicode
+    Ascii
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TerminalCapabilities {
+    pub color_depth: ColorDepth,
+    pub charset: Charset,
+    pub size: Option<(u16, u16)> // The number of columns and rows, if it can be read
+}
+
+impl TerminalCapabilities {
+    /// Detects the capabilities from the environment variables, and the size from the terminal itself.
+    pub fn detect() -> TerminalCapabilities {
+        let var = |name| env::var(name).ok();
+        let locale = var("LC_ALL").or_else(|| var("LC_CTYPE")).or_else(|| var("LANG"));
+        let mut capabilities = TerminalCapabilities::from_env(var("COLORTERM").as_deref(), var("TERM").as_deref(), locale.as_deref());
+        capabilities.size = termion::terminal_size().ok();
+        capabilities
+    }
+
+    /// COLORTERM tells if true colors are supported, otherwise TERM tells if 256 colors are. The locale tells if Unicode is supported.
+    fn from_env(colorterm: Option<&str>, term: Option<&str>, locale: Option<&str>) -> TerminalCapabilities {
+        let color_depth = match (colorterm, term) {
+            (Some("truecolor"), _) | (Some("24bit"), _) => ColorDepth::TrueColor,
+            (_, Some(term)) if term.contains("256color") || term.contains("direct") => ColorDepth::Ansi256,
+            _ => ColorDepth::Ansi16
+        };
+        let charset = match locale {
+            Some(locale) if locale.to_uppercase().replace('-', "").contains("UTF8") => Charset::Block,
+            _ => Charset::Ascii
+        };
+        TerminalCapabilities { color_depth, charset, size: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::terminal::{TerminalCapabilities, ColorDepth, Charset};
+
+    #[test]
+    fn detect_capabilities_from_env() {
+        let capabilities = TerminalCapabilities::from_env(Some("truecolor"), Some("xterm-256color"), Some("en_US.UTF-8"));
+        assert_eq!((capabilities.color_depth, capabilities.charset), (ColorDepth::TrueColor, Charset::Block));
+        let capabilities = TerminalCapabilities::from_env(None, Some("xterm-256color"), Some("fr_FR.utf8"));
+        assert_eq!((capabilities.color_depth, capabilities.charset), (ColorDepth::Ansi256, Charset::Block));
+        let capabilities = TerminalCapabilities::from_env(None, Some("vt100"), Some("C"));
+        assert_eq!((capabilities.color_depth, capabilities.charset), (ColorDepth::Ansi16, Charset::Ascii));
+        let capabilities = TerminalCapabilities::from_env(None, None, None);
+        assert_eq!((capabilities.color_depth, capabilities.charset), (ColorDepth::Ansi16, Charset::Ascii));
+    }
+}
@@ -0,0 +1,93 @@
+//! This module locates the cells of the world in the grid.
+//!
+//! The world is a tore, so any position is mapped to a cell of the grid. With the "wrap_table" feature,
+//! the toroidal correction of the coordinates near the world is precomputed, to avoid divisions in the inner loops.
+
+/// Locates the cells of a world of the given size.
+pub struct Topology {
+    size: (usize, usize),
+    #[cfg(feature = "wrap_table")]
+    wrap: (WrapTable, WrapTable),
+}
+
+impl Topology {
+    pub fn new(size: (usize, usize)) -> Topology {
+        Topology {
+            size,
+            #[cfg(feature = "wrap_table")]
+            wrap: (WrapTable::new(size.0), WrapTable::new(size.1)),
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    /// Returns the index in the grid of the cell at the given position.
+    #[cfg(feature = "wrap_table")]
+    pub fn index(&self, (x, y): (isize, isize)) -> usize {
+        self.wrap.1.wrap(y) * self.size.0 + self.wrap.0.wrap(x)
+    }
+
+    /// Returns the index in the grid of the cell at the given position.
+    #[cfg(not(feature = "wrap_table"))]
+    pub fn index(&self, position: (isize, isize)) -> usize {
+        get_index(position, self.size)
+    }
+}
+
+/// Precomputed toroidal correction of the coordinates of one axis, for coordinates in [-size; 2*size(.
+/// Coordinates outside of this range fall back to the computed correction.
+pub struct WrapTable {
+    size: usize,
+    table: Vec<usize>
+}
+
+impl WrapTable {
+    pub fn new(size: usize) -> WrapTable {
+        let signed_size = size as isize;
+        WrapTable {
+            size,
+            table: (-signed_size..2 * signed_size).map(|value| tore_correction(value, size)).collect()
+        }
+    }
+
+    #[inline]
+    pub fn wrap(&self, value: isize) -> usize {
+        let offset = value + self.size as isize;
+        if offset >= 0 && (offset as usize) < self.table.len() {
+            self.table[offset as usize]
+        } else {
+            tore_correction(value, self.size)
+        }
+    }
+}
+
+pub fn get_index((x, y): (isize, isize), size: (usize, usize)) -> usize {
+    tore_correction(y, size.1) * size.0 + tore_correction(x, size.0)
+}
+
+/// The world is a tore, so the value range can be )-inf; +inf(, and it will be mapped to (0; upper_bound-1).
+pub fn tore_correction(value: isize, upper_bound: usize) -> usize {
+    if value >= 0 {
+        (value as usize) % upper_bound
+    } else {
+        // don't question my magic
+        let signed_upper_bound = upper_bound as isize;
+        let corrected = (signed_upper_bound + (value % signed_upper_bound)) % signed_upper_bound;
+        corrected as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::topology::{WrapTable, tore_correction};
+
+    #[test]
+    fn wrap_table_matches_tore_correction() {
+        let table = WrapTable::new(7);
+        for value in -30..30 {
+            assert_eq!(table.wrap(value), tore_correction(value, 7));
+        }
+    }
+}
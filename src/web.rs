@@ -0,0 +1,78 @@
+//! This module runs automatons in web pages, with the `wasm` feature and the wasm32-unknown-unknown target. The page drives
+//! the simulation from its animation frames, instead of the loop of the executor that sleeps between the iterations :
+//!
+//! ```js
+//! import init, { WebAutomaton } from "./pkg/mutations.js";
+//!
+//! await init();
+//! const automaton = new WebAutomaton(rules, "grid", 42n);
+//! const frame = () => {
+//!     automaton.frame(1);
+//!     requestAnimationFrame(frame);
+//! };
+//! requestAnimationFrame(frame);
+//! ```
+
+use wasm_bindgen::prelude::*;
+use crate::automaton::Automaton;
+use crate::camera::Camera;
+use crate::canvas_display::CanvasDisplay;
+use crate::compiler::semantic::parse_str;
+use crate::display::{Display, Status};
+
+/// An automaton drawn in a canvas of the page, the whole world with a pixel per cell.
+#[wasm_bindgen]
+pub struct WebAutomaton {
+    automaton: Automaton,
+    camera: Camera,
+    display: CanvasDisplay
+}
+
+#[wasm_bindgen]
+impl WebAutomaton {
+    /// Compiles the rules, given as the text of a rules file, and draws the initial world in the canvas with the given id.
+    /// The errors of the compiler are thrown in a single message, a line per error.
+    #[wasm_bindgen(constructor)]
+    pub fn new(rules: &str, canvas_id: &str, seed: u64) -> Result<WebAutomaton, JsValue> {
+        let rules = parse_str(rules).map_err(|errors| {
+            JsValue::from_str(&errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))
+        })?;
+        let automaton = Automaton::with_seed(rules, seed);
+        let camera = Camera::with_size(0, 0, automaton.get_rules().world_size);
+        let display = CanvasDisplay::new(canvas_id).map_err(|error| JsValue::from_str(&error))?;
+        let mut web_automaton = WebAutomaton { automaton, camera, display };
+        web_automaton.draw();
+        Ok(web_automaton)
+    }
+
+    /// Runs the given number of ticks, then draws the world. The page calls it at each animation frame, with more ticks
+    /// per frame to speed the simulation up, or none while it is paused.
+    pub fn frame(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.automaton.tick();
+        }
+        self.draw();
+    }
+
+    /// Sets the cell under the pixel of the canvas to the state, like a click on the canvas, and draws the world.
+    pub fn paint(&mut self, x: usize, y: usize, state: usize) -> Result<(), JsValue> {
+        for (x, y) in self.camera.cells_at((x, y), &self.automaton) {
+            self.automaton.set_cell(x, y, state).map_err(|error| JsValue::from_str(&error))?;
+        }
+        self.draw();
+        Ok(())
+    }
+
+    pub fn iteration(&self) -> usize {
+        self.automaton.get_iteration()
+    }
+
+    /// Returns the status line, like "Iteration 42 | 60.0 it/s | dead 900 | alive 100", with the speed measured by the page.
+    pub fn status(&self, speed: f64) -> String {
+        Status::new(&self.automaton, speed).line()
+    }
+
+    fn draw(&mut self) {
+        self.display.render(self.camera.capture(&self.automaton), None);
+    }
+}